@@ -1,33 +1,52 @@
 use anyhow::{bail, Context, Result};
 use smithay::{
     backend::{
-        allocator::dmabuf::Dmabuf,
+        allocator::{dmabuf::Dmabuf, format::FormatSet},
         drm::{DrmDeviceFd, DrmNode, NodeType},
-        egl::context::ContextPriority,
+        egl::{context::ContextPriority, EGLDevice},
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{
+            damage::OutputDamageTracker,
+            element::memory::MemoryRenderBuffer,
             gles::GlesRenderer,
-            multigpu::{gbm::GbmGlesBackend, GpuManager, MultiRenderer, MultiTexture},
-            DebugFlags, ImportDma,
+            multigpu::{gbm::GbmGlesBackend, GpuManager, MultiRenderer},
+            DebugFlags, ImportDma, ImportEgl, ImportMemWl,
         },
         session::{
             libseat::{LibSeatSession, LibSeatSessionNotifier},
             Session,
         },
         udev::{self, UdevBackend},
+        winit::{self, WinitEventLoop, WinitGraphicsBackend},
     },
+    output::{Mode as WlMode, Output, PhysicalProperties, Subpixel},
     reexports::{
+        calloop::{LoopHandle, RegistrationToken},
         input::{Device as LibinputDevice, Libinput},
-        wayland_server::{protocol::wl_surface::WlSurface, DisplayHandle},
+        wayland_server::{protocol::{wl_shm, wl_surface::WlSurface}, DisplayHandle},
     },
     wayland::{
         dmabuf::{DmabufFeedbackBuilder, DmabufGlobal, DmabufState},
+        drm_lease::DrmLeaseState,
         drm_syncobj::DrmSyncobjState,
     },
+    utils::Transform,
 };
-use std::{collections::HashMap, ops::{Deref, DerefMut}};
+use std::{any::Any, collections::HashMap, ops::{Deref, DerefMut}, path::PathBuf, process::Child, time::Instant};
+#[cfg(test)]
+use smithay::backend::renderer::test::DummyRenderer;
+use xcursor::parser::Image;
 
-use crate::{trayle::DeviceData, Trayle};
+use crate::{
+    config::Config,
+    cursor::Cursor,
+    drawing::PointerElement,
+    trayle::{render::{self, RenderContext, Submitted}, DeviceData},
+    wallpaper::Wallpapers,
+    Trayle,
+};
+#[cfg(feature = "screencast")]
+use crate::screencast::{Cast, PipeWire};
 
 pub type UdevRenderer<'a> = MultiRenderer<
     'a,'a,
@@ -66,23 +85,341 @@ impl DerefMut for LazyDmabufState {
     }
 }
 
-pub struct Backend {
-    pub seat: String,
-    pub keyboards: Vec<LibinputDevice>,
-    pub devices: HashMap<DrmNode, DeviceData>,
+/// what the shared compositor code needs from the backend in use
+///
+/// implemented by [`Tty`], [`Winit`] and the test only `Dummy`, see [`Backend::state`]
+pub trait BackendState: Any {
+    /// gpu clients should allocate their buffers on, `None` without any gpu
+    fn primary_gpu(&self) -> Option<DrmNode>;
+
+    /// outputs driven by the backend, whether mapped in the space or not
+    fn outputs(&self) -> Vec<Output>;
+
+    /// render `output` as soon as possible instead of on its next frame, see [`render::output`]
+    fn schedule_render(&mut self, output: &Output, handle: &LoopHandle<'static, Trayle>);
+
+    /// repaint `output` and submit the frame for presentation
+    ///
+    /// `None` when nothing was submitted, surfaces shown on `output` are answered by
+    /// [`render::output`] otherwise
+    fn render(&mut self, output: &Output, ctx: RenderContext<'_>) -> Option<Submitted>;
+
+    /// run `f` with the renderer that composites client buffers
+    fn with_renderer(&mut self, f: &mut dyn FnMut(&mut GlesRenderer) -> Result<()>) -> Result<()>;
+
+    /// import a client dmabuf into the renderer that composites client buffers
+    fn import_dmabuf(&mut self, dmabuf: &Dmabuf) -> Result<()> {
+        self.with_renderer(&mut |renderer|{
+            renderer.import_dmabuf(dmabuf, None)?;
+            Ok(())
+        })
+    }
+
+    /// called on commit, before the buffer of `surface` is rendered
+    fn early_import(&mut self, surface: &WlSurface) -> Result<()>;
+
+    /// dmabuf formats clients can allocate buffers with
+    fn dmabuf_formats(&mut self) -> FormatSet {
+        let mut formats = FormatSet::default();
+        if let Err(err) = self.with_renderer(&mut |renderer|{
+            formats = ImportDma::dmabuf_formats(&*renderer);
+            Ok(())
+        }) {
+            tracing::warn!("failed to query dmabuf formats: {err:#}");
+        }
+        formats
+    }
+
+    /// shm formats clients can allocate buffers with
+    fn shm_formats(&mut self) -> Vec<wl_shm::Format> {
+        let mut formats = vec![];
+        if let Err(err) = self.with_renderer(&mut |renderer|{
+            formats = ImportMemWl::shm_formats(&*renderer).collect();
+            Ok(())
+        }) {
+            tracing::warn!("failed to query shm formats: {err:#}");
+        }
+        formats
+    }
+
+    /// let clients use EGL, see [`ImportEgl::bind_wl_display`]
+    ///
+    /// this is the only source of the legacy `wl_drm` global, mesa exposes it for the
+    /// primary gpu and handles its authentication, there is no native implementation,
+    /// without it clients have to use linux-dmabuf
+    fn bind_wl_display(&mut self, dh: &DisplayHandle) -> Result<()> {
+        self.with_renderer(&mut |renderer|{
+            ImportEgl::bind_wl_display(renderer, dh)?;
+            Ok(())
+        })
+    }
 
+    /// repaint every output on its next frame
+    fn mark_dirty(&mut self);
+}
+
+impl dyn BackendState {
+    /// the backend in use as `B`, `None` when running on another backend
+    pub fn downcast_ref<B: BackendState>(&self) -> Option<&B> {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    /// the backend in use as `B`, `None` when running on another backend
+    pub fn downcast_mut<B: BackendState>(&mut self) -> Option<&mut B> {
+        (self as &mut dyn Any).downcast_mut()
+    }
+
+    /// drm devices, `None` when running nested
+    pub fn tty(&self) -> Option<&Tty> {
+        self.downcast_ref()
+    }
+
+    /// drm devices, `None` when running nested
+    pub fn tty_mut(&mut self) -> Option<&mut Tty> {
+        self.downcast_mut()
+    }
+}
+
+/// drm devices driven on a tty
+pub struct Tty {
     pub primary_gpu: DrmNode,
     pub gpus: GpuManager<GbmGlesBackend<GlesRenderer, DrmDeviceFd>>,
+    pub devices: HashMap<DrmNode, DeviceData>,
+    /// disabled lease globals of removed devices, clients may still use them for a while,
+    /// removed along with the token of their grace timer
+    pub retired_lease_states: HashMap<DrmNode, (DrmLeaseState, RegistrationToken)>,
+    /// lease global hidden from every client, answers requests on lease objects that
+    /// outlived their global, created along with the first device lease global
+    pub lease_fallback: Option<DrmLeaseState>,
+}
+
+impl Tty {
+    /// delegate function from [`GpuManager::single_renderer`] with [`Tty::primary_gpu`]
+    pub fn primary_renderer(&mut self) -> UdevRenderer {
+        self.gpus.single_renderer(&self.primary_gpu).expect("failed to get primary renderer")
+    }
+}
+
+impl BackendState for Tty {
+    fn primary_gpu(&self) -> Option<DrmNode> {
+        Some(self.primary_gpu)
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        self.devices
+            .values()
+            .flat_map(|device|device.surfaces.values())
+            .map(|surface|surface.output.clone())
+            .collect()
+    }
+
+    fn schedule_render(&mut self, output: &Output, handle: &LoopHandle<'static, Trayle>) {
+        let output = output.clone();
+        handle.insert_idle(move|trayle|{
+            render::output(&output, trayle.clock.now(), trayle);
+        });
+    }
+
+    /// paced by the vblank of the crtc, see [`render::frame_finish`]
+    fn render(&mut self, output: &Output, ctx: RenderContext<'_>) -> Option<Submitted> {
+        render::tty(self, output, ctx)
+    }
+
+    fn with_renderer(&mut self, f: &mut dyn FnMut(&mut GlesRenderer) -> Result<()>) -> Result<()> {
+        f(self.primary_renderer().as_mut())
+    }
+
+    /// optimizing buffer imports across multiple gpus
+    ///
+    /// can call be called on commit to start necessary copy processes early
+    ///
+    /// required to use with smithay's [`utils::on_commit_buffer_handler`]
+    ///
+    /// see [`GpuManager::early_import`]
+    ///
+    /// [`utils::on_commit_buffer_handler`]: smithay::backend::renderer::utils::on_commit_buffer_handler
+    fn early_import(&mut self, surface: &WlSurface) -> Result<()> {
+        self.gpus.early_import(self.primary_gpu, surface).map_err(Into::into)
+    }
+
+    fn mark_dirty(&mut self) {
+        let surfaces = self.devices
+            .values_mut()
+            .flat_map(|device|device.surfaces.values_mut());
+        for surface in surfaces {
+            surface.dirty = true;
+        }
+    }
+}
+
+/// window of the nested backend, see [`Backend::setup`]
+pub struct Winit {
+    pub backend: WinitGraphicsBackend<GlesRenderer>,
+    pub damage_tracker: OutputDamageTracker,
+    /// the only output, sized as the window
+    pub output: Output,
+    /// gpu behind the window, for dmabuf feedback
+    pub primary_gpu: DrmNode,
+}
+
+impl BackendState for Winit {
+    fn primary_gpu(&self) -> Option<DrmNode> {
+        Some(self.primary_gpu)
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        vec![self.output.clone()]
+    }
+
+    /// rendered on the next redraw event of the window
+    fn schedule_render(&mut self, _: &Output, _: &LoopHandle<'static, Trayle>) {
+        self.backend.window().request_redraw();
+    }
+
+    fn render(&mut self, output: &Output, ctx: RenderContext<'_>) -> Option<Submitted> {
+        render::winit(self, output, ctx)
+    }
+
+    fn with_renderer(&mut self, f: &mut dyn FnMut(&mut GlesRenderer) -> Result<()>) -> Result<()> {
+        f(self.backend.renderer())
+    }
+
+    /// single gpu, there is nothing to copy
+    fn early_import(&mut self, _: &WlSurface) -> Result<()> {
+        Ok(())
+    }
+
+    /// the window is redrawn on every frame of the host compositor anyway
+    fn mark_dirty(&mut self) { }
+}
+
+/// backend without any hardware, for exercising the shell logic in tests
+///
+/// outputs are fake, nothing is presented and only shm buffers are accepted
+#[cfg(test)]
+pub struct Dummy {
+    pub outputs: Vec<Output>,
+    /// in-memory renderer, builds render elements of the outputs without a gpu
+    pub renderer: DummyRenderer,
+}
+
+#[cfg(test)]
+impl Dummy {
+    /// one fake output for each of `sizes`, named `dummy-0`, `dummy-1`, ..
+    pub fn new(dh: &DisplayHandle, sizes: &[(i32,i32)]) -> Dummy {
+        let outputs = sizes
+            .iter()
+            .enumerate()
+            .map(|(i,&size)|{
+                let physical = PhysicalProperties {
+                    size: (0,0).into(),
+                    subpixel: Subpixel::Unknown,
+                    make: "Trayle".into(),
+                    model: "Dummy".into(),
+                };
+                let output = Output::new(format!("dummy-{i}"), physical);
+                output.create_global::<Trayle>(dh);
+                let mode = WlMode { size: size.into(), refresh: 60_000 };
+                output.set_preferred(mode);
+                output.change_current_state(Some(mode), None, None, None);
+                output
+            })
+            .collect();
+        Dummy { outputs, renderer: DummyRenderer::default() }
+    }
+}
+
+#[cfg(test)]
+impl BackendState for Dummy {
+    fn primary_gpu(&self) -> Option<DrmNode> {
+        None
+    }
+
+    fn outputs(&self) -> Vec<Output> {
+        self.outputs.clone()
+    }
+
+    fn schedule_render(&mut self, _: &Output, _: &LoopHandle<'static, Trayle>) { }
+
+    /// nothing is presented, tests build render elements with [`Dummy::renderer`] instead
+    fn render(&mut self, _: &Output, _: RenderContext<'_>) -> Option<Submitted> {
+        None
+    }
+
+    fn with_renderer(&mut self, _: &mut dyn FnMut(&mut GlesRenderer) -> Result<()>) -> Result<()> {
+        bail!("dummy backend has no gpu")
+    }
+
+    fn import_dmabuf(&mut self, _: &Dmabuf) -> Result<()> {
+        bail!("dummy backend has no gpu")
+    }
+
+    fn early_import(&mut self, _: &WlSurface) -> Result<()> {
+        Ok(())
+    }
+
+    fn dmabuf_formats(&mut self) -> FormatSet {
+        FormatSet::default()
+    }
+
+    /// formats every compositor has to support
+    fn shm_formats(&mut self) -> Vec<wl_shm::Format> {
+        vec![wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888]
+    }
+
+    fn bind_wl_display(&mut self, _: &DisplayHandle) -> Result<()> {
+        bail!("dummy backend has no egl display")
+    }
+
+    fn mark_dirty(&mut self) { }
+}
+
+pub struct Backend {
+    pub seat: String,
+    pub keyboards: Vec<LibinputDevice>,
+    /// hardware the compositor runs on, downcast for backend specific state
+    pub state: Box<dyn BackendState>,
     pub debug_flags: DebugFlags,
+    /// render every frame instead of scanning out client buffers directly
+    pub disable_direct_scanout: bool,
 
-    pub session: LibSeatSession,
-    pub input: Libinput,
+    pub pointer_image: Cursor,
+    pub pointer_images: Vec<(Image, MemoryRenderBuffer)>,
+    pub pointer_element: PointerElement,
+    pub wallpapers: Wallpapers,
+
+    /// `None` when running nested
+    pub session: Option<LibSeatSession>,
+    /// `None` when running nested
+    pub input: Option<Libinput>,
     pub dmabuf_state: LazyDmabufState,
     pub syncobj_state: Option<DrmSyncobjState>,
+    /// last user input, for powering off outputs on idle
+    pub last_input: Instant,
+    /// laptop lid is closed, internal panels stay powered off
+    pub lid_closed: bool,
+    /// spawned processes, reaped once they exit
+    pub children: Vec<Child>,
+
+    /// connected lazily on the first screencast
+    #[cfg(feature = "screencast")]
+    pub pipewire: Option<PipeWire>,
+    #[cfg(feature = "screencast")]
+    pub casts: Vec<Cast>,
 }
 
 impl Backend {
-    pub fn setup(dh: &DisplayHandle) -> Result<(Backend, BackendSources)> {
+    /// setup drm and libinput backend on a tty
+    ///
+    /// when `TRAYLE_BACKEND=winit` or `--winit` is given, runs nested in a window of the
+    /// current wayland or x11 session instead, see [`Backend::setup_winit`]
+    pub fn setup(dh: &DisplayHandle, config: &Config) -> Result<(Backend, BackendSources)> {
+        let nested = std::env::var("TRAYLE_BACKEND").is_ok_and(|backend|backend == "winit")
+            || std::env::args().any(|arg|arg == "--winit");
+        if nested {
+            return Self::setup_winit(dh, config);
+        }
+
         // libseat
         let (session, session_source) = LibSeatSession::new().context("failed to setup libseat")?;
         let seat = session.seat();
@@ -90,16 +427,26 @@ impl Backend {
 
 
         // gpu devices
-        let primary_gpu = match udev::primary_gpu(&seat)
-            .context("failed to query gpu")?
-            .and_then(|gpu|DrmNode::from_path(gpu).ok()?.node_with_type(NodeType::Render)?.ok())
-        {
-            Some(ok) => ok,
-            None => udev::all_gpus(&seat)
+        let drm_device = std::env::var_os("TRAYLE_DRM_DEVICE")
+            .map(PathBuf::from)
+            .or_else(||config.drm_device.clone());
+        let primary_gpu = match drm_device {
+            Some(path) => {
+                let node = DrmNode::from_path(&path).with_context(||format!("invalid drm device {path:?}"))?;
+                // outputs compare against render nodes
+                node.node_with_type(NodeType::Render).and_then(Result::ok).unwrap_or(node)
+            },
+            None => match udev::primary_gpu(&seat)
                 .context("failed to query gpu")?
-                .into_iter()
-                .find_map(|gpu|DrmNode::from_path(gpu).ok())
-                .context("no gpu found")?,
+                .and_then(|gpu|DrmNode::from_path(gpu).ok()?.node_with_type(NodeType::Render)?.ok())
+            {
+                Some(ok) => ok,
+                None => udev::all_gpus(&seat)
+                    .context("failed to query gpu")?
+                    .into_iter()
+                    .find_map(|gpu|DrmNode::from_path(gpu).ok())
+                    .context("no gpu found")?,
+            },
         };
         let graphics_api = GbmGlesBackend::with_context_priority(ContextPriority::High);
         let mut gpus = GpuManager::new(graphics_api).context("failed to setup gbm gles renderer")?;
@@ -114,22 +461,21 @@ impl Backend {
         let input_source = LibinputInputBackend::new(input.clone());
 
 
-        let backend = Backend {
-            seat,
-            keyboards: vec![],
-            devices: HashMap::new(),
-
+        let tty = Tty {
             primary_gpu,
             gpus,
-            debug_flags: DebugFlags::empty(),
-
-            session,
-            input,
-            dmabuf_state: LazyDmabufState::Uninit,
-            syncobj_state: None,
+            devices: HashMap::new(),
+            retired_lease_states: HashMap::new(),
+            lease_fallback: None,
+        };
+        let backend = Backend {
+            disable_direct_scanout: std::env::var("TRAYLE_DISABLE_DIRECT_SCANOUT").is_ok(),
+            session: Some(session),
+            input: Some(input),
+            ..Backend::new(config, seat, tty)
         };
 
-        let sources = BackendSources {
+        let sources = BackendSources::Udev {
             session: session_source,
             input: input_source,
             udev,
@@ -137,38 +483,100 @@ impl Backend {
 
         Ok((backend, sources))
     }
+
+    /// nested backend for development, a single output rendered into a winit window
+    ///
+    /// there is no session, libinput or drm device, input comes from the window
+    fn setup_winit(dh: &DisplayHandle, config: &Config) -> Result<(Backend, BackendSources)> {
+        let (mut backend, winit_source) = winit::init::<GlesRenderer>()
+            .map_err(|err|anyhow::anyhow!("failed to setup winit: {err}"))?;
+
+        // dmabuf feedback still wants the gpu behind the window
+        let primary_gpu = EGLDevice::device_for_display(backend.renderer().egl_context().display())
+            .and_then(|device|device.try_get_render_node())
+            .context("failed to query winit gpu")?
+            .context("winit renderer has no render node")?;
+        tracing::info!("running nested on {primary_gpu:?}");
+
+        let physical = PhysicalProperties {
+            size: (0,0).into(),
+            subpixel: Subpixel::Unknown,
+            make: "Trayle".into(),
+            model: "Winit".into(),
+        };
+        let output = Output::new("winit".into(), physical);
+        output.create_global::<Trayle>(dh);
+
+        let mode = WlMode { size: backend.window_size(), refresh: 60_000 };
+        output.set_preferred(mode);
+        // gl framebuffer of the window is upside down
+        output.change_current_state(Some(mode), Some(Transform::Flipped180), None, None);
+
+        let damage_tracker = OutputDamageTracker::from_output(&output);
+
+        let winit = Winit { backend, damage_tracker, output, primary_gpu };
+        let backend = Backend::new(config, "seat0".into(), winit);
+
+        Ok((backend, BackendSources::Winit(winit_source)))
+    }
+
+    /// backend with one fake output for each of `sizes`, see [`Dummy`]
+    #[cfg(test)]
+    pub fn dummy(dh: &DisplayHandle, config: &Config, sizes: &[(i32,i32)]) -> Backend {
+        Backend::new(config, "seat0".into(), Dummy::new(dh, sizes))
+    }
+
+    /// parts shared by every backend around `state`
+    ///
+    /// there is no session or libinput, and direct scanout is disabled
+    fn new(config: &Config, seat: String, state: impl BackendState) -> Backend {
+        Backend {
+            seat,
+            keyboards: vec![],
+            state: Box::new(state),
+            debug_flags: DebugFlags::empty(),
+            disable_direct_scanout: true,
+
+            pointer_image: Cursor::load(&config.cursor),
+            pointer_images: vec![],
+            pointer_element: PointerElement::default(),
+            wallpapers: Wallpapers::default(),
+
+            session: None,
+            input: None,
+            dmabuf_state: LazyDmabufState::Uninit,
+            syncobj_state: None,
+            last_input: Instant::now(),
+            lid_closed: false,
+            children: vec![],
+
+            #[cfg(feature = "screencast")]
+            pipewire: None,
+            #[cfg(feature = "screencast")]
+            casts: vec![],
+        }
+    }
 }
 
 /// mostly delegation function
 impl Backend {
-    /// delegate function from [`GpuManager::single_renderer`] with [`Tty::primary_gpu`]
-    pub fn primary_renderer(&mut self) -> UdevRenderer {
-        self.gpus.single_renderer(&self.primary_gpu).expect("failed to get primary renderer")
-    }
-
     /// see [`ImportDma::import_dmabuf`]
-    pub fn import_dmabuf(&mut self, dmabuf: &Dmabuf) -> Result<MultiTexture> {
-        Ok(self.primary_renderer().import_dmabuf(dmabuf, None)?)
+    pub fn import_dmabuf(&mut self, dmabuf: &Dmabuf) -> Result<()> {
+        self.state.import_dmabuf(dmabuf)
     }
 
-    /// optimizing buffer imports across multiple gpus
-    ///
-    /// can call be called on commit to start necessary copy processes early
-    ///
-    /// required to use with smithay's [`utils::on_commit_buffer_handler`]
-    ///
-    /// see [`GpuManager::early_import`]
-    ///
-    /// [`utils::on_commit_buffer_handler`]: smithay::backend::renderer::utils::on_commit_buffer_handler
+    /// see [`BackendState::early_import`]
     pub fn early_import(&mut self, surface: &WlSurface) -> Result<()> {
-        self.gpus.early_import(self.primary_gpu, surface).map_err(Into::into)
+        self.state.early_import(surface)
     }
-
 }
 
-pub struct BackendSources {
-    pub session: LibSeatSessionNotifier,
-    pub input: LibinputInputBackend,
-    pub udev: UdevBackend,
+pub enum BackendSources {
+    Udev {
+        session: LibSeatSessionNotifier,
+        input: LibinputInputBackend,
+        udev: UdevBackend,
+    },
+    Winit(WinitEventLoop),
 }
 
@@ -1,5 +1,13 @@
-use anyhow::Result;
-use smithay::backend::{allocator::Fourcc, renderer::Color32F};
+use anyhow::{bail, Context, Result};
+use smithay::{
+    backend::{allocator::Fourcc, renderer::{Color32F, DebugFlags}},
+    input::keyboard::{ModifiersState, XkbConfig},
+    output::Output,
+    utils::{Logical, Point, Transform},
+};
+use crate::{shell::ssd::HEADER_BAR_HEIGHT, utils::display_info::EdidName};
+use std::{collections::HashMap, path::PathBuf, str::FromStr, time::Duration};
+use xkbcommon::xkb::{self, Keysym};
 
 
 pub const SUPPORTED_FORMATS_8BIT_ONLY: &[Fourcc] = &[Fourcc::Abgr8888, Fourcc::Argb8888];
@@ -11,30 +19,820 @@ pub const SUPPORTED_FORMATS: &[Fourcc] = &[
 ];
 
 pub struct Config {
+    /// background of outputs without their own, see [`Config::clear_color`]
     pub clear_color: Color32F,
     pub kb_repeat_delay: i32,
     pub kb_repeat_rate: i32,
     pub disable_direct_10bit: bool,
+    /// do not expose the legacy `wl_drm` global, clients use linux-dmabuf only,
+    /// set by `TRAYLE_DISABLE_WL_DRM`
+    pub disable_wl_drm: bool,
+    /// always composite the cursor, set by `TRAYLE_DISABLE_CURSOR_PLANE`
+    pub disable_cursor_plane: bool,
+    pub keybindings: HashMap<KeyCombo, Action>,
+    /// output name, eg: `DP-1`, or its edid name, eg: `Dell Inc. DELL U2720Q 1A2B3C`,
+    /// to its configuration
+    pub outputs: HashMap<String, OutputConfig>,
+    pub decoration: DecorationConfig,
+    pub cursor: CursorConfig,
+    pub lease: LeaseConfig,
+    pub xkb: XkbSettings,
+    /// power off outputs after no input for this long
+    pub dpms_timeout: Option<Duration>,
+    /// new windows are tiled instead of floating
+    pub tiling: bool,
+    /// wallpaper of outputs without their own, `clear_color` is shown if unset
+    pub wallpaper: Option<WallpaperConfig>,
+    /// extra environment variables of spawned processes
+    pub env: HashMap<String, String>,
+    /// shell commands spawned on startup and whenever the config is applied again
+    pub exec: Vec<String>,
+    /// shell commands spawned on startup only
+    pub exec_once: Vec<String>,
+    /// output names or edid names that are not driven when connected
+    pub disabled_outputs: Vec<String>,
+    /// gpu that renders every output, eg: `/dev/dri/card1`, overridden by `TRAYLE_DRM_DEVICE`
+    pub drm_device: Option<PathBuf>,
+    /// which xdg-activation requests may raise and focus a window
+    pub activation: ActivationPolicy,
+    /// libinput device name, or `type:touchpad` and `type:pointer`, to its configuration,
+    /// see [`Config::input`]
+    pub inputs: HashMap<String, InputConfig>,
+    /// pointer acceleration of devices without their own, see [`InputConfig::accel_profile`]
+    pub accel_profile: Option<AccelProfile>,
+    /// pointer speed of devices without their own, see [`InputConfig::accel_speed`]
+    pub accel_speed: Option<f64>,
+    /// output name or edid name the tablet area is mapped to, the first output if unset
+    pub tablet_output: Option<String>,
+    /// move the cursor to the center of a newly focused window it is not already over
+    pub warp_cursor_on_focus: bool,
 }
 
 impl Default for Config {
     fn default() -> Self {
+        let workspaces = (1..=9).flat_map(|n|[
+            (format!("Super+{n}"), format!("switch_workspace {n}")),
+            (format!("Super+Shift+{n}"), format!("move_to_workspace {n}")),
+        ]);
+
+        let keybindings = [
+            ("Super+Return", "spawn alacritty"),
+            ("Super+Q", "quit"),
+            ("Super+Shift+Q", "close_window"),
+            ("Super+Space", "toggle_floating"),
+            ("Alt+Tab", "cycle_windows next"),
+            ("Alt+Shift+Tab", "cycle_windows prev"),
+            ("Super+Shift+D", "toggle_debug_flag tint"),
+        ]
+        .into_iter()
+        .map(|(combo,action)|(combo.to_owned(),action.to_owned()))
+        .chain(workspaces)
+        .map(|(combo,action)|(combo.parse().unwrap(),action.parse().unwrap()))
+        .collect();
+
         Self {
             clear_color: Color32F::new(0.8, 0.8, 0.9, 1.0),
             kb_repeat_delay: 160,
             kb_repeat_rate: 50,
             disable_direct_10bit: env("TRAYLE_DISABLE_DIRECT_10BIT"),
+            disable_wl_drm: env("TRAYLE_DISABLE_WL_DRM"),
+            disable_cursor_plane: env("TRAYLE_DISABLE_CURSOR_PLANE"),
+            keybindings,
+            outputs: HashMap::new(),
+            decoration: DecorationConfig::default(),
+            cursor: CursorConfig::default(),
+            lease: LeaseConfig::default(),
+            xkb: XkbSettings::default(),
+            dpms_timeout: None,
+            tiling: false,
+            wallpaper: None,
+            env: HashMap::new(),
+            exec: vec![],
+            exec_once: vec![],
+            disabled_outputs: vec![],
+            drm_device: None,
+            activation: ActivationPolicy::default(),
+            inputs: HashMap::new(),
+            accel_profile: None,
+            accel_speed: None,
+            tablet_output: None,
+            warp_cursor_on_focus: false,
         }
     }
 }
 
 impl Config {
+    /// load config from [`Config::path`], fallback to default if the file does not exist
     pub fn setup() -> Result<Config> {
-        Ok(Config::default())
+        let mut config = Config::default();
+
+        let Some(path) = Config::path() else {
+            tracing::info!("no config directory, using default config");
+            return Ok(config);
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(ok) => ok,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                tracing::info!("no config file at {path:?}, using default config");
+                return Ok(config);
+            }
+            Err(err) => return Err(err).with_context(||format!("failed to read config {path:?}")),
+        };
+
+        let file = toml::from_str::<file::ConfigFile>(&content)
+            .with_context(||format!("failed to parse config {path:?}"))?;
+
+        config.apply(file)?;
+        tracing::info!("config loaded from {path:?}");
+
+        Ok(config)
+    }
+
+    /// `TRAYLE_CONFIG`, or `$XDG_CONFIG_HOME/trayle/config.toml`,
+    /// or `$HOME/.config/trayle/config.toml`
+    pub fn path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os("TRAYLE_CONFIG") {
+            return Some(path.into());
+        }
+
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(||Some(PathBuf::from(std::env::var_os("HOME")?).join(".config")))?;
+
+        Some(config_dir.join("trayle").join("config.toml"))
+    }
+
+    /// background color of `output`, fallback to the global one
+    pub fn clear_color(&self, output: &Output) -> Color32F {
+        self.output(output)
+            .and_then(|output|output.clear_color)
+            .unwrap_or(self.clear_color)
+    }
+
+    /// connector `name`, or monitor of `edid_name`, is listed in [`Config::disabled_outputs`]
+    pub fn output_disabled(&self, name: &str, edid_name: Option<&EdidName>) -> bool {
+        self.disabled_outputs
+            .iter()
+            .any(|disabled|disabled == name || edid_name.is_some_and(|EdidName(edid)|disabled == edid))
+    }
+
+    /// non desktop connector `name`, or monitor of `edid_name`, may be leased, see [`LeaseConfig`]
+    pub fn output_leasable(&self, name: &str, edid_name: Option<&EdidName>) -> bool {
+        let matches = |names: &[String]|names
+            .iter()
+            .any(|listed|listed == name || edid_name.is_some_and(|EdidName(edid)|listed == edid));
+        let allowed = self.lease.allow.is_empty() || matches(&self.lease.allow);
+        allowed && !matches(&self.lease.deny)
+    }
+
+    /// configuration of `output`, matched by its [`EdidName`] first, then by its connector name
+    pub fn output(&self, output: &Output) -> Option<&OutputConfig> {
+        output
+            .user_data()
+            .get::<EdidName>()
+            .and_then(|EdidName(name)|self.outputs.get(name))
+            .or_else(||self.outputs.get(&output.name()))
+    }
+
+    /// configuration of the input device `name`, its own entry takes precedence over
+    /// the one of its type, then the global acceleration, unset fields keep the libinput default
+    pub fn input(&self, name: &str, touchpad: bool) -> InputConfig {
+        let kind = match touchpad {
+            true => "type:touchpad",
+            false => "type:pointer",
+        };
+        let by_name = self.inputs.get(name).cloned().unwrap_or_default();
+        let by_kind = self.inputs.get(kind).cloned().unwrap_or_default();
+        InputConfig {
+            tap: by_name.tap.or(by_kind.tap),
+            natural_scroll: by_name.natural_scroll.or(by_kind.natural_scroll),
+            disable_while_typing: by_name.disable_while_typing.or(by_kind.disable_while_typing),
+            click_method: by_name.click_method.or(by_kind.click_method),
+            accel_profile: by_name.accel_profile.or(by_kind.accel_profile).or(self.accel_profile),
+            accel_speed: by_name.accel_speed.or(by_kind.accel_speed).or(self.accel_speed),
+        }
+    }
+
+    fn apply(&mut self, file: file::ConfigFile) -> Result<()> {
+        for (combo,action) in file.keybindings {
+            let combo = combo.parse::<KeyCombo>()?;
+            let action = action.parse::<Action>()?;
+            self.keybindings.insert(combo, action);
+        }
+
+        for (name,output) in file.outputs {
+            let output = OutputConfig::try_from(output)
+                .with_context(||format!("invalid config for output {name:?}"))?;
+            self.outputs.insert(name, output);
+        }
+
+        for (name,input) in file.inputs {
+            let input = InputConfig::try_from(input)
+                .with_context(||format!("invalid config for input {name:?}"))?;
+            self.inputs.insert(name, input);
+        }
+
+        self.accel_profile = parse_accel_profile(file.accel_profile.as_deref())?;
+        self.accel_speed = valid_accel_speed(file.accel_speed, "global");
+
+        self.xkb = XkbSettings {
+            rules: file.xkb_rules,
+            model: file.xkb_model,
+            layout: file.xkb_layout,
+            variant: file.xkb_variant,
+            options: file.xkb_options,
+        };
+
+        self.env.extend(file.env);
+        self.exec = file.exec;
+        self.exec_once = file.exec_once;
+        self.disabled_outputs = file.disabled_outputs;
+        self.drm_device = file.drm_device;
+        self.tablet_output = file.tablet_output;
+
+        self.activation = match file.activation.as_deref() {
+            None | Some("smart") => ActivationPolicy::Smart,
+            Some("always") => ActivationPolicy::Always,
+            Some("never") => ActivationPolicy::Never,
+            Some(policy) => bail!("unknown activation policy {policy:?}, expected `smart`, `always` or `never`"),
+        };
+
+        if let Some(color) = file.clear_color {
+            self.clear_color = parse_color(color).context("invalid clear_color")?;
+        }
+
+        if let Some(tiling) = file.tiling {
+            self.tiling = tiling;
+        }
+
+        if let Some(warp) = file.warp_cursor_on_focus {
+            self.warp_cursor_on_focus = warp;
+        }
+
+        if let Some(secs) = file.dpms_timeout {
+            if secs == 0 {
+                bail!("dpms_timeout must be positive");
+            }
+            self.dpms_timeout = Some(Duration::from_secs(secs));
+        }
+
+        if let Some(wallpaper) = file.wallpaper {
+            self.wallpaper = Some(WallpaperConfig::try_from(wallpaper).context("invalid wallpaper config")?);
+        }
+
+        if let Some(server_side) = file.decoration.server_side {
+            self.decoration.server_side = server_side;
+        }
+        if let Some(titlebar_height) = file.decoration.titlebar_height {
+            if titlebar_height <= 0 {
+                bail!("decoration titlebar_height must be positive, got {titlebar_height}");
+            }
+            self.decoration.titlebar_height = titlebar_height;
+        }
+
+        self.lease = LeaseConfig {
+            allow: file.lease.allow,
+            deny: file.lease.deny,
+        };
+
+        if let Some(theme) = file.cursor.theme {
+            self.cursor.theme = Some(theme);
+        }
+        if let Some(size) = file.cursor.size {
+            if size == 0 {
+                bail!("cursor size must be positive");
+            }
+            self.cursor.size = Some(size);
+        }
+
+        Ok(())
+    }
+}
+
+/// raw config file representation
+mod file {
+    use std::collections::HashMap;
+
+    #[derive(Default, serde::Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct ConfigFile {
+        pub keybindings: HashMap<String, String>,
+        pub outputs: HashMap<String, OutputFile>,
+        pub inputs: HashMap<String, InputFile>,
+        pub accel_profile: Option<String>,
+        pub accel_speed: Option<f64>,
+        pub tablet_output: Option<String>,
+        pub decoration: DecorationFile,
+        pub cursor: CursorFile,
+        pub lease: LeaseFile,
+        pub xkb_rules: Option<String>,
+        pub xkb_model: Option<String>,
+        pub xkb_layout: Option<String>,
+        pub xkb_variant: Option<String>,
+        pub xkb_options: Option<String>,
+        /// in seconds
+        pub dpms_timeout: Option<u64>,
+        pub tiling: Option<bool>,
+        pub warp_cursor_on_focus: Option<bool>,
+        pub wallpaper: Option<WallpaperFile>,
+        pub clear_color: Option<ColorFile>,
+        pub env: HashMap<String, String>,
+        pub exec: Vec<String>,
+        pub exec_once: Vec<String>,
+        pub disabled_outputs: Vec<String>,
+        pub drm_device: Option<std::path::PathBuf>,
+        pub activation: Option<String>,
+    }
+
+    /// `"#rrggbb"`, `"#rrggbbaa"`, or `[r, g, b]` and `[r, g, b, a]` floats
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    pub enum ColorFile {
+        Hex(String),
+        Rgba(Vec<f32>),
+    }
+
+    #[derive(Default, serde::Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct DecorationFile {
+        pub server_side: Option<bool>,
+        pub titlebar_height: Option<i32>,
+    }
+
+    #[derive(Default, serde::Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct CursorFile {
+        pub theme: Option<String>,
+        pub size: Option<u32>,
+    }
+
+    #[derive(Default, serde::Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct LeaseFile {
+        pub allow: Vec<String>,
+        pub deny: Vec<String>,
+    }
+
+    #[derive(Default, serde::Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct OutputFile {
+        pub position: Option<(i32, i32)>,
+        pub transform: Option<String>,
+        pub scale: Option<f64>,
+        pub vrr: Option<bool>,
+        pub mode: Option<String>,
+        pub wallpaper: Option<WallpaperFile>,
+        pub clear_color: Option<ColorFile>,
+    }
+
+    #[derive(Default, serde::Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct InputFile {
+        pub tap: Option<bool>,
+        pub natural_scroll: Option<bool>,
+        pub disable_while_typing: Option<bool>,
+        pub click_method: Option<String>,
+        pub accel_profile: Option<String>,
+        pub accel_speed: Option<f64>,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct WallpaperFile {
+        pub path: std::path::PathBuf,
+        pub mode: Option<String>,
+    }
+}
+
+/// keyboard layout, unset fields use the xkbcommon default
+#[derive(Debug, Clone, Default)]
+pub struct XkbSettings {
+    pub rules: Option<String>,
+    pub model: Option<String>,
+    pub layout: Option<String>,
+    pub variant: Option<String>,
+    pub options: Option<String>,
+}
+
+impl XkbSettings {
+    pub fn xkb_config(&self) -> XkbConfig<'_> {
+        XkbConfig {
+            rules: self.rules.as_deref().unwrap_or_default(),
+            model: self.model.as_deref().unwrap_or_default(),
+            layout: self.layout.as_deref().unwrap_or_default(),
+            variant: self.variant.as_deref().unwrap_or_default(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+/// window decoration preference
+#[derive(Debug, Clone)]
+pub struct DecorationConfig {
+    /// prefer server side decoration for clients that support negotiation
+    pub server_side: bool,
+    pub titlebar_height: i32,
+}
+
+impl Default for DecorationConfig {
+    fn default() -> Self {
+        Self {
+            server_side: true,
+            titlebar_height: HEADER_BAR_HEIGHT,
+        }
+    }
+}
+
+/// cursor theme, `XCURSOR_THEME` and `XCURSOR_SIZE` take precedence
+#[derive(Debug, Clone, Default)]
+pub struct CursorConfig {
+    pub theme: Option<String>,
+    pub size: Option<u32>,
+}
+
+/// which non desktop connectors, eg: vr headsets, are offered for leasing
+///
+/// entries are output names or edid names, non desktop connectors that may not be
+/// leased are used as regular outputs
+#[derive(Debug, Clone, Default)]
+pub struct LeaseConfig {
+    /// only these may be leased, every non desktop connector if empty
+    pub allow: Vec<String>,
+    /// never leased, takes precedence over `allow`
+    pub deny: Vec<String>,
+}
+
+/// focus stealing prevention of xdg-activation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActivationPolicy {
+    /// only tokens requested on behalf of a recent input event of the focused client,
+    /// and used within [`ActivationPolicy::TOKEN_TIMEOUT`]
+    #[default]
+    Smart,
+    /// any token activates its window
+    Always,
+    /// windows are never activated
+    Never,
+}
+
+impl ActivationPolicy {
+    /// tokens older than this are stale for [`ActivationPolicy::Smart`]
+    pub const TOKEN_TIMEOUT: Duration = Duration::from_secs(10);
+}
+
+/// per output configuration, unset fields fallback to automatic value
+#[derive(Debug, Clone, Default)]
+pub struct OutputConfig {
+    /// logical position in the global space, outputs without it are placed left to right
+    pub position: Option<Point<i32, Logical>>,
+    pub transform: Option<Transform>,
+    pub scale: Option<f64>,
+    /// enable adaptive sync if supported
+    pub vrr: Option<bool>,
+    pub mode: Option<ModeConfig>,
+    pub wallpaper: Option<WallpaperConfig>,
+    pub clear_color: Option<Color32F>,
+}
+
+/// libinput configuration of a pointer or touchpad, unset fields keep the device default
+#[derive(Debug, Clone, Default)]
+pub struct InputConfig {
+    /// tap-to-click
+    pub tap: Option<bool>,
+    pub natural_scroll: Option<bool>,
+    /// ignore the touchpad while typing
+    pub disable_while_typing: Option<bool>,
+    pub click_method: Option<ClickMethod>,
+    pub accel_profile: Option<AccelProfile>,
+    /// between -1 and 1, relative to the device default of 0
+    pub accel_speed: Option<f64>,
+}
+
+/// how pointer motion is scaled by its speed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccelProfile {
+    /// constant factor given by the accel speed, eg: for gaming mice
+    Flat,
+    /// faster motion moves the pointer further
+    Adaptive,
+}
+
+/// how a clickpad without physical buttons decides which button is pressed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClickMethod {
+    /// by the area of the touchpad that is pressed
+    ButtonAreas,
+    /// by the number of fingers on the touchpad
+    Clickfinger,
+}
+
+impl TryFrom<file::InputFile> for InputConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(file: file::InputFile) -> Result<Self> {
+        let click_method = match file.click_method.as_deref() {
+            None => None,
+            Some("button_areas") => Some(ClickMethod::ButtonAreas),
+            Some("clickfinger") => Some(ClickMethod::Clickfinger),
+            Some(method) => bail!("unknown click method {method:?}, expected `button_areas` or `clickfinger`"),
+        };
+
+        Ok(Self {
+            tap: file.tap,
+            natural_scroll: file.natural_scroll,
+            disable_while_typing: file.disable_while_typing,
+            click_method,
+            accel_profile: parse_accel_profile(file.accel_profile.as_deref())?,
+            accel_speed: valid_accel_speed(file.accel_speed, "input"),
+        })
+    }
+}
+
+fn parse_accel_profile(profile: Option<&str>) -> Result<Option<AccelProfile>> {
+    match profile {
+        None => Ok(None),
+        Some("flat") => Ok(Some(AccelProfile::Flat)),
+        Some("adaptive") => Ok(Some(AccelProfile::Adaptive)),
+        Some(profile) => bail!("unknown accel profile {profile:?}, expected `flat` or `adaptive`"),
+    }
+}
+
+/// libinput only accepts a speed between -1 and 1, others are ignored with a warning
+fn valid_accel_speed(speed: Option<f64>, scope: &str) -> Option<f64> {
+    let speed = speed?;
+    if !(-1.0..=1.0).contains(&speed) {
+        tracing::warn!("ignoring {scope} accel_speed {speed}, expected a value between -1 and 1");
+        return None;
+    }
+    Some(speed)
+}
+
+/// png image drawn below everything else on an output
+#[derive(Debug, Clone)]
+pub struct WallpaperConfig {
+    pub path: PathBuf,
+    pub mode: WallpaperMode,
+}
+
+/// how a wallpaper fills its output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WallpaperMode {
+    /// scale to cover the output, cropping what does not fit
+    #[default]
+    Scale,
+    /// native size at the center of the output
+    Center,
+    /// native size repeated from the top left of the output
+    Tile,
+}
+
+impl TryFrom<file::WallpaperFile> for WallpaperConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(file: file::WallpaperFile) -> Result<Self> {
+        let mode = match file.mode.as_deref() {
+            None | Some("scale") => WallpaperMode::Scale,
+            Some("center") => WallpaperMode::Center,
+            Some("tile") => WallpaperMode::Tile,
+            Some(mode) => bail!("unknown wallpaper mode {mode:?}, expected `scale`, `center` or `tile`"),
+        };
+
+        Ok(Self { path: file.path, mode })
+    }
+}
+
+/// output mode, eg: `1920x1080` or `1920x1080@144`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModeConfig {
+    pub width: u16,
+    pub height: u16,
+    /// refresh rate in Hz, highest available if unset
+    pub refresh: Option<f64>,
+}
+
+impl FromStr for ModeConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (size,refresh) = match s.split_once('@') {
+            Some((size,refresh)) => (size, Some(refresh)),
+            None => (s, None),
+        };
+
+        let Some((width,height)) = size.split_once('x') else {
+            bail!("invalid mode {s:?}, expected `<width>x<height>[@<refresh>]`");
+        };
+
+        let width = width.trim().parse().with_context(||format!("invalid mode width in {s:?}"))?;
+        let height = height.trim().parse().with_context(||format!("invalid mode height in {s:?}"))?;
+        let refresh = refresh
+            .map(|refresh|refresh.trim().trim_end_matches("Hz").parse::<f64>())
+            .transpose()
+            .with_context(||format!("invalid mode refresh rate in {s:?}"))?;
+
+        Ok(Self { width, height, refresh })
+    }
+}
+
+impl TryFrom<file::OutputFile> for OutputConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(file: file::OutputFile) -> Result<Self> {
+        let transform = match file.transform.as_deref() {
+            None => None,
+            Some("normal") => Some(Transform::Normal),
+            Some("90") => Some(Transform::_90),
+            Some("180") => Some(Transform::_180),
+            Some("270") => Some(Transform::_270),
+            Some("flipped") => Some(Transform::Flipped),
+            Some("flipped-90") => Some(Transform::Flipped90),
+            Some("flipped-180") => Some(Transform::Flipped180),
+            Some("flipped-270") => Some(Transform::Flipped270),
+            Some(transform) => bail!("unknown transform {transform:?}"),
+        };
+
+        if let Some(scale) = file.scale.filter(|scale|!(scale.is_finite() && *scale > 0.0)) {
+            bail!("scale must be a positive number, got {scale}");
+        }
+
+        Ok(Self {
+            position: file.position.map(Point::from),
+            transform,
+            scale: file.scale,
+            vrr: file.vrr,
+            mode: file.mode.as_deref().map(str::parse).transpose()?,
+            wallpaper: file.wallpaper.map(WallpaperConfig::try_from).transpose()?,
+            clear_color: file.clear_color.map(parse_color).transpose().context("invalid clear_color")?,
+        })
+    }
+}
+
+fn parse_color(color: file::ColorFile) -> Result<Color32F> {
+    match color {
+        file::ColorFile::Hex(hex) => {
+            let digits = hex.strip_prefix('#').unwrap_or(&hex);
+            let value = match digits.len() {
+                6 | 8 => u32::from_str_radix(digits, 16).ok(),
+                _ => None,
+            };
+            let Some(value) = value else {
+                bail!("invalid color {hex:?}, expected `#rrggbb` or `#rrggbbaa`");
+            };
+            let value = if digits.len() == 6 { value << 8 | 0xff } else { value };
+            let [r,g,b,a] = value.to_be_bytes().map(|c|c as f32 / 255.0);
+            Ok(Color32F::new(r, g, b, a))
+        },
+        file::ColorFile::Rgba(rgba) => {
+            if let Some(c) = rgba.iter().find(|c|!(0.0..=1.0).contains(*c)) {
+                bail!("color components must be between 0 and 1, got {c}");
+            }
+            match rgba[..] {
+                [r,g,b] => Ok(Color32F::new(r, g, b, 1.0)),
+                [r,g,b,a] => Ok(Color32F::new(r, g, b, a)),
+                _ => bail!("expected 3 or 4 color components, got {}", rgba.len()),
+            }
+        },
+    }
+}
+
+bitflags::bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Modifiers: u8 {
+        const CTRL = 1;
+        const ALT = 1 << 1;
+        const SHIFT = 1 << 2;
+        const SUPER = 1 << 3;
+    }
+}
+
+impl From<&ModifiersState> for Modifiers {
+    fn from(state: &ModifiersState) -> Self {
+        let mut modifiers = Modifiers::empty();
+        modifiers.set(Modifiers::CTRL, state.ctrl);
+        modifiers.set(Modifiers::ALT, state.alt);
+        modifiers.set(Modifiers::SHIFT, state.shift);
+        modifiers.set(Modifiers::SUPER, state.logo);
+        modifiers
+    }
+}
+
+/// modifiers and an unmodified keysym, eg: `Super+Shift+Q`
+///
+/// latin letters are stored in lowercase, so it can be matched against raw keysyms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub modifiers: Modifiers,
+    pub keysym: Keysym,
+}
+
+impl FromStr for KeyCombo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut modifiers = Modifiers::empty();
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let Some(key) = parts.pop().filter(|key|!key.is_empty()) else {
+            bail!("keybinding {s:?} has no key");
+        };
+
+        for part in parts {
+            modifiers |= match part.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "alt" => Modifiers::ALT,
+                "shift" => Modifiers::SHIFT,
+                "super" | "logo" | "mod4" => Modifiers::SUPER,
+                _ => bail!("unknown modifier {part:?} in keybinding {s:?}"),
+            };
+        }
+
+        let mut keysym = xkb::keysym_from_name(key, xkb::KEYSYM_NO_FLAGS);
+        if keysym.raw() == xkb::keysyms::KEY_NoSymbol {
+            keysym = xkb::keysym_from_name(key, xkb::KEYSYM_CASE_INSENSITIVE);
+        }
+        if keysym.raw() == xkb::keysyms::KEY_NoSymbol {
+            bail!("unknown key {key:?} in keybinding {s:?}");
+        }
+
+        // raw keysyms of latin letters are lowercase
+        if (xkb::keysyms::KEY_A..=xkb::keysyms::KEY_Z).contains(&keysym.raw()) {
+            keysym = Keysym::new(keysym.raw() + (xkb::keysyms::KEY_a - xkb::keysyms::KEY_A));
+        }
+
+        Ok(Self { modifiers, keysym })
+    }
+}
+
+/// compositor action triggered by a keybinding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Spawn(String),
+    Quit,
+    CloseWindow,
+    /// workspace number starts from 1
+    SwitchWorkspace(u8),
+    /// move focused window to workspace
+    MoveToWorkspace(u8),
+    /// toggle focused window between floating and tiled
+    ToggleFloating,
+    /// focus the next, or previous if false, window in most recently used order
+    CycleWindows(bool),
+    /// debugging aid for scanout glitches
+    ToggleDirectScanout,
+    /// renderer debug overlay, eg: tint damaged regions
+    ToggleDebugFlag(DebugFlags),
+    /// switch to virtual terminal, `Ctrl+Alt+F<n>` always switches to vt `n`
+    ChangeVt(i32),
+    /// stop forwarding keybindings to the focused surface until it is focused again,
+    /// `Super+Escape` always releases it
+    ReleaseShortcutsInhibitor,
+    /// move the cursor to the center of the focused window
+    CenterCursor,
+}
+
+impl FromStr for Action {
+    type Err = anyhow::Error;
+
+    /// `spawn <command>`, `quit`, `close_window`, `switch_workspace <n>`, `move_to_workspace <n>`
+    /// `toggle_floating`, `cycle_windows <next|prev>`, `toggle_direct_scanout`
+    /// `toggle_debug_flag <tint>`, `change_vt <n>`, `release_shortcuts_inhibitor` or `center_cursor`
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (name,arg) = s.split_once(char::is_whitespace).unwrap_or((s,""));
+        let arg = arg.trim();
+
+        let action = match (name,arg) {
+            ("spawn","") => bail!("action `spawn` requires a command"),
+            ("spawn",cmd) => Action::Spawn(cmd.to_owned()),
+            ("quit","") => Action::Quit,
+            ("close_window","") => Action::CloseWindow,
+            ("toggle_floating","") => Action::ToggleFloating,
+            ("cycle_windows","next") => Action::CycleWindows(true),
+            ("cycle_windows","prev") => Action::CycleWindows(false),
+            ("toggle_direct_scanout","") => Action::ToggleDirectScanout,
+            ("release_shortcuts_inhibitor","") => Action::ReleaseShortcutsInhibitor,
+            ("center_cursor","") => Action::CenterCursor,
+            ("toggle_debug_flag","tint") => Action::ToggleDebugFlag(DebugFlags::TINT),
+            ("switch_workspace",n) => Action::SwitchWorkspace(parse_workspace(n)?),
+            ("move_to_workspace",n) => Action::MoveToWorkspace(parse_workspace(n)?),
+            ("change_vt",n) => match n.parse::<i32>() {
+                Ok(n) if n > 0 => Action::ChangeVt(n),
+                _ => bail!("invalid vt {n:?}, vt number starts from 1"),
+            },
+            _ => bail!("unknown action {s:?}"),
+        };
+
+        Ok(action)
+    }
+}
+
+fn parse_workspace(n: &str) -> Result<u8> {
+    match n.parse::<u8>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => bail!("invalid workspace {n:?}, workspace number starts from 1"),
     }
 }
 
 fn env(key: &str) -> bool {
     matches!(std::env::var(key).as_deref(),Ok("1"))
 }
-
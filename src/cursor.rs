@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fs, io::Read, time::Duration};
+
+use anyhow::{Context, Result};
+use smithay::input::pointer::CursorIcon;
+use xcursor::{parser::Image, CursorTheme};
+
+use crate::config::CursorConfig;
+
+static FALLBACK_CURSOR_DATA: &[u8] = include_bytes!("../resources/cursor.rgba");
+
+pub struct Cursor {
+    theme: CursorTheme,
+    /// loaded images of each cursor shape, shapes missing from the theme use the default one
+    icons: HashMap<CursorIcon, Vec<Image>>,
+    /// animation frames of a cursor shape at the nearest available size for a scale
+    frames: HashMap<(CursorIcon, u32), Vec<Image>>,
+    size: u32,
+}
+
+impl Cursor {
+    /// load theme from `XCURSOR_THEME` and `XCURSOR_SIZE`, fallback to `config`
+    ///
+    /// missing theme fallback to the embedded cursor
+    pub fn load(config: &CursorConfig) -> Cursor {
+        let name = std::env::var("XCURSOR_THEME")
+            .ok()
+            .filter(|name|!name.is_empty())
+            .or_else(||config.theme.clone())
+            .unwrap_or_else(||"default".into());
+        let size = std::env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|s|s.parse().ok())
+            .filter(|&size|size > 0)
+            .or(config.size)
+            .unwrap_or(24);
+
+        tracing::info!("using cursor theme {name:?} size {size}");
+
+        let theme = CursorTheme::load(&name);
+        let default = match load_icon(&theme, CursorIcon::Default) {
+            Ok(ok) => ok,
+            Err(err) => {
+                tracing::warn!("failed to load xcursor theme {name:?}: {err}, using fallback");
+                vec![Image {
+                    size: 32,
+                    width: 64,
+                    height: 64,
+                    xhot: 1,
+                    yhot: 1,
+                    delay: 1,
+                    pixels_rgba: Vec::from(FALLBACK_CURSOR_DATA),
+                    pixels_argb: vec![]
+                }]
+            },
+        };
+
+        let icons = HashMap::from([(CursorIcon::Default, default)]);
+        Self { theme, icons, frames: HashMap::new(), size }
+    }
+
+    /// image of cursor shape `icon` at `scale`, at `time` of its animation
+    pub fn get_image(&mut self, icon: CursorIcon, scale: u32, time: Duration) -> Image {
+        let frames = self.frames(icon, scale);
+        let total = frames.iter().fold(0, |acc,image|acc+image.delay);
+        let mut millis = time.as_millis() as u32 % total.max(1);
+
+        for img in frames {
+            if millis < img.delay {
+                return img.clone();
+            }
+            millis -= img.delay;
+        }
+
+        frames[0].clone()
+    }
+
+    /// time until the next animation frame of cursor shape `icon`, `None` if it is not animated
+    pub fn next_frame_in(&mut self, icon: CursorIcon, scale: u32, time: Duration) -> Option<Duration> {
+        let frames = self.frames(icon, scale);
+        if frames.len() < 2 {
+            return None;
+        }
+
+        let total = frames.iter().fold(0, |acc,image|acc+image.delay);
+        let mut millis = time.as_millis() as u32 % total.max(1);
+
+        for img in frames {
+            if millis < img.delay {
+                return Some(Duration::from_millis((img.delay - millis) as u64));
+            }
+            millis -= img.delay;
+        }
+
+        None
+    }
+
+    fn frames(&mut self, icon: CursorIcon, scale: u32) -> &[Image] {
+        if !self.icons.contains_key(&icon) {
+            let images = load_icon(&self.theme, icon)
+                .inspect_err(|err|tracing::debug!("no {} cursor: {err}, using default", icon.name()))
+                .unwrap_or_else(|_|self.icons[&CursorIcon::Default].clone());
+            self.icons.insert(icon, images);
+        }
+
+        let size = self.size * scale;
+        let icons = &self.icons;
+        self.frames
+            .entry((icon, scale))
+            .or_insert_with(||nearest_image(size, &icons[&icon]).cloned().collect())
+    }
+}
+
+/// load cursor shape `icon`, trying its alternative names used by older themes
+fn load_icon(theme: &CursorTheme, icon: CursorIcon) -> Result<Vec<Image>> {
+    let icon_path = std::iter::once(icon.name())
+        .chain(icon.alt_names().iter().copied())
+        .find_map(|name|theme.load_icon(name))
+        .with_context(||format!("no {} cursor", icon.name()))?;
+    let mut cursor_file = fs::File::open(&icon_path)?;
+    let mut cursor_data = vec![];
+    cursor_file.read_to_end(&mut cursor_data)?;
+    let images = xcursor::parser::parse_xcursor(&cursor_data).context("failed to parse cursor file")?;
+    Ok(images)
+}
+
+
+fn nearest_image(size: u32, images: &[Image]) -> impl Iterator<Item = &Image> {
+    let nearest_image = images
+        .iter()
+        .min_by_key(|image| (size as i32 - image.size as i32).abs())
+        .unwrap();
+
+    images.iter().filter(move |image| {
+        image.width == nearest_image.width && image.height == nearest_image.height
+    })
+}
+
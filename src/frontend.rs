@@ -1,305 +1,255 @@
 use anyhow::{Context, Result};
 use smithay::{
     backend::renderer::element::RenderElementStates,
-    desktop::{self, utils::OutputPresentationFeedback, Space, Window},
+    desktop::{self, utils::OutputPresentationFeedback, PopupManager, Space},
     input::{pointer::CursorImageStatus, SeatState},
     output::Output,
-    reexports::wayland_server::{protocol::wl_surface::WlSurface, DisplayHandle},
-    utils::{Logical, Point},
+    reexports::{
+        calloop::LoopHandle,
+        wayland_server::{protocol::wl_surface::WlSurface, DisplayHandle},
+    },
+    utils::{ClockSource, Logical, Monotonic, Point},
     wayland::{
+        commit_timing::CommitTimingManagerState,
         compositor::CompositorState,
-        dmabuf::{DmabufFeedback, DmabufState},
-        shell::xdg::XdgShellState,
+        content_type::ContentTypeState,
+        cursor_shape::CursorShapeManagerState,
+        dmabuf::DmabufFeedback,
+        fifo::FifoManagerState,
+        fractional_scale::FractionalScaleManagerState,
+        idle_inhibit::IdleInhibitManagerState,
+        idle_notify::IdleNotifierState,
+        input_method::InputMethodManagerState,
+        keyboard_shortcuts_inhibit::{KeyboardShortcutsInhibitState, KeyboardShortcutsInhibitor},
+        output::OutputManagerState,
+        pointer_constraints::PointerConstraintsState,
+        pointer_gestures::PointerGesturesState,
+        presentation::PresentationState,
+        relative_pointer::RelativePointerManagerState,
+        security_context::SecurityContextState,
+        selection::data_device::DataDeviceState,
+        shell::{
+            wlr_layer::WlrLayerShellState,
+            xdg::{decoration::XdgDecorationState, XdgShellState},
+        },
         shm::ShmState,
+        single_pixel_buffer::SinglePixelBufferState,
         socket::ListeningSocketSource,
+        tablet_manager::TabletManagerState,
+        text_input::TextInputManagerState,
+        viewporter::ViewporterState,
+        virtual_keyboard::VirtualKeyboardManagerState,
+        xdg_activation::XdgActivationState,
     },
 };
 
-use crate::Trayle;
+use crate::{
+    protocols::{
+        gamma_control::GammaControlManagerState,
+        output_management::OutputManagementState,
+        screencopy::ScreencopyManagerState,
+        virtual_pointer::VirtualPointerManagerState,
+    },
+    shell::{
+        elements::WindowElement,
+        layout::Layout,
+        workspace::{Workspace, WORKSPACE_COUNT},
+    },
+    trayle::ClientState,
+    Trayle,
+};
+use std::collections::HashSet;
 
 pub struct Frontend {
     pub wlsocket: String,
-    pub space: Space<Window>,
+    /// x11 display number once xwayland is ready
+    pub xdisplay: Option<u32>,
+    pub space: Space<WindowElement>,
+    pub workspaces: Vec<Workspace>,
+    pub active_workspace: usize,
+    pub layout: Layout,
+    /// windows in most recently focused order
+    pub mru: Vec<WindowElement>,
+    /// window selected by window cycling, while the modifier is held
+    pub cycle: Option<WindowElement>,
+    /// id of the next mapped window, see [`WindowElement::id`]
+    pub next_window_id: u64,
+    pub popups: PopupManager,
+    pub cursor_status: CursorImageStatus,
+    pub dnd_icon: Option<DndIcon>,
     // Globals
     pub compositor_state: CompositorState,
     pub xdg_shell_state: XdgShellState,
+    pub xdg_decoration_state: XdgDecorationState,
+    pub layer_shell_state: WlrLayerShellState,
     pub seat_state: SeatState<Trayle>,
+    pub data_device_state: DataDeviceState,
     pub shm_state: ShmState,
-    pub dmabuf_state: DmabufState,
+    pub single_pixel_buffer_state: SinglePixelBufferState,
+    pub output_manager_state: OutputManagerState,
+    pub output_management_state: OutputManagementState,
+    pub fractional_scale_manager_state: FractionalScaleManagerState,
+    pub viewporter_state: ViewporterState,
+    pub gamma_control_manager_state: GammaControlManagerState,
+    pub screencopy_manager_state: ScreencopyManagerState,
+    pub idle_notifier_state: IdleNotifierState<Trayle>,
+    pub idle_inhibit_manager_state: IdleInhibitManagerState,
+    pub cursor_shape_manager_state: CursorShapeManagerState,
+    pub presentation_state: PresentationState,
+    pub commit_timing_manager_state: CommitTimingManagerState,
+    pub fifo_manager_state: FifoManagerState,
+    pub xdg_activation_state: XdgActivationState,
+    pub security_context_state: SecurityContextState,
+    pub relative_pointer_manager_state: RelativePointerManagerState,
+    pub pointer_constraints_state: PointerConstraintsState,
+    pub pointer_gestures_state: PointerGesturesState,
+    pub tablet_manager_state: TabletManagerState,
+    pub text_input_manager_state: TextInputManagerState,
+    pub input_method_manager_state: InputMethodManagerState,
+    pub virtual_keyboard_manager_state: VirtualKeyboardManagerState,
+    pub virtual_pointer_manager_state: VirtualPointerManagerState,
+    pub keyboard_shortcuts_inhibit_state: KeyboardShortcutsInhibitState,
+    pub content_type_state: ContentTypeState,
+    /// shortcuts inhibitor of the surface with keyboard focus
+    ///
+    /// keybindings are forwarded to the surface while it is active, see `input::on_keyboard`
+    pub shortcuts_inhibitor: Option<KeyboardShortcutsInhibitor>,
+    /// surfaces with an active idle inhibitor
+    ///
+    /// an inhibitor only counts while its surface is visible on an output
+    pub idle_inhibiting_surfaces: HashSet<WlSurface>,
+    /// horizontal distance of an ongoing workspace swipe, see `input::gesture_swipe_begin`
+    pub workspace_swipe: Option<f64>,
 }
 
 impl Frontend {
-    pub fn setup(dh: &DisplayHandle) -> Result<(Frontend, FrontendSources)> {
-        let space = Space::default();
+    pub fn workspace(&mut self) -> &mut Workspace {
+        &mut self.workspaces[self.active_workspace]
+    }
 
+    pub fn setup(dh: &DisplayHandle, handle: &LoopHandle<'static, Trayle>) -> Result<(Frontend, FrontendSources)> {
         let socket = ListeningSocketSource::new_auto().context("failed to setup wayland socket")?;
         let wlsocket = socket.socket_name().to_string_lossy().into_owned();
         tracing::info!(name = wlsocket, "listening on wayland socket");
 
+        let sources = FrontendSources {
+            socket,
+        };
+
+        Ok((Self::new(dh, handle, wlsocket), sources))
+    }
+
+    /// frontend with its globals, clients of `wlsocket` are accepted by the caller
+    pub fn new(dh: &DisplayHandle, handle: &LoopHandle<'static, Trayle>, wlsocket: String) -> Frontend {
+        let space = Space::default();
+        let popups = PopupManager::default();
+        let cursor_status = CursorImageStatus::default_named();
+
         //
         // setup globals, look for corresponding smithay module for documentation
         //
 
         let compositor_state = CompositorState::new::<Trayle>(dh);
         let xdg_shell_state = XdgShellState::new::<Trayle>(dh);
+        let xdg_decoration_state = XdgDecorationState::new::<Trayle>(dh);
+        let layer_shell_state = WlrLayerShellState::new::<Trayle>(dh);
         let seat_state = SeatState::new();
+        let data_device_state = DataDeviceState::new::<Trayle>(dh);
         let shm_state = ShmState::new::<Trayle>(dh, vec![]);
-        let dmabuf_state = DmabufState::new();
+        // usually sized by viewporter, eg: solid backgrounds
+        let single_pixel_buffer_state = SinglePixelBufferState::new::<Trayle>(dh);
+        // xdg-output is kept in sync by `Output::change_current_state`
+        let output_manager_state = OutputManagerState::new_with_xdg_output::<Trayle>(dh);
+        // output management, gamma control and screencopy are hidden from sandboxed clients,
+        // see `ClientState::is_privileged`
+        let output_management_state = OutputManagementState::new::<Trayle, _>(dh, ClientState::is_privileged);
+        let fractional_scale_manager_state = FractionalScaleManagerState::new::<Trayle>(dh);
+        let viewporter_state = ViewporterState::new::<Trayle>(dh);
+        let gamma_control_manager_state = GammaControlManagerState::new::<Trayle, _>(dh, ClientState::is_privileged);
+        let screencopy_manager_state = ScreencopyManagerState::new::<Trayle, _>(dh, ClientState::is_privileged);
+        // idle timers are driven by the event loop
+        let idle_notifier_state = IdleNotifierState::new(dh, handle.clone());
+        let idle_inhibit_manager_state = IdleInhibitManagerState::new::<Trayle>(dh);
+        let cursor_shape_manager_state = CursorShapeManagerState::new::<Trayle>(dh);
+        // feedback timestamps are taken from `Clock<Monotonic>`
+        let presentation_state = PresentationState::new::<Trayle>(dh, Monotonic::ID as u32);
+        // barriers are released by the render loop, see `render::pre_repaint` and `render::post_repaint`
+        let commit_timing_manager_state = CommitTimingManagerState::new::<Trayle>(dh);
+        let fifo_manager_state = FifoManagerState::new::<Trayle>(dh);
+        // tokens are checked against `Config::activation`
+        let xdg_activation_state = XdgActivationState::new::<Trayle>(dh);
+        // locked and confined pointers are handled by `input::pointer_motion`
+        let relative_pointer_manager_state = RelativePointerManagerState::new::<Trayle>(dh);
+        let pointer_constraints_state = PointerConstraintsState::new::<Trayle>(dh);
+        let pointer_gestures_state = PointerGesturesState::new::<Trayle>(dh);
+        // tablets and tools are added to the seat as libinput reports them
+        let tablet_manager_state = TabletManagerState::new::<Trayle>(dh);
+        let text_input_manager_state = TextInputManagerState::new::<Trayle>(dh);
+        // an input method sees every key, so sandboxed clients may not be one
+        let input_method_manager_state = InputMethodManagerState::new::<Trayle, _>(dh, ClientState::is_privileged);
+        // so can virtual input devices, which act as the user
+        let virtual_keyboard_manager_state = VirtualKeyboardManagerState::new::<Trayle, _>(dh, ClientState::is_privileged);
+        let virtual_pointer_manager_state = VirtualPointerManagerState::new::<Trayle, _>(dh, ClientState::is_privileged);
+        let keyboard_shortcuts_inhibit_state = KeyboardShortcutsInhibitState::new::<Trayle>(dh);
+        // hints are read on render, see [`WindowElement::content_type`]
+        let content_type_state = ContentTypeState::new::<Trayle>(dh);
+        // sandboxed clients may not create nested security contexts
+        let security_context_state = SecurityContextState::new::<Trayle, _>(dh, |client|{
+            client.get_data::<ClientState>().is_none_or(|data|data.security_context.is_none())
+        });
 
-        let sources = FrontendSources {
-            socket,
-        };
-
-        let frontend = Self {
+        Self {
             wlsocket,
+            xdisplay: None,
             space,
+            workspaces: std::iter::repeat_with(Workspace::default).take(WORKSPACE_COUNT).collect(),
+            active_workspace: 0,
+            layout: Layout::default(),
+            mru: Vec::new(),
+            cycle: None,
+            next_window_id: 1,
+            popups,
+            cursor_status,
+            dnd_icon: None,
 
             // Globals
             compositor_state,
             xdg_shell_state,
+            xdg_decoration_state,
+            layer_shell_state,
             seat_state,
+            data_device_state,
             shm_state,
-            dmabuf_state,
-        };
-
-        Ok((frontend,sources))
+            single_pixel_buffer_state,
+            output_manager_state,
+            output_management_state,
+            fractional_scale_manager_state,
+            viewporter_state,
+            gamma_control_manager_state,
+            screencopy_manager_state,
+            idle_notifier_state,
+            idle_inhibit_manager_state,
+            cursor_shape_manager_state,
+            presentation_state,
+            commit_timing_manager_state,
+            fifo_manager_state,
+            xdg_activation_state,
+            security_context_state,
+            relative_pointer_manager_state,
+            pointer_constraints_state,
+            pointer_gestures_state,
+            tablet_manager_state,
+            text_input_manager_state,
+            input_method_manager_state,
+            virtual_keyboard_manager_state,
+            virtual_pointer_manager_state,
+            keyboard_shortcuts_inhibit_state,
+            content_type_state,
+            shortcuts_inhibitor: None,
+            idle_inhibiting_surfaces: HashSet::new(),
+            workspace_swipe: None,
+        }
     }
-
-    // pub fn pre_repaint(&mut self, output: &Output, frame_target: impl Into<Time<Monotonic>>) {
-    //     let frame_target = Into::<Time<Monotonic>>::into(frame_target);
-    //
-    //     fn processor(
-    //         clients: &mut HashMap<ClientId, Client>,
-    //         frame_target: Time<Monotonic>,
-    //         surface: &WlSurface,
-    //         states: &SurfaceData,
-    //     ) {
-    //         let Some(mut commit_timer_state) = states
-    //             .data_map
-    //             .get::<CommitTimerBarrierStateUserData>()
-    //             .map(|commit_timer| commit_timer.lock().unwrap())
-    //         else {
-    //             return;
-    //         };
-    //
-    //         commit_timer_state.signal_until(frame_target);
-    //         let client = surface.client().unwrap();
-    //         clients.insert(client.id(), client);
-    //     }
-    //
-    //     let mut clients = HashMap::<ClientId, Client>::new();
-    //     self.space.elements().for_each(|window| {
-    //         window.with_surfaces(|surface, states| {
-    //             processor(&mut clients, frame_target, surface, states);
-    //         });
-    //     });
-    //
-    //     let map = desktop::layer_map_for_output(output);
-    //     for layer_surface in map.layers() {
-    //         layer_surface.with_surfaces(|surface,states|{
-    //             processor(&mut clients, frame_target, surface, states);
-    //         });
-    //     }
-    //
-    //     // map is a mutex lock
-    //     drop(map);
-    //
-    //     // if let CursorImageStatus::Surface(ref surface) = self.cursor_status {
-    //     //     desktop::utils::with_surfaces_surface_tree(surface, |surface,states|{
-    //     //         processor(&mut clients, frame_target, surface, states);
-    //     //     });
-    //     // }
-    //
-    //     // if let Some(surface) = self.dnd_icon.as_ref().map(|icon|&icon.surface) {
-    //     //     desktop::utils::with_surfaces_surface_tree(surface, |surface,states|{
-    //     //         processor(&mut clients, frame_target, surface, states);
-    //     //     });
-    //     // }
-    //
-    //     let dh = self.dh.clone();
-    //     for client in clients.into_values() {
-    //         self.client_compositor_state(&client).blocker_cleared(self, &dh);
-    //     }
-    // }
-
-    // pub fn post_repaint(
-    //     &mut self,
-    //     output: &Output,
-    //     time: impl Into<Duration>,
-    //     dmabuf_feedback: Option<SurfaceDmabufFeedback>,
-    //     render_element_states: &RenderElementStates,
-    // ) {
-    //     let time = time.into();
-    //     let throttle = Some(Duration::from_secs(1));
-    //
-    //     let mut clients = HashMap::new();
-    //
-    //     for window in self.space.elements() {
-    //         window.with_surfaces(|surface, states|{
-    //             let primary_scanout_output = desktop::utils::surface_primary_scanout_output(surface, states);
-    //
-    //             if let Some(output) = primary_scanout_output.as_ref() {
-    //                 smithay::wayland::fractional_scale::with_fractional_scale(states, |fraction_scale|{
-    //                     fraction_scale.set_preferred_scale(output.current_scale().fractional_scale());
-    //                 })
-    //             }
-    //
-    //             if primary_scanout_output
-    //                 .as_ref()
-    //                 .map(|o|o==output)
-    //                 .unwrap_or(true)
-    //             {
-    //                 let fifo_barrier = states
-    //                     .cached_state
-    //                     .get::<FifoBarrierCachedState>()
-    //                     .current()
-    //                     .barrier
-    //                     .take();
-    //
-    //                 if let Some(fifo_barrier) = fifo_barrier {
-    //                     fifo_barrier.signal();
-    //                     let client = surface.client().unwrap();
-    //                     clients.insert(client.id(), client);
-    //                 }
-    //             }
-    //         });
-    //
-    //         if self.space.outputs_for_element(window).contains(output) {
-    //             window.send_frame(output, time, throttle, desktop::utils::surface_primary_scanout_output);
-    //             if let Some(dmabuf_feedback) = dmabuf_feedback.as_ref() {
-    //                 window.send_dmabuf_feedback(
-    //                     output,
-    //                     desktop::utils::surface_primary_scanout_output,
-    //                     |surface, _| {
-    //                         smithay::backend::renderer::element::utils::select_dmabuf_feedback(
-    //                             surface,
-    //                             render_element_states,
-    //                             &dmabuf_feedback.render_feedback,
-    //                             &dmabuf_feedback.scanout_feedback,
-    //                         )
-    //                     }
-    //                 );
-    //             }
-    //         }
-    //     }
-    //
-    //     let map = desktop::layer_map_for_output(output);
-    //     for layer_surface in map.layers() {
-    //         layer_surface.with_surfaces(|surface, states| {
-    //             let primary_scanout_output = desktop::utils::surface_primary_scanout_output(surface, states);
-    //
-    //             if let Some(output) = primary_scanout_output.as_ref() {
-    //                 smithay::wayland::fractional_scale::with_fractional_scale(states, |fraction_scale|{
-    //                     fraction_scale.set_preferred_scale(output.current_scale().fractional_scale());
-    //                 })
-    //             }
-    //
-    //             if primary_scanout_output
-    //                 .as_ref()
-    //                 .map(|o|o==output)
-    //                 .unwrap_or(true)
-    //             {
-    //                 let fifo_barrier = states
-    //                     .cached_state
-    //                     .get::<FifoBarrierCachedState>()
-    //                     .current()
-    //                     .barrier
-    //                     .take();
-    //
-    //                 if let Some(fifo_barrier) = fifo_barrier {
-    //                     fifo_barrier.signal();
-    //                     let client = surface.client().unwrap();
-    //                     clients.insert(client.id(), client);
-    //                 }
-    //             }
-    //         });
-    //
-    //         layer_surface.send_frame(output, time, throttle, desktop::utils::surface_primary_scanout_output);
-    //         if let Some(dmabuf_feedback) = dmabuf_feedback.as_ref() {
-    //             layer_surface.send_dmabuf_feedback(
-    //                 output,
-    //                 desktop::utils::surface_primary_scanout_output,
-    //                 |surface, _| {
-    //                     smithay::backend::renderer::element::utils::select_dmabuf_feedback(
-    //                         surface,
-    //                         render_element_states,
-    //                         &dmabuf_feedback.render_feedback,
-    //                         &dmabuf_feedback.scanout_feedback,
-    //                     )
-    //                 }
-    //             );
-    //         }
-    //     }
-    //
-    //     drop(map);
-    //
-    //     // if let CursorImageStatus::Surface(ref surface) = self.cursor_status {
-    //     //     desktop::utils::with_surfaces_surface_tree(surface, |surface, states| {
-    //     //         let primary_scanout_output = desktop::utils::surface_primary_scanout_output(surface, states);
-    //     //
-    //     //         if let Some(output) = primary_scanout_output.as_ref() {
-    //     //             smithay::wayland::fractional_scale::with_fractional_scale(states, |fraction_scale|{
-    //     //                 fraction_scale.set_preferred_scale(output.current_scale().fractional_scale());
-    //     //             })
-    //     //         }
-    //     //
-    //     //         if primary_scanout_output
-    //     //             .as_ref()
-    //     //             .map(|o|o==output)
-    //     //             .unwrap_or(true)
-    //     //         {
-    //     //             let fifo_barrier = states
-    //     //                 .cached_state
-    //     //                 .get::<FifoBarrierCachedState>()
-    //     //                 .current()
-    //     //                 .barrier
-    //     //                 .take();
-    //     //
-    //     //             if let Some(fifo_barrier) = fifo_barrier {
-    //     //                 fifo_barrier.signal();
-    //     //                 let client = surface.client().unwrap();
-    //     //                 clients.insert(client.id(), client);
-    //     //             }
-    //     //         }
-    //     //     });
-    //     // }
-    //
-    //     // if let Some(surface) = self.dnd_icon.as_ref().map(|icon| &icon.surface) {
-    //     //     desktop::utils::with_surfaces_surface_tree(surface, |surface, states| {
-    //     //         let primary_scanout_output = desktop::utils::surface_primary_scanout_output(surface, states);
-    //     //
-    //     //         if let Some(output) = primary_scanout_output.as_ref() {
-    //     //             smithay::wayland::fractional_scale::with_fractional_scale(states, |fraction_scale|{
-    //     //                 fraction_scale.set_preferred_scale(output.current_scale().fractional_scale());
-    //     //             })
-    //     //         }
-    //     //
-    //     //         if primary_scanout_output
-    //     //             .as_ref()
-    //     //             .map(|o|o==output)
-    //     //             .unwrap_or(true)
-    //     //         {
-    //     //             let fifo_barrier = states
-    //     //                 .cached_state
-    //     //                 .get::<FifoBarrierCachedState>()
-    //     //                 .current()
-    //     //                 .barrier
-    //     //                 .take();
-    //     //
-    //     //             if let Some(fifo_barrier) = fifo_barrier {
-    //     //                 fifo_barrier.signal();
-    //     //                 let client = surface.client().unwrap();
-    //     //                 clients.insert(client.id(), client);
-    //     //             }
-    //     //         }
-    //     //     });
-    //     // }
-    //
-    //     let dh = self.dh.clone();
-    //     for client in clients.into_values() {
-    //         self.client_compositor_state(&client).blocker_cleared(self, &dh);
-    //     }
-    // }
 }
 
 pub struct FrontendSources {
@@ -333,7 +283,7 @@ pub mod utils {
     use super::*;
 
     pub fn update_primary_scanout_output(
-        space: &Space<Window>,
+        space: &Space<WindowElement>,
         output: &Output,
         dnd_icon: Option<&DndIcon>,
         cursor_status: &mut CursorImageStatus,
@@ -389,9 +339,13 @@ pub mod utils {
         }
     }
 
+    /// collect feedback of surfaces whose primary scanout output is `output`
+    ///
+    /// surfaces that are also visible on another output are left for that output when it
+    /// is their primary one, so the reported refresh is of the output that presented them
     pub fn take_presentation_feedback(
         output: &Output,
-        space: &Space<Window>,
+        space: &Space<WindowElement>,
         render_element_states: &RenderElementStates,
     ) -> OutputPresentationFeedback {
         let mut output_presentation_feedback = OutputPresentationFeedback::new(output);
@@ -407,6 +361,16 @@ pub mod utils {
             }
         }
 
+        let map = desktop::layer_map_for_output(output);
+        for layer_surface in map.layers() {
+            layer_surface.take_presentation_feedback(
+                &mut output_presentation_feedback,
+                desktop::utils::surface_primary_scanout_output,
+                |surface,_|
+                desktop::utils::surface_presentation_feedback_flags_from_states(surface,render_element_states)
+            );
+        }
+
         output_presentation_feedback
     }
 }
@@ -0,0 +1,4 @@
+use crate::Trayle;
+
+/// no handler required, commit timer barriers are signaled in `render::pre_repaint`
+smithay::delegate_commit_timing!(Trayle);
@@ -23,6 +23,10 @@ impl CompositorHandler for Trayle {
         panic!("unknown client data type")
     }
 
+    fn new_surface(&mut self, surface: &WlSurface) {
+        self.surface_new(surface);
+    }
+
     fn commit(&mut self, surface: &WlSurface) {
         self.surface_commit(surface);
     }
@@ -0,0 +1,4 @@
+use crate::Trayle;
+
+/// no handler required, the hint is double buffered surface state read when rendering
+smithay::delegate_content_type!(Trayle);
@@ -0,0 +1,5 @@
+use crate::Trayle;
+
+// the requested shape is set as `CursorImageStatus::Named` through `SeatHandler::cursor_image`,
+// or `TabletSeatHandler::tablet_tool_image` for tablet tools
+smithay::delegate_cursor_shape!(Trayle);
@@ -0,0 +1,34 @@
+#![allow(unused_variables)]
+use crate::{frontend::DndIcon, Trayle};
+use smithay::{
+    input::Seat,
+    reexports::wayland_server::protocol::{wl_data_source::WlDataSource, wl_surface::WlSurface},
+    wayland::selection::{
+        data_device::{ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler},
+        SelectionHandler,
+    },
+};
+
+smithay::delegate_data_device!(Trayle);
+
+impl DataDeviceHandler for Trayle {
+    fn data_device_state(&self) -> &DataDeviceState {
+        &self.frontend.data_device_state
+    }
+}
+
+/// selection is only offered to the keyboard focused client, see `SeatHandler::focus_changed`
+impl SelectionHandler for Trayle {
+    type SelectionUserData = ();
+}
+
+impl ClientDndGrabHandler for Trayle {
+    fn started(&mut self, source: Option<WlDataSource>, icon: Option<WlSurface>, seat: Seat<Self>) {
+        self.frontend.dnd_icon = icon.map(|surface|DndIcon { surface, offset: (0,0).into() });
+    }
+
+    fn dropped(&mut self, target: Option<WlSurface>, validated: bool, seat: Seat<Self>) {
+        self.frontend.dnd_icon = None;
+    }
+}
+impl ServerDndGrabHandler for Trayle { }
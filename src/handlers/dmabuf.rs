@@ -11,7 +11,8 @@ smithay::delegate_dmabuf!(Trayle);
 /// in case of tty backend, when the initial configuration of primary gpu
 impl DmabufHandler for Trayle {
     fn dmabuf_state(&mut self) -> &mut DmabufState {
-        &mut self.frontend.dmabuf_state
+        // the state that owns the global, otherwise buffer params are rejected
+        &mut self.backend.dmabuf_state.0
     }
 
     fn dmabuf_imported(&mut self, _global: &DmabufGlobal, dmabuf: Dmabuf, notifier: ImportNotifier) {
@@ -25,7 +26,7 @@ impl DmabufHandler for Trayle {
                 }
             },
             Err(err) => {
-                tracing::error!("{err:?}");
+                tracing::warn!("failed to import dmabuf: {err:?}");
                 notifier.failed();
             },
         }
@@ -1,24 +1,37 @@
 use smithay::{
     backend::drm::DrmNode,
+    reexports::drm::control::Device as _,
     wayland::drm_lease::{
         DrmLease, DrmLeaseBuilder, DrmLeaseHandler, DrmLeaseRequest, DrmLeaseState, LeaseRejected,
     },
 };
 
-use crate::Trayle;
+use crate::{trayle::utils, Trayle};
 
 smithay::delegate_drm_lease!(Trayle);
 
 impl DrmLeaseHandler for Trayle {
+    /// requests come through a lease global, which is either on a tracked device, or
+    /// disabled and retired along with its removed device
+    ///
+    /// objects outliving a retired global are answered by the inert fallback
     fn drm_lease_state(&mut self, node: DrmNode) -> &mut DrmLeaseState {
-        todo!()
-        // self.backend
-        //     .devices
-        //     .get_mut(&node)
-        //     .unwrap()
-        //     .leasing_global
-        //     .as_mut()
-        //     .unwrap()
+        // lease globals are only created for drm devices of a tty
+        let Some(tty) = self.backend.state.tty_mut() else {
+            unreachable!("drm lease request without a tty");
+        };
+        if let Some(state) = tty.devices.get_mut(&node).and_then(|device|device.drm_lease_state.as_mut()) {
+            return state;
+        }
+        if let Some((state,_)) = tty.retired_lease_states.get_mut(&node) {
+            return state;
+        }
+        tracing::warn!("drm lease request for {node} after its global is removed, ignored");
+        match tty.lease_fallback.as_mut() {
+            Some(state) => state,
+            // device lease globals are only created once the fallback exists
+            None => unreachable!("drm lease request without any lease global"),
+        }
     }
 
     fn lease_request(
@@ -26,53 +39,71 @@ impl DrmLeaseHandler for Trayle {
         node: DrmNode,
         request: DrmLeaseRequest,
     ) -> Result<DrmLeaseBuilder, LeaseRejected> {
-        todo!()
-        // let backend = self.backend
-        //     .backends
-        //     .get(&node)
-        //     .ok_or_else(LeaseRejected::default)?;
-        //
-        // let drm_device = backend.drm_output_manager.device();
-        // let mut builder = DrmLeaseBuilder::new(drm_device);
-        // for conn in request.connectors {
-        //     let Some((_,crtc)) = backend.non_desktop_connectors.iter().find(|(handle,_)|*handle == conn) else {
-        //         tracing::warn!(?conn, "lease request for desktop connector denied");
-        //         return Err(LeaseRejected::default());
-        //     };
-        //     builder.add_connector(conn);
-        //     builder.add_crtc(*crtc);
-        //     let planes = drm_device.planes(crtc).map_err(LeaseRejected::with_cause)?;
-        //     let (primary_plane,primary_plane_claim) = planes
-        //         .primary
-        //         .iter()
-        //         .find_map(|plane|{
-        //             drm_device
-        //                 .claim_plane(plane.handle, *crtc)
-        //                 .map(|claim|(plane,claim))
-        //         })
-        //         .ok_or_else(LeaseRejected::default)?;
-        //     builder.add_plane(primary_plane.handle, primary_plane_claim);
-        //
-        //     if let Some((cursor, claim)) = planes.cursor.iter().find_map(|plane| {
-        //         drm_device
-        //             .claim_plane(plane.handle, *crtc)
-        //             .map(|claim| (plane, claim))
-        //     }) {
-        //         builder.add_plane(cursor.handle, claim)
-        //     }
-        // }
-        //
-        // Ok(builder)
+        let Some(device) = self.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) else {
+            tracing::warn!("lease request for removed device {node} denied");
+            return Err(LeaseRejected::default());
+        };
+
+        let drm_device = device.drm_output_manager.device();
+        let mut builder = DrmLeaseBuilder::new(drm_device);
+        for conn in request.connectors.iter().copied() {
+            let Some((_,crtc)) = device.non_desktop_connectors.iter().find(|(handle,_)|*handle == conn) else {
+                tracing::warn!(?conn, "lease request for desktop connector denied");
+                return Err(LeaseRejected::default());
+            };
+            let name = drm_device
+                .get_connector(conn, false)
+                .map(|info|utils::connector_name(&info))
+                .map_err(LeaseRejected::with_cause)?;
+            let edid_name = device.monitor_infos.get(&conn).and_then(|info|info.edid_name());
+            if !self.config.output_leasable(&name, edid_name.as_ref()) {
+                tracing::warn!("lease request for {name} denied by config");
+                return Err(LeaseRejected::default());
+            }
+            builder.add_connector(conn);
+            builder.add_crtc(*crtc);
+
+            let planes = drm_device.planes(crtc).map_err(LeaseRejected::with_cause)?;
+            let (primary_plane,primary_plane_claim) = planes
+                .primary
+                .iter()
+                .find_map(|plane|{
+                    drm_device
+                        .claim_plane(plane.handle, *crtc)
+                        .map(|claim|(plane,claim))
+                })
+                .ok_or_else(LeaseRejected::default)?;
+            builder.add_plane(primary_plane.handle, primary_plane_claim);
+
+            if let Some((cursor,claim)) = planes.cursor.iter().find_map(|plane|{
+                drm_device
+                    .claim_plane(plane.handle, *crtc)
+                    .map(|claim|(plane,claim))
+            }) {
+                builder.add_plane(cursor.handle, claim);
+            }
+        }
+
+        device.requested_lease = request.connectors;
+        Ok(builder)
     }
 
     fn new_active_lease(&mut self, node: DrmNode, lease: DrmLease) {
-        // let backend = self.backend.backends.get_mut(&node).unwrap();
-        // backend.active_leases.push(lease);
+        // device removed meanwhile, dropping the lease revokes it
+        let Some(device) = self.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) else {
+            tracing::warn!("lease granted on removed device {node}, revoking");
+            return;
+        };
+        let connectors = std::mem::take(&mut device.requested_lease);
+        device.leased_connectors.insert(lease.id(), connectors);
+        device.active_leases.push(lease);
     }
 
     fn lease_destroyed(&mut self, node: DrmNode, lease_id: u32) {
-        // let backend = self.backend.backends.get_mut(&node).unwrap();
-        // backend.active_leases.retain(|l|l.id() != lease_id);
+        let Some(device) = self.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) else {
+            return;
+        };
+        device.active_leases.retain(|lease|lease.id() != lease_id);
+        device.leased_connectors.remove(&lease_id);
     }
 }
-
@@ -0,0 +1,4 @@
+use crate::Trayle;
+
+/// no handler required, fifo barriers are signaled in `render::post_repaint`
+smithay::delegate_fifo!(Trayle);
@@ -0,0 +1,44 @@
+use crate::Trayle;
+use smithay::{
+    desktop::utils::surface_primary_scanout_output,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::{
+        compositor,
+        fractional_scale::{with_fractional_scale, FractionalScaleHandler},
+    },
+};
+
+smithay::delegate_fractional_scale!(Trayle);
+
+impl FractionalScaleHandler for Trayle {
+    fn new_fractional_scale(&mut self, surface: WlSurface) {
+        let mut root = surface.clone();
+        while let Some(parent) = compositor::get_parent(&root) {
+            root = parent;
+        }
+
+        // output the surface is scanned out on, or the output its window is on,
+        // fallback to the first output
+        let output = compositor::with_states(&surface, |states|{
+            surface_primary_scanout_output(&surface, states)
+        })
+        .or_else(||{
+            let space = &self.frontend.space;
+            let window = space
+                .elements()
+                .find(|window|window.toplevel().map(|t|t.wl_surface() == &root).unwrap_or(false))?;
+            space.outputs_for_element(window).into_iter().next()
+        })
+        .or_else(||self.frontend.space.outputs().next().cloned());
+
+        let Some(output) = output else {
+            return;
+        };
+
+        compositor::with_states(&surface, |states|{
+            with_fractional_scale(states, |fractional_scale|{
+                fractional_scale.set_preferred_scale(output.current_scale().fractional_scale());
+            });
+        });
+    }
+}
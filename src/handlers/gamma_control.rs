@@ -0,0 +1,21 @@
+use crate::{
+    protocols::gamma_control::{GammaControlHandler, GammaControlManagerState},
+    Trayle,
+};
+use smithay::output::Output;
+
+crate::delegate_gamma_control!(Trayle);
+
+impl GammaControlHandler for Trayle {
+    fn gamma_control_manager_state(&mut self) -> &mut GammaControlManagerState {
+        &mut self.frontend.gamma_control_manager_state
+    }
+
+    fn gamma_size(&mut self, output: &Output) -> Option<u32> {
+        self.output_gamma_size(&output.name())
+    }
+
+    fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> anyhow::Result<()> {
+        self.set_output_gamma(&output.name(), ramp)
+    }
+}
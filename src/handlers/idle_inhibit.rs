@@ -0,0 +1,19 @@
+use crate::Trayle;
+use smithay::{
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::idle_inhibit::IdleInhibitHandler,
+};
+
+smithay::delegate_idle_inhibit!(Trayle);
+
+impl IdleInhibitHandler for Trayle {
+    fn inhibit(&mut self, surface: WlSurface) {
+        self.frontend.idle_inhibiting_surfaces.insert(surface);
+        self.update_idle_inhibit();
+    }
+
+    fn uninhibit(&mut self, surface: WlSurface) {
+        self.frontend.idle_inhibiting_surfaces.remove(&surface);
+        self.update_idle_inhibit();
+    }
+}
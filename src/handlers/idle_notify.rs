@@ -0,0 +1,10 @@
+use crate::Trayle;
+use smithay::wayland::idle_notify::{IdleNotifierHandler, IdleNotifierState};
+
+smithay::delegate_idle_notify!(Trayle);
+
+impl IdleNotifierHandler for Trayle {
+    fn idle_notifier_state(&mut self) -> &mut IdleNotifierState<Self> {
+        &mut self.frontend.idle_notifier_state
+    }
+}
@@ -0,0 +1,38 @@
+use crate::Trayle;
+use smithay::{
+    desktop::{space::SpaceElement, PopupKind, PopupManager},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Rectangle},
+    wayland::input_method::{InputMethodHandler, PopupSurface},
+};
+
+smithay::delegate_input_method_manager!(Trayle);
+
+/// key events go to the input method while it grabs the keyboard, its commits and preedit
+/// are forwarded to the focused text input by smithay
+impl InputMethodHandler for Trayle {
+    /// candidate popups are placed by smithay at the text input cursor rectangle,
+    /// relative to [`InputMethodHandler::parent_geometry`]
+    fn new_popup(&mut self, surface: PopupSurface) {
+        if let Err(err) = self.frontend.popups.track_popup(PopupKind::InputMethod(surface)) {
+            tracing::warn!("failed to track input method popup: {err}");
+        }
+    }
+
+    fn popup_repositioned(&mut self, _: PopupSurface) {
+        self.mark_dirty();
+    }
+
+    fn dismiss_popup(&mut self, surface: PopupSurface) {
+        if let Some(parent) = surface.get_parent().map(|parent|parent.surface.clone()) {
+            let _ = PopupManager::dismiss_popup(&parent, &PopupKind::InputMethod(surface));
+        }
+    }
+
+    /// window geometry of the text input surface, excluding server side decoration
+    fn parent_geometry(&self, parent: &WlSurface) -> Rectangle<i32, Logical> {
+        self.window_for_surface(parent)
+            .map(|window|window.0.geometry())
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,32 @@
+use crate::Trayle;
+use smithay::wayland::keyboard_shortcuts_inhibit::{
+    KeyboardShortcutsInhibitHandler, KeyboardShortcutsInhibitState, KeyboardShortcutsInhibitor,
+};
+
+smithay::delegate_keyboard_shortcuts_inhibit!(Trayle);
+
+impl KeyboardShortcutsInhibitHandler for Trayle {
+    fn keyboard_shortcuts_inhibit_state(&mut self) -> &mut KeyboardShortcutsInhibitState {
+        &mut self.frontend.keyboard_shortcuts_inhibit_state
+    }
+
+    /// granted right away to the focused surface, other surfaces get it once focused,
+    /// see `SeatHandler::focus_changed`
+    fn new_inhibitor(&mut self, inhibitor: KeyboardShortcutsInhibitor) {
+        let focus = self.seat.get_keyboard().and_then(|keyboard|keyboard.current_focus());
+        if focus.as_ref() != Some(inhibitor.wl_surface()) {
+            return;
+        }
+        inhibitor.activate();
+        self.frontend.shortcuts_inhibitor = Some(inhibitor);
+    }
+
+    fn inhibitor_destroyed(&mut self, inhibitor: KeyboardShortcutsInhibitor) {
+        let is_current = self.frontend.shortcuts_inhibitor
+            .as_ref()
+            .is_some_and(|current|current.wl_surface() == inhibitor.wl_surface());
+        if is_current {
+            self.frontend.shortcuts_inhibitor = None;
+        }
+    }
+}
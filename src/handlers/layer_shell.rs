@@ -0,0 +1,71 @@
+#![allow(unused_variables)]
+use crate::Trayle;
+use smithay::{
+    desktop::{layer_map_for_output, LayerSurface, PopupKind},
+    output::Output,
+    reexports::wayland_server::protocol::wl_output::WlOutput,
+    utils::SERIAL_COUNTER,
+    wayland::shell::{
+        wlr_layer::{Layer, LayerSurface as WlrLayerSurface, WlrLayerShellHandler, WlrLayerShellState},
+        xdg::PopupSurface,
+    },
+};
+
+smithay::delegate_layer_shell!(Trayle);
+
+impl WlrLayerShellHandler for Trayle {
+    fn shell_state(&mut self) -> &mut WlrLayerShellState {
+        &mut self.frontend.layer_shell_state
+    }
+
+    fn new_layer_surface(
+        &mut self,
+        surface: WlrLayerSurface,
+        wl_output: Option<WlOutput>,
+        layer: Layer,
+        namespace: String,
+    ) {
+        let output = wl_output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(||self.frontend.space.outputs().next().cloned());
+
+        let Some(output) = output else {
+            tracing::warn!("no output for layer surface {namespace:?}, closing");
+            surface.send_close();
+            return;
+        };
+
+        let mut map = layer_map_for_output(&output);
+        if let Err(err) = map.map_layer(&LayerSurface::new(surface, namespace)) {
+            tracing::error!("failed to map layer surface: {err}");
+        }
+    }
+
+    fn new_popup(&mut self, parent: WlrLayerSurface, popup: PopupSurface) {
+        if let Err(err) = self.frontend.popups.track_popup(PopupKind::Xdg(popup)) {
+            tracing::warn!("failed to track layer popup: {err}");
+        }
+    }
+
+    fn layer_destroyed(&mut self, surface: WlrLayerSurface) {
+        let layer = self.frontend.space.outputs().find_map(|output|{
+            let map = layer_map_for_output(output);
+            let layer = map.layers().find(|layer|layer.layer_surface() == &surface).cloned()?;
+            Some((output.clone(), layer))
+        });
+
+        if let Some((output, layer)) = layer {
+            let mut map = layer_map_for_output(&output);
+            map.unmap_layer(&layer);
+            drop(map);
+            self.mark_dirty();
+        }
+
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            if keyboard.current_focus().as_ref() == Some(surface.wl_surface()) {
+                keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
+            }
+        }
+    }
+}
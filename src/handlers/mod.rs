@@ -1,10 +1,37 @@
 mod compositor;
 mod xdg_shell;
+mod xdg_decoration;
+mod layer_shell;
 mod seat;
+mod data_device;
 mod shm;
 mod buffer;
+mod single_pixel_buffer;
 mod dmabuf;
 mod output;
+mod fractional_scale;
+mod viewporter;
+mod presentation;
+mod commit_timing;
+mod fifo;
+mod gamma_control;
+mod screencopy;
+mod idle_notify;
+mod idle_inhibit;
+mod output_management;
+mod cursor_shape;
+mod xdg_activation;
+mod security_context;
+mod relative_pointer;
+mod pointer_constraints;
+mod pointer_gestures;
+mod tablet_manager;
+mod text_input;
+mod input_method;
+mod virtual_keyboard;
+mod virtual_pointer;
+mod keyboard_shortcuts_inhibit;
+mod content_type;
 
 mod drm_lease;
 mod drm_syncobj;
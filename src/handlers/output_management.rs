@@ -0,0 +1,22 @@
+use crate::{
+    protocols::output_management::{OutputConfiguration, OutputManagementHandler, OutputManagementState},
+    Trayle,
+};
+
+crate::delegate_output_management!(Trayle);
+
+impl OutputManagementHandler for Trayle {
+    fn output_management_state(&mut self) -> &mut OutputManagementState {
+        &mut self.frontend.output_management_state
+    }
+
+    fn apply_configuration(&mut self, configuration: OutputConfiguration, test_only: bool) -> bool {
+        match self.apply_output_configuration(configuration, test_only) {
+            Ok(()) => true,
+            Err(err) => {
+                tracing::warn!("output configuration rejected: {err:#}");
+                false
+            }
+        }
+    }
+}
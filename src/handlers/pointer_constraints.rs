@@ -0,0 +1,46 @@
+use crate::{trayle::utils, Trayle};
+use smithay::{
+    input::pointer::PointerHandle,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Point},
+    wayland::pointer_constraints::{with_pointer_constraint, PointerConstraintsHandler},
+};
+
+smithay::delegate_pointer_constraints!(Trayle);
+
+impl PointerConstraintsHandler for Trayle {
+    /// constraint of a surface under the pointer applies right away,
+    /// otherwise it is activated when the pointer enters its region, see `input::pointer_motion`
+    fn new_constraint(&mut self, surface: &WlSurface, pointer: &PointerHandle<Self>) {
+        if pointer.current_focus().as_ref() != Some(surface) {
+            return;
+        }
+        with_pointer_constraint(surface, pointer, |constraint|{
+            if let Some(constraint) = constraint {
+                constraint.activate();
+            }
+        });
+    }
+
+    /// a locked pointer is placed where the client expects it once unlocked
+    fn cursor_position_hint(
+        &mut self,
+        surface: &WlSurface,
+        pointer: &PointerHandle<Self>,
+        location: Point<f64, Logical>,
+    ) {
+        let is_active = with_pointer_constraint(surface, pointer, |constraint|{
+            constraint.is_some_and(|constraint|constraint.is_active())
+        });
+        if !is_active {
+            return;
+        }
+
+        let Some((_,surface_location)) = utils::surface_under(&self.frontend.space, pointer.current_location())
+            .filter(|(under,_)|under == surface)
+        else {
+            return;
+        };
+        pointer.set_location(surface_location + location);
+    }
+}
@@ -0,0 +1,4 @@
+use crate::Trayle;
+
+/// no handler required, gestures are forwarded by `input::handle`
+smithay::delegate_pointer_gestures!(Trayle);
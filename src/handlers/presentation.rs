@@ -0,0 +1,5 @@
+use crate::Trayle;
+
+/// no handler required, feedbacks are collected when a frame is queued, see
+/// `take_presentation_feedback`, and presented on its vblank in `render::frame_finish`
+smithay::delegate_presentation!(Trayle);
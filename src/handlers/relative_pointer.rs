@@ -0,0 +1,4 @@
+use crate::Trayle;
+
+/// no handler required, deltas are sent by `input::pointer_motion`
+smithay::delegate_relative_pointer!(Trayle);
@@ -0,0 +1,26 @@
+use crate::{
+    protocols::screencopy::{Screencopy, ScreencopyHandler},
+    trayle::device::UdevOutputId,
+    Trayle,
+};
+
+crate::delegate_screencopy!(Trayle);
+
+impl ScreencopyHandler for Trayle {
+    fn frame(&mut self, screencopy: Screencopy) {
+        let Some(id) = screencopy.output().user_data().get::<UdevOutputId>() else {
+            return;
+        };
+
+        let Some(surface) = self.backend.state
+            .tty_mut()
+            .and_then(|tty|tty.devices.get_mut(&id.device_id))
+            .and_then(|device|device.surfaces.get_mut(&id.crtc))
+        else {
+            return;
+        };
+
+        // copied on the next render of the output
+        surface.screencopies.push(screencopy);
+    }
+}
@@ -1,8 +1,13 @@
 #![allow(unused_variables)]
 use crate::Trayle;
 use smithay::{
-    input::{SeatHandler, SeatState},
-    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    input::{keyboard::LedState, pointer::CursorImageStatus, Seat, SeatHandler, SeatState},
+    reexports::wayland_server::{protocol::wl_surface::WlSurface, Resource},
+    wayland::{
+        keyboard_shortcuts_inhibit::KeyboardShortcutsInhibitorSeat,
+        pointer_constraints::with_pointer_constraint,
+        selection::data_device::set_data_device_focus,
+    },
 };
 
 smithay::delegate_seat!(Trayle);
@@ -15,5 +20,60 @@ impl SeatHandler for Trayle {
     fn seat_state(&mut self) -> &mut SeatState<Self> {
         &mut self.frontend.seat_state
     }
+
+    fn cursor_image(&mut self, seat: &Seat<Self>, image: CursorImageStatus) {
+        self.frontend.cursor_status = image;
+        self.mark_dirty();
+    }
+
+    fn led_state_changed(&mut self, seat: &Seat<Self>, led_state: LedState) {
+        // caps lock, num lock, etc, toggled by key input
+        for keyboard in self.backend.keyboards.iter_mut() {
+            keyboard.led_update(led_state.into());
+        }
+    }
+
+    fn focus_changed(&mut self, seat: &Seat<Self>, focused: Option<&WlSurface>) {
+        // remembered for when the workspace is shown again
+        if let Some(window) = focused.and_then(|surface|self.window_for_surface(surface)) {
+            // order is kept while cycling, and committed when the cycle ends
+            if self.frontend.cycle.is_none() {
+                self.frontend.mru.retain(|w|w != &window);
+                self.frontend.mru.insert(0, window.clone());
+            }
+            self.frontend.workspace().last_focused = Some(window);
+
+            // deferred, the pointer may be in use by the grab that changed the focus
+            if self.config.warp_cursor_on_focus {
+                self.handle.insert_idle(|trayle|trayle.center_pointer_on_focused(true));
+            }
+        }
+
+        // selection follows keyboard focus
+        let client = focused.and_then(|surface|self.dh.get_client(surface.id()).ok());
+        set_data_device_focus(&self.dh, seat, client.clone());
+
+        // shortcuts are only inhibited while the inhibiting surface has keyboard focus
+        if let Some(inhibitor) = self.frontend.shortcuts_inhibitor.take() {
+            inhibitor.deactivate();
+        }
+        if let Some(inhibitor) = focused.and_then(|surface|seat.keyboard_shortcuts_inhibitor_for_surface(surface)) {
+            inhibitor.activate();
+            self.frontend.shortcuts_inhibitor = Some(inhibitor);
+        }
+
+        // pointer is released from a client that lost keyboard focus, eg: a game on `Alt+Tab`
+        let pointer = self.pointer.clone();
+        if let Some(pointer_focus) = pointer.current_focus() {
+            let pointer_client = self.dh.get_client(pointer_focus.id()).ok();
+            if pointer_client != client {
+                with_pointer_constraint(&pointer_focus, &pointer, |constraint|{
+                    if let Some(constraint) = constraint {
+                        constraint.deactivate();
+                    }
+                });
+            }
+        }
+    }
 }
 
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use crate::{trayle::ClientState, Trayle};
+use smithay::wayland::security_context::{
+    SecurityContext, SecurityContextHandler, SecurityContextListenerSource,
+};
+
+smithay::delegate_security_context!(Trayle);
+
+impl SecurityContextHandler for Trayle {
+    /// clients of the sandbox socket are tagged with its `security_context`,
+    /// the source is removed once the sandbox closes the socket
+    fn context_created(&mut self, source: SecurityContextListenerSource, security_context: SecurityContext) {
+        tracing::info!(
+            sandbox_engine = ?security_context.sandbox_engine,
+            app_id = ?security_context.app_id,
+            instance_id = ?security_context.instance_id,
+            "security context created",
+        );
+
+        let result = self.handle.insert_source(source, move|stream,_,trayle|{
+            let state = Arc::new(ClientState {
+                security_context: Some(security_context.clone()),
+                ..ClientState::default()
+            });
+            if let Err(err) = trayle.dh.insert_client(stream, state) {
+                tracing::warn!("failed to add sandboxed wayland client: {err}");
+            }
+        });
+
+        if let Err(err) = result {
+            tracing::warn!("failed to listen on security context socket: {err}");
+        }
+    }
+}
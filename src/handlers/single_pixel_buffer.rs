@@ -0,0 +1,5 @@
+use crate::Trayle;
+
+/// no handler required, surface render elements draw a single pixel buffer as a solid color
+/// stretched to the surface size, without importing it as a texture
+smithay::delegate_single_pixel_buffer!(Trayle);
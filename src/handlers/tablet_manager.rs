@@ -0,0 +1,15 @@
+use crate::Trayle;
+use smithay::{
+    input::pointer::CursorImageStatus,
+    wayland::tablet_manager::{TabletSeatHandler, TabletToolDescriptor},
+};
+
+smithay::delegate_tablet_manager!(Trayle);
+
+impl TabletSeatHandler for Trayle {
+    /// tools share the pointer cursor, they move it along with them
+    fn tablet_tool_image(&mut self, _: &TabletToolDescriptor, image: CursorImageStatus) {
+        self.frontend.cursor_status = image;
+        self.mark_dirty();
+    }
+}
@@ -0,0 +1,4 @@
+use crate::Trayle;
+
+/// no handler required, text inputs follow keyboard focus and are fed by the input method
+smithay::delegate_text_input_manager!(Trayle);
@@ -0,0 +1,8 @@
+use crate::Trayle;
+
+/// no handler required, viewport source and destination are applied to the surface state on
+/// commit by [`on_commit_buffer_handler`], and honored by `WaylandSurfaceRenderElement` and by
+/// the window geometry and bbox the layout and grabs are built on
+///
+/// [`on_commit_buffer_handler`]: smithay::backend::renderer::utils::on_commit_buffer_handler
+smithay::delegate_viewporter!(Trayle);
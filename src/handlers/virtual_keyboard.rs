@@ -0,0 +1,5 @@
+use crate::Trayle;
+
+/// no handler required, keys are sent to the focused client with the virtual keyboard keymap,
+/// bypassing keybindings
+smithay::delegate_virtual_keyboard_manager!(Trayle);
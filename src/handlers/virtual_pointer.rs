@@ -0,0 +1,29 @@
+use crate::{
+    protocols::virtual_pointer::{VirtualPointerHandler, VirtualPointerInputBackend},
+    trayle::{callbacks, input},
+    Trayle,
+};
+use smithay::backend::input::{AbsolutePositionEvent, Event, InputEvent};
+
+crate::delegate_virtual_pointer!(Trayle);
+
+impl VirtualPointerHandler for Trayle {
+    /// handled like a real pointer, absolute motion of a pointer bound to an output is mapped
+    /// to that output instead of the one under the pointer
+    fn virtual_pointer_event(&mut self, event: InputEvent<VirtualPointerInputBackend>) {
+        callbacks::activity(self);
+
+        if let InputEvent::PointerMotionAbsolute { event } = &event {
+            let output_geometry = event
+                .output()
+                .and_then(|output|self.frontend.space.output_geometry(output));
+            if let Some(geometry) = output_geometry {
+                let location = geometry.loc.to_f64() + event.position_transformed(geometry.size);
+                input::pointer_motion_to(location, event.time_msec(), self);
+                return;
+            }
+        }
+
+        input::handle(event, self);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::{config::ActivationPolicy, Trayle};
+use smithay::{
+    input::Seat,
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    wayland::xdg_activation::{
+        XdgActivationHandler, XdgActivationState, XdgActivationToken, XdgActivationTokenData,
+    },
+};
+
+smithay::delegate_xdg_activation!(Trayle);
+
+impl XdgActivationHandler for Trayle {
+    fn activation_state(&mut self) -> &mut XdgActivationState {
+        &mut self.frontend.xdg_activation_state
+    }
+
+    /// with [`ActivationPolicy::Smart`], only tokens requested with the serial of an input event
+    /// the focused client received since it got keyboard focus are valid
+    fn token_created(&mut self, _token: XdgActivationToken, data: XdgActivationTokenData) -> bool {
+        match self.config.activation {
+            ActivationPolicy::Always => true,
+            ActivationPolicy::Never => false,
+            ActivationPolicy::Smart => {
+                let Some((serial,seat)) = data.serial else {
+                    return false;
+                };
+                let Some(keyboard) = self.seat.get_keyboard() else {
+                    return false;
+                };
+                Seat::<Trayle>::from_resource(&seat).as_ref() == Some(&self.seat)
+                    && keyboard.last_enter().is_some_and(|last_enter|serial.is_no_older_than(&last_enter))
+            }
+        }
+    }
+
+    fn request_activation(
+        &mut self,
+        _token: XdgActivationToken,
+        token_data: XdgActivationTokenData,
+        surface: WlSurface,
+    ) {
+        let stale = token_data.timestamp.elapsed() > ActivationPolicy::TOKEN_TIMEOUT;
+        match self.config.activation {
+            ActivationPolicy::Never => return,
+            ActivationPolicy::Smart if stale => {
+                tracing::debug!("stale activation token denied");
+                return;
+            }
+            _ => {}
+        }
+
+        // the window may be on a hidden workspace
+        let id = self.frontend.workspaces
+            .iter()
+            .flat_map(|workspace|workspace.windows.iter())
+            .find(|(window,_)|window.toplevel().is_some_and(|toplevel|toplevel.wl_surface() == &surface))
+            .and_then(|(window,_)|window.id());
+        let Some(id) = id else {
+            return;
+        };
+
+        if let Err(err) = self.focus_window(id) {
+            tracing::warn!("failed to activate window: {err}");
+        }
+    }
+}
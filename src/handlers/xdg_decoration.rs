@@ -0,0 +1,46 @@
+use crate::Trayle;
+use smithay::{
+    reexports::wayland_protocols::xdg::decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode,
+    wayland::shell::xdg::{decoration::XdgDecorationHandler, ToplevelSurface},
+};
+
+smithay::delegate_xdg_decoration!(Trayle);
+
+/// the negotiated mode is applied to the window when the client acks the configure,
+/// see `XdgShellHandler::ack_configure`
+impl XdgDecorationHandler for Trayle {
+    fn new_decoration(&mut self, toplevel: ToplevelSurface) {
+        let mode = self.preferred_decoration_mode();
+        toplevel.with_pending_state(|state|{
+            state.decoration_mode = Some(mode);
+        });
+    }
+
+    fn request_mode(&mut self, toplevel: ToplevelSurface, mode: Mode) {
+        toplevel.with_pending_state(|state|{
+            state.decoration_mode = Some(mode);
+        });
+        if toplevel.is_initial_configure_sent() {
+            toplevel.send_pending_configure();
+        }
+    }
+
+    fn unset_mode(&mut self, toplevel: ToplevelSurface) {
+        let mode = self.preferred_decoration_mode();
+        toplevel.with_pending_state(|state|{
+            state.decoration_mode = Some(mode);
+        });
+        if toplevel.is_initial_configure_sent() {
+            toplevel.send_pending_configure();
+        }
+    }
+}
+
+impl Trayle {
+    fn preferred_decoration_mode(&self) -> Mode {
+        match self.config.decoration.server_side {
+            true => Mode::ServerSide,
+            false => Mode::ClientSide,
+        }
+    }
+}
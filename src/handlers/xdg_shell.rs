@@ -1,11 +1,37 @@
 #![allow(unused_variables)]
-use crate::Trayle;
+use crate::{
+    shell::{
+        elements::WindowElement,
+        grabs::{MoveGrab, ResizeEdge, ResizeGrab, ResizeState},
+        FullscreenSurface,
+    },
+    trayle::utils,
+    Trayle,
+};
 use smithay::{
-    desktop::Window,
-    reexports::wayland_server::protocol::wl_seat::WlSeat,
-    utils::Serial,
+    desktop::{
+        find_popup_root_surface, PopupKeyboardGrab, PopupKind, PopupPointerGrab,
+        PopupUngrabStrategy, Window,
+    },
+    input::{
+        pointer::{Focus, GrabStartData, PointerHandle},
+        Seat,
+    },
+    output::Output,
+    reexports::{
+        wayland_protocols::xdg::{
+            decoration::zv1::server::zxdg_toplevel_decoration_v1::Mode as DecorationMode,
+            shell::server::xdg_toplevel,
+        },
+        wayland_server::{
+            protocol::{wl_output::WlOutput, wl_seat::WlSeat, wl_surface::WlSurface},
+            Resource,
+        },
+    },
+    utils::{Logical, Point, Rectangle, Serial, SERIAL_COUNTER},
     wayland::shell::xdg::{
-        PopupSurface, PositionerState, ShellClient, ToplevelSurface, XdgShellHandler, XdgShellState,
+        Configure, PopupSurface, PositionerState, ShellClient, ToplevelSurface, XdgShellHandler,
+        XdgShellState,
     },
 };
 
@@ -17,23 +43,292 @@ impl XdgShellHandler for Trayle {
     }
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
-        let window = Window::new_wayland_window(surface);
-        self.frontend.space.map_element(window, (0,0), false);
+        // the output under the pointer is the active one
+        let space = &self.frontend.space;
+        let output = space
+            .output_under(self.pointer.current_location())
+            .next()
+            .or_else(||space.outputs().next())
+            .cloned();
+
+        // exclusive zone of layer surfaces is not usable for toplevels
+        let bounds = output.as_ref().and_then(|output|utils::output_usable_geometry(space, output));
+
+        let location = match bounds {
+            Some(bounds) => {
+                surface.with_pending_state(|state|{
+                    state.bounds = Some(bounds.size);
+                });
+                let size = surface.with_pending_state(|state|state.size);
+                utils::place_window(space, bounds, size)
+            }
+            None => (0,0).into(),
+        };
+
+        let wl_surface = surface.wl_surface().clone();
+        let window = WindowElement(Window::new_wayland_window(surface));
+        window.set_id(self.frontend.next_window_id);
+        self.frontend.next_window_id += 1;
+        self.frontend.space.map_element(window.clone(), location, true);
+        self.frontend.workspace().add(window.clone(), location);
+
+        if let Some(output) = output.filter(|_|self.config.tiling) {
+            self.frontend.layout.insert(&output, window);
+            self.arrange(&output);
+        }
+
+        // newly mapped window takes the keyboard focus
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, Some(wl_surface), SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    fn toplevel_destroyed(&mut self, surface: ToplevelSurface) {
+        // window may live in a hidden workspace
+        let window = self.frontend.workspaces
+            .iter()
+            .flat_map(|workspace|&workspace.windows)
+            .find(|(window,_)|window.toplevel() == Some(&surface))
+            .map(|(window,_)|window.clone());
+
+        if let Some(window) = window {
+            self.frontend.space.unmap_elem(&window);
+            for workspace in &mut self.frontend.workspaces {
+                workspace.remove(&window);
+            }
+            if let Some(output) = self.frontend.layout.remove(&window) {
+                self.arrange(&output);
+            }
+            self.mark_dirty();
+        }
+
+        // do not send keystrokes to a dead surface, the next window in stacking order takes over
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            if keyboard.current_focus().as_ref() == Some(surface.wl_surface()) {
+                self.focus_topmost_window();
+            }
+        }
+    }
+
+    fn move_request(&mut self, surface: ToplevelSurface, seat: WlSeat, serial: Serial) {
+        let Some((pointer, start_data, window, initial_window_location)) =
+            self.interactive_request(&surface, &seat, serial)
+        else {
+            return;
+        };
+
+        let grab = MoveGrab { start_data, window, initial_window_location };
+        pointer.set_grab(self, grab, serial, Focus::Clear);
+    }
+
+    fn resize_request(
+        &mut self,
+        surface: ToplevelSurface,
+        seat: WlSeat,
+        serial: Serial,
+        edges: xdg_toplevel::ResizeEdge,
+    ) {
+        let Some((pointer, start_data, window, initial_window_location)) =
+            self.interactive_request(&surface, &seat, serial)
+        else {
+            return;
+        };
+
+        let edges = ResizeEdge::from(edges);
+        if let Some(grab) = ResizeGrab::start(start_data, window, edges, initial_window_location) {
+            pointer.set_grab(self, grab, serial, Focus::Clear);
+        }
+    }
+
+    fn fullscreen_request(&mut self, surface: ToplevelSurface, wl_output: Option<WlOutput>) {
+        let Some(window) = self.window_for_surface(surface.wl_surface()) else {
+            return;
+        };
+
+        // requested output, or the one the window is currently on
+        let space = &self.frontend.space;
+        let Some(output) = wl_output
+            .as_ref()
+            .and_then(Output::from_resource)
+            .or_else(||space.outputs_for_element(&window).into_iter().next())
+            .or_else(||space.outputs().next().cloned())
+        else {
+            return;
+        };
+        let Some(geometry) = space.output_geometry(&output) else {
+            return;
+        };
+
+        let wl_output = wl_output.or_else(||{
+            let client = self.dh.get_client(surface.wl_surface().id()).ok()?;
+            output.client_outputs(&client).into_iter().next()
+        });
+
+        self.save_restore_geometry(&window);
+        surface.with_pending_state(|state|{
+            state.states.set(xdg_toplevel::State::Fullscreen);
+            state.size = Some(geometry.size);
+            state.fullscreen_output = wl_output;
+        });
+
+        output.user_data().insert_if_missing(FullscreenSurface::default);
+        output.user_data().get::<FullscreenSurface>().unwrap().set(window.clone());
+        self.frontend.space.map_element(window, geometry.loc, true);
+
+        if surface.is_initial_configure_sent() {
+            surface.send_pending_configure();
+        }
+    }
+
+    fn unfullscreen_request(&mut self, surface: ToplevelSurface) {
+        if !surface.with_pending_state(|state|state.states.contains(xdg_toplevel::State::Fullscreen)) {
+            return;
+        }
+
+        surface.with_pending_state(|state|{
+            state.states.unset(xdg_toplevel::State::Fullscreen);
+            state.size = None;
+            state.fullscreen_output = None;
+        });
+
+        if let Some(window) = self.window_for_surface(surface.wl_surface()) {
+            for output in self.frontend.space.outputs() {
+                let Some(fullscreen) = output.user_data().get::<FullscreenSurface>() else {
+                    continue;
+                };
+                if fullscreen.get().as_ref() == Some(&window) {
+                    fullscreen.clear();
+                }
+            }
+            self.restore_window(&window);
+        }
+
+        if surface.is_initial_configure_sent() {
+            surface.send_pending_configure();
+        }
+    }
+
+    fn maximize_request(&mut self, surface: ToplevelSurface) {
+        let window = self.window_for_surface(surface.wl_surface());
+
+        // tiled windows are sized by the layout, the request is still answered with a configure
+        let window = window.filter(|window|!self.frontend.layout.is_tiled(window));
+
+        let space = &self.frontend.space;
+        let area = window.as_ref().and_then(|window|{
+            let output = space
+                .outputs_for_element(window)
+                .into_iter()
+                .next()
+                .or_else(||space.outputs().next().cloned())?;
+            utils::output_usable_geometry(space, &output)
+        });
+
+        if let Some((window, area)) = window.zip(area) {
+            self.save_restore_geometry(&window);
+            let header_height = window.header_height();
+            surface.with_pending_state(|state|{
+                state.states.set(xdg_toplevel::State::Maximized);
+                state.size = Some((area.size.w, (area.size.h - header_height).max(1)).into());
+            });
+            self.frontend.space.map_element(window, area.loc, true);
+        }
+
+        if surface.is_initial_configure_sent() {
+            surface.send_configure();
+        }
+    }
+
+    fn unmaximize_request(&mut self, surface: ToplevelSurface) {
+        if !surface.with_pending_state(|state|state.states.contains(xdg_toplevel::State::Maximized)) {
+            return;
+        }
+
+        surface.with_pending_state(|state|{
+            state.states.unset(xdg_toplevel::State::Maximized);
+            state.size = None;
+        });
+
+        if let Some(window) = self.window_for_surface(surface.wl_surface()) {
+            self.restore_window(&window);
+        }
+
+        if surface.is_initial_configure_sent() {
+            surface.send_pending_configure();
+        }
+    }
+
+    fn ack_configure(&mut self, surface: WlSurface, configure: Configure) {
+        let Configure::Toplevel(configure) = configure else {
+            return;
+        };
+
+        ResizeState::ack_configure(&surface, configure.serial);
+
+        let window = self.frontend.space
+            .elements()
+            .find(|window|window.toplevel().map(|t|t.wl_surface() == &surface).unwrap_or(false));
+
+        if let Some(window) = window {
+            let is_ssd = configure.state.decoration_mode == Some(DecorationMode::ServerSide);
+            let mut state = window.decoration_state();
+            state.is_ssd = is_ssd;
+            state.header_bar.height = self.config.decoration.titlebar_height;
+        }
     }
 
     fn new_popup(&mut self, surface: PopupSurface, positioner: PositionerState) {
-        // todo!
-        tracing::warn!("popup is not yet implemented");
+        utils::unconstrain_popup(&self.frontend.space, &surface);
+        if let Err(err) = self.frontend.popups.track_popup(PopupKind::Xdg(surface)) {
+            tracing::warn!("failed to track popup: {err}");
+        }
     }
 
     fn grab(&mut self, surface: PopupSurface, seat: WlSeat, serial: Serial) {
-        // todo!
-        tracing::warn!("popup grab is not yet implemented");
+        let Some(seat) = Seat::<Trayle>::from_resource(&seat) else {
+            return;
+        };
+
+        let kind = PopupKind::Xdg(surface);
+        let Ok(root) = find_popup_root_surface(&kind) else {
+            return;
+        };
+
+        let Ok(mut grab) = self.frontend.popups.grab_popup(root, kind, &seat, serial) else {
+            return;
+        };
+
+        if let Some(keyboard) = seat.get_keyboard() {
+            if keyboard.is_grabbed()
+                && !(keyboard.has_grab(serial)
+                    || keyboard.has_grab(grab.previous_serial().unwrap_or(serial)))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            keyboard.set_focus(self, grab.current_grab(), serial);
+            keyboard.set_grab(self, PopupKeyboardGrab::new(&grab), serial);
+        }
+
+        if let Some(pointer) = seat.get_pointer() {
+            if pointer.is_grabbed()
+                && !(pointer.has_grab(serial)
+                    || pointer.has_grab(grab.previous_serial().unwrap_or_else(||grab.serial())))
+            {
+                grab.ungrab(PopupUngrabStrategy::All);
+                return;
+            }
+            pointer.set_grab(self, PopupPointerGrab::new(&grab), serial, Focus::Keep);
+        }
     }
 
     fn reposition_request(&mut self, surface: PopupSurface, positioner: PositionerState, token: u32) {
-        // todo!
-        tracing::warn!("popup reposition is not yet implemented");
+        surface.with_pending_state(|state|{
+            state.geometry = positioner.get_geometry();
+            state.positioner = positioner;
+        });
+        utils::unconstrain_popup(&self.frontend.space, &surface);
+        surface.send_repositioned(token);
     }
 
     // provided
@@ -42,3 +337,66 @@ impl XdgShellHandler for Trayle {
     }
 }
 
+impl Trayle {
+    /// remember the floating geometry of `window`, unless it is already fullscreen or maximized
+    fn save_restore_geometry(&self, window: &WindowElement) {
+        let mut restore = window.restore_geometry();
+        if restore.is_some() {
+            return;
+        }
+        if let Some(location) = self.frontend.space.element_location(window) {
+            *restore = Some(Rectangle::new(location, window.0.geometry().size));
+        }
+    }
+
+    /// put `window` back to its tile or saved floating geometry
+    ///
+    /// the caller is responsible to send the configure
+    fn restore_window(&mut self, window: &WindowElement) {
+        let Some(geometry) = window.restore_geometry().take() else {
+            return;
+        };
+
+        if let Some(output) = self.frontend.layout.output_of(window) {
+            self.arrange(&output);
+            return;
+        }
+
+        if let Some(toplevel) = window.toplevel() {
+            toplevel.with_pending_state(|state|{
+                state.size = Some(geometry.size);
+            });
+        }
+        self.frontend.space.map_element(window.clone(), geometry.loc, true);
+    }
+
+    /// validate client initiated move or resize, which only allowed while a button is pressed
+    /// on the requesting client
+    fn interactive_request(
+        &self,
+        surface: &ToplevelSurface,
+        seat: &WlSeat,
+        serial: Serial,
+    ) -> Option<(PointerHandle<Trayle>, GrabStartData<Trayle>, WindowElement, Point<i32, Logical>)> {
+        let seat = Seat::<Trayle>::from_resource(seat)?;
+        let pointer = seat.get_pointer()?;
+
+        if !pointer.has_grab(serial) {
+            return None;
+        }
+
+        let start_data = pointer.grab_start_data()?;
+        let (focus,_) = start_data.focus.as_ref()?;
+        if !focus.id().same_client_as(&surface.wl_surface().id()) {
+            return None;
+        }
+
+        let window = self.frontend.space
+            .elements()
+            .find(|window|window.toplevel() == Some(surface))
+            .cloned()?;
+        let initial_window_location = self.frontend.space.element_location(&window)?;
+
+        Some((pointer, start_data, window, initial_window_location))
+    }
+}
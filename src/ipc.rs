@@ -0,0 +1,212 @@
+//! json control socket
+//!
+//! clients send one request per line, eg: `{"cmd":"spawn","args":["foot"]}`,
+//! and receive one reply per line, either `{"ok":true,"data":...}` or `{"ok":false,"error":"..."}`
+use std::{
+    io::{self, Read, Write},
+    os::{
+        fd::{AsFd, BorrowedFd},
+        unix::net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+use smithay::reexports::calloop::{
+    self,
+    generic::Generic, EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+
+/// environment variable pointing to the socket, set for spawned processes
+pub const SOCKET_ENV: &str = "TRAYLE_SOCK";
+
+/// bytes a client may leave unread or unanswered before it is disconnected
+const MAX_BUFFER: usize = 1 << 20;
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Request {
+    Spawn { args: Vec<String> },
+    ListOutputs,
+    ListWindows,
+    FocusWindow { id: u64 },
+    SetOutputEnabled { name: String, enabled: bool },
+    Quit,
+}
+
+/// listening socket at `$XDG_RUNTIME_DIR/trayle.<wayland socket>.sock`, removed on drop
+pub struct IpcListener {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl IpcListener {
+    pub fn bind(wlsocket: &str) -> Result<IpcListener> {
+        let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").context("XDG_RUNTIME_DIR is not set")?;
+        let path = Path::new(&runtime_dir).join(format!("trayle.{wlsocket}.sock"));
+
+        // stale socket of a previous instance on the same wayland socket
+        let _ = std::fs::remove_file(&path);
+
+        let listener = UnixListener::bind(&path).with_context(||format!("failed to bind {path:?}"))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self { listener, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// accept a pending client, `None` if there is none
+    pub fn accept(&self) -> io::Result<Option<IpcStream>> {
+        match self.listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(true)?;
+                Ok(Some(IpcStream { stream, buffer: vec![], pending: vec![] }))
+            },
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl AsFd for IpcListener {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.listener.as_fd()
+    }
+}
+
+impl Drop for IpcListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// connected client, buffering partial lines between reads and replies the socket did not take yet
+pub struct IpcStream {
+    stream: UnixStream,
+    buffer: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl IpcStream {
+    /// read everything available and return the complete lines, `None` when the client disconnected
+    pub fn read_lines(&mut self) -> io::Result<Option<Vec<String>>> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(None),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+            if self.buffer.len() > MAX_BUFFER {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "requests exceed the buffer limit"));
+            }
+        }
+
+        let Some(end) = self.buffer.iter().rposition(|&b|b == b'\n') else {
+            return Ok(Some(vec![]));
+        };
+        let lines = self.buffer
+            .drain(..=end)
+            .collect::<Vec<_>>()
+            .split(|&b|b == b'\n')
+            .filter(|line|!line.is_empty())
+            .map(|line|String::from_utf8_lossy(line).into_owned())
+            .collect();
+
+        Ok(Some(lines))
+    }
+
+    /// queue a reply line for `result` and write as much as the socket takes
+    pub fn reply(&mut self, result: Result<Value>) -> io::Result<()> {
+        let reply = match result {
+            Ok(data) => serde_json::json!({ "ok": true, "data": data }),
+            Err(err) => serde_json::json!({ "ok": false, "error": format!("{err:#}") }),
+        };
+        let mut line = reply.to_string();
+        line.push('\n');
+        self.pending.extend_from_slice(line.as_bytes());
+        self.flush()?;
+
+        if self.pending.len() > MAX_BUFFER {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "replies exceed the buffer limit"));
+        }
+        Ok(())
+    }
+
+    /// write queued replies until the socket would block
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.stream.write(&self.pending) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AsFd for IpcStream {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.stream.as_fd()
+    }
+}
+
+/// [`IpcStream`] event source, also woken up by a writable socket while replies are queued
+pub struct IpcSource {
+    inner: Generic<IpcStream>,
+}
+
+impl IpcSource {
+    pub fn new(stream: IpcStream) -> IpcSource {
+        IpcSource { inner: Generic::new(stream, Interest::READ, Mode::Level) }
+    }
+}
+
+impl EventSource for IpcSource {
+    type Event = Readiness;
+    type Metadata = IpcStream;
+    type Ret = io::Result<PostAction>;
+    type Error = io::Error;
+
+    fn process_events<F>(&mut self, readiness: Readiness, token: Token, mut callback: F) -> io::Result<PostAction>
+    where
+        F: FnMut(Readiness, &mut IpcStream) -> io::Result<PostAction>,
+    {
+        let action = self.inner.process_events(readiness, token, |readiness, stream|{
+            // SAFETY: we dont drop the stream
+            callback(readiness, unsafe { stream.get_mut() })
+        })?;
+
+        // SAFETY: we dont drop the stream
+        let writable = !unsafe { self.inner.get_mut() }.pending.is_empty();
+        if !matches!(action, PostAction::Continue) || self.inner.interest.writable == writable {
+            return Ok(action);
+        }
+        // level triggered, so only wait for writable until the queue is drained
+        self.inner.interest = Interest { readable: true, writable };
+        Ok(PostAction::Reregister)
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.inner.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> calloop::Result<()> {
+        self.inner.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.inner.unregister(poll)
+    }
+}
@@ -11,15 +11,22 @@ pub mod trayle;
 pub mod config;
 pub mod backend;
 pub mod frontend;
+pub mod ipc;
 
 pub mod utils;
+pub mod cursor;
+pub mod wallpaper;
+pub mod drawing;
+pub mod shell;
+pub mod protocols;
+#[cfg(feature = "screencast")]
+pub mod screencast;
 
 mod handlers;
+#[cfg(test)]
+mod tests;
 // mod input_handler;
 
 // mod render;
-// mod shell;
-// mod drawing;
-// mod cursor;
 
 pub use trayle::Trayle;
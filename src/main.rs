@@ -6,7 +6,11 @@ fn main() -> anyhow::Result<()> {
     let _guard = setup_tracing();
     let mut event_loop = EventLoop::<Trayle>::try_new().context("failed to setup event loop")?;
     let mut trayle = Trayle::setup(&mut event_loop)?;
-    event_loop.run(None, &mut trayle, Trayle::refresh).unwrap();
+    event_loop.run(None, &mut trayle, Trayle::refresh).context("event loop failed")?;
+
+    // event sources hold device and session handles too
+    drop(event_loop);
+    trayle.shutdown();
     Ok(())
 }
 
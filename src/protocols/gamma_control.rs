@@ -0,0 +1,228 @@
+//! wlr gamma control protocol, used by redshift-like clients
+//!
+//! only one client can control gamma of an output at a time,
+//! and its gamma ramp is reset when the control is destroyed
+use smithay::{
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::gamma_control::v1::server::{
+            zwlr_gamma_control_manager_v1::{self, ZwlrGammaControlManagerV1},
+            zwlr_gamma_control_v1::{self, ZwlrGammaControlV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+};
+use std::{collections::HashMap, fs::File, io::Read, os::fd::OwnedFd};
+
+const VERSION: u32 = 1;
+
+/// gamma control manager global
+pub struct GammaControlManagerState {
+    global: GlobalId,
+    controls: HashMap<Output, ZwlrGammaControlV1>,
+}
+
+/// global data of the gamma control manager
+pub struct GammaControlManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+/// user data of each gamma control
+pub struct GammaControlData {
+    output: Option<Output>,
+    gamma_size: u32,
+}
+
+pub trait GammaControlHandler {
+    fn gamma_control_manager_state(&mut self) -> &mut GammaControlManagerState;
+
+    /// gamma ramp size of output, `None` if output does not support gamma
+    fn gamma_size(&mut self, output: &Output) -> Option<u32>;
+
+    /// set gamma ramp of output, red, green and blue ramp are laid out consecutively
+    ///
+    /// `None` means the ramp should be reset
+    fn set_gamma(&mut self, output: &Output, ramp: Option<Vec<u16>>) -> anyhow::Result<()>;
+}
+
+impl GammaControlManagerState {
+    /// `filter` decides which clients can see the global, eg: to hide it from sandboxed clients
+    pub fn new<D, F>(dh: &DisplayHandle, filter: F) -> Self
+    where
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+        D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData>
+            + Dispatch<ZwlrGammaControlManagerV1, ()>
+            + Dispatch<ZwlrGammaControlV1, GammaControlData>
+            + GammaControlHandler
+            + 'static,
+    {
+        let global_data = GammaControlManagerGlobalData { filter: Box::new(filter) };
+        let global = dh.create_global::<D, ZwlrGammaControlManagerV1, _>(VERSION, global_data);
+        Self { global, controls: HashMap::new() }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// fail the control of removed output
+    pub fn output_removed(&mut self, output: &Output) {
+        if let Some(control) = self.controls.remove(output) {
+            control.failed();
+        }
+    }
+
+    fn is_active(&self, output: &Output, control: &ZwlrGammaControlV1) -> bool {
+        self.controls.get(output) == Some(control)
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData, D> for GammaControlManagerState
+where
+    D: GlobalDispatch<ZwlrGammaControlManagerV1, GammaControlManagerGlobalData>
+        + Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<ZwlrGammaControlManagerV1>,
+        _: &GammaControlManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &GammaControlManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlManagerV1, (), D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlManagerV1, ()>
+        + Dispatch<ZwlrGammaControlV1, GammaControlData>
+        + GammaControlHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        _: &ZwlrGammaControlManagerV1,
+        request: zwlr_gamma_control_manager_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_gamma_control_manager_v1::Request::GetGammaControl { id, output } => {
+                let output = Output::from_resource(&output);
+                let gamma_size = output.as_ref().and_then(|output|{
+                    if state.gamma_control_manager_state().controls.contains_key(output) {
+                        return None;
+                    }
+                    state.gamma_size(output)
+                });
+
+                let Some((output, gamma_size)) = output.zip(gamma_size) else {
+                    let control = data_init.init(id, GammaControlData { output: None, gamma_size: 0 });
+                    control.failed();
+                    return;
+                };
+
+                let control = data_init.init(id, GammaControlData { output: Some(output.clone()), gamma_size });
+                control.gamma_size(gamma_size);
+                state.gamma_control_manager_state().controls.insert(output, control);
+            }
+            zwlr_gamma_control_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrGammaControlV1, GammaControlData, D> for GammaControlManagerState
+where
+    D: Dispatch<ZwlrGammaControlV1, GammaControlData> + GammaControlHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        resource: &ZwlrGammaControlV1,
+        request: zwlr_gamma_control_v1::Request,
+        data: &GammaControlData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        let Some(output) = data.output.as_ref() else {
+            return;
+        };
+
+        if !state.gamma_control_manager_state().is_active(output, resource) {
+            return;
+        }
+
+        match request {
+            zwlr_gamma_control_v1::Request::SetGamma { fd } => {
+                let result = read_ramp(fd, data.gamma_size)
+                    .and_then(|ramp|state.set_gamma(output, Some(ramp)));
+
+                if let Err(err) = result {
+                    tracing::warn!("failed to set gamma of {}: {err:#}", output.name());
+                    state.gamma_control_manager_state().controls.remove(output);
+                    resource.failed();
+                    let _ = state.set_gamma(output, None);
+                }
+            }
+            zwlr_gamma_control_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _: ClientId, resource: &ZwlrGammaControlV1, data: &GammaControlData) {
+        let Some(output) = data.output.as_ref() else {
+            return;
+        };
+
+        if state.gamma_control_manager_state().is_active(output, resource) {
+            state.gamma_control_manager_state().controls.remove(output);
+            if let Err(err) = state.set_gamma(output, None) {
+                tracing::warn!("failed to reset gamma of {}: {err:#}", output.name());
+            }
+        }
+    }
+}
+
+/// ramp is `gamma_size` of red, green and blue `u16` respectively
+fn read_ramp(fd: OwnedFd, gamma_size: u32) -> anyhow::Result<Vec<u16>> {
+    let mut buf = vec![0u8; gamma_size as usize * 3 * 2];
+    let mut file = File::from(fd);
+    file.read_exact(&mut buf)?;
+
+    // trailing data means the client got the gamma size wrong
+    if file.read(&mut [0u8])? != 0 {
+        anyhow::bail!("gamma ramp is larger than gamma size");
+    }
+
+    Ok(buf.chunks_exact(2).map(|b|u16::from_ne_bytes([b[0],b[1]])).collect())
+}
+
+#[macro_export]
+macro_rules! delegate_gamma_control {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: $crate::protocols::gamma_control::GammaControlManagerGlobalData
+        ] => $crate::protocols::gamma_control::GammaControlManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_manager_v1::ZwlrGammaControlManagerV1: ()
+        ] => $crate::protocols::gamma_control::GammaControlManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::gamma_control::v1::server::zwlr_gamma_control_v1::ZwlrGammaControlV1: $crate::protocols::gamma_control::GammaControlData
+        ] => $crate::protocols::gamma_control::GammaControlManagerState);
+    };
+}
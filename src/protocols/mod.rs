@@ -0,0 +1,35 @@
+//! wayland protocols that are not provided by smithay
+//!
+//! each module follows smithay convention, a `*State` that holds the global,
+//! a `*Handler` trait to be implemented by the compositor state,
+//! and a `delegate_*` macro
+pub mod gamma_control;
+pub mod screencopy;
+pub mod output_management;
+pub mod virtual_pointer;
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::Fixture;
+
+    /// globals filtered by `ClientState::is_privileged` in `Frontend::new`
+    const PRIVILEGED: [&str; 3] = [
+        "zwlr_screencopy_manager_v1",
+        "zwlr_gamma_control_manager_v1",
+        "zwlr_output_manager_v1",
+    ];
+
+    #[test]
+    fn privileged_globals_hidden_from_sandboxed_clients() {
+        let mut fixture = Fixture::new(&[(1920,1080)]);
+        let mut regular = fixture.client();
+        let sandboxed = fixture.sandboxed_client(&mut regular);
+
+        for interface in PRIVILEGED {
+            assert!(regular.advertises(interface), "{interface} is hidden from a regular client");
+            assert!(!sandboxed.advertises(interface), "{interface} is advertised to a sandboxed client");
+        }
+        // sandboxed client is still connected and served
+        assert!(sandboxed.advertises("wl_compositor"));
+    }
+}
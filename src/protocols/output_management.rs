@@ -0,0 +1,593 @@
+//! wlr output management protocol, used by display configuration clients
+//! like `wlr-randr` or `kanshi`
+//!
+//! the compositor pushes snapshots of its outputs with
+//! [`OutputManagementState::update`], client configurations are handed over
+//! to [`OutputManagementHandler::apply_configuration`]
+use smithay::{
+    output::Mode,
+    reexports::{
+        wayland_protocols_wlr::output_management::v1::server::{
+            zwlr_output_configuration_head_v1::{self, ZwlrOutputConfigurationHeadV1},
+            zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+            zwlr_output_head_v1::{self, AdaptiveSyncState, ZwlrOutputHeadV1},
+            zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+            zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+        },
+        wayland_server::{
+            backend::{ClientId, GlobalId},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Point, Raw, Size, Transform},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+const VERSION: u32 = 4;
+
+/// output management global
+pub struct OutputManagementState {
+    global: GlobalId,
+    dh: DisplayHandle,
+    serial: u32,
+    heads: HashMap<String, OutputHead>,
+    managers: Vec<ManagerData>,
+}
+
+/// global data of the output manager
+pub struct OutputManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+/// snapshot of an output, as advertised to clients
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputHead {
+    pub name: String,
+    pub description: String,
+    pub make: String,
+    pub model: String,
+    pub physical_size: Size<i32, Raw>,
+    pub modes: Vec<Mode>,
+    pub preferred_mode: Option<Mode>,
+    pub current_mode: Option<Mode>,
+    pub enabled: bool,
+    pub position: Point<i32, Logical>,
+    pub transform: Transform,
+    pub scale: f64,
+    /// `None` if not supported
+    pub adaptive_sync: Option<bool>,
+}
+
+/// requested state of an enabled head, unset fields are left unchanged
+#[derive(Debug, Clone, Default)]
+pub struct HeadConfiguration {
+    pub mode: Option<Mode>,
+    pub position: Option<Point<i32, Logical>>,
+    pub transform: Option<Transform>,
+    pub scale: Option<f64>,
+    pub adaptive_sync: Option<bool>,
+}
+
+/// requested state of each head by its output name, `None` disables the head
+pub type OutputConfiguration = HashMap<String, Option<HeadConfiguration>>;
+
+pub trait OutputManagementHandler {
+    fn output_management_state(&mut self) -> &mut OutputManagementState;
+
+    /// validate, and unless `test_only`, apply `configuration`
+    ///
+    /// on failure, any applied change must be rolled back
+    fn apply_configuration(&mut self, configuration: OutputConfiguration, test_only: bool) -> bool;
+}
+
+struct ManagerData {
+    manager: ZwlrOutputManagerV1,
+    heads: HashMap<String, (ZwlrOutputHeadV1, Vec<ZwlrOutputModeV1>)>,
+}
+
+/// user data of configuration
+pub struct ConfigurationData {
+    serial: u32,
+    inner: Arc<Mutex<ConfigurationInner>>,
+}
+
+#[derive(Default)]
+struct ConfigurationInner {
+    /// applied or tested, it is no longer usable
+    used: bool,
+    heads: OutputConfiguration,
+}
+
+/// user data of configuration head
+pub struct ConfigurationHeadData {
+    name: String,
+    inner: Arc<Mutex<ConfigurationInner>>,
+}
+
+impl OutputManagementState {
+    /// `filter` decides which clients can see the global, eg: to hide it from sandboxed clients
+    pub fn new<D, F>(dh: &DisplayHandle, filter: F) -> Self
+    where
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+        D: GlobalDispatch<ZwlrOutputManagerV1, OutputManagerGlobalData>
+            + Dispatch<ZwlrOutputManagerV1, ()>
+            + Dispatch<ZwlrOutputHeadV1, String>
+            + Dispatch<ZwlrOutputModeV1, Mode>
+            + Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+            + Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData>
+            + OutputManagementHandler
+            + 'static,
+    {
+        let global_data = OutputManagerGlobalData { filter: Box::new(filter) };
+        let global = dh.create_global::<D, ZwlrOutputManagerV1, _>(VERSION, global_data);
+        Self {
+            global,
+            dh: dh.clone(),
+            serial: 0,
+            heads: HashMap::new(),
+            managers: Vec::new(),
+        }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+
+    /// replace the advertised outputs, changed properties are sent to every client
+    ///
+    /// a head is only recreated when it is replaced by another monitor on the same connector
+    pub fn update<D>(&mut self, heads: Vec<OutputHead>)
+    where
+        D: Dispatch<ZwlrOutputHeadV1, String> + Dispatch<ZwlrOutputModeV1, Mode> + 'static,
+    {
+        let heads = heads.into_iter().map(|head|(head.name.clone(), head)).collect::<HashMap<_,_>>();
+        if heads == self.heads {
+            return;
+        }
+
+        for manager in &mut self.managers {
+            manager.heads.retain(|name,(head,modes)|{
+                if heads.contains_key(name) {
+                    return true;
+                }
+                modes.iter().for_each(|mode|mode.finished());
+                head.finished();
+                false
+            });
+
+            for (name,head) in &heads {
+                let old = self.heads.get(name);
+                if old == Some(head) && manager.heads.contains_key(name) {
+                    continue;
+                }
+
+                if let (Some(old), Some((resource,modes))) = (old, manager.heads.get_mut(name)) {
+                    if old.same_monitor(head) {
+                        send_state::<D>(&self.dh, resource, modes, Some(old), head);
+                        continue;
+                    }
+                }
+
+                if let Some((resource,modes)) = manager.heads.remove(name) {
+                    modes.iter().for_each(|mode|mode.finished());
+                    resource.finished();
+                }
+
+                if let Some(sent) = send_head::<D>(&self.dh, &manager.manager, head) {
+                    manager.heads.insert(name.clone(), sent);
+                }
+            }
+        }
+
+        self.heads = heads;
+        self.serial = self.serial.wrapping_add(1);
+        for manager in &self.managers {
+            manager.manager.done(self.serial);
+        }
+    }
+}
+
+impl OutputHead {
+    /// properties that are only sent once per head
+    fn same_monitor(&self, other: &OutputHead) -> bool {
+        self.description == other.description
+            && self.make == other.make
+            && self.model == other.model
+            && self.physical_size == other.physical_size
+    }
+}
+
+fn send_head<D>(
+    dh: &DisplayHandle,
+    manager: &ZwlrOutputManagerV1,
+    head: &OutputHead,
+) -> Option<(ZwlrOutputHeadV1, Vec<ZwlrOutputModeV1>)>
+where
+    D: Dispatch<ZwlrOutputHeadV1, String> + Dispatch<ZwlrOutputModeV1, Mode> + 'static,
+{
+    let client = manager.client()?;
+    let resource = client
+        .create_resource::<ZwlrOutputHeadV1, _, D>(dh, manager.version(), head.name.clone())
+        .ok()?;
+    manager.head(&resource);
+
+    resource.name(head.name.clone());
+    resource.description(head.description.clone());
+    if head.physical_size.w > 0 && head.physical_size.h > 0 {
+        resource.physical_size(head.physical_size.w, head.physical_size.h);
+    }
+
+    if resource.version() >= 2 {
+        resource.make(head.make.clone());
+        resource.model(head.model.clone());
+    }
+
+    let mut modes = Vec::with_capacity(head.modes.len());
+    send_state::<D>(dh, &resource, &mut modes, None, head);
+
+    Some((resource, modes))
+}
+
+/// send the state of `head` that differs from `old` on an existing head, every state without `old`
+///
+/// modes still supported keep their resource, `modes` is updated to match [`OutputHead::modes`]
+fn send_state<D>(
+    dh: &DisplayHandle,
+    resource: &ZwlrOutputHeadV1,
+    modes: &mut Vec<ZwlrOutputModeV1>,
+    old: Option<&OutputHead>,
+    head: &OutputHead,
+) where
+    D: Dispatch<ZwlrOutputModeV1, Mode> + 'static,
+{
+    let modes_changed = old.is_none_or(|old|old.modes != head.modes || old.preferred_mode != head.preferred_mode);
+    if modes_changed {
+        let mut previous = std::mem::take(modes);
+        for mode in &head.modes {
+            if let Some(index) = previous.iter().position(|resource|resource.data::<Mode>() == Some(mode)) {
+                modes.push(previous.remove(index));
+                continue;
+            }
+            let Some(client) = resource.client() else {
+                break;
+            };
+            let Ok(mode_resource) = client.create_resource::<ZwlrOutputModeV1, _, D>(dh, resource.version(), *mode) else {
+                continue;
+            };
+            resource.mode(&mode_resource);
+            mode_resource.size(mode.size.w, mode.size.h);
+            mode_resource.refresh(mode.refresh);
+            if head.preferred_mode == Some(*mode) {
+                mode_resource.preferred();
+            }
+            modes.push(mode_resource);
+        }
+        previous.iter().for_each(|mode|mode.finished());
+    }
+
+    if old.is_none_or(|old|old.enabled != head.enabled) {
+        resource.enabled(head.enabled as i32);
+    }
+    if head.enabled {
+        // a head that was just enabled sends its whole state again
+        let old = old.filter(|old|old.enabled);
+        if modes_changed || old.is_none_or(|old|old.current_mode != head.current_mode) {
+            let current = head.current_mode.and_then(|current|{
+                modes.iter().find(|resource|resource.data::<Mode>() == Some(&current))
+            });
+            if let Some(current) = current {
+                resource.current_mode(current);
+            }
+        }
+        if old.is_none_or(|old|old.position != head.position) {
+            resource.position(head.position.x, head.position.y);
+        }
+        if old.is_none_or(|old|old.transform != head.transform) {
+            resource.transform(head.transform.into());
+        }
+        if old.is_none_or(|old|old.scale != head.scale) {
+            resource.scale(head.scale);
+        }
+    }
+
+    if resource.version() >= 4 && old.is_none_or(|old|old.adaptive_sync != head.adaptive_sync) {
+        if let Some(enabled) = head.adaptive_sync {
+            resource.adaptive_sync(match enabled {
+                true => AdaptiveSyncState::Enabled,
+                false => AdaptiveSyncState::Disabled,
+            });
+        }
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrOutputManagerV1, OutputManagerGlobalData, D> for OutputManagementState
+where
+    D: GlobalDispatch<ZwlrOutputManagerV1, OutputManagerGlobalData>
+        + Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputHeadV1, String>
+        + Dispatch<ZwlrOutputModeV1, Mode>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn bind(
+        state: &mut D,
+        dh: &DisplayHandle,
+        _: &Client,
+        resource: New<ZwlrOutputManagerV1>,
+        _: &OutputManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let manager = data_init.init(resource, ());
+        let state = state.output_management_state();
+
+        let heads = state.heads
+            .values()
+            .filter_map(|head|Some((head.name.clone(), send_head::<D>(dh, &manager, head)?)))
+            .collect();
+        manager.done(state.serial);
+
+        state.managers.push(ManagerData { manager, heads });
+    }
+
+    fn can_view(client: Client, global_data: &OutputManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputManagerV1, (), D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputManagerV1, ()>
+        + Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        resource: &ZwlrOutputManagerV1,
+        request: zwlr_output_manager_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_manager_v1::Request::CreateConfiguration { id, serial } => {
+                data_init.init(id, ConfigurationData {
+                    serial,
+                    inner: Arc::default(),
+                });
+            }
+            zwlr_output_manager_v1::Request::Stop => {
+                let state = state.output_management_state();
+                state.managers.retain(|manager|&manager.manager != resource);
+                resource.finished();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn destroyed(state: &mut D, _: ClientId, resource: &ZwlrOutputManagerV1, _: &()) {
+        let state = state.output_management_state();
+        state.managers.retain(|manager|&manager.manager != resource);
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputHeadV1, String, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputHeadV1, String> + OutputManagementHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        resource: &ZwlrOutputHeadV1,
+        request: zwlr_output_head_v1::Request,
+        _: &String,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_head_v1::Request::Release => {
+                for manager in &mut state.output_management_state().managers {
+                    manager.heads.retain(|_,(head,_)|head != resource);
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputModeV1, Mode, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputModeV1, Mode> + OutputManagementHandler + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &ZwlrOutputModeV1,
+        request: zwlr_output_mode_v1::Request,
+        _: &Mode,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_output_mode_v1::Request::Release => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationV1, ConfigurationData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationV1, ConfigurationData>
+        + Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData>
+        + OutputManagementHandler
+        + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        resource: &ZwlrOutputConfigurationV1,
+        request: zwlr_output_configuration_v1::Request,
+        data: &ConfigurationData,
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let mut inner = data.inner.lock().unwrap();
+
+        let (name, configuration) = match request {
+            zwlr_output_configuration_v1::Request::EnableHead { id, head } => {
+                let name = head.data::<String>().cloned().unwrap_or_default();
+                data_init.init(id, ConfigurationHeadData { name: name.clone(), inner: data.inner.clone() });
+                (name, Some(HeadConfiguration::default()))
+            }
+            zwlr_output_configuration_v1::Request::DisableHead { head } => {
+                (head.data::<String>().cloned().unwrap_or_default(), None)
+            }
+            zwlr_output_configuration_v1::Request::Apply | zwlr_output_configuration_v1::Request::Test => {
+                if inner.used {
+                    resource.post_error(zwlr_output_configuration_v1::Error::AlreadyUsed, "configuration already used");
+                    return;
+                }
+                inner.used = true;
+
+                if data.serial != state.output_management_state().serial {
+                    resource.cancelled();
+                    return;
+                }
+
+                let test_only = matches!(request, zwlr_output_configuration_v1::Request::Test);
+                let heads = std::mem::take(&mut inner.heads);
+                drop(inner);
+
+                match state.apply_configuration(heads, test_only) {
+                    true => resource.succeeded(),
+                    false => resource.failed(),
+                }
+                return;
+            }
+            zwlr_output_configuration_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if inner.used {
+            resource.post_error(zwlr_output_configuration_v1::Error::AlreadyUsed, "configuration already used");
+            return;
+        }
+
+        if inner.heads.contains_key(&name) {
+            resource.post_error(zwlr_output_configuration_v1::Error::AlreadyConfiguredHead, "head already configured");
+            return;
+        }
+
+        inner.heads.insert(name, configuration);
+    }
+}
+
+impl<D> Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData, D> for OutputManagementState
+where
+    D: Dispatch<ZwlrOutputConfigurationHeadV1, ConfigurationHeadData> + OutputManagementHandler + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        resource: &ZwlrOutputConfigurationHeadV1,
+        request: zwlr_output_configuration_head_v1::Request,
+        data: &ConfigurationHeadData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        use zwlr_output_configuration_head_v1::Error;
+
+        let mut inner = data.inner.lock().unwrap();
+        if inner.used {
+            return;
+        }
+
+        let Some(Some(head)) = inner.heads.get_mut(&data.name) else {
+            return;
+        };
+
+        macro_rules! set {
+            ($field:ident, $value:expr) => {{
+                if head.$field.is_some() {
+                    resource.post_error(Error::AlreadySet, concat!(stringify!($field), " already set"));
+                    return;
+                }
+                head.$field = Some($value);
+            }};
+        }
+
+        match request {
+            zwlr_output_configuration_head_v1::Request::SetMode { mode } => {
+                let Some(mode) = mode.data::<Mode>().copied() else {
+                    resource.post_error(Error::InvalidMode, "invalid mode");
+                    return;
+                };
+                set!(mode, mode);
+            }
+            zwlr_output_configuration_head_v1::Request::SetCustomMode { width, height, refresh } => {
+                if width <= 0 || height <= 0 || refresh < 0 {
+                    resource.post_error(Error::InvalidCustomMode, "invalid custom mode");
+                    return;
+                }
+                set!(mode, Mode { size: (width, height).into(), refresh });
+            }
+            zwlr_output_configuration_head_v1::Request::SetPosition { x, y } => {
+                set!(position, (x, y).into());
+            }
+            zwlr_output_configuration_head_v1::Request::SetTransform { transform } => {
+                let Ok(transform) = transform.into_result() else {
+                    resource.post_error(Error::InvalidTransform, "invalid transform");
+                    return;
+                };
+                set!(transform, Transform::from(transform));
+            }
+            zwlr_output_configuration_head_v1::Request::SetScale { scale } => {
+                if scale <= 0.0 {
+                    resource.post_error(Error::InvalidScale, "invalid scale");
+                    return;
+                }
+                set!(scale, scale);
+            }
+            zwlr_output_configuration_head_v1::Request::SetAdaptiveSync { state } => {
+                let enabled = match state.into_result() {
+                    Ok(AdaptiveSyncState::Enabled) => true,
+                    Ok(AdaptiveSyncState::Disabled) => false,
+                    _ => {
+                        resource.post_error(Error::InvalidAdaptiveSyncState, "invalid adaptive sync state");
+                        return;
+                    }
+                };
+                set!(adaptive_sync, enabled);
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_output_management {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: $crate::protocols::output_management::OutputManagerGlobalData
+        ] => $crate::protocols::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_manager_v1::ZwlrOutputManagerV1: ()
+        ] => $crate::protocols::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_head_v1::ZwlrOutputHeadV1: String
+        ] => $crate::protocols::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_mode_v1::ZwlrOutputModeV1: smithay::output::Mode
+        ] => $crate::protocols::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1: $crate::protocols::output_management::ConfigurationData
+        ] => $crate::protocols::output_management::OutputManagementState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::output_management::v1::server::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1: $crate::protocols::output_management::ConfigurationHeadData
+        ] => $crate::protocols::output_management::OutputManagementState);
+    };
+}
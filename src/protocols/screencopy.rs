@@ -0,0 +1,321 @@
+//! wlr screencopy protocol, used by screenshot clients like `grim`
+//!
+//! frames are copied in output buffer coordinate, so clients
+//! are expected to apply the output transform themselves
+use smithay::{
+    backend::allocator::{Buffer as _, Fourcc},
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::screencopy::v1::server::{
+            zwlr_screencopy_frame_v1::{self, ZwlrScreencopyFrameV1},
+            zwlr_screencopy_manager_v1::{self, ZwlrScreencopyManagerV1},
+        },
+        wayland_server::{
+            backend::GlobalId,
+            protocol::{wl_buffer::WlBuffer, wl_output::WlOutput, wl_shm},
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, Resource,
+        },
+    },
+    utils::{Logical, Physical, Rectangle, Size},
+    wayland::{dmabuf::get_dmabuf, shm::with_buffer_contents},
+};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+const VERSION: u32 = 3;
+
+/// screencopy manager global
+pub struct ScreencopyManagerState {
+    global: GlobalId,
+}
+
+/// global data of the screencopy manager
+pub struct ScreencopyManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+/// user data of each screencopy frame
+pub struct ScreencopyFrameData {
+    info: Option<FrameInfo>,
+    used: AtomicBool,
+}
+
+#[derive(Clone)]
+struct FrameInfo {
+    output: Output,
+    region: Rectangle<i32, Physical>,
+    overlay_cursor: bool,
+}
+
+pub trait ScreencopyHandler {
+    /// client requested a frame to be copied into its buffer
+    ///
+    /// dropping `screencopy` without [`Screencopy::submit`] fails the frame
+    fn frame(&mut self, screencopy: Screencopy);
+}
+
+/// pending copy of an output into client buffer
+pub struct Screencopy {
+    frame: ZwlrScreencopyFrameV1,
+    buffer: WlBuffer,
+    info: FrameInfo,
+    with_damage: bool,
+    submitted: bool,
+}
+
+impl Screencopy {
+    pub fn buffer(&self) -> &WlBuffer {
+        &self.buffer
+    }
+
+    pub fn output(&self) -> &Output {
+        &self.info.output
+    }
+
+    /// region to copy in transformed output physical coordinate
+    pub fn region(&self) -> Rectangle<i32, Physical> {
+        self.info.region
+    }
+
+    /// size of client buffer, that is [`Screencopy::region`] in output buffer coordinate
+    pub fn buffer_size(&self) -> Size<i32, Physical> {
+        buffer_size(&self.info)
+    }
+
+    /// whether cursor should be drawn
+    pub fn overlay_cursor(&self) -> bool {
+        self.info.overlay_cursor
+    }
+
+    /// whether client only wants a copy when the output is damaged
+    pub fn with_damage(&self) -> bool {
+        self.with_damage
+    }
+
+    pub fn alive(&self) -> bool {
+        self.frame.is_alive()
+    }
+
+    /// buffer is filled, `time` is the presentation time in monotonic clock
+    pub fn submit(mut self, time: Duration) {
+        self.frame.flags(zwlr_screencopy_frame_v1::Flags::empty());
+        if self.with_damage {
+            let size = self.buffer_size();
+            self.frame.damage(0, 0, size.w as u32, size.h as u32);
+        }
+
+        let secs = time.as_secs();
+        self.frame.ready((secs >> 32) as u32, secs as u32, time.subsec_nanos());
+        self.submitted = true;
+    }
+}
+
+impl Drop for Screencopy {
+    fn drop(&mut self) {
+        if !self.submitted {
+            self.frame.failed();
+        }
+    }
+}
+
+impl ScreencopyManagerState {
+    /// `filter` decides which clients can see the global, eg: to hide it from sandboxed clients
+    pub fn new<D, F>(dh: &DisplayHandle, filter: F) -> Self
+    where
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+        D: GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyManagerGlobalData>
+            + Dispatch<ZwlrScreencopyManagerV1, ()>
+            + Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData>
+            + ScreencopyHandler
+            + 'static,
+    {
+        let global_data = ScreencopyManagerGlobalData { filter: Box::new(filter) };
+        let global = dh.create_global::<D, ZwlrScreencopyManagerV1, _>(VERSION, global_data);
+        Self { global }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyManagerGlobalData, D> for ScreencopyManagerState
+where
+    D: GlobalDispatch<ZwlrScreencopyManagerV1, ScreencopyManagerGlobalData>
+        + Dispatch<ZwlrScreencopyManagerV1, ()>
+        + Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData>
+        + ScreencopyHandler
+        + 'static,
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<ZwlrScreencopyManagerV1>,
+        _: &ScreencopyManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &ScreencopyManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyManagerV1, (), D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyManagerV1, ()>
+        + Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData>
+        + ScreencopyHandler
+        + 'static,
+{
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &ZwlrScreencopyManagerV1,
+        request: zwlr_screencopy_manager_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        match request {
+            zwlr_screencopy_manager_v1::Request::CaptureOutput { frame, overlay_cursor, output } => {
+                capture(frame, overlay_cursor != 0, &output, None, data_init);
+            }
+            zwlr_screencopy_manager_v1::Request::CaptureOutputRegion {
+                frame, overlay_cursor, output, x, y, width, height,
+            } => {
+                let region = Rectangle::new((x, y).into(), (width, height).into());
+                capture(frame, overlay_cursor != 0, &output, Some(region), data_init);
+            }
+            zwlr_screencopy_manager_v1::Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<D> Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData, D> for ScreencopyManagerState
+where
+    D: Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData> + ScreencopyHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        resource: &ZwlrScreencopyFrameV1,
+        request: zwlr_screencopy_frame_v1::Request,
+        data: &ScreencopyFrameData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        let (buffer, with_damage) = match request {
+            zwlr_screencopy_frame_v1::Request::Copy { buffer } => (buffer, false),
+            zwlr_screencopy_frame_v1::Request::CopyWithDamage { buffer } => (buffer, true),
+            zwlr_screencopy_frame_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        if data.used.swap(true, Ordering::Relaxed) {
+            resource.post_error(zwlr_screencopy_frame_v1::Error::AlreadyUsed, "frame already used");
+            return;
+        }
+
+        // failed have been sent on capture
+        let Some(info) = data.info.clone() else {
+            return;
+        };
+
+        if !is_valid_buffer(&buffer, buffer_size(&info)) {
+            resource.post_error(zwlr_screencopy_frame_v1::Error::InvalidBuffer, "invalid buffer");
+            return;
+        }
+
+        state.frame(Screencopy {
+            frame: resource.clone(),
+            buffer,
+            info,
+            with_damage,
+            submitted: false,
+        });
+    }
+}
+
+fn capture<D>(
+    frame: New<ZwlrScreencopyFrameV1>,
+    overlay_cursor: bool,
+    output: &WlOutput,
+    region: Option<Rectangle<i32, Logical>>,
+    data_init: &mut DataInit<'_, D>,
+)
+where
+    D: Dispatch<ZwlrScreencopyFrameV1, ScreencopyFrameData> + 'static,
+{
+    let info = Output::from_resource(output).and_then(|output|{
+        let mode = output.current_mode()?;
+        let output_rect = Rectangle::from_size(output.current_transform().transform_size(mode.size));
+        let region = match region {
+            Some(region) => {
+                let scale = output.current_scale().fractional_scale();
+                region.to_f64().to_physical_precise_round(scale).intersection(output_rect)?
+            }
+            None => output_rect,
+        };
+        Some(FrameInfo { output, region, overlay_cursor })
+    })
+    .filter(|info|!info.region.is_empty());
+
+    let frame = data_init.init(frame, ScreencopyFrameData {
+        info: info.clone(),
+        used: AtomicBool::new(false),
+    });
+
+    let Some(info) = info else {
+        frame.failed();
+        return;
+    };
+
+    let size = buffer_size(&info);
+    frame.buffer(wl_shm::Format::Xrgb8888, size.w as u32, size.h as u32, size.w as u32 * 4);
+    if frame.version() >= 3 {
+        frame.linux_dmabuf(Fourcc::Xrgb8888 as u32, size.w as u32, size.h as u32);
+        frame.buffer_done();
+    }
+}
+
+fn buffer_size(info: &FrameInfo) -> Size<i32, Physical> {
+    info.output.current_transform().invert().transform_size(info.region.size)
+}
+
+/// buffer must match the one advertised on capture
+fn is_valid_buffer(buffer: &WlBuffer, size: Size<i32, Physical>) -> bool {
+    if let Ok(dmabuf) = get_dmabuf(buffer) {
+        return dmabuf.format().code == Fourcc::Xrgb8888
+            && dmabuf.width() as i32 == size.w
+            && dmabuf.height() as i32 == size.h;
+    }
+
+    with_buffer_contents(buffer, |_, _, data|{
+        data.format == wl_shm::Format::Xrgb8888
+            && data.width == size.w
+            && data.height == size.h
+            && data.stride == size.w * 4
+    })
+    .unwrap_or(false)
+}
+
+#[macro_export]
+macro_rules! delegate_screencopy {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: $crate::protocols::screencopy::ScreencopyManagerGlobalData
+        ] => $crate::protocols::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_manager_v1::ZwlrScreencopyManagerV1: ()
+        ] => $crate::protocols::screencopy::ScreencopyManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::screencopy::v1::server::zwlr_screencopy_frame_v1::ZwlrScreencopyFrameV1: $crate::protocols::screencopy::ScreencopyFrameData
+        ] => $crate::protocols::screencopy::ScreencopyManagerState);
+    };
+}
@@ -0,0 +1,503 @@
+//! wlr virtual pointer protocol, used by automation tools like `ydotool` or `wlrctl`
+//!
+//! requests are turned into [`InputEvent`]s of [`VirtualPointerInputBackend`], so they are
+//! handled like events of a real pointer, axis requests are accumulated until `frame`
+use smithay::{
+    backend::input::{
+        AbsolutePositionEvent, Axis, AxisRelativeDirection, AxisSource, ButtonState, Device,
+        DeviceCapability, Event, InputBackend, InputEvent, PointerAxisEvent, PointerButtonEvent,
+        PointerMotionAbsoluteEvent, PointerMotionEvent, UnusedEvent,
+    },
+    output::Output,
+    reexports::{
+        wayland_protocols_wlr::virtual_pointer::v1::server::{
+            zwlr_virtual_pointer_manager_v1::{self, ZwlrVirtualPointerManagerV1},
+            zwlr_virtual_pointer_v1::{self, ZwlrVirtualPointerV1},
+        },
+        wayland_server::{
+            backend::GlobalId,
+            protocol::wl_pointer,
+            Client, DataInit, Dispatch, DisplayHandle, GlobalDispatch, New, WEnum,
+        },
+    },
+};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+};
+
+const VERSION: u32 = 2;
+
+/// virtual pointer manager global
+pub struct VirtualPointerManagerState {
+    global: GlobalId,
+}
+
+/// global data of the virtual pointer manager
+pub struct VirtualPointerManagerGlobalData {
+    filter: Box<dyn Fn(&Client) -> bool + Send + Sync>,
+}
+
+/// user data of each virtual pointer
+pub struct VirtualPointerData {
+    device: VirtualPointerDevice,
+    /// output absolute motion is mapped to, see [`VirtualPointerMotionAbsoluteEvent::output`]
+    output: Option<Output>,
+    axis: Mutex<VirtualPointerAxisEvent>,
+}
+
+pub trait VirtualPointerHandler {
+    /// input event of a virtual pointer
+    fn virtual_pointer_event(&mut self, event: InputEvent<VirtualPointerInputBackend>);
+}
+
+impl VirtualPointerManagerState {
+    /// `filter` decides which clients can see the global, eg: to hide it from sandboxed clients
+    pub fn new<D, F>(dh: &DisplayHandle, filter: F) -> Self
+    where
+        F: Fn(&Client) -> bool + Send + Sync + 'static,
+        D: GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualPointerManagerGlobalData>
+            + Dispatch<ZwlrVirtualPointerManagerV1, ()>
+            + Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>
+            + VirtualPointerHandler
+            + 'static,
+    {
+        let global_data = VirtualPointerManagerGlobalData { filter: Box::new(filter) };
+        let global = dh.create_global::<D, ZwlrVirtualPointerManagerV1, _>(VERSION, global_data);
+        Self { global }
+    }
+
+    pub fn global(&self) -> GlobalId {
+        self.global.clone()
+    }
+}
+
+impl<D> GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualPointerManagerGlobalData, D> for VirtualPointerManagerState
+where
+    D: GlobalDispatch<ZwlrVirtualPointerManagerV1, VirtualPointerManagerGlobalData>
+        + Dispatch<ZwlrVirtualPointerManagerV1, ()>
+        + Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>
+        + VirtualPointerHandler
+        + 'static,
+{
+    fn bind(
+        _: &mut D,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<ZwlrVirtualPointerManagerV1>,
+        _: &VirtualPointerManagerGlobalData,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        data_init.init(resource, ());
+    }
+
+    fn can_view(client: Client, global_data: &VirtualPointerManagerGlobalData) -> bool {
+        (global_data.filter)(&client)
+    }
+}
+
+impl<D> Dispatch<ZwlrVirtualPointerManagerV1, (), D> for VirtualPointerManagerState
+where
+    D: Dispatch<ZwlrVirtualPointerManagerV1, ()>
+        + Dispatch<ZwlrVirtualPointerV1, VirtualPointerData>
+        + VirtualPointerHandler
+        + 'static,
+{
+    /// there is only one seat, the requested one is ignored
+    fn request(
+        _: &mut D,
+        _: &Client,
+        _: &ZwlrVirtualPointerManagerV1,
+        request: zwlr_virtual_pointer_manager_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, D>,
+    ) {
+        let (id, output) = match request {
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointer { id, .. } => (id, None),
+            zwlr_virtual_pointer_manager_v1::Request::CreateVirtualPointerWithOutput { id, output, .. } => {
+                (id, output.as_ref().and_then(Output::from_resource))
+            }
+            zwlr_virtual_pointer_manager_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        let device = VirtualPointerDevice::new();
+        data_init.init(id, VirtualPointerData {
+            axis: Mutex::new(VirtualPointerAxisEvent::new(device.clone())),
+            device,
+            output,
+        });
+    }
+}
+
+impl<D> Dispatch<ZwlrVirtualPointerV1, VirtualPointerData, D> for VirtualPointerManagerState
+where
+    D: Dispatch<ZwlrVirtualPointerV1, VirtualPointerData> + VirtualPointerHandler + 'static,
+{
+    fn request(
+        state: &mut D,
+        _: &Client,
+        _: &ZwlrVirtualPointerV1,
+        request: zwlr_virtual_pointer_v1::Request,
+        data: &VirtualPointerData,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, D>,
+    ) {
+        let device = data.device.clone();
+        let event = match request {
+            zwlr_virtual_pointer_v1::Request::Motion { time, dx, dy } => InputEvent::PointerMotion {
+                event: VirtualPointerMotionEvent { device, time, dx, dy },
+            },
+            zwlr_virtual_pointer_v1::Request::MotionAbsolute { time, x, y, x_extent, y_extent } => {
+                if x_extent == 0 || y_extent == 0 {
+                    return;
+                }
+                InputEvent::PointerMotionAbsolute {
+                    event: VirtualPointerMotionAbsoluteEvent {
+                        device,
+                        time,
+                        x: x as f64 / x_extent as f64,
+                        y: y as f64 / y_extent as f64,
+                        output: data.output.clone(),
+                    },
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::Button { time, button, state: button_state } => {
+                let state = match button_state {
+                    WEnum::Value(wl_pointer::ButtonState::Pressed) => ButtonState::Pressed,
+                    _ => ButtonState::Released,
+                };
+                InputEvent::PointerButton {
+                    event: VirtualPointerButtonEvent { device, time, button, state },
+                }
+            }
+            zwlr_virtual_pointer_v1::Request::Axis { time, axis, value } => {
+                let mut pending = data.axis.lock().unwrap();
+                pending.time = time;
+                if let Some(axis) = self::axis(axis) {
+                    *pending.amount_mut(axis).get_or_insert(0.0) += value;
+                }
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::AxisDiscrete { time, axis, value, discrete } => {
+                let mut pending = data.axis.lock().unwrap();
+                pending.time = time;
+                if let Some(axis) = self::axis(axis) {
+                    *pending.amount_mut(axis).get_or_insert(0.0) += value;
+                    *pending.v120_mut(axis).get_or_insert(0.0) += discrete as f64 * 120.0;
+                }
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::AxisStop { time, axis } => {
+                let mut pending = data.axis.lock().unwrap();
+                pending.time = time;
+                if let Some(axis) = self::axis(axis) {
+                    *pending.amount_mut(axis) = Some(0.0);
+                }
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::AxisSource { axis_source } => {
+                data.axis.lock().unwrap().source = match axis_source {
+                    WEnum::Value(wl_pointer::AxisSource::Finger) => AxisSource::Finger,
+                    WEnum::Value(wl_pointer::AxisSource::Continuous) => AxisSource::Continuous,
+                    WEnum::Value(wl_pointer::AxisSource::WheelTilt) => AxisSource::WheelTilt,
+                    _ => AxisSource::Wheel,
+                };
+                return;
+            }
+            zwlr_virtual_pointer_v1::Request::Frame => {
+                let mut pending = data.axis.lock().unwrap();
+                let is_empty = pending.horizontal.is_none() && pending.vertical.is_none();
+                let event = std::mem::replace(&mut *pending, VirtualPointerAxisEvent::new(device));
+                if is_empty {
+                    return;
+                }
+                InputEvent::PointerAxis { event }
+            }
+            zwlr_virtual_pointer_v1::Request::Destroy => return,
+            _ => unreachable!(),
+        };
+
+        state.virtual_pointer_event(event);
+    }
+}
+
+fn axis(axis: WEnum<wl_pointer::Axis>) -> Option<Axis> {
+    match axis {
+        WEnum::Value(wl_pointer::Axis::HorizontalScroll) => Some(Axis::Horizontal),
+        WEnum::Value(wl_pointer::Axis::VerticalScroll) => Some(Axis::Vertical),
+        _ => None,
+    }
+}
+
+/// input backend of virtual pointer events, only pointer events are ever emitted
+#[derive(Debug)]
+pub struct VirtualPointerInputBackend;
+
+impl InputBackend for VirtualPointerInputBackend {
+    type Device = VirtualPointerDevice;
+    type KeyboardKeyEvent = UnusedEvent;
+    type PointerAxisEvent = VirtualPointerAxisEvent;
+    type PointerButtonEvent = VirtualPointerButtonEvent;
+    type PointerMotionEvent = VirtualPointerMotionEvent;
+    type PointerMotionAbsoluteEvent = VirtualPointerMotionAbsoluteEvent;
+    type GestureSwipeBeginEvent = UnusedEvent;
+    type GestureSwipeUpdateEvent = UnusedEvent;
+    type GestureSwipeEndEvent = UnusedEvent;
+    type GesturePinchBeginEvent = UnusedEvent;
+    type GesturePinchUpdateEvent = UnusedEvent;
+    type GesturePinchEndEvent = UnusedEvent;
+    type GestureHoldBeginEvent = UnusedEvent;
+    type GestureHoldEndEvent = UnusedEvent;
+    type TouchDownEvent = UnusedEvent;
+    type TouchUpEvent = UnusedEvent;
+    type TouchMotionEvent = UnusedEvent;
+    type TouchCancelEvent = UnusedEvent;
+    type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+    type SwitchToggleEvent = UnusedEvent;
+    type SpecialEvent = UnusedEvent;
+}
+
+/// each virtual pointer is its own device
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VirtualPointerDevice {
+    id: u32,
+}
+
+impl VirtualPointerDevice {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU32 = AtomicU32::new(0);
+        Self { id: NEXT_ID.fetch_add(1, Ordering::Relaxed) }
+    }
+}
+
+impl Device for VirtualPointerDevice {
+    fn id(&self) -> String {
+        format!("virtual-pointer-{}", self.id)
+    }
+
+    fn name(&self) -> String {
+        "virtual pointer".into()
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        capability == DeviceCapability::Pointer
+    }
+
+    fn usb_id(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn syspath(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+pub struct VirtualPointerMotionEvent {
+    device: VirtualPointerDevice,
+    /// in milliseconds
+    time: u32,
+    dx: f64,
+    dy: f64,
+}
+
+impl Event<VirtualPointerInputBackend> for VirtualPointerMotionEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointerDevice {
+        self.device.clone()
+    }
+}
+
+/// deltas are sent unaccelerated as is
+impl PointerMotionEvent<VirtualPointerInputBackend> for VirtualPointerMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.dx
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.dy
+    }
+
+    fn delta_x_unaccel(&self) -> f64 {
+        self.dx
+    }
+
+    fn delta_y_unaccel(&self) -> f64 {
+        self.dy
+    }
+}
+
+pub struct VirtualPointerMotionAbsoluteEvent {
+    device: VirtualPointerDevice,
+    /// in milliseconds
+    time: u32,
+    /// normalized to `0.0..=1.0`
+    x: f64,
+    y: f64,
+    output: Option<Output>,
+}
+
+impl VirtualPointerMotionAbsoluteEvent {
+    /// output the position is relative to, the compositor chooses one if `None`
+    pub fn output(&self) -> Option<&Output> {
+        self.output.as_ref()
+    }
+}
+
+impl Event<VirtualPointerInputBackend> for VirtualPointerMotionAbsoluteEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointerDevice {
+        self.device.clone()
+    }
+}
+
+impl AbsolutePositionEvent<VirtualPointerInputBackend> for VirtualPointerMotionAbsoluteEvent {
+    fn x(&self) -> f64 {
+        self.x
+    }
+
+    fn y(&self) -> f64 {
+        self.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        self.x * width as f64
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        self.y * height as f64
+    }
+}
+
+impl PointerMotionAbsoluteEvent<VirtualPointerInputBackend> for VirtualPointerMotionAbsoluteEvent {}
+
+pub struct VirtualPointerButtonEvent {
+    device: VirtualPointerDevice,
+    /// in milliseconds
+    time: u32,
+    button: u32,
+    state: ButtonState,
+}
+
+impl Event<VirtualPointerInputBackend> for VirtualPointerButtonEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointerDevice {
+        self.device.clone()
+    }
+}
+
+impl PointerButtonEvent<VirtualPointerInputBackend> for VirtualPointerButtonEvent {
+    fn button_code(&self) -> u32 {
+        self.button
+    }
+
+    fn state(&self) -> ButtonState {
+        self.state
+    }
+}
+
+/// axis requests accumulated until `frame`
+pub struct VirtualPointerAxisEvent {
+    device: VirtualPointerDevice,
+    /// in milliseconds
+    time: u32,
+    source: AxisSource,
+    horizontal: Option<f64>,
+    vertical: Option<f64>,
+    horizontal_v120: Option<f64>,
+    vertical_v120: Option<f64>,
+}
+
+impl VirtualPointerAxisEvent {
+    fn new(device: VirtualPointerDevice) -> Self {
+        Self {
+            device,
+            time: 0,
+            source: AxisSource::Wheel,
+            horizontal: None,
+            vertical: None,
+            horizontal_v120: None,
+            vertical_v120: None,
+        }
+    }
+
+    fn amount_mut(&mut self, axis: Axis) -> &mut Option<f64> {
+        match axis {
+            Axis::Horizontal => &mut self.horizontal,
+            Axis::Vertical => &mut self.vertical,
+        }
+    }
+
+    fn v120_mut(&mut self, axis: Axis) -> &mut Option<f64> {
+        match axis {
+            Axis::Horizontal => &mut self.horizontal_v120,
+            Axis::Vertical => &mut self.vertical_v120,
+        }
+    }
+}
+
+impl Event<VirtualPointerInputBackend> for VirtualPointerAxisEvent {
+    fn time(&self) -> u64 {
+        self.time as u64 * 1000
+    }
+
+    fn device(&self) -> VirtualPointerDevice {
+        self.device.clone()
+    }
+}
+
+impl PointerAxisEvent<VirtualPointerInputBackend> for VirtualPointerAxisEvent {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.horizontal,
+            Axis::Vertical => self.vertical,
+        }
+    }
+
+    fn amount_v120(&self, axis: Axis) -> Option<f64> {
+        match axis {
+            Axis::Horizontal => self.horizontal_v120,
+            Axis::Vertical => self.vertical_v120,
+        }
+    }
+
+    fn source(&self) -> AxisSource {
+        self.source
+    }
+
+    fn relative_direction(&self, _: Axis) -> AxisRelativeDirection {
+        AxisRelativeDirection::Identical
+    }
+}
+
+#[macro_export]
+macro_rules! delegate_virtual_pointer {
+    ($ty:ty) => {
+        smithay::reexports::wayland_server::delegate_global_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1: $crate::protocols::virtual_pointer::VirtualPointerManagerGlobalData
+        ] => $crate::protocols::virtual_pointer::VirtualPointerManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_manager_v1::ZwlrVirtualPointerManagerV1: ()
+        ] => $crate::protocols::virtual_pointer::VirtualPointerManagerState);
+        smithay::reexports::wayland_server::delegate_dispatch!($ty: [
+            smithay::reexports::wayland_protocols_wlr::virtual_pointer::v1::server::zwlr_virtual_pointer_v1::ZwlrVirtualPointerV1: $crate::protocols::virtual_pointer::VirtualPointerData
+        ] => $crate::protocols::virtual_pointer::VirtualPointerManagerState);
+    };
+}
@@ -0,0 +1,382 @@
+//! pipewire screencast of outputs
+//!
+//! each [`Cast`] is a pipewire video source node fed with dmabuf buffers, rendered
+//! after every vblank of its output
+//!
+//! only single plane `XRGB8888` buffers are negotiated, with modifiers taken from
+//! the output primary plane, the cursor is not embedded
+use anyhow::{Context as _, Result};
+use pipewire::{
+    context::Context,
+    core::Core,
+    main_loop::MainLoop,
+    properties::properties,
+    spa::{
+        self,
+        buffer::DataType,
+        param::{
+            format::{FormatProperties, MediaSubtype, MediaType},
+            video::{VideoFormat, VideoInfoRaw},
+            ParamType,
+        },
+        pod::{self, serialize::PodSerializer, ChoiceValue, Pod, Property, PropertyFlags, Value},
+        utils::{Choice, ChoiceEnum, ChoiceFlags, Direction, Fraction, Rectangle as SpaRectangle, SpaTypes},
+    },
+    stream::{Stream, StreamFlags, StreamListener, StreamState},
+};
+use smithay::{
+    backend::{
+        allocator::{
+            dmabuf::{AsDmabuf, Dmabuf},
+            gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+            Allocator, Buffer, Fourcc, Modifier,
+        },
+        drm::DrmDeviceFd,
+        renderer::{damage::OutputDamageTracker, Bind, Color32F},
+    },
+    output::Output,
+    reexports::calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction, RegistrationToken},
+};
+use std::{cell::RefCell, collections::HashMap, io::Cursor, os::fd::AsRawFd, rc::Rc, time::Duration};
+
+use crate::{
+    backend::UdevRenderer,
+    trayle::render::elements::{OutputRenderElements, WindowRenderElement},
+    Trayle,
+};
+
+/// pipewire connection, dispatched in the compositor event loop
+pub struct PipeWire {
+    _context: Context,
+    core: Core,
+    token: RegistrationToken,
+}
+
+impl PipeWire {
+    pub fn new(handle: &LoopHandle<'static, Trayle>) -> Result<PipeWire> {
+        let main_loop = MainLoop::new(None).context("failed to create pipewire loop")?;
+        let context = Context::new(&main_loop).context("failed to create pipewire context")?;
+        let core = context.connect(None).context("failed to connect to pipewire")?;
+
+        let fd = main_loop.loop_().fd().try_clone_to_owned()?;
+        let source = Generic::new(fd, Interest::READ, Mode::Level);
+        let token = handle
+            .insert_source(source, move|_,_,_|{
+                main_loop.loop_().iterate(Duration::ZERO);
+                Ok(PostAction::Continue)
+            })
+            .map_err(|err|anyhow::anyhow!("failed to insert pipewire source: {err}"))?;
+
+        Ok(PipeWire { _context: context, core, token })
+    }
+
+    pub fn remove(self, handle: &LoopHandle<'static, Trayle>) {
+        handle.remove(self.token);
+    }
+
+    /// start casting `output`, `modifiers` are the usable `XRGB8888` modifiers
+    pub fn cast(&self, output: &Output, gbm: GbmDevice<DrmDeviceFd>, modifiers: Vec<Modifier>) -> Result<Cast> {
+        anyhow::ensure!(!modifiers.is_empty(), "output has no usable dmabuf format");
+
+        let mode = output.current_mode().context("output has no mode")?;
+
+        let stream = Stream::new(
+            &self.core,
+            "trayle-screencast",
+            properties! {
+                *pipewire::keys::MEDIA_CLASS => "Video/Source",
+                *pipewire::keys::NODE_NAME => "trayle-screencast",
+                *pipewire::keys::NODE_DESCRIPTION => output.name(),
+            },
+        )
+        .context("failed to create pipewire stream")?;
+
+        let inner = Rc::new(RefCell::new(CastInner {
+            active: false,
+            size: (mode.size.w as u32, mode.size.h as u32),
+            modifier: None,
+            allocator: GbmAllocator::new(gbm, GbmBufferFlags::RENDERING),
+            dmabufs: HashMap::new(),
+        }));
+
+        let output_name = output.name();
+        let listener = stream
+            .add_local_listener::<()>()
+            .state_changed({
+                let inner = inner.clone();
+                move|stream,_,_,new|{
+                    match &new {
+                        StreamState::Paused => tracing::info!(
+                            "screencast of {output_name} is ready on node {}", stream.node_id()
+                        ),
+                        StreamState::Error(err) => tracing::warn!("screencast of {output_name} failed: {err}"),
+                        _ => {}
+                    }
+                    inner.borrow_mut().active = matches!(new, StreamState::Streaming);
+                }
+            })
+            .param_changed({
+                let inner = inner.clone();
+                move|stream,_,id,pod|{
+                    let Some(pod) = pod.filter(|_|id == ParamType::Format.as_raw()) else {
+                        return;
+                    };
+
+                    let mut info = VideoInfoRaw::new();
+                    if let Err(err) = info.parse(pod) {
+                        tracing::warn!("failed to parse screencast format: {err:?}");
+                        return;
+                    }
+
+                    inner.borrow_mut().modifier = Some(Modifier::from(info.modifier()));
+
+                    let params = buffer_params();
+                    let mut params = [Pod::from_bytes(&params).unwrap()];
+                    if let Err(err) = stream.update_params(&mut params) {
+                        tracing::warn!("failed to update screencast params: {err}");
+                    }
+                }
+            })
+            .add_buffer({
+                let inner = inner.clone();
+                move|_,_,buffer|{
+                    if let Err(err) = inner.borrow_mut().add_buffer(buffer) {
+                        tracing::warn!("failed to allocate screencast buffer: {err:#}");
+                    }
+                }
+            })
+            .remove_buffer({
+                let inner = inner.clone();
+                move|_,_,buffer|{
+                    // SAFETY: pipewire gives valid buffer with at least one data
+                    let fd = unsafe { (*(*(*buffer).buffer).datas).fd };
+                    inner.borrow_mut().dmabufs.remove(&fd);
+                }
+            })
+            .register()
+            .context("failed to register pipewire listener")?;
+
+        let params = format_params(mode.size.w as u32, mode.size.h as u32, mode.refresh as u32, &modifiers);
+        let mut params = [Pod::from_bytes(&params).unwrap()];
+        stream
+            .connect(
+                Direction::Output,
+                None,
+                StreamFlags::DRIVER | StreamFlags::ALLOC_BUFFERS,
+                &mut params,
+            )
+            .context("failed to connect pipewire stream")?;
+
+        Ok(Cast {
+            output: output.clone(),
+            damage_tracker: OutputDamageTracker::from_output(output),
+            stream,
+            _listener: listener,
+            inner,
+        })
+    }
+}
+
+/// a single output screencast
+pub struct Cast {
+    pub output: Output,
+    damage_tracker: OutputDamageTracker,
+    stream: Stream,
+    _listener: StreamListener<()>,
+    inner: Rc<RefCell<CastInner>>,
+}
+
+struct CastInner {
+    active: bool,
+    size: (u32, u32),
+    modifier: Option<Modifier>,
+    allocator: GbmAllocator<DrmDeviceFd>,
+    /// allocated buffers by its fd
+    dmabufs: HashMap<i64, Dmabuf>,
+}
+
+impl Cast {
+    /// render `elements` into the next buffer, if the consumer has one available
+    pub fn render<'a>(
+        &mut self,
+        renderer: &mut UdevRenderer<'a>,
+        elements: &[OutputRenderElements<UdevRenderer<'a>, WindowRenderElement<UdevRenderer<'a>>>],
+        clear_color: Color32F,
+    ) -> Result<()> {
+        let inner = self.inner.borrow();
+        if !inner.active {
+            return Ok(());
+        }
+
+        // consumer is behind, frame is skipped
+        let Some(mut buffer) = self.stream.dequeue_buffer() else {
+            return Ok(());
+        };
+
+        let data = &mut buffer.datas_mut()[0];
+        let dmabuf = inner.dmabufs.get(&data.as_raw().fd).context("unknown screencast buffer")?.clone();
+
+        let mut target = dmabuf.clone();
+        let mut framebuffer = renderer
+            .bind(&mut target)
+            .map_err(|err|anyhow::anyhow!("failed to bind screencast buffer: {err:?}"))?;
+        self.damage_tracker
+            .render_output(renderer, &mut framebuffer, 0, elements, clear_color)
+            .map_err(|err|anyhow::anyhow!("failed to render screencast: {err:?}"))?
+            .sync
+            .wait()
+            .map_err(|err|anyhow::anyhow!("failed to wait for screencast render: {err:?}"))?;
+
+        let stride = dmabuf.strides().next().unwrap_or_default();
+        let chunk = data.chunk_mut();
+        *chunk.offset_mut() = dmabuf.offsets().next().unwrap_or_default();
+        *chunk.stride_mut() = stride as i32;
+        *chunk.size_mut() = stride * dmabuf.height();
+
+        Ok(())
+    }
+}
+
+impl Drop for Cast {
+    fn drop(&mut self) {
+        if let Err(err) = self.stream.disconnect() {
+            tracing::warn!("failed to disconnect screencast of {}: {err}", self.output.name());
+        }
+    }
+}
+
+impl CastInner {
+    fn add_buffer(&mut self, buffer: *mut pipewire::sys::pw_buffer) -> Result<()> {
+        let modifier = self.modifier.context("format is not negotiated")?;
+        let (width, height) = self.size;
+
+        let dmabuf = self.allocator
+            .create_buffer(width, height, Fourcc::Xrgb8888, &[modifier])
+            .context("failed to allocate buffer")?
+            .export()
+            .context("failed to export buffer")?;
+
+        anyhow::ensure!(dmabuf.num_planes() == 1, "multi plane modifier is not supported");
+
+        let fd = dmabuf.handles().next().unwrap().as_raw_fd() as i64;
+        let stride = dmabuf.strides().next().unwrap();
+
+        // SAFETY: pipewire gives valid buffer with the one data block we requested
+        unsafe {
+            let data = &mut *(*(*buffer).buffer).datas;
+            data.type_ = DataType::DmaBuf.as_raw();
+            data.flags = spa::sys::SPA_DATA_FLAG_READWRITE;
+            data.fd = fd;
+            data.mapoffset = 0;
+            data.maxsize = stride * height;
+        }
+
+        self.dmabufs.insert(fd, dmabuf);
+        Ok(())
+    }
+}
+
+fn format_params(width: u32, height: u32, refresh: u32, modifiers: &[Modifier]) -> Vec<u8> {
+    let modifiers = modifiers.iter().map(|m|u64::from(*m) as i64).collect::<Vec<_>>();
+    let object = pod::object!(
+        SpaTypes::ObjectParamFormat,
+        ParamType::EnumFormat,
+        pod::property!(FormatProperties::MediaType, Id, MediaType::Video),
+        pod::property!(FormatProperties::MediaSubtype, Id, MediaSubtype::Raw),
+        pod::property!(FormatProperties::VideoFormat, Id, VideoFormat::BGRx),
+        Property {
+            key: FormatProperties::VideoModifier.as_raw(),
+            flags: PropertyFlags::MANDATORY,
+            value: Value::Choice(ChoiceValue::Long(Choice(
+                ChoiceFlags::empty(),
+                ChoiceEnum::Enum { default: modifiers[0], alternatives: modifiers },
+            ))),
+        },
+        pod::property!(FormatProperties::VideoSize, Rectangle, SpaRectangle { width, height }),
+        pod::property!(FormatProperties::VideoFramerate, Fraction, Fraction { num: 0, denom: 1 }),
+        pod::property!(
+            FormatProperties::VideoMaxFramerate,
+            Choice, Range, Fraction,
+            Fraction { num: refresh, denom: 1000 },
+            Fraction { num: 1, denom: 1 },
+            Fraction { num: refresh, denom: 1000 }
+        ),
+    );
+
+    PodSerializer::serialize(Cursor::new(Vec::new()), &Value::Object(object))
+        .unwrap()
+        .0
+        .into_inner()
+}
+
+fn buffer_params() -> Vec<u8> {
+    let dmabuf = 1 << DataType::DmaBuf.as_raw();
+    let object = pod::object!(
+        SpaTypes::ObjectParamBuffers,
+        ParamType::Buffers,
+        Property::new(
+            spa::sys::SPA_PARAM_BUFFERS_buffers,
+            Value::Choice(ChoiceValue::Int(Choice(
+                ChoiceFlags::empty(),
+                ChoiceEnum::Range { default: 8, min: 2, max: 16 },
+            ))),
+        ),
+        Property::new(spa::sys::SPA_PARAM_BUFFERS_blocks, Value::Int(1)),
+        Property::new(
+            spa::sys::SPA_PARAM_BUFFERS_dataType,
+            Value::Choice(ChoiceValue::Int(Choice(
+                ChoiceFlags::empty(),
+                ChoiceEnum::Flags { default: dmabuf, flags: vec![dmabuf] },
+            ))),
+        ),
+    );
+
+    PodSerializer::serialize(Cursor::new(Vec::new()), &Value::Object(object))
+        .unwrap()
+        .0
+        .into_inner()
+}
+
+/// screencast control
+impl Trayle {
+    /// start pipewire screencast of output, the node id is logged once the stream is ready
+    pub fn start_screencast(&mut self, output_name: &str) -> Result<()> {
+        let output = self.frontend.space
+            .outputs()
+            .find(|o|o.name() == output_name)
+            .cloned()
+            .with_context(||format!("no output named {output_name:?}"))?;
+        let id = output
+            .user_data()
+            .get::<crate::trayle::device::UdevOutputId>()
+            .context("output is not a drm output")?;
+
+        let device = self.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&id.device_id)).context("output device is gone")?;
+        let surface = device.surfaces.get_mut(&id.crtc).context("output surface is gone")?;
+
+        let modifiers = surface.drm_output.with_compositor(|compositor|{
+            compositor
+                .surface()
+                .plane_info()
+                .formats
+                .iter()
+                .filter(|format|format.code == Fourcc::Xrgb8888)
+                .map(|format|format.modifier)
+                .collect::<Vec<_>>()
+        });
+
+        let pipewire = match self.backend.pipewire.as_ref() {
+            Some(pipewire) => pipewire,
+            None => self.backend.pipewire.insert(PipeWire::new(&self.handle)?),
+        };
+
+        let cast = pipewire.cast(&output, device.gbm.clone(), modifiers)?;
+        self.backend.casts.push(cast);
+        Ok(())
+    }
+
+    /// stop all screencast of output
+    pub fn stop_screencast(&mut self, output_name: &str) {
+        self.backend.casts.retain(|cast|cast.output.name() != output_name);
+    }
+}
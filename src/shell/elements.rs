@@ -8,17 +8,25 @@ use smithay::{
         },
         ImportAll, ImportMem, Renderer, Texture,
     },
-    desktop::{space::SpaceElement, utils::OutputPresentationFeedback, Window},
+    desktop::{space::SpaceElement, utils::OutputPresentationFeedback, Window, WindowSurfaceType},
     output::Output,
     reexports::{
-        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
+        wayland_protocols::wp::{
+            content_type::v1::server::wp_content_type_v1::Type as ContentType,
+            presentation_time::server::wp_presentation_feedback,
+        },
         wayland_server::protocol::wl_surface::WlSurface,
     },
     utils::{IsAlive, Logical, Physical, Point, Rectangle, Scale},
-    wayland::{compositor::SurfaceData as WlSurfaceData, dmabuf::DmabufFeedback, shell::xdg::ToplevelSurface},
+    wayland::{
+        compositor::{self, SurfaceData as WlSurfaceData},
+        content_type::ContentTypeSurfaceCachedState,
+        dmabuf::DmabufFeedback,
+        shell::xdg::ToplevelSurface,
+    },
 };
 
-use crate::shell::ssd::HEADER_BAR_HEIGHT;
+use crate::{shell::ssd::HEADER_BAR_HEIGHT, trayle::render};
 
 use super::ssd::{HeaderBar, WindowState};
 
@@ -30,6 +38,21 @@ impl WindowElement {
         self.0.with_surfaces(processor);
     }
 
+    /// content type hinted by the client for its toplevel surface
+    pub fn content_type(&self) -> ContentType {
+        let Some(surface) = self.0.wl_surface() else {
+            return ContentType::None;
+        };
+        compositor::with_states(&surface, |states|{
+            *states.cached_state.get::<ContentTypeSurfaceCachedState>().current().content_type()
+        })
+    }
+
+    /// video or game content, preferred to be scanned out directly and left undecorated
+    pub fn is_scanout_content(&self) -> bool {
+        matches!(self.content_type(), ContentType::Video | ContentType::Game)
+    }
+
     pub fn decoration_state(&self) -> RefMut<'_, WindowState> {
         self.0.user_data().insert_if_missing(||{
             RefCell::new(WindowState {
@@ -37,6 +60,7 @@ impl WindowElement {
                 header_bar: HeaderBar {
                     pointer_loc: None,
                     width: 0,
+                    height: HEADER_BAR_HEIGHT,
                     close_button_hover: false,
                     maximize_button_hover: false,
                     background: Default::default(),
@@ -90,6 +114,44 @@ impl WindowElement {
         self.0.send_dmabuf_feedback(output, primary_scan_out_output, select_dmabuf_feedback)
     }
 
+    /// floating location and size saved before the window is fullscreened or maximized
+    pub fn restore_geometry(&self) -> RefMut<'_, Option<Rectangle<i32, Logical>>> {
+        self.0.user_data().insert_if_missing(||RestoreGeometry(RefCell::new(None)));
+        self.0.user_data()
+            .get::<RestoreGeometry>()
+            .unwrap()
+            .0
+            .borrow_mut()
+    }
+
+    /// stable id assigned when the window is mapped
+    pub fn id(&self) -> Option<u64> {
+        self.0.user_data().get::<WindowId>().map(|id|id.0)
+    }
+
+    /// assign the window id, the first assigned id is kept
+    pub fn set_id(&self, id: u64) {
+        self.0.user_data().insert_if_missing(||WindowId(id));
+    }
+
+    /// header bar height if server side decorated, otherwise zero
+    pub fn header_height(&self) -> i32 {
+        let state = self.decoration_state();
+        if state.is_ssd { state.header_bar.height } else { 0 }
+    }
+
+    /// surface under element relative `location`, and its element relative location
+    pub fn surface_under(
+        &self,
+        location: Point<f64, Logical>,
+        surface_type: WindowSurfaceType,
+    ) -> Option<(WlSurface, Point<i32, Logical>)> {
+        let offset = Point::from((0, self.header_height()));
+        self.0
+            .surface_under(location - offset.to_f64(), surface_type)
+            .map(|(surface, surface_location)|(surface, surface_location + offset))
+    }
+
     pub fn toplevel(&self) -> Option<&ToplevelSurface> {
         self.0.toplevel()
     }
@@ -99,6 +161,10 @@ impl WindowElement {
     }
 }
 
+struct RestoreGeometry(RefCell<Option<Rectangle<i32, Logical>>>);
+
+struct WindowId(u64);
+
 impl IsAlive for WindowElement {
     #[inline]
     fn alive(&self) -> bool {
@@ -109,16 +175,15 @@ impl IsAlive for WindowElement {
 impl SpaceElement for WindowElement {
     fn bbox(&self) -> Rectangle<i32, Logical> {
         let mut bbox = self.0.bbox();
-        if self.decoration_state().is_ssd {
-            bbox.size.h += HEADER_BAR_HEIGHT;
-        }
+        bbox.size.h += self.header_height();
         bbox
     }
 
     fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
-        if self.decoration_state().is_ssd {
-            point.y < HEADER_BAR_HEIGHT as f64 ||
-            self.0.is_in_input_region(&(*point - Point::from((0., HEADER_BAR_HEIGHT as f64))))
+        let header_height = self.header_height();
+        if header_height > 0 {
+            point.y < header_height as f64 ||
+            self.0.is_in_input_region(&(*point - Point::from((0., header_height as f64))))
         } else {
             self.0.is_in_input_region(point)
         }
@@ -146,9 +211,7 @@ impl SpaceElement for WindowElement {
 
     fn geometry(&self) -> Rectangle<i32, Logical> {
         let mut geo = self.0.geometry();
-        if self.decoration_state().is_ssd {
-            geo.size.h += HEADER_BAR_HEIGHT;
-        }
+        geo.size.h += self.header_height();
         geo
     }
 }
@@ -169,7 +232,7 @@ where
     ) -> Vec<C> {
         let window_bbox = SpaceElement::bbox(&self.0);
 
-        if self.decoration_state().is_ssd && window_bbox.is_empty() {
+        if self.decoration_state().is_ssd && !window_bbox.is_empty() {
             let window_geo = SpaceElement::geometry(&self.0);
 
             let mut state = self.decoration_state();
@@ -183,25 +246,12 @@ where
                 alpha
             );
 
-            location.y += (scale.y * HEADER_BAR_HEIGHT as f64) as i32;
+            location.y += (scale.y * state.header_bar.height as f64) as i32;
 
-            let window_elements = AsRenderElements::<R>::render_elements(
-                &self.0,
-                renderer,
-                location,
-                scale,
-                alpha
-            );
-            vec.extend(window_elements);
+            vec.extend(self.surface_elements(renderer, location, scale, alpha));
             vec.into_iter().map(C::from).collect()
         } else {
-            AsRenderElements::<R>::render_elements(
-                &self.0,
-                renderer,
-                location,
-                scale,
-                alpha
-            )
+            self.surface_elements(renderer, location, scale, alpha)
                 .into_iter()
                 .map(C::from)
                 .collect()
@@ -209,9 +259,41 @@ where
     }
 }
 
+impl WindowElement {
+    /// surfaces and popups of the window, see [`render::elements::single_pixel`]
+    ///
+    /// [`render::elements::single_pixel`]: crate::trayle::render::elements::single_pixel
+    fn surface_elements<R>(
+        &self,
+        renderer: &mut R,
+        location: Point<i32, Physical>,
+        scale: Scale<f64>,
+        alpha: f32,
+    ) -> Vec<WindowRenderElement<R>>
+    where
+        R: Renderer + ImportAll + ImportMem,
+        <R as Renderer>::TextureId: Clone + Texture + 'static,
+    {
+        AsRenderElements::<R>::render_elements::<WaylandSurfaceRenderElement<R>>(
+            &self.0,
+            renderer,
+            location,
+            scale,
+            alpha
+        )
+            .into_iter()
+            .map(|element|match render::elements::single_pixel(&element, renderer, scale) {
+                Some(solid) => WindowRenderElement::SolidColor(solid),
+                None => WindowRenderElement::Window(element),
+            })
+            .collect()
+    }
+}
+
 smithay::render_elements! {
     pub WindowRenderElement<R> where R: ImportAll + ImportMem;
     Window=WaylandSurfaceRenderElement<R>,
-    Decoration=SolidColorRenderElement,
+    /// server side decorations, and surfaces with a single pixel buffer
+    SolidColor=SolidColorRenderElement,
 }
 
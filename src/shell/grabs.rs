@@ -0,0 +1,402 @@
+//! interactive pointer grabs
+use std::cell::RefCell;
+
+use smithay::{
+    input::pointer::{
+        AxisFrame, ButtonEvent, GestureHoldBeginEvent, GestureHoldEndEvent,
+        GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+        GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent, GrabStartData,
+        MotionEvent, PointerGrab, PointerInnerHandle, RelativeMotionEvent,
+    },
+    reexports::{
+        wayland_protocols::xdg::shell::server::xdg_toplevel,
+        wayland_server::protocol::wl_surface::WlSurface,
+    },
+    utils::{Logical, Point, Serial, Size},
+    wayland::{
+        compositor::{self, SurfaceCachedState},
+        shell::xdg::ToplevelSurface,
+    },
+};
+
+use crate::{shell::elements::WindowElement, Trayle};
+
+/// forward the remaining [`PointerGrab`] events untouched
+macro_rules! forward_pointer_grab {
+    () => {
+        fn relative_motion(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            focus: Option<(WlSurface, Point<f64, Logical>)>,
+            event: &RelativeMotionEvent,
+        ) {
+            handle.relative_motion(data, focus, event);
+        }
+
+        fn axis(&mut self, data: &mut Trayle, handle: &mut PointerInnerHandle<'_, Trayle>, details: AxisFrame) {
+            handle.axis(data, details)
+        }
+
+        fn frame(&mut self, data: &mut Trayle, handle: &mut PointerInnerHandle<'_, Trayle>) {
+            handle.frame(data);
+        }
+
+        fn gesture_swipe_begin(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GestureSwipeBeginEvent,
+        ) {
+            handle.gesture_swipe_begin(data, event)
+        }
+
+        fn gesture_swipe_update(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GestureSwipeUpdateEvent,
+        ) {
+            handle.gesture_swipe_update(data, event)
+        }
+
+        fn gesture_swipe_end(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GestureSwipeEndEvent,
+        ) {
+            handle.gesture_swipe_end(data, event)
+        }
+
+        fn gesture_pinch_begin(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GesturePinchBeginEvent,
+        ) {
+            handle.gesture_pinch_begin(data, event)
+        }
+
+        fn gesture_pinch_update(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GesturePinchUpdateEvent,
+        ) {
+            handle.gesture_pinch_update(data, event)
+        }
+
+        fn gesture_pinch_end(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GesturePinchEndEvent,
+        ) {
+            handle.gesture_pinch_end(data, event)
+        }
+
+        fn gesture_hold_begin(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GestureHoldBeginEvent,
+        ) {
+            handle.gesture_hold_begin(data, event)
+        }
+
+        fn gesture_hold_end(
+            &mut self,
+            data: &mut Trayle,
+            handle: &mut PointerInnerHandle<'_, Trayle>,
+            event: &GestureHoldEndEvent,
+        ) {
+            handle.gesture_hold_end(data, event)
+        }
+
+        fn start_data(&self) -> &GrabStartData<Trayle> {
+            &self.start_data
+        }
+    };
+}
+
+/// move window following the pointer until all buttons are released
+pub struct MoveGrab {
+    pub start_data: GrabStartData<Trayle>,
+    pub window: WindowElement,
+    pub initial_window_location: Point<i32, Logical>,
+}
+
+impl PointerGrab<Trayle> for MoveGrab {
+    fn motion(
+        &mut self,
+        data: &mut Trayle,
+        handle: &mut PointerInnerHandle<'_, Trayle>,
+        _focus: Option<(WlSurface, Point<f64, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // no client has pointer focus while moving
+        handle.motion(data, None, event);
+
+        let delta = event.location - self.start_data.location;
+        let location = self.initial_window_location.to_f64() + delta;
+        data.frontend.space.map_element(self.window.clone(), location.to_i32_round(), true);
+    }
+
+    fn button(
+        &mut self,
+        data: &mut Trayle,
+        handle: &mut PointerInnerHandle<'_, Trayle>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+        if handle.current_pressed().is_empty() {
+            handle.unset_grab(self, data, event.serial, event.time, true);
+        }
+    }
+
+    fn unset(&mut self, _data: &mut Trayle) {}
+
+    forward_pointer_grab!();
+}
+
+bitflags::bitflags! {
+    /// edges of the window being dragged
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct ResizeEdge: u32 {
+        const TOP = 1;
+        const BOTTOM = 1 << 1;
+        const LEFT = 1 << 2;
+        const RIGHT = 1 << 3;
+    }
+}
+
+impl ResizeEdge {
+    /// edges closest to `location` relative to a window of `size`
+    pub fn nearest(location: Point<f64, Logical>, size: Size<i32, Logical>) -> ResizeEdge {
+        let mut edges = ResizeEdge::empty();
+        edges |= if location.x < size.w as f64 / 2.0 { ResizeEdge::LEFT } else { ResizeEdge::RIGHT };
+        edges |= if location.y < size.h as f64 / 2.0 { ResizeEdge::TOP } else { ResizeEdge::BOTTOM };
+        edges
+    }
+}
+
+impl From<xdg_toplevel::ResizeEdge> for ResizeEdge {
+    fn from(edge: xdg_toplevel::ResizeEdge) -> Self {
+        use xdg_toplevel::ResizeEdge as E;
+        match edge {
+            E::Top => ResizeEdge::TOP,
+            E::Bottom => ResizeEdge::BOTTOM,
+            E::Left => ResizeEdge::LEFT,
+            E::Right => ResizeEdge::RIGHT,
+            E::TopLeft => ResizeEdge::TOP | ResizeEdge::LEFT,
+            E::TopRight => ResizeEdge::TOP | ResizeEdge::RIGHT,
+            E::BottomLeft => ResizeEdge::BOTTOM | ResizeEdge::LEFT,
+            E::BottomRight => ResizeEdge::BOTTOM | ResizeEdge::RIGHT,
+            _ => ResizeEdge::empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ResizeData {
+    pub edges: ResizeEdge,
+    pub initial_window_location: Point<i32, Logical>,
+    pub initial_window_size: Size<i32, Logical>,
+}
+
+/// resize progress, stored in the toplevel surface data
+///
+/// when resizing from top or left edges, the window location is only updated once the
+/// client commits a buffer of the new size, see [`ResizeState::commit_location`]
+#[derive(Debug, Default, Clone, Copy)]
+pub enum ResizeState {
+    #[default]
+    NotResizing,
+    Resizing(ResizeData),
+    WaitingForFinalAck(ResizeData, Serial),
+    WaitingForCommit(ResizeData),
+}
+
+impl ResizeState {
+    pub fn get(surface: &WlSurface) -> ResizeState {
+        compositor::with_states(surface, |states|{
+            *states
+                .data_map
+                .get_or_insert(RefCell::<ResizeState>::default)
+                .borrow()
+        })
+    }
+
+    pub fn set(surface: &WlSurface, state: ResizeState) {
+        compositor::with_states(surface, |states|{
+            *states
+                .data_map
+                .get_or_insert(RefCell::<ResizeState>::default)
+                .borrow_mut() = state;
+        })
+    }
+
+    /// on final configure acked, wait for the client to commit the new size
+    pub fn ack_configure(surface: &WlSurface, serial: Serial) {
+        if let ResizeState::WaitingForFinalAck(data, final_serial) = ResizeState::get(surface) {
+            if serial >= final_serial {
+                ResizeState::set(surface, ResizeState::WaitingForCommit(data));
+            }
+        }
+    }
+
+    /// new window location after commit of `geometry_size`, if it has to be moved
+    pub fn commit_location(
+        surface: &WlSurface,
+        geometry_size: Size<i32, Logical>,
+    ) -> Option<Point<i32, Logical>> {
+        let state = ResizeState::get(surface);
+        let data = match state {
+            ResizeState::Resizing(data) |
+            ResizeState::WaitingForFinalAck(data, _) |
+            ResizeState::WaitingForCommit(data) => data,
+            ResizeState::NotResizing => return None,
+        };
+
+        if let ResizeState::WaitingForCommit(_) = state {
+            ResizeState::set(surface, ResizeState::NotResizing);
+        }
+
+        let mut location = data.initial_window_location;
+        if data.edges.contains(ResizeEdge::LEFT) {
+            location.x += data.initial_window_size.w - geometry_size.w;
+        }
+        if data.edges.contains(ResizeEdge::TOP) {
+            location.y += data.initial_window_size.h - geometry_size.h;
+        }
+
+        data.edges.intersects(ResizeEdge::LEFT | ResizeEdge::TOP).then_some(location)
+    }
+}
+
+/// resize toplevel following the pointer until all buttons are released
+pub struct ResizeGrab {
+    pub start_data: GrabStartData<Trayle>,
+    pub window: WindowElement,
+    pub edges: ResizeEdge,
+    pub initial_window_location: Point<i32, Logical>,
+    pub initial_window_size: Size<i32, Logical>,
+    pub last_window_size: Size<i32, Logical>,
+}
+
+impl ResizeGrab {
+    pub fn start(
+        start_data: GrabStartData<Trayle>,
+        window: WindowElement,
+        edges: ResizeEdge,
+        initial_window_location: Point<i32, Logical>,
+    ) -> Option<ResizeGrab> {
+        let toplevel = window.toplevel()?;
+        // size of the toplevel, excluding decoration
+        let initial_window_size = window.0.geometry().size;
+
+        ResizeState::set(toplevel.wl_surface(), ResizeState::Resizing(ResizeData {
+            edges,
+            initial_window_location,
+            initial_window_size,
+        }));
+
+        Some(ResizeGrab {
+            start_data,
+            window,
+            edges,
+            initial_window_location,
+            initial_window_size,
+            last_window_size: initial_window_size,
+        })
+    }
+
+    fn toplevel(&self) -> Option<ToplevelSurface> {
+        self.window.toplevel().cloned()
+    }
+}
+
+impl PointerGrab<Trayle> for ResizeGrab {
+    fn motion(
+        &mut self,
+        data: &mut Trayle,
+        handle: &mut PointerInnerHandle<'_, Trayle>,
+        _focus: Option<(WlSurface, Point<f64, Logical>)>,
+        event: &MotionEvent,
+    ) {
+        // no client has pointer focus while resizing
+        handle.motion(data, None, event);
+
+        let Some(toplevel) = self.toplevel() else {
+            handle.unset_grab(self, data, event.serial, event.time, true);
+            return;
+        };
+
+        let delta = (event.location - self.start_data.location).to_i32_round::<i32>();
+        let mut size = self.initial_window_size;
+
+        if self.edges.contains(ResizeEdge::LEFT) {
+            size.w -= delta.x;
+        } else if self.edges.contains(ResizeEdge::RIGHT) {
+            size.w += delta.x;
+        }
+        if self.edges.contains(ResizeEdge::TOP) {
+            size.h -= delta.y;
+        } else if self.edges.contains(ResizeEdge::BOTTOM) {
+            size.h += delta.y;
+        }
+
+        // zero means unlimited
+        let (min_size, max_size) = compositor::with_states(toplevel.wl_surface(), |states|{
+            let mut cached = states.cached_state.get::<SurfaceCachedState>();
+            let current = cached.current();
+            (current.min_size, current.max_size)
+        });
+
+        let max_w = if max_size.w == 0 { i32::MAX } else { max_size.w };
+        let max_h = if max_size.h == 0 { i32::MAX } else { max_size.h };
+        size.w = size.w.max(min_size.w).min(max_w).max(1);
+        size.h = size.h.max(min_size.h).min(max_h).max(1);
+
+        self.last_window_size = size;
+
+        toplevel.with_pending_state(|state|{
+            state.states.set(xdg_toplevel::State::Resizing);
+            state.size = Some(size);
+        });
+        toplevel.send_pending_configure();
+    }
+
+    fn button(
+        &mut self,
+        data: &mut Trayle,
+        handle: &mut PointerInnerHandle<'_, Trayle>,
+        event: &ButtonEvent,
+    ) {
+        handle.button(data, event);
+        if handle.current_pressed().is_empty() {
+            handle.unset_grab(self, data, event.serial, event.time, true);
+        }
+    }
+
+    fn unset(&mut self, _data: &mut Trayle) {
+        let Some(toplevel) = self.toplevel() else {
+            return;
+        };
+
+        toplevel.with_pending_state(|state|{
+            state.states.unset(xdg_toplevel::State::Resizing);
+            state.size = Some(self.last_window_size);
+        });
+        let serial = toplevel.send_configure();
+
+        ResizeState::set(toplevel.wl_surface(), ResizeState::WaitingForFinalAck(ResizeData {
+            edges: self.edges,
+            initial_window_location: self.initial_window_location,
+            initial_window_size: self.initial_window_size,
+        }, serial));
+    }
+
+    forward_pointer_grab!();
+}
@@ -0,0 +1,149 @@
+//! master-stack tiling layout
+//!
+//! each output has its own list of tiled windows, the first one is the master taking
+//! the left half of the output, the rest are stacked vertically on the right half
+//!
+//! only tiled windows that are mapped, that is on the active workspace, are arranged
+use smithay::{
+    desktop::Space,
+    output::Output,
+    reexports::wayland_protocols::xdg::shell::server::xdg_toplevel,
+    utils::{IsAlive, Logical, Rectangle},
+};
+use std::collections::HashMap;
+
+use super::elements::WindowElement;
+
+const TILED_STATES: [xdg_toplevel::State; 4] = [
+    xdg_toplevel::State::TiledLeft,
+    xdg_toplevel::State::TiledRight,
+    xdg_toplevel::State::TiledTop,
+    xdg_toplevel::State::TiledBottom,
+];
+
+#[derive(Default)]
+pub struct Layout {
+    outputs: HashMap<Output, Vec<WindowElement>>,
+}
+
+impl Layout {
+    pub fn is_tiled(&self, window: &WindowElement) -> bool {
+        self.output_of(window).is_some()
+    }
+
+    /// output where tiled `window` is placed
+    pub fn output_of(&self, window: &WindowElement) -> Option<Output> {
+        self.outputs
+            .iter()
+            .find(|(_,windows)|windows.contains(window))
+            .map(|(output,_)|output.clone())
+    }
+
+    /// tile `window` on `output`, it becomes the last in the stack
+    pub fn insert(&mut self, output: &Output, window: WindowElement) {
+        if let Some(toplevel) = window.toplevel() {
+            toplevel.with_pending_state(|state|{
+                for tiled in TILED_STATES {
+                    state.states.set(tiled);
+                }
+            });
+        }
+        self.outputs.entry(output.clone()).or_default().push(window);
+    }
+
+    /// make `window` floating, returning the output it was tiled on
+    pub fn remove(&mut self, window: &WindowElement) -> Option<Output> {
+        let output = self.output_of(window)?;
+        self.outputs.get_mut(&output)?.retain(|w|w != window);
+
+        if let Some(toplevel) = window.toplevel().filter(|_|window.alive()) {
+            toplevel.with_pending_state(|state|{
+                for tiled in TILED_STATES {
+                    state.states.unset(tiled);
+                }
+            });
+            toplevel.send_pending_configure();
+        }
+
+        Some(output)
+    }
+
+    /// windows on removed output become floating
+    pub fn remove_output(&mut self, output: &Output) -> Vec<WindowElement> {
+        let windows = self.outputs.remove(output).unwrap_or_default();
+        for window in windows.iter().filter_map(|w|w.toplevel()) {
+            window.with_pending_state(|state|{
+                for tiled in TILED_STATES {
+                    state.states.unset(tiled);
+                }
+            });
+            window.send_pending_configure();
+        }
+        windows
+    }
+
+    /// resize and place tiled windows of `output` within `area`
+    pub fn arrange(&mut self, space: &mut Space<WindowElement>, output: &Output, area: Rectangle<i32, Logical>) {
+        let Some(windows) = self.outputs.get_mut(output) else {
+            return;
+        };
+        windows.retain(|window|window.alive());
+
+        // fullscreen windows keep the whole output until unfullscreened
+        let mapped = windows
+            .iter()
+            .filter(|window|space.element_location(window).is_some())
+            .filter(|window|!is_fullscreen(window))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let Some((master, stack)) = mapped.split_first() else {
+            return;
+        };
+
+        if stack.is_empty() {
+            configure(space, master, area);
+            return;
+        }
+
+        let master_width = area.size.w / 2;
+        configure(space, master, Rectangle::new(area.loc, (master_width, area.size.h).into()));
+
+        let stack_width = area.size.w - master_width;
+        let stack_height = area.size.h / stack.len() as i32;
+        for (i, window) in stack.iter().enumerate() {
+            let y = stack_height * i as i32;
+            // the last one takes the rounding remainder
+            let height = match i == stack.len() - 1 {
+                true => area.size.h - y,
+                false => stack_height,
+            };
+            let rect = Rectangle::new(
+                (area.loc.x + master_width, area.loc.y + y).into(),
+                (stack_width, height).into(),
+            );
+            configure(space, window, rect);
+        }
+    }
+}
+
+fn is_fullscreen(window: &WindowElement) -> bool {
+    window.toplevel().is_some_and(|toplevel|{
+        toplevel.with_pending_state(|state|state.states.contains(xdg_toplevel::State::Fullscreen))
+    })
+}
+
+fn configure(space: &mut Space<WindowElement>, window: &WindowElement, rect: Rectangle<i32, Logical>) {
+    if let Some(toplevel) = window.toplevel() {
+        // header bar is part of the window geometry, but not of the client surface
+        let header_height = window.header_height();
+        toplevel.with_pending_state(|state|{
+            state.size = Some((rect.size.w, (rect.size.h - header_height).max(1)).into());
+        });
+        // otherwise the pending state is sent with the initial configure
+        if toplevel.is_initial_configure_sent() {
+            toplevel.send_pending_configure();
+        }
+    }
+    space.map_element(window.clone(), rect.loc, false);
+}
@@ -4,9 +4,16 @@ use elements::WindowElement;
 use smithay::utils::IsAlive;
 
 pub mod elements;
+pub mod grabs;
+pub mod layout;
 pub mod utils;
 pub mod ssd;
+pub mod workspace;
 
+#[cfg(test)]
+mod tests;
+
+/// window shown fullscreen on an output, stored in the output user data
 #[derive(Default)]
 pub struct FullscreenSurface(RefCell<Option<WindowElement>>);
 
@@ -18,6 +25,14 @@ impl FullscreenSurface {
         }
         window.clone()
     }
+
+    pub fn set(&self, window: WindowElement) {
+        *self.0.borrow_mut() = Some(window);
+    }
+
+    pub fn clear(&self) -> Option<WindowElement> {
+        self.0.borrow_mut().take()
+    }
 }
 
 
@@ -7,8 +7,8 @@ const CLOSE_COLOR: [f32; 4] = [1f32, 0.66f32, 0.612f32, 1f32];
 const MAX_COLOR_HOVER: [f32; 4] = [0.71f32, 0.624f32, 0f32, 1f32];
 const CLOSE_COLOR_HOVER: [f32; 4] = [0.75f32, 0.11f32, 0.016f32, 1f32];
 
+/// default header bar height
 pub const HEADER_BAR_HEIGHT: i32 = 32;
-const BUTTON_HEIGHT: u32 = HEADER_BAR_HEIGHT as u32;
 const BUTTON_WIDTH: u32 = 32;
 
 
@@ -21,6 +21,7 @@ pub struct WindowState {
 pub struct HeaderBar {
     pub pointer_loc: Option<Point<f64, Logical>>,
     pub width: u32,
+    pub height: i32,
     pub close_button_hover: bool,
     pub maximize_button_hover: bool,
     pub background: SolidColorBuffer,
@@ -29,13 +30,18 @@ pub struct HeaderBar {
 }
 
 impl HeaderBar {
+    /// whether header relative `location` is on the close button
+    pub fn is_close_button(&self, location: Point<f64, Logical>) -> bool {
+        location.x >= self.width.saturating_sub(BUTTON_WIDTH) as f64
+    }
+
     pub fn redraw(&mut self, width: u32) {
         if width == 0 {
             self.width = 0;
             return;
         }
 
-        self.background.update((width as i32, HEADER_BAR_HEIGHT), BG_COLOR);
+        self.background.update((width as i32, self.height), BG_COLOR);
 
         let mut needs_redraw_buttons = false;
         if width != self.width {
@@ -51,7 +57,7 @@ impl HeaderBar {
             && (needs_redraw_buttons || !self.close_button_hover)
         {
             self.close_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
+                (BUTTON_WIDTH as i32, self.height),
                 CLOSE_COLOR_HOVER,
             );
             self.close_button_hover = true;
@@ -65,7 +71,7 @@ impl HeaderBar {
             && (needs_redraw_buttons || self.close_button_hover)
         {
             self.close_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
+                (BUTTON_WIDTH as i32, self.height),
                 CLOSE_COLOR,
             );
             self.close_button_hover = false;
@@ -79,7 +85,7 @@ impl HeaderBar {
             && (needs_redraw_buttons || !self.maximize_button_hover)
         {
             self.maximize_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
+                (BUTTON_WIDTH as i32, self.height),
                 MAX_COLOR_HOVER,
             );
             self.maximize_button_hover = true;
@@ -93,7 +99,7 @@ impl HeaderBar {
             && (needs_redraw_buttons || self.maximize_button_hover)
         {
             self.maximize_button.update(
-                (BUTTON_WIDTH as i32, BUTTON_HEIGHT as i32),
+                (BUTTON_WIDTH as i32, self.height),
                 MAX_COLOR,
             );
             self.maximize_button_hover = false;
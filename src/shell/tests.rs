@@ -0,0 +1,169 @@
+use smithay::{
+    backend::renderer::element::Element,
+    desktop::space::SpaceElement,
+    utils::{Rectangle, Scale},
+};
+
+use crate::{backend::Dummy, tests::Fixture, trayle::render};
+
+/// two outputs side by side
+fn fixture() -> Fixture {
+    Fixture::new(&[(1920,1080),(1280,720)])
+}
+
+#[test]
+fn outputs_are_mapped_left_to_right() {
+    let fixture = fixture();
+    let space = &fixture.trayle.frontend.space;
+    let geometries = space
+        .outputs()
+        .filter_map(|output|space.output_geometry(output))
+        .collect::<Vec<_>>();
+    assert_eq!(geometries, [
+        Rectangle::new((0,0).into(), (1920,1080).into()),
+        Rectangle::new((1920,0).into(), (1280,720).into()),
+    ]);
+}
+
+#[test]
+fn windows_are_placed_on_the_output_under_the_pointer() {
+    let mut fixture = fixture();
+    let mut client = fixture.client();
+
+    // first window is centered, sized half of the output before the client picks a size
+    let first = client.window();
+    fixture.roundtrip(&mut client);
+    let first = fixture.window(&first);
+    assert_eq!(fixture.trayle.frontend.space.element_location(&first), Some((480,270).into()));
+
+    // next one is cascaded from the topmost window
+    let second = client.window();
+    fixture.roundtrip(&mut client);
+    let second = fixture.window(&second);
+    assert_eq!(fixture.trayle.frontend.space.element_location(&second), Some((512,302).into()));
+
+    // second output has no window yet
+    fixture.trayle.warp_pointer((2000.0, 100.0).into());
+    let third = client.window();
+    fixture.roundtrip(&mut client);
+    let third = fixture.window(&third);
+    assert_eq!(fixture.trayle.frontend.space.element_location(&third), Some((2240,180).into()));
+
+    assert_eq!(fixture.trayle.frontend.space.elements().collect::<Vec<_>>(), [&first, &second, &third]);
+}
+
+#[test]
+fn focus_follows_mapped_and_destroyed_windows() {
+    let mut fixture = fixture();
+    let mut client = fixture.client();
+    let first = client.window();
+    let second = client.window();
+    fixture.roundtrip(&mut client);
+    let (first_window, second_window) = (fixture.window(&first), fixture.window(&second));
+
+    // newly mapped window is focused and on top
+    assert_eq!(fixture.trayle.focused_window(), Some(second_window.clone()));
+    assert_eq!(fixture.trayle.frontend.space.elements().last(), Some(&second_window));
+
+    fixture.trayle.focus_window(first_window.id().unwrap()).unwrap();
+    assert_eq!(fixture.trayle.focused_window(), Some(first_window.clone()));
+    assert_eq!(fixture.trayle.frontend.space.elements().last(), Some(&first_window));
+
+    // focus goes to the topmost window left
+    first.toplevel.destroy();
+    first.xdg_surface.destroy();
+    fixture.roundtrip(&mut client);
+    assert_eq!(fixture.trayle.focused_window(), Some(second_window.clone()));
+    assert_eq!(fixture.trayle.frontend.space.elements().collect::<Vec<_>>(), [&second_window]);
+}
+
+#[test]
+fn switching_workspace_maps_only_its_windows() {
+    let mut fixture = fixture();
+    let mut client = fixture.client();
+    let first = client.window();
+    let second = client.window();
+    fixture.roundtrip(&mut client);
+    let (first, second) = (fixture.window(&first), fixture.window(&second));
+
+    let trayle = &mut fixture.trayle;
+    let first_location = trayle.frontend.space.element_location(&first).unwrap();
+    let second_location = trayle.frontend.space.element_location(&second).unwrap();
+
+    // focused window leaves the space, and the focus with it
+    trayle.move_to_workspace(1);
+    assert_eq!(trayle.frontend.space.elements().collect::<Vec<_>>(), [&first]);
+    assert!(!trayle.frontend.workspaces[0].contains(&second));
+    assert!(trayle.frontend.workspaces[1].contains(&second));
+    assert_eq!(trayle.focused_window(), None);
+
+    trayle.switch_workspace(1);
+    assert_eq!(trayle.frontend.active_workspace, 1);
+    assert_eq!(trayle.frontend.space.elements().collect::<Vec<_>>(), [&second]);
+    assert_eq!(trayle.frontend.space.element_location(&second), Some(second_location));
+    trayle.focus_window(second.id().unwrap()).unwrap();
+
+    // hidden windows come back at the location they left
+    trayle.switch_workspace(0);
+    assert_eq!(trayle.frontend.space.elements().collect::<Vec<_>>(), [&first]);
+    assert_eq!(trayle.frontend.space.element_location(&first), Some(first_location));
+
+    // focus returns to the last focused window of the workspace
+    trayle.switch_workspace(1);
+    assert_eq!(trayle.focused_window(), Some(second));
+}
+
+#[test]
+fn cycling_windows_keeps_its_selection_when_a_window_closes() {
+    let mut fixture = fixture();
+    let mut client = fixture.client();
+    let windows = [client.window(), client.window(), client.window()];
+    fixture.roundtrip(&mut client);
+    let [first, second, _] = windows.each_ref().map(|window|fixture.window(window));
+
+    fixture.trayle.cycle_windows(true);
+    assert_eq!(fixture.trayle.focused_window(), Some(second.clone()));
+
+    // most recently used window goes away while the modifier is held
+    windows[2].toplevel.destroy();
+    windows[2].xdg_surface.destroy();
+    fixture.roundtrip(&mut client);
+
+    fixture.trayle.cycle_windows(true);
+    assert_eq!(fixture.trayle.focused_window(), Some(first.clone()));
+
+    fixture.trayle.finish_cycle_windows();
+    assert_eq!(fixture.trayle.frontend.mru, [first, second]);
+}
+
+#[test]
+fn viewport_sizes_the_window_and_its_render_element() {
+    let mut fixture = fixture();
+    let mut client = fixture.client();
+    let toplevel = client.window();
+    fixture.roundtrip(&mut client);
+
+    // top left quarter of a 10x10 buffer, stretched to 20x20
+    let viewport = client.viewport(&toplevel.surface);
+    viewport.set_source(0.0, 0.0, 5.0, 5.0);
+    viewport.set_destination(20, 20);
+    client.attach(&toplevel.surface, (10,10));
+    fixture.roundtrip(&mut client);
+
+    // layout and grabs work on the window geometry
+    let window = fixture.window(&toplevel);
+    assert_eq!(window.geometry(), Rectangle::new((0,0).into(), (20,20).into()));
+    assert_eq!(window.bbox(), Rectangle::new((0,0).into(), (20,20).into()));
+
+    let trayle = &mut fixture.trayle;
+    let location = trayle.frontend.space.element_location(&window).unwrap();
+    let output = trayle.frontend.space.outputs().next().unwrap().clone();
+    let renderer = &mut trayle.backend.state.downcast_mut::<Dummy>().unwrap().renderer;
+    let elements = render::elements::outputs(&output, &trayle.frontend.space, None, [], renderer);
+
+    let [element] = elements.as_slice() else {
+        panic!("expected only the window element, got {}", elements.len());
+    };
+    assert_eq!(element.geometry(Scale::from(1.0)), Rectangle::new(location.to_physical(1), (20,20).into()));
+    assert_eq!(element.src(), Rectangle::new((0.0,0.0).into(), (5.0,5.0).into()));
+}
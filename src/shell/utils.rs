@@ -1,24 +1,15 @@
-use super::elements::WindowElement;
+use super::{elements::WindowElement, workspace::Workspace};
 use smithay::{
     desktop::{self, space::SpaceElement, Space},
-    utils::{Logical, Point, Rectangle, Size},
+    utils::{Logical, Point, Rectangle},
 };
 
-pub fn fixup_positions(space: &mut Space<WindowElement>, pointer_location: Point<f64, Logical>) {
-    // fixup outputs
-    let mut offset = Point::<i32, Logical>::from((0, 0));
-    for output in space.outputs().cloned().collect::<Vec<_>>().into_iter() {
-        let size = space
-            .output_geometry(&output)
-            .map(|geo| geo.size)
-            .unwrap_or_else(|| Size::from((0, 0)));
-        space.map_output(&output, offset);
-        desktop::layer_map_for_output(&output).arrange();
-        offset.x += size.w;
-    }
-
-    // fixup windows
-    let mut orphaned_windows = vec![];
+/// move windows that no longer intersect any output onto the nearest remaining output
+///
+/// windows are moved by the least distance that puts them inside the output usable area,
+/// keeping as much of their previous position as possible, saved locations of hidden
+/// workspaces are fixed up as well
+pub fn fixup_positions(space: &mut Space<WindowElement>, workspaces: &mut [Workspace]) {
     let outputs = space
         .outputs()
         .flat_map(|o| {
@@ -28,69 +19,61 @@ pub fn fixup_positions(space: &mut Space<WindowElement>, pointer_location: Point
             Some(Rectangle::new(geo.loc + zone.loc, zone.size))
         })
         .collect::<Vec<_>>();
-    for window in space.elements() {
-        let window_location = match space.element_location(window) {
-            Some(loc) => loc,
-            None => continue,
-        };
-        let geo_loc = window.bbox().loc + window_location;
 
-        if !outputs.iter().any(|o_geo|o_geo.contains(geo_loc)) {
-            orphaned_windows.push(window.clone());
-        }
+    if outputs.is_empty() {
+        return;
     }
-    for window in orphaned_windows {
-        self::place_new_window(space, pointer_location, &window, false)
-    }
-}
 
-fn place_new_window(
-    space: &mut Space<WindowElement>,
-    pointer_location: Point<f64, Logical>,
-    window: &WindowElement,
-    activate: bool,
-) {
-    let output = space
-        .output_under(pointer_location)
-        .next()
-        .or_else(|| space.outputs().next())
-        .cloned();
-    let output_geometry = output
-        .and_then(|o| {
-            let geo = space.output_geometry(&o)?;
-            let map = desktop::layer_map_for_output(&o);
-            let zone = map.non_exclusive_zone();
-            Some(Rectangle::new(geo.loc + zone.loc, zone.size))
+    let orphaned_windows = space
+        .elements()
+        .filter_map(|window| {
+            let location = space.element_location(window)?;
+            let fixed = fixup_location(&outputs, window, location)?;
+            Some((window.clone(), fixed))
         })
-        .unwrap_or_else(|| Rectangle::from_size((800, 800).into()));
+        .collect::<Vec<_>>();
 
-    // set initial toplevel bounds
-    if let Some(toplevel) = window.0.toplevel() {
-        toplevel.with_pending_state(|state|{
-            state.bounds = Some(output_geometry.size);
-        });
+    for (window, location) in orphaned_windows {
+        space.map_element(window, location, false);
     }
 
-    let max_x = output_geometry.loc.x + (((output_geometry.size.w as f32) / 3.0) * 2.0) as i32;
-    let max_y = output_geometry.loc.y + (((output_geometry.size.h as f32) / 3.0) * 2.0) as i32;
-
-    use rand::distr::{Uniform, Distribution};
-    let x_range = Uniform::new(output_geometry.loc.x, max_x);
-    let y_range = Uniform::new(output_geometry.loc.y, max_y);
+    for (window, location) in workspaces.iter_mut().flat_map(|workspace| &mut workspace.windows) {
+        if space.element_location(window).is_some() {
+            continue;
+        }
+        if let Some(fixed) = fixup_location(&outputs, window, *location) {
+            *location = fixed;
+        }
+    }
+}
 
-    let (x_range,y_range) = match (x_range,y_range) {
-            (Ok(x), Ok(y)) => (x,y),
-            _ => {
-                tracing::error!("failed to create rand Uniform, further investigation required");
-                return;
-            }
-        };
+/// new location of `window` at `location` if it does not intersect any of `outputs`
+fn fixup_location(
+    outputs: &[Rectangle<i32, Logical>],
+    window: &WindowElement,
+    location: Point<i32, Logical>,
+) -> Option<Point<i32, Logical>> {
+    let mut geo = window.geometry();
+    geo.loc += location;
 
-    let mut rng = rand::rng();
-    let x = x_range.sample(&mut rng);
-    let y = y_range.sample(&mut rng);
+    if outputs.iter().any(|o_geo| o_geo.overlaps(geo)) {
+        return None;
+    }
 
-    space.map_element(window.clone(), (x,y), activate);
+    outputs
+        .iter()
+        .map(|o_geo| clamp_into(*o_geo, geo))
+        .min_by_key(|fixed| {
+            let (dx, dy) = ((fixed.x - geo.loc.x) as i64, (fixed.y - geo.loc.y) as i64);
+            dx * dx + dy * dy
+        })
+        // location is of the element, not of its geometry
+        .map(|fixed| fixed - window.geometry().loc)
 }
 
-
+/// closest location of `geo` inside `area`, windows larger than `area` are aligned to its origin
+fn clamp_into(area: Rectangle<i32, Logical>, geo: Rectangle<i32, Logical>) -> Point<i32, Logical> {
+    let max_x = (area.loc.x + area.size.w - geo.size.w).max(area.loc.x);
+    let max_y = (area.loc.y + area.size.h - geo.size.h).max(area.loc.y);
+    (geo.loc.x.clamp(area.loc.x, max_x), geo.loc.y.clamp(area.loc.y, max_y)).into()
+}
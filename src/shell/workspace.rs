@@ -0,0 +1,58 @@
+//! virtual workspaces
+//!
+//! only windows of the active workspace are mapped in the [`Space`]
+use smithay::{
+    desktop::Space,
+    utils::{IsAlive, Logical, Point},
+};
+
+use super::elements::WindowElement;
+
+/// number of workspaces
+pub const WORKSPACE_COUNT: usize = 9;
+
+/// a set of windows shown together
+#[derive(Default)]
+pub struct Workspace {
+    /// windows and its location, location is saved when the workspace is hidden
+    pub windows: Vec<(WindowElement, Point<i32, Logical>)>,
+    /// window that takes keyboard focus when the workspace is shown
+    pub last_focused: Option<WindowElement>,
+}
+
+impl Workspace {
+    pub fn contains(&self, window: &WindowElement) -> bool {
+        self.windows.iter().any(|(w,_)|w == window)
+    }
+
+    pub fn add(&mut self, window: WindowElement, location: Point<i32, Logical>) {
+        self.windows.push((window, location));
+    }
+
+    /// remove window, returning its saved location
+    pub fn remove(&mut self, window: &WindowElement) -> Option<Point<i32, Logical>> {
+        let index = self.windows.iter().position(|(w,_)|w == window)?;
+        if self.last_focused.as_ref() == Some(window) {
+            self.last_focused = None;
+        }
+        Some(self.windows.remove(index).1)
+    }
+
+    /// unmap windows from `space`, saving its location
+    pub fn hide(&mut self, space: &mut Space<WindowElement>) {
+        for (window, location) in &mut self.windows {
+            if let Some(current) = space.element_location(window) {
+                *location = current;
+            }
+            space.unmap_elem(window);
+        }
+    }
+
+    /// map windows back to `space` at its saved location
+    pub fn show(&mut self, space: &mut Space<WindowElement>) {
+        self.windows.retain(|(window,_)|window.alive());
+        for (window, location) in &self.windows {
+            space.map_element(window.clone(), *location, false);
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! in-process client against [`Trayle`] on the dummy backend
+//!
+//! compositor and client share one thread, [`Fixture::roundtrip`] alternates between them
+//! until the compositor answered every request sent so far
+use smithay::reexports::{
+    calloop::EventLoop,
+    rustix::fs::{memfd_create, MemfdFlags},
+    wayland_server::{Display, Resource},
+};
+use std::{
+    fs::File,
+    io::Write,
+    os::{fd::AsFd, unix::net::{UnixListener, UnixStream}},
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering}, Arc},
+    time::Duration,
+};
+use wayland_client::{
+    delegate_noop,
+    protocol::{wl_buffer, wl_callback, wl_compositor, wl_registry, wl_shm, wl_shm_pool, wl_surface},
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
+};
+use wayland_protocols::{
+    wp::{
+        security_context::v1::client::{wp_security_context_manager_v1, wp_security_context_v1},
+        viewporter::client::{wp_viewport, wp_viewporter},
+    },
+    xdg::shell::client::{xdg_surface, xdg_toplevel, xdg_wm_base},
+};
+
+use crate::{shell::elements::WindowElement, trayle::ClientState, Trayle};
+
+/// [`Trayle`] on the dummy backend, dispatched by hand
+pub struct Fixture {
+    pub event_loop: EventLoop<'static, Trayle>,
+    pub display: Display<Trayle>,
+    pub trayle: Trayle,
+}
+
+impl Fixture {
+    /// one fake output for each of `sizes`, mapped left to right
+    pub fn new(sizes: &[(i32,i32)]) -> Fixture {
+        let event_loop = EventLoop::try_new().unwrap();
+        let display = Display::new().unwrap();
+        let trayle = Trayle::dummy(&event_loop, &display.handle(), sizes).unwrap();
+        Fixture { event_loop, display, trayle }
+    }
+
+    /// connect a client with the globals it needs bound
+    pub fn client(&mut self) -> TestClient {
+        let (stream, server) = UnixStream::pair().unwrap();
+        self.display.handle().insert_client(server, Arc::new(ClientState::default())).unwrap();
+        self.connect(stream, None)
+    }
+
+    /// connect a client through a security context created by `parent`, as flatpak does
+    /// for the apps it runs
+    pub fn sandboxed_client(&mut self, parent: &mut TestClient) -> TestClient {
+        static SOCKETS: AtomicUsize = AtomicUsize::new(0);
+        let index = SOCKETS.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("trayle-test-{}-{index}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        // the context is closed once the sandbox hangs up its end
+        let (close, sandbox) = UnixStream::pair().unwrap();
+
+        let manager = parent.globals.security_context.as_ref().expect("security context is hidden");
+        let context = manager.create_listener(listener.as_fd(), close.as_fd(), &parent.qh, ());
+        context.set_sandbox_engine("flatpak".into());
+        context.set_app_id("org.example.App".into());
+        context.commit();
+        context.destroy();
+        self.roundtrip(parent);
+
+        let stream = UnixStream::connect(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        self.connect(stream, Some(sandbox))
+    }
+
+    /// client side of a connection the compositor accepts, with its globals bound
+    fn connect(&mut self, stream: UnixStream, sandbox: Option<UnixStream>) -> TestClient {
+        let conn = Connection::from_socket(stream).unwrap();
+        let queue = conn.new_event_queue();
+        let qh = queue.handle();
+        conn.display().get_registry(&qh, ());
+
+        let mut client = TestClient { conn, queue, qh, globals: Globals::default(), _sandbox: sandbox };
+        self.roundtrip(&mut client);
+        client
+    }
+
+    /// dispatch both sides until `client` received the replies of every request sent so far
+    pub fn roundtrip(&mut self, client: &mut TestClient) {
+        let done = Arc::new(AtomicBool::new(false));
+        client.conn.display().sync(&client.qh, done.clone());
+
+        for _ in 0..16 {
+            client.conn.flush().unwrap();
+            self.dispatch();
+            if let Some(guard) = client.conn.prepare_read() {
+                // compositor may have nothing to send yet
+                let _ = guard.read();
+            }
+            client.queue.dispatch_pending(&mut client.globals).unwrap();
+            if done.load(Ordering::Relaxed) {
+                return;
+            }
+        }
+        panic!("compositor did not answer the client");
+    }
+
+    /// one iteration of the compositor, as the event loop runs it
+    fn dispatch(&mut self) {
+        self.display.dispatch_clients(&mut self.trayle).unwrap();
+        self.event_loop.dispatch(Some(Duration::ZERO), &mut self.trayle).unwrap();
+        self.trayle.refresh();
+    }
+
+    /// compositor side of `window`, on any workspace
+    ///
+    /// surface is matched by its protocol id, so only one client is expected
+    pub fn window(&self, window: &TestWindow) -> WindowElement {
+        let id = window.surface.id().protocol_id();
+        self.trayle.frontend.workspaces
+            .iter()
+            .flat_map(|workspace|&workspace.windows)
+            .map(|(window,_)|window)
+            .find(|window|window.toplevel().is_some_and(|t|t.wl_surface().id().protocol_id() == id))
+            .cloned()
+            .expect("window is not mapped")
+    }
+}
+
+/// client driven by [`Fixture::roundtrip`]
+pub struct TestClient {
+    conn: Connection,
+    queue: EventQueue<Globals>,
+    qh: QueueHandle<Globals>,
+    globals: Globals,
+    /// keeps the security context of a sandboxed client open
+    _sandbox: Option<UnixStream>,
+}
+
+impl TestClient {
+    /// global of `interface` is advertised to the client
+    pub fn advertises(&self, interface: &str) -> bool {
+        self.globals.interfaces.iter().any(|i|i == interface)
+    }
+
+    /// create a toplevel and do the initial commit, the compositor maps it right away
+    pub fn window(&mut self) -> TestWindow {
+        let globals = &self.globals;
+        let surface = globals.compositor.as_ref().unwrap().create_surface(&self.qh, ());
+        let xdg_surface = globals.wm_base.as_ref().unwrap().get_xdg_surface(&surface, &self.qh, ());
+        let toplevel = xdg_surface.get_toplevel(&self.qh, ());
+        surface.commit();
+        TestWindow { surface, xdg_surface, toplevel }
+    }
+
+    /// attach an opaque shm buffer of `width`x`height` and commit
+    pub fn attach(&mut self, surface: &wl_surface::WlSurface, (width,height): (i32,i32)) {
+        let stride = width * 4;
+        let len = stride * height;
+        let fd = memfd_create("trayle-test", MemfdFlags::CLOEXEC).unwrap();
+        let mut file = File::from(fd);
+        file.write_all(&vec![0xff; len as usize]).unwrap();
+
+        let pool = self.globals.shm.as_ref().unwrap().create_pool(file.as_fd(), len, &self.qh, ());
+        let buffer = pool.create_buffer(0, width, height, stride, wl_shm::Format::Argb8888, &self.qh, ());
+        pool.destroy();
+
+        surface.attach(Some(&buffer), 0, 0);
+        surface.damage_buffer(0, 0, width, height);
+        surface.commit();
+    }
+
+    /// viewport of `surface`, its state is applied on the next commit
+    pub fn viewport(&mut self, surface: &wl_surface::WlSurface) -> wp_viewport::WpViewport {
+        self.globals.viewporter.as_ref().unwrap().get_viewport(surface, &self.qh, ())
+    }
+}
+
+/// client side of a toplevel
+pub struct TestWindow {
+    pub surface: wl_surface::WlSurface,
+    pub xdg_surface: xdg_surface::XdgSurface,
+    pub toplevel: xdg_toplevel::XdgToplevel,
+}
+
+/// globals bound by [`TestClient`]
+#[derive(Default)]
+pub struct Globals {
+    compositor: Option<wl_compositor::WlCompositor>,
+    shm: Option<wl_shm::WlShm>,
+    wm_base: Option<xdg_wm_base::XdgWmBase>,
+    viewporter: Option<wp_viewporter::WpViewporter>,
+    security_context: Option<wp_security_context_manager_v1::WpSecurityContextManagerV1>,
+    /// every advertised global
+    interfaces: Vec<String>,
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for Globals {
+    fn event(
+        globals: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        let wl_registry::Event::Global { name, interface, .. } = event else {
+            return;
+        };
+        match interface.as_str() {
+            // damage_buffer needs version 4
+            "wl_compositor" => globals.compositor = Some(registry.bind(name, 4, qh, ())),
+            "wl_shm" => globals.shm = Some(registry.bind(name, 1, qh, ())),
+            "xdg_wm_base" => globals.wm_base = Some(registry.bind(name, 1, qh, ())),
+            "wp_viewporter" => globals.viewporter = Some(registry.bind(name, 1, qh, ())),
+            "wp_security_context_manager_v1" => globals.security_context = Some(registry.bind(name, 1, qh, ())),
+            _ => {}
+        }
+        globals.interfaces.push(interface);
+    }
+}
+
+impl Dispatch<wl_callback::WlCallback, Arc<AtomicBool>> for Globals {
+    fn event(
+        _: &mut Self,
+        _: &wl_callback::WlCallback,
+        event: wl_callback::Event,
+        done: &Arc<AtomicBool>,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_callback::Event::Done { .. } = event {
+            done.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Dispatch<xdg_wm_base::XdgWmBase, ()> for Globals {
+    fn event(
+        _: &mut Self,
+        wm_base: &xdg_wm_base::XdgWmBase,
+        event: xdg_wm_base::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_wm_base::Event::Ping { serial } = event {
+            wm_base.pong(serial);
+        }
+    }
+}
+
+impl Dispatch<xdg_surface::XdgSurface, ()> for Globals {
+    fn event(
+        _: &mut Self,
+        xdg_surface: &xdg_surface::XdgSurface,
+        event: xdg_surface::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let xdg_surface::Event::Configure { serial } = event {
+            xdg_surface.ack_configure(serial);
+        }
+    }
+}
+
+delegate_noop!(Globals: ignore wl_compositor::WlCompositor);
+delegate_noop!(Globals: ignore wl_surface::WlSurface);
+delegate_noop!(Globals: ignore wl_shm::WlShm);
+delegate_noop!(Globals: ignore wl_shm_pool::WlShmPool);
+delegate_noop!(Globals: ignore wl_buffer::WlBuffer);
+delegate_noop!(Globals: ignore xdg_toplevel::XdgToplevel);
+delegate_noop!(Globals: ignore wp_viewporter::WpViewporter);
+delegate_noop!(Globals: ignore wp_viewport::WpViewport);
+delegate_noop!(Globals: ignore wp_security_context_manager_v1::WpSecurityContextManagerV1);
+delegate_noop!(Globals: ignore wp_security_context_v1::WpSecurityContextV1);
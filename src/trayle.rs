@@ -10,13 +10,20 @@
 //! - [`utils`], utilities for combining multiple domain logic
 //!
 use crate::{
-    backend::{Backend, BackendSources},
-    config::{Config, SUPPORTED_FORMATS, SUPPORTED_FORMATS_8BIT_ONLY},
-    frontend::{Frontend, FrontendSources, SurfaceDmabufFeedback},
+    backend::{Backend, BackendSources, Tty, UdevRenderer, Winit},
+    config::{Config, ModeConfig, SUPPORTED_FORMATS, SUPPORTED_FORMATS_8BIT_ONLY},
+    frontend::{DndIcon, Frontend, FrontendSources, SurfaceDmabufFeedback},
+    ipc::{self, IpcListener, IpcSource, IpcStream},
+    protocols::{
+        output_management::{HeadConfiguration, OutputConfiguration, OutputHead},
+        screencopy::Screencopy,
+    },
+    shell::{elements::WindowElement, grabs::ResizeState, FullscreenSurface},
     utils::{
-        display_info,
-        drm_scanner::{DrmScanEvent, DrmScanner},
+        display_info::{EdidName, MonitorInfo},
+        drm_scanner::{DrmScanEvent, DrmScanResult, DrmScanner},
     },
+    wallpaper::Wallpaper,
 };
 use anyhow::{Context, Result};
 use smithay::{
@@ -24,43 +31,53 @@ use smithay::{
         allocator::{
             format::FormatSet,
             gbm::{GbmAllocator, GbmBufferFlags, GbmDevice},
+            Fourcc,
         },
         drm::{
             compositor::FrameFlags,
             output::{DrmOutput, DrmOutputManager, DrmOutputRenderElements},
             CreateDrmNodeError, DrmAccessError, DrmDevice, DrmDeviceFd,
-            DrmError, DrmEvent, DrmEventMetadata, DrmNode, NodeType,
+            DrmError, DrmEvent, DrmEventMetadata, DrmNode, NodeType, VrrSupport,
         },
         egl::{self, EGLDevice, EGLDisplay},
         libinput::LibinputInputBackend,
         renderer::{
             element::{
-                memory::MemoryRenderBufferRenderElement,
-                solid::SolidColorRenderElement,
-                surface::WaylandSurfaceRenderElement,
-                utils::{CropRenderElement, RelocateRenderElement, RescaleRenderElement},
-                RenderElementStates, Wrap,
+                memory::MemoryRenderBuffer,
+                surface::{render_elements_from_surface_tree, WaylandSurfaceRenderElement},
+                utils::{select_dmabuf_feedback, CropRenderElement, RelocateRenderElement, RescaleRenderElement},
+                AsRenderElements, Kind, RenderElementStates, Wrap,
             },
             gles::GlesRenderer,
             multigpu::{gbm::GbmGlesBackend, GpuManager},
-            utils as renderer_utils, ImportAll, ImportDma, ImportEgl, ImportMem, ImportMemWl,
+            utils as renderer_utils, DebugFlags, ImportAll, ImportDma, ImportMem,
         },
         session::{
             libseat,
             Event as SessionEvent, Session,
         },
         udev::{UdevBackend, UdevEvent},
+        winit::WinitEvent,
         SwapBuffersError,
     },
-    desktop::{space::SpaceRenderElements, utils::OutputPresentationFeedback, Space, Window},
-    input::Seat,
-    output::{Mode as WlMode, Output, PhysicalProperties},
+    desktop::{
+        self,
+        space::{SpaceElement, SpaceRenderElements},
+        utils::OutputPresentationFeedback,
+        PopupKind, Space, WindowSurfaceType,
+    },
+    input::{
+        keyboard::XkbConfig,
+        pointer::{CursorIcon, CursorImageStatus, CursorImageSurfaceData, MotionEvent, PointerHandle},
+        Seat,
+    },
+    output::{Mode as WlMode, Output, PhysicalProperties, Scale as OutputScale},
     reexports::{
         calloop::{
-            generic::{Generic, NoIoDrop}, timer::{TimeoutAction, Timer}, EventLoop, Interest, LoopHandle, LoopSignal, Mode as FdMode, PostAction, RegistrationToken
+            generic::{Generic, NoIoDrop}, Readiness, signals::{self, Signal, Signals}, timer::{TimeoutAction, Timer}, Dispatcher, EventLoop, Interest, LoopHandle, LoopSignal, Mode as FdMode, PostAction, RegistrationToken
         },
         drm::{
-            control::{connector, crtc, Device as _, ModeTypeFlags},
+            control::{connector, crtc, Device as _, Mode as DrmMode, ModeTypeFlags},
             Device as _,
         },
         rustix::fs::OFlags,
@@ -71,21 +88,35 @@ use smithay::{
         wayland_server::{
             backend::{ClientData, ClientId, DisconnectReason, GlobalId},
             protocol::wl_surface::WlSurface,
-            Display, DisplayHandle,
+            Client, Display, DisplayHandle, Resource,
         },
     },
-    utils::{Clock, Monotonic, Time},
+    utils::{Clock, IsAlive, Logical, Monotonic, Point, Scale, Time, Transform, SERIAL_COUNTER},
     wayland::{
-        compositor::{self, CompositorClientState},
+        commit_timing::CommitTimerBarrierStateUserData,
+        compositor::{self, CompositorClientState, CompositorHandler, SurfaceAttributes},
         dmabuf::{DmabufFeedbackBuilder, DmabufState},
         drm_lease::{DrmLease, DrmLeaseState},
-        drm_syncobj::DrmSyncobjState,
+        drm_syncobj::{DrmSyncobjCachedState, DrmSyncobjState},
+        fifo::FifoBarrierCachedState,
+        fractional_scale,
         presentation::Refresh,
-        shell::xdg::XdgToplevelSurfaceData,
+        security_context::SecurityContext,
+        shell::{
+            wlr_layer::{KeyboardInteractivity, Layer, LayerSurfaceData},
+            xdg::XdgToplevelSurfaceData,
+        },
     },
 };
 
-use std::{collections::HashMap, os::unix::net::UnixStream, path::Path, sync::Arc, time::{Duration, Instant}};
+use std::{
+    collections::{HashMap, VecDeque},
+    os::unix::{net::UnixStream, process::CommandExt},
+    path::Path,
+    process::{Command, Stdio},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 type InputEvent = smithay::backend::input::InputEvent<LibinputInputBackend>;
 
@@ -97,10 +128,21 @@ pub struct Trayle {
     pub frontend: Frontend,
     pub backend: Backend,
     pub seat: Seat<Trayle>,
+    pub pointer: PointerHandle<Trayle>,
     pub handle: LoopHandle<'static, Trayle>,
     pub signal: LoopSignal,
     pub dh: DisplayHandle,
     pub clock: Clock<Monotonic>,
+    /// `None` when clients are dispatched by the caller, eg: in tests
+    pub display_source: Option<DisplaySource>,
+}
+
+/// event source that dispatches clients, see [`callbacks::display`]
+pub struct DisplaySource {
+    pub dispatcher: Dispatcher<'static, Generic<Display<Trayle>>, Trayle>,
+    pub token: RegistrationToken,
+    /// also woken up by a writable client socket, while one of them is full
+    pub writable: bool,
 }
 
 impl Trayle {
@@ -112,10 +154,12 @@ impl Trayle {
     /// - setup [`Config`], it holds user configurations, see [`Config::setup`] for more detail
     /// - setup [`Frontend`], it holds wayland protocol states, see [`Frontend::setup`] for more detail
     /// - setup [`Backend`], it holds os interaction states, see [`Backend::setup`] for more detail
-    /// - setup [`Seat`], it require [`Frontend::seat_state`] and [`Backend::seat`] to setup
+    /// - setup [`Seat`] in [`Trayle::new`], it require [`Frontend::seat_state`] and [`Backend::seat`]
+    ///   to setup, along with its keyboard and pointer
     ///
     /// now that `Trayle` is fully constructed, further setup is called in
-    /// [`Trayle::setup_bindings`], see its documentation for more detail
+    /// [`Trayle::setup_bindings`], or [`Trayle::setup_nested_bindings`] when running nested,
+    /// see its documentation for more detail
     ///
     /// [`Frontend`] and [`Backend`] setup also return event sources as [`FrontendSources`] and
     /// [`BackendSources`] respectively, registered to event loop with its corresponding
@@ -125,58 +169,150 @@ impl Trayle {
     /// [`SeatState::new_wl_seat`]: smithay::input::SeatState::new_wl_seat
     pub fn setup(event_loop: &mut EventLoop<'static, Trayle>) -> Result<Trayle> {
         let display = Display::<Trayle>::new().context("failed to setup display")?;
-        let handle = event_loop.handle();
-        let signal = event_loop.get_signal();
         let dh = display.handle();
-        let clock = Clock::new();
 
         // states
         let config = Config::setup()?;
-        let (mut frontend, frontend_sources) = Frontend::setup(&dh)?;
-        let (backend, backend_sources) = Backend::setup(&dh)?;
+        let (frontend, frontend_sources) = Frontend::setup(&dh, &event_loop.handle())?;
+        let (backend, backend_sources) = Backend::setup(&dh, &config)?;
 
-        let seat = frontend.seat_state.new_wl_seat(&dh, &backend.seat);
+        let mut trayle = Trayle::new(config, frontend, backend, dh, event_loop)?;
 
-        let mut trayle = Trayle {
-            config,
-            frontend,
-            backend,
-            seat,
-            handle,
-            signal,
-            dh,
-            clock,
-        };
+        match &backend_sources {
+            BackendSources::Udev { udev, .. } => trayle.setup_bindings(udev)?,
+            BackendSources::Winit(_) => trayle.setup_nested_bindings(),
+        }
 
-        trayle.setup_bindings(&backend_sources.udev)?;
+        if let Some(timeout) = trayle.config.dpms_timeout {
+            let timer = Timer::from_duration(timeout);
+            trayle.handle.insert_source(timer, move|_,_,trayle|{
+                let idle = trayle.backend.last_input.elapsed();
+                if idle < timeout {
+                    return TimeoutAction::ToDuration(timeout - idle);
+                }
+                if !trayle.is_idle_inhibited() {
+                    trayle.set_all_outputs_dpms(false);
+                }
+                TimeoutAction::ToDuration(timeout)
+            })
+            .map_err(|err|anyhow::anyhow!("failed to insert dpms timer: {err}"))?;
+        }
 
         let handle = event_loop.handle();
-        let display = Generic::new(display, Interest::READ, FdMode::Edge);
+        // level triggered, so a dispatch that leaves requests unread is woken up again
+        // instead of stalling until the client writes more
+        let display = Dispatcher::new(Generic::new(display, Interest::READ, FdMode::Level), callbacks::display);
 
         let FrontendSources { socket } = frontend_sources;
-        let BackendSources { session, input, udev } = backend_sources;
 
         handle.insert_source(socket, callbacks::socket).unwrap();
-        handle.insert_source(session, callbacks::session).unwrap();
-        handle.insert_source(input, callbacks::input).unwrap();
-        handle.insert_source(udev, device::handle_udev).unwrap();
-        handle.insert_source(display, callbacks::display).unwrap();
+        match backend_sources {
+            BackendSources::Udev { session, input, udev } => {
+                handle.insert_source(session, callbacks::session).unwrap();
+                handle.insert_source(input, callbacks::input).unwrap();
+                handle.insert_source(udev, device::handle_udev).unwrap();
+            },
+            BackendSources::Winit(winit) => {
+                handle.insert_source(winit, callbacks::winit).unwrap();
+            },
+        }
+        let token = handle.register_dispatcher(display.clone()).unwrap();
+        trayle.display_source = Some(DisplaySource { dispatcher: display, token, writable: false });
+
+        let sigchld = Signals::new(&[Signal::SIGCHLD]).context("failed to setup SIGCHLD handler")?;
+        handle.insert_source(sigchld, callbacks::sigchld).unwrap();
+        let terminate = Signals::new(&[Signal::SIGTERM, Signal::SIGINT]).context("failed to setup SIGTERM handler")?;
+        handle.insert_source(terminate, callbacks::terminate).unwrap();
+
+        match IpcListener::bind(&trayle.frontend.wlsocket) {
+            Ok(ipc) => {
+                tracing::info!("listening on ipc socket {:?}", ipc.path());
+                std::env::set_var(ipc::SOCKET_ENV, ipc.path());
+                let ipc = Generic::new(ipc, Interest::READ, FdMode::Level);
+                handle.insert_source(ipc, callbacks::ipc).unwrap();
+            },
+            Err(err) => tracing::warn!("failed to setup ipc socket: {err:#}"),
+        }
 
         tracing::info!("setup complete");
 
+        trayle.spawn_autostart(true);
+
+        Ok(trayle)
+    }
+
+    /// construct `Trayle` from its states, adding the seat with a pointer and a keyboard
+    fn new(
+        config: Config,
+        mut frontend: Frontend,
+        backend: Backend,
+        dh: DisplayHandle,
+        event_loop: &EventLoop<'static, Trayle>,
+    ) -> Result<Trayle> {
+        let mut seat = frontend.seat_state.new_wl_seat(&dh, &backend.seat);
+        let pointer = seat.add_pointer();
+        let keyboard = seat.add_keyboard(
+            config.xkb.xkb_config(),
+            config.kb_repeat_delay,
+            config.kb_repeat_rate,
+        );
+        if let Err(err) = keyboard {
+            tracing::error!("invalid xkb config, fallback to default layout: {err}");
+            seat.add_keyboard(XkbConfig::default(), config.kb_repeat_delay, config.kb_repeat_rate)
+                .context("failed to setup keyboard")?;
+        }
+
+        Ok(Trayle {
+            config,
+            frontend,
+            backend,
+            seat,
+            pointer,
+            handle: event_loop.handle(),
+            signal: event_loop.get_signal(),
+            dh,
+            clock: Clock::new(),
+            display_source: None,
+        })
+    }
+
+    /// `Trayle` on the [`Dummy`] backend with one fake output for each of `sizes`, with the
+    /// default config, mapped left to right
+    ///
+    /// there is no socket, event source or autostart, clients are inserted to the display
+    /// of `dh` and dispatched by the caller
+    ///
+    /// [`Dummy`]: crate::backend::Dummy
+    #[cfg(test)]
+    pub fn dummy(event_loop: &EventLoop<'static, Trayle>, dh: &DisplayHandle, sizes: &[(i32,i32)]) -> Result<Trayle> {
+        let config = Config::default();
+        let frontend = Frontend::new(dh, &event_loop.handle(), "wayland-dummy".into());
+        let backend = Backend::dummy(dh, &config, sizes);
+
+        let mut trayle = Trayle::new(config, frontend, backend, dh.clone(), event_loop)?;
+        trayle.setup_nested_bindings();
         Ok(trayle)
     }
 
+    /// release drm devices before the session, called after the event loop is dropped
+    ///
+    /// outputs are restored to their previous state and the vt is handed back usable
+    pub fn shutdown(mut self) {
+        if let Some(tty) = self.backend.state.tty_mut() {
+            tty.devices.clear();
+        }
+        // session goes with the backend
+        drop(self);
+        tracing::info!("exiting");
+    }
+
     /// setups inside this function is required trayle to be fully constructed
     ///
     /// - setup [`UdevBackend`], and retrieve available drm devices, see [`UdevBackend::device_list`]
     /// - setup [`SurfaceDmabufFeedback`] for every drm devices via [`utils::get_surface_dmabuf_feedback`]
-    /// - update [`ShmState`] formats via renderer through [`ImportMemWl::shm_formats`]
-    /// - try enabling EGL hardware-acceleration for renderer through [`ImportEgl::bind_wl_display`]
+    /// - setup dmabuf, shm and EGL bindings, see [`Trayle::setup_renderer_bindings`]
     /// - expose syncobj protocol if supported by primary gpu by setting up [`DrmSyncobjState`],
     ///   see [`smithay::wayland::drm_syncobj`]
-    ///
-    /// [`ShmState`]: smithay::wayland::shm::ShmState
     fn setup_bindings(&mut self, udev: &UdevBackend) -> Result<()> {
         // udev
         for (device_id, path) in udev.device_list() {
@@ -186,60 +322,42 @@ impl Trayle {
 
 
         // update each drm surface dmabuf feedback
-        for device_data in self.backend.devices.values_mut() {
-            for surface_data in device_data.surfaces.values_mut() {
-                if surface_data.dmabuf_feedback.is_some() {
-                    continue;
+        if let Some(tty) = self.backend.state.tty_mut() {
+            for device_data in tty.devices.values_mut() {
+                for surface_data in device_data.surfaces.values_mut() {
+                    if surface_data.dmabuf_feedback.is_some() {
+                        continue;
+                    }
+                    surface_data.dmabuf_feedback = surface_data.drm_output.with_compositor(|compositor|{
+                        utils::get_surface_dmabuf_feedback(
+                            tty.primary_gpu,
+                            surface_data.render_node,
+                            &mut tty.gpus,
+                            compositor.surface(),
+                        )
+                    });
                 }
-                surface_data.dmabuf_feedback = surface_data.drm_output.with_compositor(|compositor|{
-                    utils::get_surface_dmabuf_feedback(
-                        self.backend.primary_gpu,
-                        surface_data.render_node,
-                        &mut self.backend.gpus,
-                        compositor.surface(),
-                    )
-                });
             }
         }
 
 
-        let mut renderer = self
-            .backend
-            .gpus
-            .single_renderer(&self.backend.primary_gpu)
-            .expect("failed to get primary renderer");
-
-        // setup dmabuf support with format list from primary gpu
-        let dmabuf_formats = ImportDma::dmabuf_formats(&renderer);
-        let feedback = DmabufFeedbackBuilder::new(self.backend.primary_gpu.dev_id(), dmabuf_formats)
-            .build()
-            .unwrap();
-        let mut dmabuf_state = DmabufState::new();
-        let global = dmabuf_state.create_global_with_default_feedback::<Trayle>(&self.dh, &feedback);
-        self.backend.dmabuf_state.write((dmabuf_state, global));
-
-
-        // setup shared memory formats
-        self.frontend.shm_state.update_formats(ImportMemWl::shm_formats(&renderer));
-
-
-        // try to enable EGL hardware-acceleration
-        match ImportEgl::bind_wl_display(&mut renderer, &self.dh) {
-            Ok(_) => tracing::info!("EGL hardware-acceleration enabled"),
-            Err(err) => tracing::info!("EGL hardware-acceleration disabled, {err}"),
-        };
+        self.setup_renderer_bindings();
 
 
         // expose syncobj protocol if supported by primary gpu
         'syncobj: {
-            let Some(primary_node) = self.backend.primary_gpu
+            let Some(tty) = self.backend.state.tty() else {
+                break 'syncobj;
+            };
+
+            let Some(primary_node) = tty.primary_gpu
                 .node_with_type(NodeType::Primary)
                 .and_then(Result::ok)
             else {
                 break 'syncobj;
             };
 
-            let Some(device) = self.backend.devices.get(&primary_node) else {
+            let Some(device) = tty.devices.get(&primary_node) else {
                 break 'syncobj;
             };
 
@@ -258,6 +376,67 @@ impl Trayle {
         Ok(())
     }
 
+    /// [`Trayle::setup_bindings`] counterpart of backends without drm devices, maps their
+    /// outputs left to right
+    ///
+    /// see [`BackendState::outputs`]
+    ///
+    /// [`BackendState::outputs`]: crate::backend::BackendState::outputs
+    fn setup_nested_bindings(&mut self) {
+        self.setup_renderer_bindings();
+
+        let mut x = 0;
+        for output in self.backend.state.outputs() {
+            utils::map_output(&mut self.frontend.space, &output, (x,0).into());
+            x += self.frontend.space.output_geometry(&output).map_or(0, |geo|geo.size.w);
+            self.arrange(&output);
+        }
+        self.update_output_management();
+    }
+
+    /// client buffer bindings from the renderer of the backend in use
+    ///
+    /// - setup dmabuf support with format list from primary gpu, unless the backend has no gpu,
+    ///   see [`BackendState::primary_gpu`]
+    /// - update [`ShmState`] formats, see [`BackendState::shm_formats`]
+    /// - try enabling EGL hardware-acceleration and legacy `wl_drm`, unless
+    ///   [`Config::disable_wl_drm`], see [`BackendState::bind_wl_display`]
+    ///
+    /// [`ShmState`]: smithay::wayland::shm::ShmState
+    /// [`BackendState::primary_gpu`]: crate::backend::BackendState::primary_gpu
+    /// [`BackendState::shm_formats`]: crate::backend::BackendState::shm_formats
+    /// [`BackendState::bind_wl_display`]: crate::backend::BackendState::bind_wl_display
+    fn setup_renderer_bindings(&mut self) {
+        match self.backend.state.primary_gpu() {
+            Some(primary_gpu) => {
+                let dmabuf_formats = self.backend.state.dmabuf_formats();
+                let feedback = DmabufFeedbackBuilder::new(primary_gpu.dev_id(), dmabuf_formats)
+                    .build()
+                    .unwrap();
+                let mut dmabuf_state = DmabufState::new();
+                let global = dmabuf_state.create_global_with_default_feedback::<Trayle>(&self.dh, &feedback);
+                self.backend.dmabuf_state.write((dmabuf_state, global));
+            },
+            None => tracing::info!("backend has no gpu, linux-dmabuf unavailable"),
+        }
+
+        let shm_formats = self.backend.state.shm_formats();
+        self.frontend.shm_state.update_formats(shm_formats);
+
+        if self.config.disable_wl_drm {
+            tracing::info!("legacy wl_drm disabled by TRAYLE_DISABLE_WL_DRM, clients have to use linux-dmabuf");
+            return;
+        }
+
+        match self.backend.state.bind_wl_display(&self.dh) {
+            Ok(()) => tracing::info!("EGL hardware-acceleration enabled, legacy wl_drm available"),
+            Err(err) => tracing::warn!(
+                "legacy wl_drm unavailable, {err}, clients relying on it, eg: older electron, \
+                lose hardware-acceleration unless forced to use linux-dmabuf"
+            ),
+        };
+    }
+
     /// refresh internal state
     ///
     /// need to be called periodically
@@ -265,10 +444,36 @@ impl Trayle {
     /// this can be a callback for an event loop run
     pub fn refresh(&mut self) {
         self.frontend.space.refresh();
-        // state.popups.refresh();
+        self.frontend.popups.cleanup();
+        self.update_idle_inhibit();
+        self.update_fractional_scale();
+        self.update_pointer_focus();
+
+        self.flush_clients();
+    }
 
-        if let Err(err) = self.dh.flush_clients() {
-            tracing::error!("failed to flush clients in display handle: {err}");
+    /// flush pending events to every client
+    ///
+    /// called once per event loop iteration by [`Trayle::refresh`], right after client requests
+    /// and every other source are dispatched, so replies are not flushed twice
+    ///
+    /// while a client socket is full, the display source also waits for it to be writable,
+    /// so this must not be called from within [`callbacks::display`]
+    pub fn flush_clients(&mut self) {
+        let writable = !utils::flush_clients(&mut self.dh);
+        let Some(source) = self.display_source.as_mut() else {
+            return;
+        };
+        if source.writable == writable {
+            return;
+        }
+        source.writable = writable;
+        source.dispatcher.as_source_mut().interest = match writable {
+            true => Interest::BOTH,
+            false => Interest::READ,
+        };
+        if let Err(err) = self.handle.update(&source.token) {
+            tracing::error!("failed to update display source interest: {err}");
         }
     }
 }
@@ -276,12 +481,161 @@ impl Trayle {
 
 /// contain functions that called on smithay's handler traits
 impl Trayle {
+    /// send pointer enter and leave when surfaces move, map or unmap under a still pointer
+    pub fn update_pointer_focus(&mut self) {
+        // grab decides the focus by itself
+        if self.pointer.is_grabbed() {
+            return;
+        }
+
+        let location = self.pointer.current_location();
+        let under = utils::surface_under(&self.frontend.space, location);
+        if under.as_ref().map(|(surface,_)|surface) == self.pointer.current_focus().as_ref() {
+            return;
+        }
+
+        let event = MotionEvent {
+            location,
+            serial: SERIAL_COUNTER.next_serial(),
+            time: Duration::from(self.clock.now()).as_millis() as u32,
+        };
+        let pointer = self.pointer.clone();
+        pointer.motion(self, under, &event);
+        pointer.frame(self);
+    }
+
+    /// move the pointer to `location`, clamped to the outputs, and focus the surface under it
+    pub fn warp_pointer(&mut self, location: Point<f64, Logical>) {
+        let location = utils::clamp_to_outputs(&self.frontend.space, location);
+        let under = utils::surface_under(&self.frontend.space, location);
+        let event = MotionEvent {
+            location,
+            serial: SERIAL_COUNTER.next_serial(),
+            time: Duration::from(self.clock.now()).as_millis() as u32,
+        };
+        let pointer = self.pointer.clone();
+        pointer.motion(self, under, &event);
+        pointer.frame(self);
+        // cursor is drawn by the compositor
+        self.mark_dirty();
+    }
+
+    /// warp the pointer to the center of the focused window
+    ///
+    /// with `only_outside`, a pointer already over the window is left alone
+    pub fn center_pointer_on_focused(&mut self, only_outside: bool) {
+        let Some(geometry) = self.focused_window().and_then(|window|self.frontend.space.element_geometry(&window)) else {
+            return;
+        };
+        let geometry = geometry.to_f64();
+        if only_outside && geometry.contains(self.pointer.current_location()) {
+            return;
+        }
+        self.warp_pointer(geometry.loc + Point::from((geometry.size.w / 2.0, geometry.size.h / 2.0)));
+    }
+
+    /// inhibit idle notification while any inhibiting surface is visible,
+    /// dead and unmapped surfaces drop their inhibitor
+    pub fn update_idle_inhibit(&mut self) {
+        self.frontend.idle_inhibiting_surfaces.retain(|surface|surface.alive());
+        let is_inhibited = self.is_idle_inhibited();
+        self.frontend.idle_notifier_state.set_is_inhibited(is_inhibited);
+    }
+
+    /// prefer the scale of the output a window mostly overlaps, for surfaces not yet
+    /// scanned out on any of the outputs it is on, eg: just mapped or moved to another output
+    ///
+    /// scanned out surfaces follow their primary scanout output in `render::post_repaint`
+    pub fn update_fractional_scale(&mut self) {
+        let space = &self.frontend.space;
+        for window in space.elements() {
+            let Some(geometry) = space.element_geometry(window) else {
+                continue;
+            };
+            let outputs = space.outputs_for_element(window);
+            let Some(output) = outputs.iter().max_by_key(|output|{
+                space
+                    .output_geometry(output)
+                    .and_then(|geo|geo.intersection(geometry))
+                    .map(|overlap|overlap.size.w * overlap.size.h)
+                    .unwrap_or_default()
+            }) else {
+                continue;
+            };
+            window.with_surfaces(|surface,states|{
+                let primary_scanout_output = desktop::utils::surface_primary_scanout_output(surface, states);
+                if primary_scanout_output.is_some_and(|primary|outputs.contains(&primary)) {
+                    return;
+                }
+                fractional_scale::with_fractional_scale(states, |fractional_scale|{
+                    fractional_scale.set_preferred_scale(output.current_scale().fractional_scale());
+                });
+            });
+        }
+    }
+
+    /// whether any inhibiting surface is visible
+    pub fn is_idle_inhibited(&self) -> bool {
+        self.frontend
+            .idle_inhibiting_surfaces
+            .iter()
+            .any(|surface|surface.alive() && utils::is_surface_visible(&self.frontend.space, surface))
+    }
+
+    /// called on [`CompositorHandler::new_surface`] in [`crate::handlers::compositor`]
+    ///
+    /// commits with an explicit sync acquire point are held back until the fence signals,
+    /// the release point is signaled by the renderer once every user of the buffer,
+    /// including scanout, has dropped it
+    ///
+    /// [`CompositorHandler::new_surface`]: compositor::CompositorHandler::new_surface
+    pub fn surface_new(&mut self, surface: &WlSurface) {
+        compositor::add_pre_commit_hook::<Self, _>(surface, |trayle, _dh, surface|{
+            let acquire_point = compositor::with_states(surface, |states|{
+                let has_new_buffer = matches!(
+                    states.cached_state.get::<SurfaceAttributes>().pending().buffer,
+                    Some(compositor::BufferAssignment::NewBuffer(_))
+                );
+                if !has_new_buffer {
+                    return None;
+                }
+                states.cached_state.get::<DrmSyncobjCachedState>().pending().acquire_point.clone()
+            });
+            let Some(acquire_point) = acquire_point else {
+                return;
+            };
+
+            let (blocker, source) = match acquire_point.generate_blocker() {
+                Ok(ok) => ok,
+                Err(err) => {
+                    tracing::warn!("failed to wait for acquire point: {err}");
+                    return;
+                },
+            };
+
+            let Some(client) = surface.client() else {
+                return;
+            };
+            let res = trayle.handle.insert_source(source, move |_, _, trayle|{
+                let dh = trayle.dh.clone();
+                trayle.client_compositor_state(&client).blocker_cleared(trayle, &dh);
+                Ok(())
+            });
+
+            match res {
+                Ok(_) => compositor::add_blocker(surface, blocker),
+                Err(err) => tracing::warn!("failed to wait for acquire point: {}", err.error),
+            }
+        });
+    }
+
     /// called on [`CompositorHandler::commit`] in [`crate::handlers::compositor`]
     ///
     /// [`CompositorHandler::commit`]: compositor::CompositorHandler::commit
     pub fn surface_commit(&mut self, surface: &WlSurface) {
         // smithay take over buffer management
         renderer_utils::on_commit_buffer_handler::<Self>(surface);
+        self.mark_dirty();
 
         // idk
         if let Err(err) = self.backend.early_import(surface) {
@@ -310,12 +664,18 @@ impl Trayle {
 
         let current = self.frontend.space.elements().find_map(|window| {
             let toplevel = window.toplevel()?;
-            (toplevel.wl_surface() == surface).then_some((toplevel,window))
+            (toplevel.wl_surface() == surface).then(||(toplevel.clone(),window.clone()))
         });
 
-        if let Some((toplevel_surface, _window)) = current {
+        if let Some((toplevel_surface, window)) = current {
+            // interactive resize from top or left edges also moves the window
+            let geometry_size = window.0.geometry().size;
+            if let Some(location) = ResizeState::commit_location(surface, geometry_size) {
+                self.frontend.space.map_element(window, location, false);
+            }
+
             // xdg
-            if compositor::with_states(surface, |state| {
+            if !compositor::with_states(surface, |state| {
                 state
                     .data_map
                     .get::<XdgToplevelSurfaceData>()
@@ -328,1138 +688,4039 @@ impl Trayle {
                 toplevel_surface.send_configure();
             }
 
-            // eg: popup commit
-
             // eg: dnd
         }
-    }
-}
 
-/// each client state
-#[derive(Default)]
-pub struct ClientState {
-    pub compositor_state: CompositorClientState,
-}
+        // dnd icon follows the surface offset
+        if let Some(icon) = self.frontend.dnd_icon.as_mut().filter(|icon|&icon.surface == surface) {
+            let delta = compositor::with_states(surface, |states|{
+                states
+                    .cached_state
+                    .get::<SurfaceAttributes>()
+                    .current()
+                    .buffer_delta
+                    .take()
+            });
+            if let Some(delta) = delta {
+                icon.offset += delta;
+            }
+        }
 
-impl ClientData for ClientState {
-    fn initialized(&self, _client_id: ClientId) { }
-    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) { }
-}
+        // layer shell
+        let layer_output = self.frontend.space.outputs().find(|output|{
+            let map = desktop::layer_map_for_output(output);
+            map.layer_for_surface(surface, WindowSurfaceType::TOPLEVEL).is_some()
+        }).cloned();
 
-/// each connected drm device
-pub struct DeviceData {
-    pub drm_loop_token: RegistrationToken,
-    pub drm_output_manager: DrmOutputManager<
-        GbmAllocator<DrmDeviceFd>,
-        GbmDevice<DrmDeviceFd>,
-        Option<OutputPresentationFeedback>,
-        DrmDeviceFd,
-    >,
-    pub drm_scanner: DrmScanner,
-    pub non_desktop_connectors: Vec<(connector::Handle, crtc::Handle)>,
-    pub render_node: DrmNode,
-    pub surfaces: HashMap<crtc::Handle, SurfaceData>,
-    pub drm_lease_state: Option<DrmLeaseState>,
-    pub active_leases: Vec<DrmLease>,
-}
+        if let Some(output) = layer_output {
+            let initial_configure_sent = compositor::with_states(surface, |states|{
+                states
+                    .data_map
+                    .get::<LayerSurfaceData>()
+                    .unwrap()
+                    .lock()
+                    .unwrap()
+                    .initial_configure_sent
+            });
 
-/// surface for each [`DeviceData`]
-pub struct SurfaceData {
-    pub dh: DisplayHandle,
-    pub device_id: DrmNode,
-    pub render_node: DrmNode,
-    pub global: Option<GlobalId>,
-    pub drm_output: DrmOutput<
-        GbmAllocator<DrmDeviceFd>,
-        GbmDevice<DrmDeviceFd>,
-        Option<OutputPresentationFeedback>,
-        DrmDeviceFd,
-    >,
-    pub disable_direct_scanout: bool,
-    pub dmabuf_feedback: Option<SurfaceDmabufFeedback>,
-}
+            let mut map = desktop::layer_map_for_output(&output);
 
-pub mod device {
-    //! `udev` specific handlers
-    use super::render::elements::{OutputRenderElements, WindowRenderElement};
-    use crate::backend::UdevRenderer;
+            // arrange also recalculate the exclusive zones
+            map.arrange();
 
-    use super::*;
+            let layer = map.layer_for_surface(surface, WindowSurfaceType::TOPLEVEL).cloned();
+            drop(map);
 
-    /// handler for [`UdevBackend`] event source
-    pub fn handle_udev(event: UdevEvent, _: &mut (), trayle: &mut Trayle) {
-        let result = match event {
-            UdevEvent::Added { device_id, path } => {
-                tracing::info!("device added {path:?}({device_id})");
-                device::added(device_id, &path, trayle)
-            }
-            UdevEvent::Changed { device_id } => {
-                tracing::info!("device changed {device_id}");
-                device::changed(device_id, trayle)
-            }
-            UdevEvent::Removed { device_id } => {
-                tracing::info!("device removed {device_id}");
-                device::removed(device_id, trayle)
+            if let Some(layer) = layer {
+                if !initial_configure_sent {
+                    layer.layer_surface().send_configure();
+                }
+
+                // keyboard interactive layers, eg: launcher, grab focus
+                let exclusive = layer.cached_state().keyboard_interactivity == KeyboardInteractivity::Exclusive;
+                if exclusive && matches!(layer.layer(), Layer::Top | Layer::Overlay) {
+                    if let Some(keyboard) = self.seat.get_keyboard() {
+                        if keyboard.current_focus().as_ref() != Some(surface) {
+                            keyboard.set_focus(self, Some(surface.clone()), SERIAL_COUNTER.next_serial());
+                        }
+                    }
+                }
             }
-        };
+        }
 
-        if let Err(err) = result {
-            tracing::error!("device error, {err}");
+        // popup
+        self.frontend.popups.commit(surface);
+        if let Some(PopupKind::Xdg(ref popup)) = self.frontend.popups.find_popup(surface) {
+            if !popup.is_initial_configure_sent() {
+                // NOTE: this should never fail as the initial configure is always allowed
+                popup.send_configure().expect("initial configure failed");
+            }
         }
     }
+}
 
-    fn added(device_id: u64, path: &Path, trayle: &mut Trayle) -> Result<(), DeviceError> {
-        let node = DrmNode::from_dev_id(device_id)?;
+/// output control
+impl Trayle {
+    /// output and its drm surface by output name, eg: `DP-1`
+    pub fn output_surface(&mut self, output_name: &str) -> Option<(Output, &mut SurfaceData)> {
+        let output = self.frontend.space.outputs().find(|o|o.name() == output_name).cloned()?;
+        let id = output.user_data().get::<device::UdevOutputId>()?;
+        let surface = self.backend.state.tty_mut()?.devices.get_mut(&id.device_id)?.surfaces.get_mut(&id.crtc)?;
+        Some((output, surface))
+    }
 
-        let flags = OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK;
-        let fd = Session::open(&mut trayle.backend.session, path, flags)?;
-        let fd = DrmDeviceFd::new(fd.into());
+    /// repaint every output on its next frame
+    ///
+    /// called on surface commits, input, and compositor side changes of windows or outputs
+    pub fn mark_dirty(&mut self) {
+        self.backend.state.mark_dirty();
+    }
 
-        let (drm,drm_source) = DrmDevice::new(fd.clone(), true)?;
-        let gbm = GbmDevice::new(fd).map_err(DeviceError::GbmDevice)?;
+    /// enable or disable direct scanout of client buffers on every output
+    pub fn set_direct_scanout(&mut self, enabled: bool) {
+        self.backend.disable_direct_scanout = !enabled;
+
+        let surfaces = self.backend.state
+            .tty_mut().into_iter()
+            .flat_map(|tty|tty.devices.values_mut())
+            .flat_map(|device|device.surfaces.values_mut());
+        for surface in surfaces {
+            surface.disable_direct_scanout = !enabled;
+            // planes assigned in previous frames are only reconsidered on a full repaint
+            surface.drm_output.with_compositor(|compositor|compositor.reset_buffers());
+        }
 
-        let drm_loop_token = trayle.handle.insert_source(
-            drm_source, move|e,m,t|callbacks::drm(e,node,m,t)
-        ).unwrap();
+        self.mark_dirty();
+        tracing::info!("direct scanout {}", if enabled { "enabled" } else { "disabled" });
+    }
 
-        let display = unsafe { EGLDisplay::new(gbm.clone()) }?;
-        let egldevice = EGLDevice::device_for_display(&display)?;
-        let render_node = egldevice.try_get_render_node()?.ok_or(DeviceError::EGLRenderNode)?;
+    /// toggle renderer debug overlay `flag` on every output
+    pub fn toggle_debug_flag(&mut self, flag: DebugFlags) {
+        self.backend.debug_flags.toggle(flag);
+        let debug_flags = self.backend.debug_flags;
+
+        let surfaces = self.backend.state
+            .tty_mut().into_iter()
+            .flat_map(|tty|tty.devices.values_mut())
+            .flat_map(|device|device.surfaces.values_mut());
+        for surface in surfaces {
+            surface.drm_output.with_compositor(|compositor|compositor.set_debug_flags(debug_flags));
+        }
 
-        trayle.backend.gpus.as_mut().add_node(render_node, gbm.clone())?;
+        self.mark_dirty();
+        tracing::info!(?debug_flags, "debug flags changed");
+    }
 
-        let color_formats = match trayle.config.disable_direct_10bit {
-            true => SUPPORTED_FORMATS_8BIT_ONLY,
-            false => SUPPORTED_FORMATS,
-        };
-        let gbm_buffer_flags = GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT;
-        let allocator = GbmAllocator::new(gbm.clone(), gbm_buffer_flags);
-        let mut renderer = trayle.backend.gpus.single_renderer(&render_node).expect("failed to get renderer");
-        let render_formats = renderer.as_mut().egl_context().dmabuf_render_formats().clone();
+    /// change output mode, `mode` must be one of the connector modes
+    pub fn set_output_mode(&mut self, output_name: &str, mode: WlMode) -> Result<()> {
+        let output = self.frontend.space
+            .outputs()
+            .find(|o|o.name() == output_name)
+            .cloned()
+            .with_context(||format!("no output named {output_name:?}"))?;
+        let id = output.user_data().get::<device::UdevOutputId>().context("output is not a drm output")?;
+        let (node, crtc) = (id.device_id, id.crtc);
 
-        let drm_output_manager = DrmOutputManager::new(
-            drm,
-            allocator,
-            gbm.clone(),
-            Some(gbm),
-            color_formats.iter().copied(),
-            render_formats
-        );
+        let drm_mode = self.drm_mode(&output, mode)?;
+        let tty = self.backend.state.tty_mut().context("output device is gone")?;
+        let device = tty.devices.get_mut(&node).context("output device is gone")?;
 
-        let drm_lease_state = match DrmLeaseState::new::<Trayle>(&trayle.dh, &node) {
-            Ok(ok) => Some(ok),
-            Err(err) => {
-                tracing::warn!("failed to setup drm lease global for {node}: {err:?}");
-                None
-            },
-        };
+        let mut renderer = tty.gpus
+            .single_renderer(&device.render_node)
+            .context("failed to get renderer")?;
+
+        device.drm_output_manager
+            .use_mode::<_, render::elements::OutputRenderElements<
+                UdevRenderer<'_>,
+                render::elements::WindowRenderElement<UdevRenderer<'_>>,
+            >>(
+                &crtc,
+                drm_mode,
+                &mut renderer,
+                &DrmOutputRenderElements::default(),
+            )
+            .map_err(|err|anyhow::anyhow!("failed to set mode: {err}"))?;
 
-        let device_data = DeviceData {
-            drm_loop_token,
-            render_node,
-            drm_output_manager,
-            drm_scanner: DrmScanner::new(),
-            surfaces: HashMap::new(),
-            // known crtcs
-            drm_lease_state,
-            active_leases: vec![],
-            non_desktop_connectors: vec![],
-        };
+        output.change_current_state(Some(mode), None, None, None);
+        desktop::layer_map_for_output(&output).arrange();
+        self.update_output_management();
+        self.mark_dirty();
+        tracing::info!("mode changed to {}x{}@{} on {output_name}", mode.size.w, mode.size.h, mode.refresh);
 
-        assert!(trayle.backend.devices.insert(node, device_data).is_none());
-        device::changed(device_id, trayle)
-    }
+        self.backend.state.schedule_render(&output, &self.handle);
 
-    fn changed(device_id: u64, trayle: &mut Trayle) -> Result<(), DeviceError> {
-        let node = DrmNode::from_dev_id(device_id)?;
-        let Some(device) = trayle.backend.devices.get_mut(&node) else {
-            return Err(DeviceError::DeviceUntracked(node));
-        };
+        Ok(())
+    }
 
-        let scan_result = device
-            .drm_scanner
-            .scan_connectors(device.drm_output_manager.device())
-            .map_err(DeviceError::ScanConnector)?;
+    /// connector mode matching `mode`
+    fn drm_mode(&self, output: &Output, mode: WlMode) -> Result<DrmMode> {
+        let id = output.user_data().get::<device::UdevOutputId>().context("output is not a drm output")?;
+        let device = self.backend.state.tty().and_then(|tty|tty.devices.get(&id.device_id)).context("output device is gone")?;
+        let surface = device.surfaces.get(&id.crtc).context("output surface is gone")?;
 
-        for event in scan_result {
-            match event {
-                DrmScanEvent::Connected { connector, crtc: Some(crtc) } => {
-                    device::connector_connected(node, connector, crtc, trayle)?;
-                },
-                DrmScanEvent::Disconnected { connector, crtc: Some(crtc) } => {
-                    device::connector_disconnected(node, connector, crtc, trayle)?
-                },
-                _ => {}
-            }
-        }
+        let connector = device.drm_output_manager
+            .device()
+            .get_connector(surface.connector, false)
+            .context("failed to query connector")?;
 
-        // fixup window coordinates
-        // crate::shell::utils::fixup_positions(&mut self.space, self.pointer.current_location());
+        connector
+            .modes()
+            .iter()
+            .find(|drm_mode|WlMode::from(**drm_mode) == mode)
+            .copied()
+            .with_context(||format!(
+                "mode {}x{}@{} is not supported by {}", mode.size.w, mode.size.h, mode.refresh, output.name()
+            ))
+    }
 
-        Ok(())
+    /// whether adaptive sync is currently enabled on output
+    pub fn output_vrr(&mut self, output_name: &str) -> Option<bool> {
+        self.output_surface(output_name).map(|(_,surface)|surface.vrr)
     }
 
-    fn removed(device_id: u64, trayle: &mut Trayle) -> Result<(), DeviceError> {
-        let node = DrmNode::from_dev_id(device_id)?;
-        let Some(device) = trayle.backend.devices.get_mut(&node) else {
-            return Err(DeviceError::DeviceUntracked(node));
-        };
+    /// enable or disable adaptive sync on output
+    pub fn set_output_vrr(&mut self, output_name: &str, enabled: bool) -> Result<()> {
+        let (output, surface) = self
+            .output_surface(output_name)
+            .with_context(||format!("no output named {output_name:?}"))?;
 
-        let crtcs = device
-            .drm_scanner
-            .crtcs()
-            .map(|(info, crtc)| (info.clone(), crtc))
-            .collect::<Vec<_>>();
+        utils::set_vrr(surface, enabled)?;
+        self.update_output_management();
+        self.backend.state.schedule_render(&output, &self.handle);
 
-        for (connector,crtc) in crtcs {
-            if let Err(err) = device::connector_disconnected(node, connector, crtc, trayle) {
-                tracing::error!("{err}");
-            }
-        }
+        Ok(())
+    }
 
-        if let Some(mut device) = trayle.backend.devices.remove(&node) {
-            if let Some(mut leasing_global) = device.drm_lease_state.take() {
-                leasing_global.disable_global::<Trayle>();
+    /// current state of outputs, as advertised by output management
+    pub fn output_heads(&self) -> Vec<OutputHead> {
+        let disabled = self.disabled_connectors().map(|(device,connector)|{
+            let name = utils::connector_name(connector);
+            let monitor_info = device.monitor_infos.get(&connector.handle()).cloned().unwrap_or_default();
+            let (make, model) = (monitor_info.make(), monitor_info.model());
+            let (phys_w, phys_h) = connector.size().unwrap_or((0,0));
+            let preferred_mode = connector
+                .modes()
+                .iter()
+                .find(|mode|mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+                .map(|mode|WlMode::from(*mode));
+
+            OutputHead {
+                description: format!("{make} {model} ({name})"),
+                name,
+                make,
+                model,
+                physical_size: (phys_w as i32, phys_h as i32).into(),
+                modes: connector.modes().iter().map(|mode|WlMode::from(*mode)).collect(),
+                preferred_mode,
+                current_mode: None,
+                enabled: false,
+                position: Point::default(),
+                transform: Transform::Normal,
+                scale: 1.0,
+                adaptive_sync: None,
             }
+        });
 
-            trayle.backend
-                .gpus
-                .as_mut()
-                .remove_node(&device.render_node);
+        self.frontend.space
+            .outputs()
+            .map(|output|{
+                let physical = output.physical_properties();
+                let vrr = output
+                    .user_data()
+                    .get::<device::UdevOutputId>()
+                    .and_then(|id|self.backend.state.tty()?.devices.get(&id.device_id)?.surfaces.get(&id.crtc))
+                    .map(|surface|surface.vrr);
+
+                OutputHead {
+                    name: output.name(),
+                    description: output.description(),
+                    make: physical.make,
+                    model: physical.model,
+                    physical_size: physical.size,
+                    modes: output.modes(),
+                    preferred_mode: output.preferred_mode(),
+                    current_mode: output.current_mode(),
+                    enabled: true,
+                    position: output.current_location(),
+                    transform: output.current_transform(),
+                    scale: output.current_scale().fractional_scale(),
+                    adaptive_sync: vrr,
+                }
+            })
+            .chain(disabled)
+            .collect()
+    }
 
-            trayle.handle.remove(device.drm_loop_token);
-        }
+    /// every connected output, including non-desktop connectors reserved for leasing,
+    /// disabled connectors and connectors waiting for a crtc
+    pub fn list_outputs(&self) -> Vec<OutputInfo> {
+        let outputs = self.frontend.space.outputs().map(|output|{
+            let physical = output.physical_properties();
+            let surface = output
+                .user_data()
+                .get::<device::UdevOutputId>()
+                .and_then(|id|self.backend.state.tty()?.devices.get(&id.device_id)?.surfaces.get(&id.crtc));
+
+            OutputInfo {
+                name: output.name(),
+                edid_name: output.user_data().get::<EdidName>().map(|name|name.0.clone()),
+                make: physical.make,
+                model: physical.model,
+                current_mode: output.current_mode().map(ModeInfo::from),
+                modes: output.modes().into_iter().map(ModeInfo::from).collect(),
+                position: self.frontend.space.output_geometry(output).map(|geo|(geo.loc.x, geo.loc.y)),
+                scale: Some(output.current_scale().fractional_scale()),
+                enabled: true,
+                powered: surface.is_some_and(|surface|surface.dpms),
+                adaptive_sync: surface.is_some_and(|surface|surface.vrr),
+                non_desktop: false,
+                leased: false,
+            }
+        });
 
-        // fixup position
-        // crate::shell::utils::fixup_positions(&mut self.space, self.pointer.current_location());
+        // connectors without an output, either non desktop, disabled or waiting for a crtc
+        let devices = self.backend.state.tty().into_iter().flat_map(|tty|tty.devices.values());
+        let connectors = devices.flat_map(|device|{
+            let drm_device = device.drm_output_manager.device();
+            let non_desktop = device.non_desktop_connectors.iter().map(|(handle,_)|(*handle,true));
+            let pending = device.pending_connectors.iter().map(|handle|(*handle,false));
+            let disabled = device.drm_scanner
+                .connectors()
+                .values()
+                .filter(|conn|conn.state() == connector::State::Connected)
+                .filter(move|conn|!device.drm_scanner.is_enabled(&conn.handle()))
+                .map(|conn|(conn.handle(),false));
+            non_desktop.chain(pending).chain(disabled).filter_map(move |(handle,non_desktop)|{
+                let connector = drm_device.get_connector(handle, false).ok()?;
+                let monitor_info = device
+                    .monitor_infos
+                    .get(&handle)
+                    .cloned()
+                    .unwrap_or_else(||MonitorInfo::parse(drm_device, handle));
+                let leased = device.leased_connectors.values().flatten().any(|leased|*leased == handle);
+
+                Some(OutputInfo {
+                    name: utils::connector_name(&connector),
+                    edid_name: monitor_info.edid_name().map(|name|name.0),
+                    make: monitor_info.make(),
+                    model: monitor_info.model(),
+                    current_mode: None,
+                    modes: connector.modes().iter().map(|mode|ModeInfo::from(WlMode::from(*mode))).collect(),
+                    position: None,
+                    scale: None,
+                    enabled: false,
+                    powered: false,
+                    adaptive_sync: false,
+                    non_desktop,
+                    leased,
+                })
+            })
+        });
 
-        Ok(())
+        outputs.chain(connectors).collect()
     }
 
-    fn connector_connected(
-        node: DrmNode,
-        connector: connector::Info,
-        crtc: crtc::Handle,
-        trayle: &mut Trayle,
-    ) -> Result<(), DeviceError> {
-        let Some(device) = trayle.backend.devices.get_mut(&node) else {
-            return Err(DeviceError::DeviceUntracked(node));
-        };
-
-        let mut renderer = trayle
-            .backend
-            .gpus
-            .single_renderer(&device.render_node)
-            .expect("failed to get renderer");
+    /// push output changes to output management clients
+    ///
+    /// called wherever outputs are connected, disconnected, enabled, disabled or reconfigured
+    pub fn update_output_management(&mut self) {
+        let heads = self.output_heads();
+        self.frontend.output_management_state.update::<Trayle>(heads);
+    }
 
-        let output_name = format!("{}-{}",connector.interface().as_str(),connector.interface_id());
-        tracing::info!(?crtc,"setting up connector {output_name}");
+    /// validate and apply output management configuration
+    ///
+    /// every head is validated first, including a drm test commit of new modes,
+    /// applied changes are rolled back if a later head fails to apply,
+    /// outputs are enabled and disabled last and are not rolled back
+    pub fn apply_output_configuration(&mut self, configuration: OutputConfiguration, test_only: bool) -> Result<()> {
+        let mut heads = Vec::with_capacity(configuration.len());
+        let mut toggled = vec![];
+
+        for (name, head) in configuration {
+            let output = self.frontend.space
+                .outputs()
+                .find(|o|o.name() == name)
+                .cloned();
 
-        let drm_device = device.drm_output_manager.device();
+            let (output, head) = match (output, head) {
+                (Some(output), Some(head)) => (output, head),
+                (Some(_), None) => {
+                    toggled.push((name, None));
+                    continue;
+                },
+                (None, head) if self.disabled_connectors().any(|(_,conn)|utils::connector_name(conn) == name) => {
+                    toggled.push((name, head));
+                    continue;
+                },
+                (None, _) => anyhow::bail!("no output named {name:?}"),
+            };
 
-        let non_desktop = drm_device
-            .get_properties(connector.handle())
-            .ok()
-            .and_then(|props|{
-                let (info,value) = props
-                    .into_iter()
-                    .filter_map(|(handle,value)|Some((drm_device.get_property(handle).ok()?,value)))
-                    .find(|(info,_)|info.name().to_str()==Ok("non-desktop"))?;
-                info.value_type().convert_value(value).as_boolean()
-            })
-            .unwrap_or(false);
+            if let Some(mode) = head.mode {
+                let drm_mode = self.drm_mode(&output, mode)?;
+                let (_, surface) = self.output_surface(&name).context("output surface is gone")?;
+                utils::test_mode(surface, drm_mode).with_context(||format!("mode test failed on {name}"))?;
+            }
 
-        let display_info = display_info::for_connectors(drm_device, connector.handle());
+            heads.push((output, head));
+        }
 
-        let make = display_info.as_ref().and_then(|info|info.make()).unwrap_or_else(||"Unknown".into());
-        let model = display_info.as_ref().and_then(|info|info.model()).unwrap_or_else(||"Unknown".into());
+        if test_only {
+            return Ok(());
+        }
 
-        if non_desktop {
-            tracing::info!(
-                "connector {output_name} is non-desktop, setting up for leasing",
-            );
+        let mut applied = Vec::with_capacity(heads.len());
+        for (output, head) in heads {
+            let previous = HeadConfiguration {
+                mode: output.current_mode(),
+                position: Some(output.current_location()),
+                transform: Some(output.current_transform()),
+                scale: Some(output.current_scale().fractional_scale()),
+                adaptive_sync: self.output_vrr(&output.name()),
+            };
 
-            device.non_desktop_connectors.push((connector.handle(), crtc));
-            if let Some(lease_state) = device.drm_lease_state.as_mut() {
-                lease_state.add_connector::<Trayle>(
-                    connector.handle(), output_name, format!("{make} {model}")
-                );
+            if let Err(err) = self.apply_head_configuration(&output, &head) {
+                tracing::warn!("rolling back output configuration");
+                let _ = self.apply_head_configuration(&output, &previous);
+                for (output, previous) in applied.into_iter().rev() {
+                    let _ = self.apply_head_configuration(&output, &previous);
+                }
+                self.update_output_management();
+                return Err(err);
             }
 
-            return Ok(());
+            applied.push((output, previous));
         }
 
-        let mode_id = connector
-            .modes()
-            .iter()
-            .position(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
-            .unwrap_or(0);
-
-        let drm_mode = connector.modes()[mode_id];
-        let wl_mode = WlMode::from(drm_mode);
+        for (name, head) in toggled {
+            self.set_output_enabled(&name, head.is_some())?;
+            let output = self.frontend.space.outputs().find(|o|o.name() == name).cloned();
+            if let (Some(output), Some(head)) = (output, head) {
+                self.apply_head_configuration(&output, &head)?;
+            }
+        }
 
-        let (phys_w, phys_h) = connector.size().unwrap_or((0,0));
-        let physical = PhysicalProperties {
-            size: (phys_w as i32,phys_h as i32).into(),
-            subpixel: connector.subpixel().into(), make, model
-        };
-        let output = Output::new(output_name, physical);
+        self.update_output_management();
+        Ok(())
+    }
 
-        let global = output.create_global::<Trayle>(&trayle.dh);
+    fn apply_head_configuration(&mut self, output: &Output, head: &HeadConfiguration) -> Result<()> {
+        let name = output.name();
 
-        let x = trayle.frontend.space.outputs().fold(0, |acc, o| {
-            acc + trayle.frontend.space.output_geometry(o).unwrap().size.w
-        });
+        if let Some(mode) = head.mode.filter(|&mode|output.current_mode() != Some(mode)) {
+            self.set_output_mode(&name, mode)?;
+        }
 
-        let position = (x, 0).into();
+        if head.transform.is_some() || head.scale.is_some() {
+            output.change_current_state(None, head.transform, head.scale.map(OutputScale::Fractional), None);
+            desktop::layer_map_for_output(output).arrange();
+        }
 
-        output.set_preferred(wl_mode);
-        output.change_current_state(Some(wl_mode), None, None, Some(position));
-        trayle.frontend.space.map_output(&output, position);
+        if let Some(position) = head.position {
+            utils::map_output(&mut self.frontend.space, output, position);
+        }
 
-        output.user_data().insert_if_missing(||UdevOutputId { crtc, device_id: node });
+        if let Some(enabled) = head.adaptive_sync.filter(|&enabled|self.output_vrr(&name) != Some(enabled)) {
+            self.set_output_vrr(&name, enabled)?;
+        }
 
-        let driver = drm_device.get_driver().map_err(DeviceError::DrmDriver)?;
-        let mut planes = drm_device.planes(&crtc)?;
+        self.mark_dirty();
+        Ok(())
+    }
 
-        // nvidia moment
-        if driver.name().to_string_lossy().to_lowercase().contains("nvidia") ||
-            driver.description().to_string_lossy().to_lowercase().contains("nvidia")
-        {
-            planes.overlay.clear();
+    /// enable or disable the output of connector or monitor edid `name`
+    ///
+    /// a disabled output is unmapped and releases its crtc for other connectors
+    pub fn set_output_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        let (node, connector) = self
+            .find_connector(name)
+            .with_context(||format!("no connected output named {name:?}"))?;
+
+        let device = self.backend.state.tty().and_then(|tty|tty.devices.get(&node)).context("output device is gone")?;
+        if device.drm_scanner.is_enabled(&connector) == enabled {
+            return Ok(());
         }
 
-        let drm_output = device.drm_output_manager
-            .initialize_output::<_, OutputRenderElements<UdevRenderer<'_>, WindowRenderElement<UdevRenderer<'_>>>>(
-                crtc,
-                drm_mode,
-                &[connector.handle()],
-                &output,
-                Some(planes),
-                &mut renderer,
-                &DrmOutputRenderElements::default()
-            )
-            .map_err(DeviceError::drm_output)?;
+        device::set_connector_enabled(node, connector, enabled, self)?;
+        tracing::info!("{name} {}", if enabled { "enabled" } else { "disabled" });
 
-        let disable_direct_scanout = std::env::var("TRAYLE_DISABLE_DIRECT_SCANOUT").is_ok();
+        self.mark_dirty();
+        Ok(())
+    }
 
-        let dmabuf_feedback = drm_output.with_compositor(|compositor|{
-            compositor.set_debug_flags(trayle.backend.debug_flags);
+    /// connected connector of connector or monitor edid `name`
+    fn find_connector(&self, name: &str) -> Option<(DrmNode, connector::Handle)> {
+        self.backend.state.tty()?.devices.iter().find_map(|(&node,device)|{
+            device.drm_scanner
+                .connectors()
+                .values()
+                .filter(|conn|conn.state() == connector::State::Connected)
+                .find(|conn|{
+                    utils::connector_name(conn) == name || device.monitor_infos
+                        .get(&conn.handle())
+                        .and_then(MonitorInfo::edid_name)
+                        .is_some_and(|EdidName(edid)|edid == name)
+                })
+                .map(|conn|(node, conn.handle()))
+        })
+    }
 
-            utils::get_surface_dmabuf_feedback(
-                trayle.backend.primary_gpu,
-                device.render_node,
-                &mut trayle.backend.gpus,
-                compositor.surface(),
-            )
-        });
+    /// connected connectors disabled with [`Trayle::set_output_enabled`] or by config
+    fn disabled_connectors(&self) -> impl Iterator<Item = (&DeviceData, &connector::Info)> {
+        self.backend.state.tty().into_iter().flat_map(|tty|tty.devices.values()).flat_map(|device|{
+            device.drm_scanner
+                .connectors()
+                .values()
+                .filter(|conn|conn.state() == connector::State::Connected)
+                .filter(|conn|!device.drm_scanner.is_enabled(&conn.handle()))
+                .map(move|conn|(device, conn))
+        })
+    }
 
-        let surface = SurfaceData {
-            dh: trayle.dh.clone(),
-            device_id: node,
-            render_node: device.render_node,
-            global: Some(global),
-            drm_output,
-            disable_direct_scanout,
-            dmabuf_feedback,
-        };
+    /// power output on or off, rendering is paused while the output is off
+    pub fn set_output_dpms(&mut self, node: DrmNode, crtc: crtc::Handle, on: bool) -> Result<()> {
+        let device = self.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)).context("output device is gone")?;
+        let surface = device.surfaces.get_mut(&crtc).context("output surface is gone")?;
 
-        device.surfaces.insert(crtc, surface);
+        if surface.dpms == on {
+            return Ok(());
+        }
 
-        // kick-off rendering
-        trayle.handle.insert_idle(move|trayle|{
-            render::surface(node, crtc, trayle.clock.now(), trayle);
-        });
+        if on {
+            // some drivers only light up the crtc again on a full modeset,
+            // resetting the state makes the next frame do one
+            surface.drm_output
+                .with_compositor(|compositor|compositor.reset_state())
+                .map_err(|err|anyhow::anyhow!("failed to reset drm state: {err:?}"))?;
+            surface.dpms = true;
+            surface.dirty = true;
+
+            let output = surface.output.clone();
+            self.backend.state.schedule_render(&output, &self.handle);
+        } else {
+            // disables the crtc, which turns `ACTIVE` off
+            surface.drm_output
+                .with_compositor(|compositor|compositor.clear())
+                .map_err(|err|anyhow::anyhow!("failed to power off: {err:?}"))?;
+            surface.dpms = false;
+        }
 
+        tracing::info!("{crtc:?} on {node} powered {}", if on { "on" } else { "off" });
         Ok(())
     }
 
-    fn connector_disconnected(
-        node: DrmNode,
-        connector: connector::Info,
-        crtc: crtc::Handle,
-        trayle: &mut Trayle,
-    ) -> Result<(), DeviceError> {
-        let Some(device) = trayle.backend.devices.get_mut(&node) else {
-            return Err(DeviceError::DeviceUntracked(node));
-        };
+    /// power all outputs on or off, internal panels stay off while the lid is closed
+    pub fn set_all_outputs_dpms(&mut self, on: bool) {
+        let lid_closed = self.backend.lid_closed;
+        let surfaces = self.backend.state
+            .tty().into_iter()
+            .flat_map(|tty|&tty.devices)
+            .flat_map(|(&node,device)|{
+                device.surfaces
+                    .iter()
+                    .filter(|(_,surface)|surface.dpms != on)
+                    .filter(move|(_,surface)|!(on && lid_closed && device.is_internal_panel(surface)))
+                    .map(move|(&crtc,_)|(node, crtc))
+            })
+            .collect::<Vec<_>>();
 
-        if let Some(pos) = device
-            .non_desktop_connectors
-            .iter()
-            .position(|(handle,_)|*handle==connector.handle())
-        {
-            let _ = device.non_desktop_connectors.remove(pos);
-            if let Some(leasing_state) = device.drm_lease_state.as_mut() {
-                leasing_state.withdraw_connector(connector.handle());
+        for (node, crtc) in surfaces {
+            if let Err(err) = self.set_output_dpms(node, crtc, on) {
+                tracing::warn!("failed to set dpms of {crtc:?} on {node}: {err:#}");
             }
-        } else {
-            device.surfaces.remove(&crtc);
+        }
+    }
 
-            let output = trayle.frontend.space
-                .outputs()
-                .find(|o|{
-                    o.user_data()
-                        .get::<UdevOutputId>()
-                        .map(|id|id.device_id == node && id.crtc == crtc)
-                        .unwrap_or(false)
-                })
-                .cloned();
+    /// power internal panels off when the laptop lid is closed, and on again when opened
+    pub fn set_lid_closed(&mut self, closed: bool) {
+        if self.backend.lid_closed == closed {
+            return;
+        }
+        self.backend.lid_closed = closed;
+        tracing::info!("lid {}", if closed { "closed" } else { "opened" });
+
+        let panels = self.backend.state
+            .tty().into_iter()
+            .flat_map(|tty|&tty.devices)
+            .flat_map(|(&node,device)|{
+                device.surfaces
+                    .iter()
+                    .filter(|(_,surface)|device.is_internal_panel(surface))
+                    .map(move|(&crtc,_)|(node, crtc))
+            })
+            .collect::<Vec<_>>();
 
-            if let Some(output) = output {
-                trayle.frontend.space.unmap_output(&output);
+        for (node, crtc) in panels {
+            if let Err(err) = self.set_output_dpms(node, crtc, !closed) {
+                tracing::warn!("failed to set dpms of {crtc:?} on {node}: {err:#}");
             }
         }
+    }
 
-        let mut renderer = trayle.backend.gpus.single_renderer(&device.render_node).unwrap();
-
-        let _ = device.drm_output_manager.try_to_restore_modifiers::<_, OutputRenderElements<
-            UdevRenderer<'_>,
-            WindowRenderElement<UdevRenderer<'_>>,
-        >>(
-            &mut renderer,
-            // FIXME: For a flicker free operation we should return the actual elements for this output..
-            // Instead we just use black to "simulate" a modeset :)
-            &DrmOutputRenderElements::default(),
-        );
-
-        Ok(())
+    /// gamma ramp size of output, `None` if its crtc does not support gamma
+    pub fn output_gamma_size(&mut self, output_name: &str) -> Option<u32> {
+        let output = self.frontend.space.outputs().find(|o|o.name() == output_name)?;
+        let id = output.user_data().get::<device::UdevOutputId>()?;
+        let device = self.backend.state.tty()?.devices.get(&id.device_id)?;
+        let crtc = device.drm_output_manager.device().get_crtc(id.crtc).ok()?;
+        Some(crtc.gamma_length()).filter(|&size|size > 0)
     }
 
-    #[derive(Debug, PartialEq)]
-    pub struct UdevOutputId {
-        pub device_id: DrmNode,
-        pub crtc: crtc::Handle,
+    /// set gamma ramp of output, `None` to reset it to linear
+    ///
+    /// the ramp is kept to be reapplied on session resume
+    pub fn set_output_gamma(&mut self, output_name: &str, ramp: Option<Vec<u16>>) -> Result<()> {
+        let output = self.frontend.space
+            .outputs()
+            .find(|o|o.name() == output_name)
+            .with_context(||format!("no output named {output_name:?}"))?;
+        let id = output.user_data().get::<device::UdevOutputId>().context("output is not a drm output")?;
+
+        let device = self.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&id.device_id)).context("output device is gone")?;
+        let surface = device.surfaces.get_mut(&id.crtc).context("output surface is gone")?;
+
+        utils::set_gamma(device.drm_output_manager.device(), id.crtc, ramp.as_deref())?;
+        surface.gamma = ramp;
+
+        Ok(())
     }
+}
 
-    #[derive(Debug, thiserror::Error)]
-    pub enum DeviceError {
-        #[error("failed to create drm node: {0}")]
-        DrmNode(#[from] CreateDrmNodeError),
-        #[error("failed to open device via libseat: {0}")]
-        LibSeat(#[from] libseat::Error),
-        #[error("drm device error: {0}")]
-        DrmDevice(#[from] DrmError),
-        #[error("failed to setup gbm device: {0}")]
-        GbmDevice(std::io::Error),
-        #[error("failed to setup egl: {0}")]
-        EGLError(#[from] egl::Error),
-        #[error("failed to get render node for current egl")]
-        EGLRenderNode,
-        #[error("device {0} untracked")]
-        DeviceUntracked(DrmNode),
-        #[error("failed to scan connectors: {0}")]
-        ScanConnector(std::io::Error),
-        #[error("failed to query drm driver: {0}")]
-        DrmDriver(std::io::Error),
-        #[error("failed to setup drm output")]
-        DrmOutput(String/* the error type is complicated */),
+/// workspace control
+impl Trayle {
+    /// mapped window of toplevel `surface`
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<WindowElement> {
+        self.frontend.space
+            .elements()
+            .find(|window|window.toplevel().is_some_and(|toplevel|toplevel.wl_surface() == surface))
+            .cloned()
     }
 
-    impl DeviceError {
-        fn drm_output(error: impl std::error::Error) -> Self {
-            Self::DrmOutput(error.to_string())
+    /// window with keyboard focus
+    pub fn focused_window(&self) -> Option<WindowElement> {
+        let focus = self.seat.get_keyboard()?.current_focus()?;
+        self.window_for_surface(&focus)
+    }
+
+    /// ask the focused window to close, the window is removed once the client destroys it
+    pub fn close_focused_window(&mut self) {
+        if let Some(toplevel) = self.focused_window().as_ref().and_then(|window|window.toplevel()) {
+            toplevel.send_close();
         }
     }
-}
 
-pub mod callbacks {
-    //! handlers for various smithay event sources handler
-    use super::*;
+    /// raise and focus the next window in most recently used order
+    ///
+    /// the order is updated by [`Trayle::finish_cycle_windows`]
+    pub fn cycle_windows(&mut self, forward: bool) {
+        let frontend = &mut self.frontend;
+        let space = &frontend.space;
+        frontend.mru.retain(|window|window.alive());
+
+        // only windows of the active workspace
+        let candidates = frontend.mru
+            .iter()
+            .enumerate()
+            .filter(|(_,window)|space.element_location(window).is_some())
+            .map(|(i,_)|i)
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return;
+        }
 
-    type IoDisplay = NoIoDrop<Display<Trayle>>;
-    type IoPostAction = std::io::Result<PostAction>;
+        let current = frontend.cycle
+            .as_ref()
+            .and_then(|cycle|candidates.iter().position(|&i|&frontend.mru[i] == cycle))
+            .unwrap_or(0);
+        let next = match forward {
+            true => (current + 1) % candidates.len(),
+            false => (current + candidates.len() - 1) % candidates.len(),
+        };
 
-    /// handler for [`Generic<Display>`] event source
-    pub fn display<R>(_: R, display: &mut IoDisplay, trayle: &mut Trayle) -> IoPostAction {
-        // SAFETY: we dont drop the display
-        unsafe { display.get_mut().dispatch_clients(trayle).unwrap() };
-        Ok(PostAction::Continue)
+        let window = frontend.mru[candidates[next]].clone();
+        frontend.cycle = Some(window.clone());
+        frontend.space.raise_element(&window, true);
+
+        let focus = window.toplevel().map(|toplevel|toplevel.wl_surface().clone());
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, focus, SERIAL_COUNTER.next_serial());
+        }
     }
 
-    /// handler for [`LibinputInputBackend`] event source
-    pub fn input(mut event: InputEvent, _: &mut (), trayle: &mut Trayle) {
-        use smithay::reexports::input::DeviceCapability;
+    /// commit the window selected by [`Trayle::cycle_windows`] as the most recently used
+    pub fn finish_cycle_windows(&mut self) {
+        let Some(window) = self.frontend.cycle.take() else {
+            return;
+        };
+        // the window may be gone by the time the modifier is released
+        if let Some(index) = self.frontend.mru.iter().position(|w|w == &window) {
+            self.frontend.mru.remove(index);
+            self.frontend.mru.insert(0, window);
+        }
+    }
 
-        match &mut event {
-            InputEvent::DeviceAdded { device } => {
-                if device.has_capability(DeviceCapability::Keyboard) {
-                    if let Some(keyboard) = trayle.seat.get_keyboard() {
-                        device.led_update(keyboard.led_state().into());
-                    };
-                    trayle.backend.keyboards.push(device.clone());
-                }
-            }
-            InputEvent::DeviceRemoved { device } => {
-                if device.has_capability(DeviceCapability::Keyboard) {
-                    trayle.backend.keyboards.retain(|kb|kb!=device);
-                }
-            }
-            _ => {}
+    /// give keyboard focus to the topmost mapped window
+    pub fn focus_topmost_window(&mut self) {
+        let focus = self.frontend.space
+            .elements()
+            .rev()
+            .find_map(|window|window.toplevel())
+            .map(|toplevel|toplevel.wl_surface().clone());
+
+        if let Some(window) = focus.as_ref().and_then(|surface|self.window_for_surface(surface)) {
+            self.frontend.space.raise_element(&window, true);
         }
 
-        input::handle(event, trayle);
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, focus, SERIAL_COUNTER.next_serial());
+        }
     }
 
-    /// handler for [`ListeningSocketSource`] event source
-    ///
-    /// [`ListeningSocketSource`]: smithay::wayland::socket::ListeningSocketSource
-    pub fn socket(stream: UnixStream, _: &mut (), trayle: &mut Trayle) {
-        let state = Arc::new(ClientState::default());
-        match trayle.dh.insert_client(stream, state) {
-            Ok(_client) => {}
-            Err(err) => tracing::warn!("failed to add wayland client: {err}"),
-        };
+    /// every window of every workspace, in mapping order
+    pub fn list_windows(&self) -> Vec<WindowInfo> {
+        let focused = self.focused_window();
+        let space = &self.frontend.space;
+
+        self.frontend.workspaces
+            .iter()
+            .enumerate()
+            .flat_map(|(index,workspace)|workspace.windows.iter().map(move |window|(index,window)))
+            .filter_map(|(index,(window,location))|{
+                let id = window.id()?;
+                let toplevel = window.toplevel()?;
+                let (title, app_id) = compositor::with_states(toplevel.wl_surface(), |states|{
+                    let data = states.data_map.get::<XdgToplevelSurfaceData>()?.lock().ok()?;
+                    Some((data.title.clone(), data.app_id.clone()))
+                })
+                .unwrap_or_default();
+
+                // saved location is outdated while the workspace is shown
+                let location = space.element_location(window).unwrap_or(*location);
+                let size = window.geometry().size;
+
+                Some(WindowInfo {
+                    id,
+                    title,
+                    app_id,
+                    location: (location.x, location.y),
+                    size: (size.w, size.h),
+                    focused: focused.as_ref() == Some(window),
+                    workspace: index + 1,
+                })
+            })
+            .collect()
     }
 
-    /// handler for [`LibSeatSessionNotifier`] event source
-    ///
-    /// [`LibSeatSessionNotifier`]: smithay::backend::session::libseat::LibSeatSessionNotifier
-    pub fn session(event: SessionEvent, _: &mut (), trayle: &mut Trayle) {
-        match event {
-            SessionEvent::PauseSession => {
-                tracing::info!("session pause");
-                trayle.backend.input.suspend();
-                for backend in trayle.backend.devices.values_mut() {
-                    backend.drm_output_manager.pause();
-                    backend.active_leases.clear();
-                    if let Some(lease_global) = backend.drm_lease_state.as_mut() {
-                        lease_global.suspend();
-                    }
-                }
-            }
-            SessionEvent::ActivateSession => {
-                tracing::info!("session resume");
-                if let Err(err) = trayle.backend.input.resume() {
-                    tracing::error!("failed to resume libinput context: {err:?}");
-                }
-                for (&node, backend) in trayle.backend.devices.iter_mut() {
-                    // if we do not care about flicking (caused by modesetting) we could just
-                    // pass true for disable connectors here. this would make sure our drm
-                    // device is in a known state (all connectors and planes disabled).
-                    // but for demonstration we choose a more optimistic path by leaving the
-                    // state as is and assume it will just work. If this assumption fails
-                    // we will try to reset the state when trying to queue a frame.
-                    backend.drm_output_manager
-                        .activate(false /* disable connectors */)
-                        .expect("failed to activate drm backend");
-                    if let Some(lease_global) = backend.drm_lease_state.as_mut() {
-                        lease_global.resume::<Trayle>();
-                    }
-                    trayle.handle.insert_idle(move|trayle|render::node(node, None, trayle.clock.now(), trayle));
-                }
-            }
+    /// raise and focus window by its [`WindowElement::id`], showing its workspace if hidden
+    pub fn focus_window(&mut self, id: u64) -> Result<()> {
+        let (index, window) = self.frontend.workspaces
+            .iter()
+            .enumerate()
+            .find_map(|(index,workspace)|{
+                let (window,_) = workspace.windows.iter().find(|(window,_)|window.id() == Some(id))?;
+                Some((index, window.clone()))
+            })
+            .with_context(||format!("no window with id {id}"))?;
+
+        self.switch_workspace(index);
+        self.frontend.space.raise_element(&window, true);
+
+        let focus = window.toplevel().map(|toplevel|toplevel.wl_surface().clone());
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, focus, SERIAL_COUNTER.next_serial());
         }
+        self.mark_dirty();
+
+        Ok(())
     }
 
-    /// handler for [`DrmDeviceNotifier`] event source
-    pub fn drm(event: DrmEvent, node: DrmNode, meta: &mut Option<DrmEventMetadata>, trayle: &mut Trayle) {
-        match event {
-            DrmEvent::VBlank(crtc) => {
-                render::frame_finish(node, crtc, meta, trayle);
-            }
-            DrmEvent::Error(error) => {
-                tracing::error!("{error:?}");
-            }
+    /// show workspace `index`, keyboard focus goes to its last focused window
+    pub fn switch_workspace(&mut self, index: usize) {
+        let frontend = &mut self.frontend;
+        if index >= frontend.workspaces.len() {
+            tracing::warn!("no workspace {}", index + 1);
+            return;
+        }
+        if index == frontend.active_workspace {
+            return;
+        }
+
+        frontend.workspaces[frontend.active_workspace].hide(&mut frontend.space);
+        frontend.active_workspace = index;
+        frontend.workspaces[index].show(&mut frontend.space);
+        self.arrange_all();
+
+        let frontend = &mut self.frontend;
+        let focus = frontend.workspaces[index]
+            .last_focused
+            .as_ref()
+            .filter(|window|window.alive())
+            .and_then(|window|window.toplevel())
+            .map(|toplevel|toplevel.wl_surface().clone());
+
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, focus, SERIAL_COUNTER.next_serial());
         }
     }
-}
 
+    /// move the focused window to workspace `index`
+    pub fn move_to_workspace(&mut self, index: usize) {
+        if index >= self.frontend.workspaces.len() {
+            tracing::warn!("no workspace {}", index + 1);
+            return;
+        }
+        if index == self.frontend.active_workspace {
+            return;
+        }
 
-pub mod render {
-    use device::UdevOutputId;
-    use crate::backend::UdevRenderer;
-    use super::*;
+        let Some(window) = self.focused_window() else {
+            return;
+        };
 
-    pub fn node(
-        node: DrmNode,
-        crtc: Option<crtc::Handle>,
-        frame_target: Time<Monotonic>,
-        trayle: &mut Trayle
-    ) {
-        let device_backend = match trayle.backend.devices.get_mut(&node) {
-            Some(backend) => backend,
-            None => {
-                tracing::error!("trying to render on non-existent backend {node}");
-                return;
-            }
+        let location = self.frontend.space.element_location(&window);
+        let saved = self.frontend.workspace().remove(&window);
+        let Some(location) = location.or(saved) else {
+            return;
         };
 
-        match crtc {
-            Some(crtc) => {
-                self::surface(node, crtc, frame_target, trayle);
-            },
-            None => {
-                let crtcs = device_backend.surfaces.keys().copied().collect::<Vec<_>>();
-                for crtc in crtcs {
-                    self::surface(node, crtc, frame_target, trayle);
-                }
-            },
+        self.frontend.space.unmap_elem(&window);
+        self.frontend.workspaces[index].add(window, location);
+        self.arrange_all();
+
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, None, SERIAL_COUNTER.next_serial());
         }
     }
+}
 
-    pub fn surface(
-        node: DrmNode,
-        crtc: crtc::Handle,
-        frame_target: Time<Monotonic>,
-        trayle: &mut Trayle
-    ) {
-        // let Some(device) = trayle.backend.devices.get_mut(&node) else {
-        //     return; //Err(DeviceError::DeviceUntracked(node));
-        // };
-
-        let Some(output) = trayle.frontend
-            .space
-            .outputs()
-            .find(|o|{
-                o.user_data().get::<UdevOutputId>()==Some(&UdevOutputId { device_id: node, crtc })
-            })
-            .cloned()
-        else {
-            // somehow called with invalid output
+/// layout control
+impl Trayle {
+    /// tile windows of `output` within its usable area
+    pub fn arrange(&mut self, output: &Output) {
+        let Some(area) = utils::output_usable_geometry(&self.frontend.space, output) else {
             return;
         };
+        let frontend = &mut self.frontend;
+        frontend.layout.arrange(&mut frontend.space, output, area);
+    }
 
-        // self.pre_repaint(&output, frame_target);
+    pub fn arrange_all(&mut self) {
+        let outputs = self.frontend.space.outputs().cloned().collect::<Vec<_>>();
+        for output in outputs {
+            self.arrange(&output);
+        }
+    }
 
-        let Some(device) = trayle.backend.devices.get_mut(&node) else {
+    /// toggle focused window between floating and tiled
+    pub fn toggle_floating(&mut self) {
+        let Some(window) = self.focused_window() else {
             return;
         };
 
-        let Some(surface) = device.surfaces.get_mut(&crtc) else {
+        if let Some(output) = self.frontend.layout.remove(&window) {
+            self.arrange(&output);
+            return;
+        }
+
+        let space = &self.frontend.space;
+        let Some(output) = space
+            .outputs_for_element(&window)
+            .into_iter()
+            .next()
+            .or_else(||space.outputs().next().cloned())
+        else {
             return;
         };
 
-        let start = Instant::now();
+        self.frontend.layout.insert(&output, window);
+        self.arrange(&output);
+    }
+}
 
-        /*
-        // TODO: get scale from render surface when supporting HiDPI
-        let frame = trayle
-            .backend
-            .pointer_image
-            .get_image(1 /*scale*/, trayle.frontend.clock.now().into());
-        */
+/// process control
+impl Trayle {
+    /// spawn `command` detached in its own process group, with the session environment
+    ///
+    /// - `WAYLAND_DISPLAY`, and `DISPLAY` when xwayland is ready
+    /// - `XDG_CURRENT_DESKTOP=trayle`
+    /// - variables of the config `[env]` table
+    ///
+    /// the child is reaped once it exits, see [`Trayle::reap_children`]
+    pub fn spawn(&mut self, mut command: Command) -> Result<u32> {
+        command
+            .env("WAYLAND_DISPLAY", &self.frontend.wlsocket)
+            .env("XDG_CURRENT_DESKTOP", "trayle")
+            .envs(&self.config.env)
+            .stdin(Stdio::null())
+            .process_group(0);
+        if let Some(xdisplay) = self.frontend.xdisplay {
+            command.env("DISPLAY", format!(":{xdisplay}"));
+        }
 
-        let render_node = surface.render_node;
-        let primary_gpu = trayle.backend.primary_gpu;
-        let mut renderer = if primary_gpu == render_node {
-            trayle.backend.gpus.single_renderer(&render_node)
-        } else {
-            let format = surface.drm_output.format();
-            trayle.backend.gpus.renderer(&primary_gpu, &render_node, format)
-        }.unwrap();
+        let program = command.get_program().to_owned();
+        let child = command
+            .spawn()
+            .inspect_err(|err|tracing::error!("failed to spawn {program:?}: {err}"))
+            .with_context(||format!("failed to spawn {program:?}"))?;
 
-        /*
-        let pointer_images = &mut self.backend.pointer_images;
-        let pointer_image = pointer_images
-            .iter()
-            .find_map(|(image,texture)|{
-                if image == &frame {
-                    Some(texture.clone())
-                } else {
-                    None
-                }
-            })
-            .unwrap_or_else(||{
-                let buffer = MemoryRenderBuffer::from_slice(
-                    &frame.pixels_rgba,
-                    Fourcc::Argb8888,
-                    (frame.width as i32, frame.height as i32),
-                    1,
-                    Transform::Normal,
-                    None,
-                );
-                pointer_images.push((frame, buffer.clone()));
-                buffer
-            });
-        */
+        let pid = child.id();
+        tracing::debug!(pid, "spawned {program:?}");
+        self.backend.children.push(child);
+        Ok(pid)
+    }
 
-        let result = inner_render_surface(
-            surface,
-            &mut renderer,
-            &trayle.frontend.space,
-            &output,
-            &trayle.config
-        );
+    /// spawn `command` with `sh -c`, see [`Trayle::spawn`]
+    pub fn spawn_shell(&mut self, command: &str) -> Result<u32> {
+        let mut sh = Command::new("sh");
+        sh.arg("-c").arg(command);
+        self.spawn(sh)
+    }
 
-        let reschedule = match result {
-            Ok((has_rendered, states)) => {
-                let dmabuf_feedback = surface.dmabuf_feedback.clone();
-                // self.post_repaint(&output, frame_target, dmabuf_feedback, &states);
-                !has_rendered
+    /// spawn config `exec` commands, and `exec_once` commands on `startup`
+    ///
+    /// a command failing to spawn does not stop the rest
+    pub fn spawn_autostart(&mut self, startup: bool) {
+        let once = match startup {
+            true => self.config.exec_once.clone(),
+            false => vec![],
+        };
+        let commands = once.into_iter().chain(self.config.exec.clone());
+
+        for command in commands {
+            // failure is logged by spawn
+            let _ = self.spawn_shell(&command);
+        }
+    }
+
+    /// wait exited children so they do not linger as zombies, called on `SIGCHLD`
+    pub fn reap_children(&mut self) {
+        self.backend.children.retain_mut(|child|match child.try_wait() {
+            Ok(Some(status)) => {
+                tracing::debug!(pid = child.id(), "child exited with {status}");
+                false
             }
+            Ok(None) => true,
             Err(err) => {
-                tracing::warn!("failed to render: {err:?}");
+                tracing::warn!(pid = child.id(), "failed to wait child: {err}");
                 false
-                // match err {
-                //     SwapBuffersError::AlreadySwapped => false,
-                //     SwapBuffersError::TemporaryFailure(err) => match err.downcast_ref::<DrmError>() {
-                //         Some(DrmError::DeviceInactive) => true,
-                //         Some(DrmError::Access(DrmAccessError { source, .. })) => {
-                //             source.kind() == std::io::ErrorKind::PermissionDenied
-                //         }
-                //         _ => false,
-                //     },
-                //     SwapBuffersError::ContextLost(err) => match err.downcast_ref::<DrmError>() {
-                //         Some(DrmError::TestFailed(_)) => {
-                //             // reset the complete state, disabling all connectors and planes in case we hit a test failed
-                //             // most likely we hit this after a tty switch when a foreign master changed CRTC <-> connector bindings
-                //             // and we run in a mismatch
-                //             device
-                //                 .drm_output_manager
-                //                 .device_mut()
-                //                 .reset_state()
-                //                 .expect("failed to reset drm device");
-                //             true
-                //         }
-                //         _ => panic!("rendering loop lost: {err}"),
-                //     },
-                // }
             }
-        };
+        });
+    }
+}
 
-        if reschedule {
-            let output_refresh = match output.current_mode() {
-                Some(mode) => mode.refresh,
-                None => return,
-            };
+/// each client state
+#[derive(Default)]
+pub struct ClientState {
+    pub compositor_state: CompositorClientState,
+    /// set for clients connected through a socket created with `wp_security_context_v1`,
+    /// eg: by flatpak for a sandboxed app
+    pub security_context: Option<SecurityContext>,
+}
 
-            // if rescheduling, rendering either hit a temporary failure or did not cause any
-            // damage on the output.
-            // in this case, just re-scehdule a repaint after approx.
-            // one frame to re-test for damage
-            let next_frame_target = frame_target + Duration::from_millis(1_000_000 / output_refresh as u64);
-            let reschedule_timeout = Duration::from(
-                next_frame_target).saturating_sub(trayle.clock.now().into()
-            );
+impl ClientState {
+    /// client without a security context, which can see privileged globals, eg: screencopy
+    pub fn is_privileged(client: &Client) -> bool {
+        client.get_data::<ClientState>().is_some_and(|data|data.security_context.is_none())
+    }
+}
 
-            tracing::trace!("reschedule repaint timer with delay {reschedule_timeout:?} on {crtc:?}");
+impl ClientData for ClientState {
+    fn initialized(&self, _client_id: ClientId) { }
+    fn disconnected(&self, _client_id: ClientId, _reason: DisconnectReason) { }
+}
+
+/// connected output, see [`Trayle::list_outputs`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct OutputInfo {
+    pub name: String,
+    /// see [`EdidName`]
+    pub edid_name: Option<String>,
+    pub make: String,
+    pub model: String,
+    pub current_mode: Option<ModeInfo>,
+    pub modes: Vec<ModeInfo>,
+    /// logical position in the global space, `None` if not mapped
+    pub position: Option<(i32, i32)>,
+    pub scale: Option<f64>,
+    /// mapped in the global space
+    pub enabled: bool,
+    /// not powered off by dpms
+    pub powered: bool,
+    pub adaptive_sync: bool,
+    /// reserved for leasing, eg: vr headset
+    pub non_desktop: bool,
+    /// non-desktop connector currently leased to a client
+    pub leased: bool,
+}
+
+/// mapped window, see [`Trayle::list_windows`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct WindowInfo {
+    /// see [`WindowElement::id`]
+    pub id: u64,
+    pub title: Option<String>,
+    pub app_id: Option<String>,
+    /// logical position in the global space
+    pub location: (i32, i32),
+    pub size: (i32, i32),
+    pub focused: bool,
+    /// workspace number, starting from 1
+    pub workspace: usize,
+}
+
+/// output mode, refresh rate in mHz
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct ModeInfo {
+    pub width: i32,
+    pub height: i32,
+    pub refresh: i32,
+}
+
+impl From<WlMode> for ModeInfo {
+    fn from(mode: WlMode) -> Self {
+        Self { width: mode.size.w, height: mode.size.h, refresh: mode.refresh }
+    }
+}
+
+/// each connected drm device
+pub struct DeviceData {
+    pub drm_loop_token: RegistrationToken,
+    pub drm_output_manager: DrmOutputManager<
+        GbmAllocator<DrmDeviceFd>,
+        GbmDevice<DrmDeviceFd>,
+        Option<OutputPresentationFeedback>,
+        DrmDeviceFd,
+    >,
+    pub drm_scanner: DrmScanner,
+    pub gbm: GbmDevice<DrmDeviceFd>,
+    pub non_desktop_connectors: Vec<(connector::Handle, crtc::Handle)>,
+    pub render_node: DrmNode,
+    pub surfaces: HashMap<crtc::Handle, SurfaceData>,
+    pub drm_lease_state: Option<DrmLeaseState>,
+    pub active_leases: Vec<DrmLease>,
+    /// connectors of each active lease, by lease id
+    pub leased_connectors: HashMap<u32, Vec<connector::Handle>>,
+    /// connectors of the lease request being granted, moved to `leased_connectors` once active
+    pub requested_lease: Vec<connector::Handle>,
+    /// connected connectors waiting for a crtc to be released
+    pub pending_connectors: Vec<connector::Handle>,
+    /// parsed edid of connected connectors, removed on disconnect
+    pub monitor_infos: HashMap<connector::Handle, MonitorInfo>,
+}
+
+impl DeviceData {
+    /// `surface` drives a built-in laptop panel, see [`utils::is_internal_panel`]
+    pub fn is_internal_panel(&self, surface: &SurfaceData) -> bool {
+        self.drm_scanner
+            .connectors()
+            .get(&surface.connector)
+            .is_some_and(utils::is_internal_panel)
+    }
+}
+
+/// surface for each [`DeviceData`]
+pub struct SurfaceData {
+    /// output driven by the surface, mapped in the space unless it is being torn down
+    pub output: Output,
+    pub dh: DisplayHandle,
+    pub device_id: DrmNode,
+    pub render_node: DrmNode,
+    pub global: Option<GlobalId>,
+    pub drm_output: DrmOutput<
+        GbmAllocator<DrmDeviceFd>,
+        GbmDevice<DrmDeviceFd>,
+        Option<OutputPresentationFeedback>,
+        DrmDeviceFd,
+    >,
+    pub connector: connector::Handle,
+    pub disable_direct_scanout: bool,
+    pub dmabuf_feedback: Option<SurfaceDmabufFeedback>,
+    /// adaptive sync enabled
+    pub vrr: bool,
+    /// gamma ramp set by client
+    pub gamma: Option<Vec<u16>>,
+    /// screencopy waiting for the next render
+    pub screencopies: Vec<Screencopy>,
+    /// output is powered on
+    pub dpms: bool,
+    /// a queued frame is waiting for its vblank, which schedules the next repaint
+    pub frame_pending: bool,
+    /// repaint for the next frame of an animated cursor
+    pub cursor_timer: Option<RegistrationToken>,
+    /// duration of recent compositor repaints, for predicting the repaint delay
+    pub repaint_durations: VecDeque<Duration>,
+    /// something may have changed on the output since the last repaint, see [`Trayle::mark_dirty`]
+    pub dirty: bool,
+}
+
+impl SurfaceData {
+    /// duration of one frame in the mode being driven, see [`utils::frame_duration`]
+    pub fn frame_duration(&self) -> Option<Duration> {
+        self.drm_output.with_compositor(|compositor|utils::frame_duration(&compositor.surface().pending_mode()))
+    }
+}
+
+impl Drop for SurfaceData {
+    fn drop(&mut self) {
+        // removes wl_output along with its xdg-output
+        if let Some(global) = self.global.take() {
+            self.dh.remove_global::<Trayle>(global);
+        }
+    }
+}
+
+pub mod device {
+    //! `udev` specific handlers
+    use super::render::elements::{OutputRenderElements, WindowRenderElement};
+
+    use super::*;
+
+    /// how long the lease global of a removed device stays disabled before it is destroyed
+    const LEASE_GLOBAL_GRACE: Duration = Duration::from_secs(10);
+
+    /// handler for [`UdevBackend`] event source
+    pub fn handle_udev(event: UdevEvent, _: &mut (), trayle: &mut Trayle) {
+        let result = match event {
+            UdevEvent::Added { device_id, path } => {
+                tracing::info!("device added {path:?}({device_id})");
+                device::added(device_id, &path, trayle)
+            }
+            UdevEvent::Changed { device_id } => {
+                tracing::info!("device changed {device_id}");
+                device::changed(device_id, trayle)
+            }
+            UdevEvent::Removed { device_id } => {
+                tracing::info!("device removed {device_id}");
+                device::removed(device_id, trayle)
+            }
+        };
+
+        if let Err(err) = result {
+            tracing::error!("device error, {err}");
+        }
+    }
+
+    fn added(device_id: u64, path: &Path, trayle: &mut Trayle) -> Result<(), DeviceError> {
+        let node = DrmNode::from_dev_id(device_id)?;
+        let tty = trayle.backend.state.tty_mut().ok_or(DeviceError::NoTty)?;
+
+        let flags = OFlags::RDWR | OFlags::CLOEXEC | OFlags::NOCTTY | OFlags::NONBLOCK;
+        let session = trayle.backend.session.as_mut().ok_or(DeviceError::NoSession)?;
+        let fd = Session::open(session, path, flags)?;
+        let fd = DrmDeviceFd::new(fd.into());
+
+        let (drm,drm_source) = DrmDevice::new(fd.clone(), true)?;
+        let gbm = GbmDevice::new(fd).map_err(DeviceError::GbmDevice)?;
+
+        let drm_loop_token = trayle.handle.insert_source(
+            drm_source, move|e,m,t|callbacks::drm(e,node,m,t)
+        ).unwrap();
+
+        let display = unsafe { EGLDisplay::new(gbm.clone()) }?;
+        let egldevice = EGLDevice::device_for_display(&display)?;
+        let render_node = egldevice.try_get_render_node()?.ok_or(DeviceError::EGLRenderNode)?;
+
+        tty.gpus.as_mut().add_node(render_node, gbm.clone())?;
+
+        let color_formats = match trayle.config.disable_direct_10bit {
+            true => SUPPORTED_FORMATS_8BIT_ONLY,
+            false => SUPPORTED_FORMATS,
+        };
+        let gbm_buffer_flags = GbmBufferFlags::RENDERING | GbmBufferFlags::SCANOUT;
+        let allocator = GbmAllocator::new(gbm.clone(), gbm_buffer_flags);
+        let mut renderer = tty.gpus.single_renderer(&render_node).expect("failed to get renderer");
+        let render_formats = renderer.as_mut().egl_context().dmabuf_render_formats().clone();
+
+        let drm_output_manager = DrmOutputManager::new(
+            drm,
+            allocator,
+            gbm.clone(),
+            Some(gbm.clone()),
+            color_formats.iter().copied(),
+            render_formats
+        );
+
+        if let Some((mut retired,token)) = tty.retired_lease_states.remove(&node) {
+            trayle.handle.remove(token);
+            retired.remove_global::<Trayle>();
+        }
+        if tty.lease_fallback.is_none() {
+            match DrmLeaseState::new_with_filter::<Trayle,_>(&trayle.dh, &node, |_|false) {
+                Ok(ok) => tty.lease_fallback = Some(ok),
+                Err(err) => tracing::warn!("failed to setup inert drm lease global: {err:?}"),
+            }
+        }
+        let drm_lease_state = match tty.lease_fallback.is_some() {
+            true => match DrmLeaseState::new::<Trayle>(&trayle.dh, &node) {
+                Ok(ok) => Some(ok),
+                Err(err) => {
+                    tracing::warn!("failed to setup drm lease global for {node}: {err:?}");
+                    None
+                },
+            },
+            false => None,
+        };
+
+        let device_data = DeviceData {
+            drm_loop_token,
+            render_node,
+            drm_output_manager,
+            drm_scanner: DrmScanner::new(),
+            gbm,
+            surfaces: HashMap::new(),
+            // known crtcs
+            drm_lease_state,
+            active_leases: vec![],
+            leased_connectors: HashMap::new(),
+            requested_lease: vec![],
+            pending_connectors: Vec::new(),
+            monitor_infos: HashMap::new(),
+            non_desktop_connectors: vec![],
+        };
+
+        assert!(tty.devices.insert(node, device_data).is_none());
+        device::changed(device_id, trayle)
+    }
+
+    fn changed(device_id: u64, trayle: &mut Trayle) -> Result<(), DeviceError> {
+        let node = DrmNode::from_dev_id(device_id)?;
+        let Some(device) = trayle.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) else {
+            return Err(DeviceError::DeviceUntracked(node));
+        };
+
+        let mut scan_result = device
+            .drm_scanner
+            .scan_connectors(device.drm_output_manager.device())
+            .map_err(DeviceError::ScanConnector)?;
+
+        // connectors disabled by config are never set up, their crtc goes to the next connector
+        let connected = scan_result.connected.iter().map(|(conn,_)|conn.clone()).collect::<Vec<_>>();
+        for connector in connected {
+            let name = utils::connector_name(&connector);
+            let edid_name = device
+                .monitor_infos
+                .entry(connector.handle())
+                .or_insert_with(||MonitorInfo::parse(device.drm_output_manager.device(), connector.handle()))
+                .edid_name();
+            if !trayle.config.output_disabled(&name, edid_name.as_ref()) {
+                continue;
+            }
+
+            tracing::info!("{name} is disabled by config");
+            scan_result.connected.retain(|(conn,_)|conn.handle() != connector.handle());
+            let released = device.drm_scanner.set_enabled(device.drm_output_manager.device(), connector.handle(), false);
+            scan_result.connected.extend(released.connected);
+        }
+
+        device::apply_scan(node, scan_result, trayle)?;
+
+        // fixup window coordinates
+        let frontend = &mut trayle.frontend;
+        crate::shell::utils::fixup_positions(&mut frontend.space, &mut frontend.workspaces);
+        trayle.update_output_management();
+
+        Ok(())
+    }
+
+    /// enable or disable `connector`, see [`DrmScanner::set_enabled`]
+    pub fn set_connector_enabled(
+        node: DrmNode,
+        connector: connector::Handle,
+        enabled: bool,
+        trayle: &mut Trayle,
+    ) -> Result<(), DeviceError> {
+        let Some(device) = trayle.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) else {
+            return Err(DeviceError::DeviceUntracked(node));
+        };
+
+        // turn the crtc off, dropping the surface alone leaves the last frame on screen
+        let surface = device.drm_scanner
+            .crtc_for_connector(&connector)
+            .and_then(|crtc|device.surfaces.get_mut(&crtc));
+        if let Some(surface) = surface.filter(|_|!enabled) {
+            if let Err(err) = surface.drm_output.with_compositor(|compositor|compositor.clear()) {
+                tracing::warn!("failed to turn off disabled connector: {err:?}");
+            }
+        }
+
+        let scan_result = device.drm_scanner.set_enabled(device.drm_output_manager.device(), connector, enabled);
+        device::apply_scan(node, scan_result, trayle)?;
+
+        let frontend = &mut trayle.frontend;
+        crate::shell::utils::fixup_positions(&mut frontend.space, &mut frontend.workspaces);
+        trayle.update_output_management();
+
+        Ok(())
+    }
+
+    /// set up and tear down connectors reported by the [`DrmScanner`]
+    fn apply_scan(node: DrmNode, scan_result: DrmScanResult, trayle: &mut Trayle) -> Result<(), DeviceError> {
+        for event in scan_result {
+            match event {
+                DrmScanEvent::Connected { connector, crtc } => {
+                    if let Some(device) = trayle.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) {
+                        device.pending_connectors.retain(|pending|pending != &connector.handle());
+                    }
+                    device::connector_connected(node, connector, crtc, trayle)?;
+                },
+                DrmScanEvent::ConnectedNoCrtc { connector } => {
+                    tracing::warn!(
+                        "{} connected without an available crtc, waiting for one to be released",
+                        utils::connector_name(&connector),
+                    );
+                    if let Some(device) = trayle.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) {
+                        device.pending_connectors.push(connector.handle());
+                    }
+                },
+                DrmScanEvent::Disconnected { connector, crtc } => {
+                    if let Some(device) = trayle.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) {
+                        device.pending_connectors.retain(|pending|pending != &connector.handle());
+                        device.monitor_infos.remove(&connector.handle());
+                    }
+                    if let Some(crtc) = crtc {
+                        device::connector_disconnected(node, connector, crtc, trayle)?
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn removed(device_id: u64, trayle: &mut Trayle) -> Result<(), DeviceError> {
+        let node = DrmNode::from_dev_id(device_id)?;
+        let Some(device) = trayle.backend.state.tty_mut().and_then(|tty|tty.devices.get_mut(&node)) else {
+            return Err(DeviceError::DeviceUntracked(node));
+        };
+
+        let crtcs = device
+            .drm_scanner
+            .crtcs()
+            .map(|(info, crtc)| (info.clone(), crtc))
+            .collect::<Vec<_>>();
+
+        for (connector,crtc) in crtcs {
+            if let Err(err) = device::connector_disconnected(node, connector, crtc, trayle) {
+                tracing::error!("{err}");
+            }
+        }
+
+        let tty = trayle.backend.state.tty_mut().ok_or(DeviceError::NoTty)?;
+        if let Some(mut device) = tty.devices.remove(&node) {
+            if let Some(mut leasing_global) = device.drm_lease_state.take() {
+                leasing_global.disable_global::<Trayle>();
+                // give clients time to notice the disabled global before it is destroyed
+                let timer = Timer::from_duration(LEASE_GLOBAL_GRACE);
+                let token = trayle.handle.insert_source(timer, move|_,_,trayle|{
+                    let retired = trayle.backend.state.tty_mut().and_then(|tty|tty.retired_lease_states.remove(&node));
+                    if let Some((mut retired,_)) = retired {
+                        retired.remove_global::<Trayle>();
+                    }
+                    TimeoutAction::Drop
+                });
+                match token {
+                    Ok(token) => {
+                        tty.retired_lease_states.insert(node, (leasing_global, token));
+                    },
+                    Err(err) => {
+                        tracing::warn!("failed to insert lease global timer, removing it now: {err}");
+                        leasing_global.remove_global::<Trayle>();
+                    },
+                }
+            }
+
+            tty.gpus.as_mut().remove_node(&device.render_node);
+
+            trayle.handle.remove(device.drm_loop_token);
+        }
+
+        // fixup position
+        let frontend = &mut trayle.frontend;
+        crate::shell::utils::fixup_positions(&mut frontend.space, &mut frontend.workspaces);
+        trayle.update_output_management();
+
+        Ok(())
+    }
+
+    fn connector_connected(
+        node: DrmNode,
+        connector: connector::Info,
+        crtc: crtc::Handle,
+        trayle: &mut Trayle,
+    ) -> Result<(), DeviceError> {
+        let tty = trayle.backend.state.tty_mut().ok_or(DeviceError::NoTty)?;
+        let Some(device) = tty.devices.get_mut(&node) else {
+            return Err(DeviceError::DeviceUntracked(node));
+        };
+
+        let mut renderer = tty.gpus
+            .single_renderer(&device.render_node)
+            .expect("failed to get renderer");
+
+        let output_name = utils::connector_name(&connector);
+        tracing::info!(?crtc,"setting up connector {output_name}");
+
+        let drm_device = device.drm_output_manager.device();
+
+        let non_desktop = drm_device
+            .get_properties(connector.handle())
+            .ok()
+            .and_then(|props|{
+                let (info,value) = props
+                    .into_iter()
+                    .filter_map(|(handle,value)|Some((drm_device.get_property(handle).ok()?,value)))
+                    .find(|(info,_)|info.name().to_str()==Ok("non-desktop"))?;
+                info.value_type().convert_value(value).as_boolean()
+            })
+            .unwrap_or(false);
+
+        let monitor_info = device
+            .monitor_infos
+            .entry(connector.handle())
+            .or_insert_with(||MonitorInfo::parse(drm_device, connector.handle()))
+            .clone();
+
+        let make = monitor_info.make();
+        let model = monitor_info.model();
+
+        let leasable = non_desktop
+            && trayle.config.output_leasable(&output_name, monitor_info.edid_name().as_ref());
+        if non_desktop && !leasable {
+            tracing::info!("connector {output_name} is non-desktop but not leasable, using it as an output");
+        }
+
+        if leasable {
+            tracing::info!(
+                "connector {output_name} is non-desktop, setting up for leasing",
+            );
+
+            device.non_desktop_connectors.push((connector.handle(), crtc));
+            if let Some(lease_state) = device.drm_lease_state.as_mut() {
+                lease_state.add_connector::<Trayle>(
+                    connector.handle(), output_name, format!("{make} {model}")
+                );
+            }
+
+            return Ok(());
+        }
+
+        let (phys_w, phys_h) = connector.size().unwrap_or((0,0));
+        let physical = PhysicalProperties {
+            size: (phys_w as i32,phys_h as i32).into(),
+            subpixel: connector.subpixel().into(), make, model
+        };
+        let output = Output::new(output_name.clone(), physical);
+        if let Some(edid_name) = monitor_info.edid_name() {
+            tracing::info!("{output_name} is {:?}", edid_name.0);
+            output.user_data().insert_if_missing(||edid_name);
+        }
+
+        let output_config = trayle.config.output(&output).cloned().unwrap_or_default();
+
+        let preferred_mode_id = connector
+            .modes()
+            .iter()
+            .position(|mode| mode.mode_type().contains(ModeTypeFlags::PREFERRED))
+            .unwrap_or(0);
+
+        let mode_id = match output_config.mode {
+            Some(wanted) => utils::find_mode(connector.modes(), wanted).unwrap_or_else(||{
+                tracing::warn!("mode {wanted:?} is not available on {output_name}, using preferred mode");
+                preferred_mode_id
+            }),
+            None => preferred_mode_id,
+        };
+
+        let drm_mode = connector.modes()[mode_id];
+        let wl_mode = WlMode::from(drm_mode);
+        tracing::info!(
+            "using mode {}x{}@{:.3} on {output_name}",
+            wl_mode.size.w, wl_mode.size.h, wl_mode.refresh as f64 / 1000.0,
+        );
+
+        let global = output.create_global::<Trayle>(&trayle.dh);
+
+        // unconfigured outputs are stacked left to right
+        let position = output_config.position.unwrap_or_else(||{
+            let x = trayle.frontend.space
+                .outputs()
+                .filter_map(|o|trayle.frontend.space.output_geometry(o))
+                .fold(0, |acc, geo|acc.max(geo.loc.x + geo.size.w));
+            (x, 0).into()
+        });
+
+        for mode in connector.modes() {
+            output.add_mode(WlMode::from(*mode));
+        }
+        output.set_preferred(wl_mode);
+        output.change_current_state(
+            Some(wl_mode),
+            output_config.transform,
+            output_config.scale.map(OutputScale::Fractional),
+            None,
+        );
+        utils::map_output(&mut trayle.frontend.space, &output, position);
+
+        output.user_data().insert_if_missing(||UdevOutputId { crtc, device_id: node });
+
+        let driver = drm_device.get_driver().map_err(DeviceError::DrmDriver)?;
+        let mut planes = drm_device.planes(&crtc)?;
+
+        // nvidia moment
+        if driver.name().to_string_lossy().to_lowercase().contains("nvidia") ||
+            driver.description().to_string_lossy().to_lowercase().contains("nvidia")
+        {
+            planes.overlay.clear();
+        }
+
+        let drm_output = device.drm_output_manager
+            .initialize_output::<_, OutputRenderElements<UdevRenderer<'_>, WindowRenderElement<UdevRenderer<'_>>>>(
+                crtc,
+                drm_mode,
+                &[connector.handle()],
+                &output,
+                Some(planes),
+                &mut renderer,
+                &DrmOutputRenderElements::default()
+            )
+            .map_err(DeviceError::drm_output)?;
+
+        let disable_direct_scanout = trayle.backend.disable_direct_scanout;
+
+        let dmabuf_feedback = drm_output.with_compositor(|compositor|{
+            compositor.set_debug_flags(trayle.backend.debug_flags);
+
+            utils::get_surface_dmabuf_feedback(
+                tty.primary_gpu,
+                device.render_node,
+                &mut tty.gpus,
+                compositor.surface(),
+            )
+        });
+
+        let mut surface = SurfaceData {
+            output: output.clone(),
+            dh: trayle.dh.clone(),
+            device_id: node,
+            render_node: device.render_node,
+            global: Some(global),
+            drm_output,
+            connector: connector.handle(),
+            disable_direct_scanout,
+            dmabuf_feedback,
+            vrr: false,
+            gamma: None,
+            screencopies: Vec::new(),
+            dpms: true,
+            frame_pending: false,
+            cursor_timer: None,
+            repaint_durations: VecDeque::with_capacity(utils::REPAINT_HISTORY),
+            dirty: true,
+        };
+
+        if output_config.vrr == Some(true) {
+            match utils::set_vrr(&mut surface, true) {
+                Ok(()) => tracing::info!("adaptive sync enabled on {}", output.name()),
+                Err(err) => tracing::warn!("failed to enable adaptive sync on {}: {err}", output.name()),
+            }
+        }
+
+        let primary_gpu = tty.primary_gpu;
+        match primary_gpu == device.render_node {
+            true => tracing::info!("{output_name} is rendered on {primary_gpu}"),
+            false => tracing::info!("{output_name} is rendered on {primary_gpu} and copied to {}", device.render_node),
+        }
+
+        device.surfaces.insert(crtc, surface);
+        trayle.arrange(&output);
+        trayle.mark_dirty();
+
+        // kick-off rendering
+        trayle.handle.insert_idle(move|trayle|{
+            render::node(node, Some(crtc), trayle.clock.now(), trayle);
+        });
+
+        Ok(())
+    }
+
+    fn connector_disconnected(
+        node: DrmNode,
+        connector: connector::Info,
+        crtc: crtc::Handle,
+        trayle: &mut Trayle,
+    ) -> Result<(), DeviceError> {
+        let tty = trayle.backend.state.tty_mut().ok_or(DeviceError::NoTty)?;
+        let Some(device) = tty.devices.get_mut(&node) else {
+            return Err(DeviceError::DeviceUntracked(node));
+        };
+
+        if let Some(pos) = device
+            .non_desktop_connectors
+            .iter()
+            .position(|(handle,_)|*handle==connector.handle())
+        {
+            let _ = device.non_desktop_connectors.remove(pos);
+            if let Some(leasing_state) = device.drm_lease_state.as_mut() {
+                leasing_state.withdraw_connector(connector.handle());
+            }
+        } else {
+            device.surfaces.remove(&crtc);
+
+            let output = trayle.frontend.space
+                .outputs()
+                .find(|o|{
+                    o.user_data()
+                        .get::<UdevOutputId>()
+                        .map(|id|id.device_id == node && id.crtc == crtc)
+                        .unwrap_or(false)
+                })
+                .cloned();
+
+            if let Some(output) = output {
+                trayle.frontend.gamma_control_manager_state.output_removed(&output);
+                #[cfg(feature = "screencast")]
+                trayle.backend.casts.retain(|cast|cast.output != output);
+                trayle.frontend.layout.remove_output(&output);
+                trayle.frontend.space.unmap_output(&output);
+            }
+        }
+
+        let mut renderer = tty.gpus.single_renderer(&device.render_node).unwrap();
+
+        let _ = device.drm_output_manager.try_to_restore_modifiers::<_, OutputRenderElements<
+            UdevRenderer<'_>,
+            WindowRenderElement<UdevRenderer<'_>>,
+        >>(
+            &mut renderer,
+            // FIXME: For a flicker free operation we should return the actual elements for this output..
+            // Instead we just use black to "simulate" a modeset :)
+            &DrmOutputRenderElements::default(),
+        );
+
+        // windows on the removed output may now be shown elsewhere
+        trayle.mark_dirty();
+
+        Ok(())
+    }
+
+    #[derive(Debug, PartialEq)]
+    pub struct UdevOutputId {
+        pub device_id: DrmNode,
+        pub crtc: crtc::Handle,
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    pub enum DeviceError {
+        #[error("failed to create drm node: {0}")]
+        DrmNode(#[from] CreateDrmNodeError),
+        #[error("failed to open device via libseat: {0}")]
+        LibSeat(#[from] libseat::Error),
+        #[error("drm device error: {0}")]
+        DrmDevice(#[from] DrmError),
+        #[error("failed to setup gbm device: {0}")]
+        GbmDevice(std::io::Error),
+        #[error("failed to setup egl: {0}")]
+        EGLError(#[from] egl::Error),
+        #[error("failed to get render node for current egl")]
+        EGLRenderNode,
+        #[error("no session to open the device with")]
+        NoSession,
+        #[error("not running on a tty")]
+        NoTty,
+        #[error("device {0} untracked")]
+        DeviceUntracked(DrmNode),
+        #[error("failed to scan connectors: {0}")]
+        ScanConnector(std::io::Error),
+        #[error("failed to query drm driver: {0}")]
+        DrmDriver(std::io::Error),
+        #[error("failed to setup drm output")]
+        DrmOutput(String/* the error type is complicated */),
+    }
+
+    impl DeviceError {
+        fn drm_output(error: impl std::error::Error) -> Self {
+            Self::DrmOutput(error.to_string())
+        }
+    }
+}
+
+pub mod callbacks {
+    //! handlers for various smithay event sources handler
+    use super::*;
+
+    type IoDisplay = NoIoDrop<Display<Trayle>>;
+    type IoPostAction = std::io::Result<PostAction>;
+
+    /// handler for [`Generic<Display>`] event source, see [`DisplaySource`]
+    ///
+    /// replies are flushed by [`Trayle::refresh`] in the same loop iteration, along with events
+    /// queued by other sources, a writable wakeup has it retry clients with a full socket
+    pub fn display(readiness: Readiness, display: &mut IoDisplay, trayle: &mut Trayle) -> IoPostAction {
+        if readiness.readable {
+            // SAFETY: we dont drop the display
+            let display = unsafe { display.get_mut() };
+            if let Err(err) = display.dispatch_clients(trayle) {
+                tracing::error!("failed to dispatch clients: {err}");
+            }
+        }
+        Ok(PostAction::Continue)
+    }
+
+    /// handler for [`LibinputInputBackend`] event source
+    pub fn input(mut event: InputEvent, _: &mut (), trayle: &mut Trayle) {
+        use smithay::{
+            reexports::input::DeviceCapability,
+            wayland::tablet_manager::{TabletDescriptor, TabletSeatTrait},
+        };
+
+        match &mut event {
+            InputEvent::DeviceAdded { device } => {
+                if device.has_capability(DeviceCapability::Keyboard) {
+                    if let Some(keyboard) = trayle.seat.get_keyboard() {
+                        // make sure hot-added keyboard uses the configured layout
+                        let xkb = trayle.config.xkb.clone();
+                        if let Err(err) = keyboard.set_xkb_config(trayle, xkb.xkb_config()) {
+                            tracing::error!("invalid xkb config, keeping current layout: {err}");
+                        }
+                        device.led_update(keyboard.led_state().into());
+                    };
+                    trayle.backend.keyboards.push(device.clone());
+                }
+                if device.has_capability(DeviceCapability::Pointer) {
+                    self::configure_pointer(device, &trayle.config);
+                }
+                if device.has_capability(DeviceCapability::TabletTool) {
+                    let descriptor = TabletDescriptor::from(&*device);
+                    trayle.seat.tablet_seat().add_tablet::<Trayle>(&trayle.dh, &descriptor);
+                }
+            }
+            InputEvent::DeviceRemoved { device } => {
+                if device.has_capability(DeviceCapability::Keyboard) {
+                    trayle.backend.keyboards.retain(|kb|kb!=device);
+                }
+                if device.has_capability(DeviceCapability::TabletTool) {
+                    let tablet_seat = trayle.seat.tablet_seat();
+                    tablet_seat.remove_tablet(&TabletDescriptor::from(&*device));
+                    // tools are not tied to a tablet, they are gone along with the last one
+                    if tablet_seat.count_tablets() == 0 {
+                        tablet_seat.clear_tools();
+                    }
+                }
+            }
+            _ => self::activity(trayle),
+        }
+
+        input::handle(event, trayle);
+    }
+
+    /// apply the [`InputConfig`] of a hot-added pointer, touchpads are the ones that can tap
+    ///
+    /// settings the device does not support are skipped with a warning
+    ///
+    /// [`InputConfig`]: crate::config::InputConfig
+    fn configure_pointer(device: &mut smithay::reexports::input::Device, config: &Config) {
+        use smithay::reexports::input::{AccelProfile, ClickMethod, DeviceConfigResult};
+
+        let name = device.name().to_owned();
+        let touchpad = device.config_tap_finger_count() > 0;
+        let input = config.input(&name, touchpad);
+        let check = |setting: &str, result: DeviceConfigResult|{
+            if let Err(err) = result {
+                tracing::warn!("failed to set {setting} of input device {name:?}: {err:?}");
+            }
+        };
+
+        if let Some(tap) = input.tap {
+            check("tap", device.config_tap_set_enabled(tap));
+        }
+        if let Some(natural_scroll) = input.natural_scroll {
+            check("natural_scroll", device.config_scroll_set_natural_scroll_enabled(natural_scroll));
+        }
+        if let Some(dwt) = input.disable_while_typing {
+            check("disable_while_typing", device.config_dwt_set_enabled(dwt));
+        }
+        if let Some(method) = input.click_method {
+            let method = match method {
+                crate::config::ClickMethod::ButtonAreas => ClickMethod::ButtonAreas,
+                crate::config::ClickMethod::Clickfinger => ClickMethod::Clickfinger,
+            };
+            check("click_method", device.config_click_set_method(method));
+        }
+        if let Some(profile) = input.accel_profile {
+            let profile = match profile {
+                crate::config::AccelProfile::Flat => AccelProfile::Flat,
+                crate::config::AccelProfile::Adaptive => AccelProfile::Adaptive,
+            };
+            check("accel_profile", device.config_accel_set_profile(profile));
+        }
+        if let Some(speed) = input.accel_speed {
+            check("accel_speed", device.config_accel_set_speed(speed));
+        }
+    }
+
+    /// any user input resets idle timers, and may move the cursor or windows
+    pub fn activity(trayle: &mut Trayle) {
+        trayle.mark_dirty();
+        trayle.frontend.idle_notifier_state.notify_activity(&trayle.seat);
+        trayle.backend.last_input = Instant::now();
+        trayle.set_all_outputs_dpms(true);
+    }
+
+    /// handler for [`WinitEventLoop`] event source of the nested backend
+    ///
+    /// [`WinitEventLoop`]: smithay::backend::winit::WinitEventLoop
+    pub fn winit(event: WinitEvent, _: &mut (), trayle: &mut Trayle) {
+        use smithay::backend::input::{AbsolutePositionEvent, Event as _};
+
+        match event {
+            WinitEvent::Resized { size, .. } => {
+                let Some(winit) = trayle.backend.state.downcast_ref::<Winit>() else {
+                    return;
+                };
+                let output = winit.output.clone();
+                let mode = WlMode { size, refresh: 60_000 };
+                output.set_preferred(mode);
+                output.change_current_state(Some(mode), None, None, None);
+                trayle.arrange(&output);
+                trayle.update_output_management();
+            }
+            // the window is not rotated along with the output transform, unlike a panel
+            WinitEvent::Input(smithay::backend::input::InputEvent::PointerMotionAbsolute { event }) => {
+                self::activity(trayle);
+                let Some(geo) = trayle.backend.state
+                    .downcast_ref::<Winit>()
+                    .and_then(|winit|trayle.frontend.space.output_geometry(&winit.output))
+                else {
+                    return;
+                };
+                let location = event.position_transformed(geo.size) + geo.loc.to_f64();
+                input::pointer_motion_to(location, event.time_msec(), trayle);
+            }
+            WinitEvent::Input(event) => {
+                self::activity(trayle);
+                input::handle(event, trayle);
+            }
+            WinitEvent::Redraw => {
+                for output in trayle.backend.state.outputs() {
+                    render::output(&output, trayle.clock.now(), trayle);
+                }
+            }
+            WinitEvent::CloseRequested => trayle.signal.stop(),
+            WinitEvent::Focus(_) => {}
+        }
+    }
+
+    /// handler for [`ListeningSocketSource`] event source
+    ///
+    /// [`ListeningSocketSource`]: smithay::wayland::socket::ListeningSocketSource
+    pub fn socket(stream: UnixStream, _: &mut (), trayle: &mut Trayle) {
+        let state = Arc::new(ClientState::default());
+        match trayle.dh.insert_client(stream, state) {
+            Ok(_client) => {}
+            Err(err) => tracing::warn!("failed to add wayland client: {err}"),
+        };
+    }
+
+    /// handler for `SIGTERM` and `SIGINT` [`Signals`] event source
+    pub fn terminate(event: signals::Event, _: &mut (), trayle: &mut Trayle) {
+        tracing::info!("received {:?}, shutting down", event.signal());
+        trayle.signal.stop();
+    }
+
+    /// handler for `SIGCHLD` [`Signals`] event source
+    pub fn sigchld(_: signals::Event, _: &mut (), trayle: &mut Trayle) {
+        trayle.reap_children();
+    }
+
+    /// handler for [`Generic<IpcListener>`] event source
+    pub fn ipc<R>(_: R, listener: &mut NoIoDrop<IpcListener>, trayle: &mut Trayle) -> IoPostAction {
+        loop {
+            match listener.accept() {
+                Ok(Some(stream)) => {
+                    if let Err(err) = trayle.handle.insert_source(IpcSource::new(stream), ipc_client) {
+                        tracing::warn!("failed to add ipc client: {}", err.error);
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::warn!("failed to accept ipc client: {err}");
+                    break;
+                }
+            }
+        }
+        Ok(PostAction::Continue)
+    }
+
+    /// handler for [`IpcSource`] event source, one per ipc client
+    fn ipc_client(readiness: Readiness, stream: &mut IpcStream, trayle: &mut Trayle) -> IoPostAction {
+        if readiness.writable {
+            if let Err(err) = stream.flush() {
+                tracing::warn!("failed to reply ipc client: {err}");
+                return Ok(PostAction::Remove);
+            }
+        }
+        if !readiness.readable {
+            return Ok(PostAction::Continue);
+        }
+
+        let lines = match stream.read_lines() {
+            Ok(Some(lines)) => lines,
+            Ok(None) => return Ok(PostAction::Remove),
+            Err(err) => {
+                tracing::warn!("failed to read ipc client: {err}");
+                return Ok(PostAction::Remove);
+            }
+        };
+
+        for line in lines {
+            let result = serde_json::from_str::<ipc::Request>(&line)
+                .context("invalid request")
+                .and_then(|request|ipc_request(request, trayle));
+            if let Err(err) = stream.reply(result) {
+                tracing::warn!("failed to reply ipc client: {err}");
+                return Ok(PostAction::Remove);
+            }
+        }
+
+        Ok(PostAction::Continue)
+    }
+
+    fn ipc_request(request: ipc::Request, trayle: &mut Trayle) -> Result<serde_json::Value> {
+        tracing::debug!(?request, "ipc request");
+        match request {
+            ipc::Request::Spawn { args } => {
+                let Some((program, args)) = args.split_first() else {
+                    anyhow::bail!("spawn requires a program");
+                };
+                let mut command = Command::new(program);
+                command.args(args);
+                let pid = trayle.spawn(command)?;
+                Ok(serde_json::json!({ "pid": pid }))
+            }
+            ipc::Request::ListOutputs => Ok(serde_json::to_value(trayle.list_outputs())?),
+            ipc::Request::ListWindows => Ok(serde_json::to_value(trayle.list_windows())?),
+            ipc::Request::FocusWindow { id } => {
+                trayle.focus_window(id)?;
+                Ok(serde_json::Value::Null)
+            }
+            ipc::Request::SetOutputEnabled { name, enabled } => {
+                trayle.set_output_enabled(&name, enabled)?;
+                Ok(serde_json::Value::Null)
+            }
+            ipc::Request::Quit => {
+                tracing::info!("shutting down");
+                trayle.signal.stop();
+                Ok(serde_json::Value::Null)
+            }
+        }
+    }
+
+    /// handler for [`LibSeatSessionNotifier`] event source
+    ///
+    /// [`LibSeatSessionNotifier`]: smithay::backend::session::libseat::LibSeatSessionNotifier
+    pub fn session(event: SessionEvent, _: &mut (), trayle: &mut Trayle) {
+        match event {
+            SessionEvent::PauseSession => {
+                tracing::info!("session pause");
+                if let Some(input) = trayle.backend.input.as_mut() {
+                    input.suspend();
+                }
+                for backend in trayle.backend.state.tty_mut().into_iter().flat_map(|tty|tty.devices.values_mut()) {
+                    backend.drm_output_manager.pause();
+                    backend.active_leases.clear();
+                    backend.leased_connectors.clear();
+                    if let Some(lease_global) = backend.drm_lease_state.as_mut() {
+                        lease_global.suspend();
+                    }
+                }
+            }
+            SessionEvent::ActivateSession => {
+                tracing::info!("session resume");
+                trayle.mark_dirty();
+                if let Some(Err(err)) = trayle.backend.input.as_mut().map(|input|input.resume()) {
+                    tracing::error!("failed to resume libinput context: {err:?}");
+                }
+                for (&node, backend) in trayle.backend.state.tty_mut().into_iter().flat_map(|tty|&mut tty.devices) {
+                    // if we do not care about flicking (caused by modesetting) we could just
+                    // pass true for disable connectors here. this would make sure our drm
+                    // device is in a known state (all connectors and planes disabled).
+                    // but for demonstration we choose a more optimistic path by leaving the
+                    // state as is and assume it will just work. If this assumption fails
+                    // we will try to reset the state when trying to queue a frame.
+                    backend.drm_output_manager
+                        .activate(false /* disable connectors */)
+                        .expect("failed to activate drm backend");
+                    if let Some(lease_global) = backend.drm_lease_state.as_mut() {
+                        lease_global.resume::<Trayle>();
+                    }
+                    // gamma ramps are reset by whoever owned the drm device meanwhile
+                    for (&crtc, surface) in backend.surfaces.iter() {
+                        let Some(ramp) = surface.gamma.as_deref() else { continue };
+                        if let Err(err) = utils::set_gamma(backend.drm_output_manager.device(), crtc, Some(ramp)) {
+                            tracing::warn!("failed to restore gamma: {err:#}");
+                        }
+                    }
+                    trayle.handle.insert_idle(move|trayle|render::node(node, None, trayle.clock.now(), trayle));
+                }
+            }
+        }
+    }
+
+    /// handler for [`DrmDeviceNotifier`] event source
+    pub fn drm(event: DrmEvent, node: DrmNode, meta: &mut Option<DrmEventMetadata>, trayle: &mut Trayle) {
+        match event {
+            DrmEvent::VBlank(crtc) => {
+                render::frame_finish(node, crtc, meta, trayle);
+            }
+            DrmEvent::Error(error) => {
+                tracing::error!("{error:?}");
+            }
+        }
+    }
+}
+
+
+pub mod render {
+    use device::UdevOutputId;
+    use elements::CustomRenderElements;
+    use crate::{backend::UdevRenderer, cursor::Cursor, drawing::PointerElement};
+    use smithay::{
+        backend::{
+            drm::compositor::RenderFrameError,
+            renderer::{
+                damage::{Error as OutputDamageTrackerError, OutputDamageTracker},
+                element::utils::Relocate,
+                gles::GlesRenderbuffer,
+                Bind, Color32F, ExportMem, Offscreen,
+            },
+        },
+        utils::{Rectangle, Size},
+        wayland::{dmabuf::get_dmabuf, shm},
+    };
+    use xcursor::parser::Image;
+    use super::*;
+
+    /// element states of a submitted frame, with the dmabuf feedback of its output
+    pub type Submitted = (RenderElementStates, Option<SurfaceDmabufFeedback>);
+
+    /// what a backend repaints an output with, borrowed from [`Trayle`] by [`render::output`]
+    ///
+    /// [`render::output`]: self::output
+    pub struct RenderContext<'a> {
+        /// presentation time the frame is rendered for
+        pub frame_target: Time<Monotonic>,
+        pub now: Time<Monotonic>,
+        pub handle: &'a LoopHandle<'static, Trayle>,
+        pub config: &'a Config,
+        pub space: &'a Space<WindowElement>,
+        pub wallpaper: Option<Wallpaper>,
+        pub pointer_location: Point<f64, Logical>,
+        pub cursor_status: &'a mut CursorImageStatus,
+        pub dnd_icon: Option<&'a DndIcon>,
+        pub pointer_image: &'a mut Cursor,
+        pub pointer_images: &'a mut Vec<(Image, MemoryRenderBuffer)>,
+        pub pointer_element: &'a mut PointerElement,
+    }
+
+    /// repaint `output` with the backend in use, then answer the surfaces shown on it
+    pub fn output(output: &Output, frame_target: Time<Monotonic>, trayle: &mut Trayle) {
+        // disabled, or not arranged yet
+        if trayle.frontend.space.output_geometry(output).is_none() {
+            return;
+        }
+
+        self::pre_repaint(output, frame_target, trayle);
+
+        let ctx = RenderContext {
+            frame_target,
+            now: trayle.clock.now(),
+            handle: &trayle.handle,
+            config: &trayle.config,
+            space: &trayle.frontend.space,
+            wallpaper: trayle.backend.wallpapers.get(&trayle.config, output),
+            pointer_location: trayle.pointer.current_location(),
+            cursor_status: &mut trayle.frontend.cursor_status,
+            dnd_icon: trayle.frontend.dnd_icon.as_ref(),
+            pointer_image: &mut trayle.backend.pointer_image,
+            pointer_images: &mut trayle.backend.pointer_images,
+            pointer_element: &mut trayle.backend.pointer_element,
+        };
+        let Some((states, dmabuf_feedback)) = trayle.backend.state.render(output, ctx) else {
+            return;
+        };
+
+        self::post_repaint(output, frame_target, dmabuf_feedback.as_ref(), &states, trayle);
+    }
+
+    /// repaint outputs of the drm device `node`, or only the one on `crtc`
+    pub fn node(
+        node: DrmNode,
+        crtc: Option<crtc::Handle>,
+        frame_target: Time<Monotonic>,
+        trayle: &mut Trayle
+    ) {
+        let Some(device) = trayle.backend.state.tty().and_then(|tty|tty.devices.get(&node)) else {
+            tracing::error!("trying to render on non-existent backend {node}");
+            return;
+        };
+
+        let crtcs = match crtc {
+            Some(crtc) => vec![crtc],
+            None => device.surfaces.keys().copied().collect(),
+        };
+        let outputs = trayle.frontend.space
+            .outputs()
+            .filter(|o|{
+                o.user_data().get::<UdevOutputId>().is_some_and(|id|id.device_id == node && crtcs.contains(&id.crtc))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for output in outputs {
+            self::output(&output, frame_target, trayle);
+        }
+    }
+
+    /// [`BackendState::render`] of [`Tty`], renders into the drm surface driving `output`
+    ///
+    /// [`BackendState::render`]: crate::backend::BackendState::render
+    pub fn tty(tty: &mut Tty, output: &Output, ctx: RenderContext<'_>) -> Option<Submitted> {
+        let &UdevOutputId { device_id: node, crtc } = output.user_data().get::<UdevOutputId>()?;
+        let device = tty.devices.get_mut(&node)?;
+        let surface = device.surfaces.get_mut(&crtc)?;
+
+        // render loop is resumed when powered on
+        if !surface.dpms {
+            return None;
+        }
+
+        let frame_duration = surface.frame_duration();
+
+        // nothing changed since the last repaint, skip building elements entirely,
+        // clients still waiting on a frame callback are answered by the rescheduled timer
+        if !surface.dirty && surface.screencopies.is_empty() {
+            self::reschedule(node, crtc, frame_duration, ctx.frame_target, ctx.now, ctx.handle);
+            return None;
+        }
+
+        let start = Instant::now();
+
+        // cursor image is loaded at integer scale, then downscaled by the renderer
+        let cursor_scale = output.current_scale().integer_scale().max(1);
+        let cursor_icon = match &*ctx.cursor_status {
+            CursorImageStatus::Named(icon) => *icon,
+            _ => CursorIcon::Default,
+        };
+        let frame = ctx.pointer_image.get_image(cursor_icon, cursor_scale as u32, ctx.now.into());
+
+        // animated cursor keeps repainting even when nothing else changes
+        let pointer_on_output = ctx.space
+            .output_geometry(output)
+            .is_some_and(|geo|geo.to_f64().contains(ctx.pointer_location));
+        let cursor_delay = matches!(ctx.cursor_status, CursorImageStatus::Named(_))
+            .then(||ctx.pointer_image.next_frame_in(cursor_icon, cursor_scale as u32, ctx.now.into()))
+            .flatten()
+            .filter(|_|pointer_on_output);
+
+        match cursor_delay {
+            Some(delay) if surface.cursor_timer.is_none() => {
+                let timer = Timer::from_duration(delay);
+                let token = ctx.handle.insert_source(timer, move|_,_,trayle|{
+                    let Some(surface) = trayle.backend.state
+                        .tty_mut()
+                        .and_then(|tty|tty.devices.get_mut(&node))
+                        .and_then(|device|device.surfaces.get_mut(&crtc))
+                    else {
+                        return TimeoutAction::Drop;
+                    };
+                    surface.cursor_timer = None;
+                    surface.dirty = true;
+                    if !surface.frame_pending {
+                        self::node(node, Some(crtc), trayle.clock.now(), trayle);
+                    }
+                    TimeoutAction::Drop
+                });
+                surface.cursor_timer = token
+                    .inspect_err(|err|tracing::warn!("failed to schedule cursor frame: {err}"))
+                    .ok();
+            }
+            Some(_) => {}
+            None => {
+                if let Some(token) = surface.cursor_timer.take() {
+                    ctx.handle.remove(token);
+                }
+            }
+        }
+
+        let render_node = surface.render_node;
+        let primary_gpu = tty.primary_gpu;
+        let renderer = if primary_gpu == render_node {
+            tty.gpus.single_renderer(&render_node)
+        } else {
+            // offload, rendered on the primary gpu then copied to the gpu driving the output
+            let format = surface.drm_output.format();
+            tty.gpus.renderer(&primary_gpu, &render_node, format)
+        };
+        let mut renderer = match renderer {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                tracing::error!("failed to get renderer of {primary_gpu} for {render_node}: {err:?}");
+                return None;
+            }
+        };
+        tracing::trace!(%primary_gpu, %render_node, "rendering {}", output.name());
+
+        let pointer_images = ctx.pointer_images;
+        let pointer_image = pointer_images
+            .iter()
+            .find_map(|(image,texture)|{
+                if image == &frame {
+                    Some(texture.clone())
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_else(||{
+                let buffer = MemoryRenderBuffer::from_slice(
+                    &frame.pixels_rgba,
+                    Fourcc::Argb8888,
+                    (frame.width as i32, frame.height as i32),
+                    cursor_scale,
+                    Transform::Normal,
+                    None,
+                );
+                pointer_images.push((frame, buffer.clone()));
+                buffer
+            });
+
+        let result = inner_render_surface(
+            surface,
+            &mut renderer,
+            ctx.space,
+            output,
+            ctx.config,
+            ctx.wallpaper.as_ref(),
+            ctx.pointer_location,
+            &pointer_image,
+            ctx.pointer_element,
+            ctx.cursor_status,
+            ctx.dnd_icon,
+            ctx.now,
+        );
+
+        let mut submitted = None;
+        let reschedule = match result {
+            Ok((has_rendered, states)) => {
+                surface.dirty = false;
+                if has_rendered {
+                    if surface.repaint_durations.len() == utils::REPAINT_HISTORY {
+                        surface.repaint_durations.pop_front();
+                    }
+                    surface.repaint_durations.push_back(start.elapsed());
+                }
+                submitted = Some((states, surface.dmabuf_feedback.clone()));
+                !has_rendered
+            }
+            Err(err) => {
+                tracing::warn!("failed to render: {err:?}");
+                match err {
+                    SwapBuffersError::AlreadySwapped => false,
+                    // If the device has been deactivated do not reschedule, this will be done
+                    // by session resume
+                    SwapBuffersError::TemporaryFailure(err) => matches!{
+                        err.downcast_ref::<DrmError>(),
+                        Some(DrmError::Access(DrmAccessError { source, .. }))
+                            if source.kind() == std::io::ErrorKind::PermissionDenied,
+                    },
+                    SwapBuffersError::ContextLost(err) => match err.downcast_ref::<DrmError>() {
+                        Some(DrmError::TestFailed(_)) => {
+                            // reset the complete state, disabling all connectors and planes in case we hit a test failed
+                            // most likely we hit this after a tty switch when a foreign master changed CRTC <-> connector bindings
+                            // and we run in a mismatch
+                            if let Err(err) = device.drm_output_manager.device_mut().reset_state() {
+                                tracing::error!("failed to reset drm device: {err}");
+                            }
+                            // the next frame has to be a full repaint
+                            if let Some(surface) = device.surfaces.get_mut(&crtc) {
+                                surface.dirty = true;
+                            }
+                            true
+                        }
+                        _ => panic!("rendering loop lost: {err}"),
+                    },
+                }
+            }
+        };
+
+        if reschedule {
+            self::reschedule(node, crtc, frame_duration, ctx.frame_target, ctx.now, ctx.handle);
+        } else {
+            let elapsed = start.elapsed();
+            tracing::trace!(?elapsed, "rendered surface");
+        }
+
+        submitted
+    }
+
+    /// release commit timer barriers due by `frame_target`, before elements are built
+    fn pre_repaint(output: &Output, frame_target: Time<Monotonic>, trayle: &mut Trayle) {
+        let mut clients = HashMap::new();
+        self::with_output_surfaces(output, trayle, |surface, states|{
+            let Some(mut commit_timer) = states
+                .data_map
+                .get::<CommitTimerBarrierStateUserData>()
+                .map(|commit_timer|commit_timer.lock().unwrap())
+            else {
+                return;
+            };
+            commit_timer.signal_until(frame_target);
+            if let Some(client) = surface.client() {
+                clients.insert(client.id(), client);
+            }
+        });
+        self::blockers_cleared(clients, trayle);
+    }
+
+    /// send frame callbacks and dmabuf feedback, and release fifo barriers of surfaces
+    /// shown on `output`, once it is repainted
+    ///
+    /// surfaces on multiple outputs are paced by their primary scanout output,
+    /// which also gives their preferred fractional scale
+    fn post_repaint(
+        output: &Output,
+        frame_target: Time<Monotonic>,
+        dmabuf_feedback: Option<&SurfaceDmabufFeedback>,
+        states: &RenderElementStates,
+        trayle: &mut Trayle,
+    ) {
+        self::send_frames(output, frame_target, trayle);
+
+        if let Some(feedback) = dmabuf_feedback {
+            for window in trayle.frontend.space.elements() {
+                if !trayle.frontend.space.outputs_for_element(window).contains(output) {
+                    continue;
+                }
+                window.send_dmabuf_feedback(output, desktop::utils::surface_primary_scanout_output, |surface,_|{
+                    select_dmabuf_feedback(surface, states, &feedback.render_feedback, &feedback.scanout_feedback)
+                });
+            }
+            for layer in desktop::layer_map_for_output(output).layers() {
+                layer.send_dmabuf_feedback(output, desktop::utils::surface_primary_scanout_output, |surface,_|{
+                    select_dmabuf_feedback(surface, states, &feedback.render_feedback, &feedback.scanout_feedback)
+                });
+            }
+        }
+
+        let mut clients = HashMap::new();
+        self::with_output_surfaces(output, trayle, |surface, states|{
+            let primary_scanout_output = desktop::utils::surface_primary_scanout_output(surface, states);
+            if let Some(primary) = primary_scanout_output.as_ref() {
+                fractional_scale::with_fractional_scale(states, |fractional_scale|{
+                    fractional_scale.set_preferred_scale(primary.current_scale().fractional_scale());
+                });
+            }
+            if primary_scanout_output.is_some_and(|primary|&primary != output) {
+                return;
+            }
+            let fifo_barrier = states
+                .cached_state
+                .get::<FifoBarrierCachedState>()
+                .current()
+                .barrier
+                .take();
+            if let Some(fifo_barrier) = fifo_barrier {
+                fifo_barrier.signal();
+                if let Some(client) = surface.client() {
+                    clients.insert(client.id(), client);
+                }
+            }
+        });
+        self::blockers_cleared(clients, trayle);
+    }
+
+    /// frame callbacks of surfaces shown on `output`
+    ///
+    /// surfaces whose primary scanout output is another one are throttled to one per second
+    fn send_frames(output: &Output, frame_target: Time<Monotonic>, trayle: &Trayle) {
+        let throttle = Some(Duration::from_secs(1));
+        for window in trayle.frontend.space.elements() {
+            if trayle.frontend.space.outputs_for_element(window).contains(output) {
+                window.send_frame(output, frame_target, throttle, desktop::utils::surface_primary_scanout_output);
+            }
+        }
+        for layer in desktop::layer_map_for_output(output).layers() {
+            layer.send_frame(output, frame_target, throttle, desktop::utils::surface_primary_scanout_output);
+        }
+        if let CursorImageStatus::Surface(surface) = &trayle.frontend.cursor_status {
+            desktop::utils::send_frames_surface_tree(surface, output, frame_target, throttle, desktop::utils::surface_primary_scanout_output);
+        }
+        if let Some(icon) = trayle.frontend.dnd_icon.as_ref() {
+            desktop::utils::send_frames_surface_tree(&icon.surface, output, frame_target, throttle, desktop::utils::surface_primary_scanout_output);
+        }
+    }
+
+    /// windows, layer surfaces of `output`, the cursor and the dnd icon, with their subsurfaces
+    fn with_output_surfaces(
+        output: &Output,
+        trayle: &Trayle,
+        mut processor: impl FnMut(&WlSurface, &compositor::SurfaceData),
+    ) {
+        for window in trayle.frontend.space.elements() {
+            window.with_surfaces(&mut processor);
+        }
+        for layer in desktop::layer_map_for_output(output).layers() {
+            layer.with_surfaces(&mut processor);
+        }
+        if let CursorImageStatus::Surface(surface) = &trayle.frontend.cursor_status {
+            desktop::utils::with_surfaces_surface_tree(surface, &mut processor);
+        }
+        if let Some(icon) = trayle.frontend.dnd_icon.as_ref() {
+            desktop::utils::with_surfaces_surface_tree(&icon.surface, &mut processor);
+        }
+    }
+
+    /// let clients whose barriers were signaled apply their blocked commits
+    fn blockers_cleared(clients: HashMap<ClientId, Client>, trayle: &mut Trayle) {
+        let dh = trayle.dh.clone();
+        for client in clients.into_values() {
+            trayle.client_compositor_state(&client).blocker_cleared(trayle, &dh);
+        }
+    }
+
+    /// check again for damage after approx. one frame
+    ///
+    /// used when rendering either hit a temporary failure or did not cause any damage on
+    /// the output, frame callbacks are sent when the timer fires
+    fn reschedule(
+        node: DrmNode,
+        crtc: crtc::Handle,
+        frame_duration: Option<Duration>,
+        frame_target: Time<Monotonic>,
+        now: Time<Monotonic>,
+        handle: &LoopHandle<'static, Trayle>,
+    ) {
+        let Some(frame_duration) = frame_duration else {
+            return;
+        };
+
+        let next_frame_target = frame_target + frame_duration;
+        let reschedule_timeout = Duration::from(next_frame_target).saturating_sub(now.into());
+
+        tracing::trace!("reschedule repaint timer with delay {reschedule_timeout:?} on {crtc:?}");
+
+        let timer = Timer::from_duration(reschedule_timeout);
+        handle.insert_source(timer, move|_,_,trayle|{
+            // a frame queued meanwhile, eg: by the cursor timer, already schedules the next repaint
+            let frame_pending = trayle.backend.state
+                .tty()
+                .and_then(|tty|tty.devices.get(&node))
+                .and_then(|device|device.surfaces.get(&crtc))
+                .is_some_and(|surface|surface.frame_pending);
+            if frame_pending {
+                return TimeoutAction::Drop;
+            }
+            // estimated vblank of an output without damage, frame callbacks are paced by it
+            // instead of the skipped repaint, which may also run off a vblank
+            let output = trayle.frontend.space.outputs().find(|o|{
+                o.user_data().get::<UdevOutputId>() == Some(&UdevOutputId { device_id: node, crtc })
+            }).cloned();
+            if let Some(output) = output {
+                self::send_frames(&output, next_frame_target, trayle);
+            }
+            self::node(node, Some(crtc), next_frame_target, trayle);
+            TimeoutAction::Drop
+        })
+        .expect("failed to reschedule frame timer");
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn inner_render_surface<'a>(
+        surface: &'a mut SurfaceData,
+        renderer: &mut UdevRenderer<'a>,
+        space: &Space<WindowElement>,
+        output: &Output,
+        config: &Config,
+        wallpaper: Option<&Wallpaper>,
+        pointer_location: Point<f64, Logical>,
+        pointer_image: &MemoryRenderBuffer,
+        pointer_element: &mut PointerElement,
+        cursor_status: &mut CursorImageStatus,
+        dnd_icon: Option<&DndIcon>,
+        now: Time<Monotonic>,
+    ) -> Result<(bool, RenderElementStates), SwapBuffersError> {
+        let output_geometry = space.output_geometry(output).unwrap();
+        let scale = Scale::from(output.current_scale().fractional_scale());
+
+        let mut custom_elements = Vec::<CustomRenderElements<_>>::new();
+
+        if output_geometry.to_f64().contains(pointer_location) {
+            let cursor_hotspot = if let CursorImageStatus::Surface(ref surface) = *cursor_status {
+                compositor::with_states(surface, |states|{
+                    states.data_map
+                        .get::<CursorImageSurfaceData>()
+                        .unwrap()
+                        .lock()
+                        .unwrap()
+                        .hotspot
+                })
+            } else {
+                (0,0).into()
+            };
+
+            let cursor_pos = pointer_location - output_geometry.loc.to_f64();
+
+            // set cursor
+            pointer_element.set_buffer(pointer_image.clone());
+
+            // draw the cursor as relevant
+            {
+                // reset the cursor if the surface is no longer alive
+                let mut reset = false;
+                if let CursorImageStatus::Surface(ref surface) = *cursor_status {
+                    reset = !surface.alive();
+                }
+                if reset {
+                    *cursor_status = CursorImageStatus::default_named();
+                }
+
+                pointer_element.set_status(cursor_status.clone());
+            }
+
+            custom_elements.extend(
+                pointer_element.render_elements(
+                    renderer,
+                    (cursor_pos - cursor_hotspot.to_f64())
+                        .to_physical(scale)
+                        .to_i32_round(),
+                    scale,
+                    1.0
+                ),
+            );
+
+            // draw the dnd icon if applicable
+            if let Some(icon) = dnd_icon.filter(|icon|icon.surface.alive()) {
+                let dnd_icon_pos = (cursor_pos + icon.offset.to_f64())
+                    .to_physical(scale)
+                    .to_i32_round();
+                custom_elements.extend(
+                    render_elements_from_surface_tree(
+                        renderer,
+                        &icon.surface,
+                        dnd_icon_pos,
+                        scale,
+                        1.0,
+                        Kind::Unspecified,
+                    )
+                    .into_iter()
+                    .map(CustomRenderElements::Surface),
+                );
+            }
+        }
+
+        let elements = self::elements::outputs(output, space, wallpaper, custom_elements, renderer);
+
+        // a fullscreen window hinting video or game content is scanned out even when direct
+        // scanout is disabled on the output, other content follows the output setting
+        let scanout_content = output
+            .user_data()
+            .get::<FullscreenSurface>()
+            .and_then(|fullscreen|fullscreen.get())
+            .filter(|window|space.element_location(window).is_some())
+            .is_some_and(|window|window.is_scanout_content());
+        let mut frame_mode = match surface.disable_direct_scanout && !scanout_content {
+            // the cursor stays on its plane, so moving it over static content only updates
+            // that plane, a cursor that does not fit the plane is composited instead
+            true => FrameFlags::ALLOW_CURSOR_PLANE_SCANOUT,
+            false => FrameFlags::DEFAULT,
+        };
+        if config.disable_cursor_plane {
+            frame_mode.remove(FrameFlags::ALLOW_CURSOR_PLANE_SCANOUT);
+        }
+
+        let (rendered, render_elements_states) = surface
+            .drm_output
+            .render_frame(renderer, &elements, config.clear_color(output), frame_mode)
+            .map(|render_frame_result|{
+                // renderer_sync feature
+                (!render_frame_result.is_empty,render_frame_result.states)
+            })
+            .map_err(|err|match err {
+                RenderFrameError::PrepareFrame(err) => SwapBuffersError::from(err),
+                RenderFrameError::RenderFrame(OutputDamageTrackerError::Rendering(err)) => err.into(),
+                // output damage tracker of drm compositor always has a mode
+                _ => unreachable!(),
+            })?;
+
+        // copy with damage waits until the output actually changes
+        let (screencopies, pending) = std::mem::take(&mut surface.screencopies)
+            .into_iter()
+            .filter(|screencopy|screencopy.alive())
+            .partition::<Vec<_>, _>(|screencopy|rendered || !screencopy.with_damage());
+        surface.screencopies = pending;
+
+        if !screencopies.is_empty() {
+            let elements_without_cursor = screencopies
+                .iter()
+                .any(|screencopy|!screencopy.overlay_cursor())
+                .then(||self::elements::outputs(output, space, wallpaper, [], renderer));
+
+            for screencopy in screencopies {
+                let elements = match &elements_without_cursor {
+                    Some(elements) if !screencopy.overlay_cursor() => elements,
+                    _ => &elements,
+                };
+                match self::screencopy(renderer, output, elements, config.clear_color(output), &screencopy) {
+                    Ok(()) => screencopy.submit(now.into()),
+                    Err(err) => tracing::warn!("failed to copy {}: {err:#}", output.name()),
+                }
+            }
+        }
+
+        // a surface spanning multiple outputs gets its feedback from the primary scanout output
+        crate::frontend::utils::update_primary_scanout_output(
+            space,
+            output,
+            dnd_icon,
+            cursor_status,
+            &render_elements_states,
+        );
+
+        if rendered {
+            let output_presentation_feedback = crate::frontend::utils::take_presentation_feedback(
+                output,
+                space,
+                &render_elements_states,
+            );
+            surface
+                .drm_output
+                .queue_frame(Some(output_presentation_feedback))
+                .map_err(Into::<SwapBuffersError>::into)?;
+            surface.frame_pending = true;
+        }
+
+        Ok((rendered,render_elements_states))
+    }
+
+    /// render output `elements` into screencopy client buffer
+    fn screencopy<'a>(
+        renderer: &mut UdevRenderer<'a>,
+        output: &Output,
+        elements: &[elements::OutputRenderElements<UdevRenderer<'a>, elements::WindowRenderElement<UdevRenderer<'a>>>],
+        clear_color: Color32F,
+        screencopy: &Screencopy,
+    ) -> Result<()> {
+        let region = screencopy.region();
+        let buffer_size = screencopy.buffer_size();
+        let scale = output.current_scale().fractional_scale();
+
+        // elements are relative to the output, region is moved to the buffer origin
+        let elements = elements
+            .iter()
+            .map(|element|RelocateRenderElement::from_element(element, region.loc.upscale(-1), Relocate::Relative))
+            .collect::<Vec<_>>();
+
+        let mut damage_tracker = OutputDamageTracker::new(buffer_size, scale, output.current_transform());
+
+        if let Ok(dmabuf) = get_dmabuf(screencopy.buffer()) {
+            let mut dmabuf = dmabuf.clone();
+            let mut framebuffer = renderer
+                .bind(&mut dmabuf)
+                .map_err(|err|anyhow::anyhow!("failed to bind dmabuf: {err:?}"))?;
+            damage_tracker
+                .render_output(renderer, &mut framebuffer, 0, &elements, clear_color)
+                .map_err(|err|anyhow::anyhow!("failed to render: {err:?}"))?;
+            return Ok(());
+        }
+
+        let size = Size::from((buffer_size.w, buffer_size.h));
+        let mut offscreen: GlesRenderbuffer = renderer
+            .create_buffer(Fourcc::Xrgb8888, size)
+            .map_err(|err|anyhow::anyhow!("failed to create offscreen buffer: {err:?}"))?;
+        let mut framebuffer = renderer
+            .bind(&mut offscreen)
+            .map_err(|err|anyhow::anyhow!("failed to bind offscreen buffer: {err:?}"))?;
+        damage_tracker
+            .render_output(renderer, &mut framebuffer, 0, &elements, clear_color)
+            .map_err(|err|anyhow::anyhow!("failed to render: {err:?}"))?;
+
+        let mapping = renderer
+            .copy_framebuffer(&framebuffer, Rectangle::from_size(size), Fourcc::Xrgb8888)
+            .map_err(|err|anyhow::anyhow!("failed to copy framebuffer: {err:?}"))?;
+        let pixels = renderer
+            .map_texture(&mapping)
+            .map_err(|err|anyhow::anyhow!("failed to map framebuffer: {err:?}"))?;
+
+        shm::with_buffer_contents_mut(screencopy.buffer(), |ptr, len, _|{
+            anyhow::ensure!(len >= pixels.len(), "shm buffer is too small");
+            // SAFETY: ptr is valid for `len` bytes while the shm pool is mapped
+            unsafe { std::ptr::copy_nonoverlapping(pixels.as_ptr(), ptr, pixels.len()) };
+            Ok(())
+        })
+        .context("failed to access shm buffer")?
+    }
+
+    pub fn frame_finish(node: DrmNode, crtc: crtc::Handle, meta: &mut Option<DrmEventMetadata>, trayle: &mut Trayle) {
+        let Some(tty) = trayle.backend.state.tty_mut() else {
+            return;
+        };
+
+        let device_backend = match tty.devices.get_mut(&node) {
+            Some(some) => some,
+            None => {
+                tracing::error!("attempt to finish frame on non-existent crtc {crtc:?}");
+                return;
+            },
+        };
+
+        let surface = match device_backend.surfaces.get_mut(&crtc) {
+            Some(some) => some,
+            None => {
+                tracing::error!("attempt to finish frame on non-existent crtc {crtc:?}");
+                return;
+            },
+        };
+
+        let Some(output) = trayle.frontend.space.outputs().find(|o|{
+            o.user_data().get::<UdevOutputId>() == Some(&UdevOutputId {
+                device_id: surface.device_id, crtc
+            })
+        }).cloned()
+        else {
+            return;
+        };
+
+        let tp = meta.as_ref().and_then(|meta|match meta.time {
+            smithay::backend::drm::DrmEventTime::Monotonic(tp) => Some(tp),
+            smithay::backend::drm::DrmEventTime::Realtime(_) => None,
+        });
+
+        let seq = meta.as_ref().map(|meta|meta.sequence).unwrap_or(0);
+
+        let (clock,flags) = match tp {
+            Some(tp) => (tp.into(),
+                wp_presentation_feedback::Kind::Vsync
+                | wp_presentation_feedback::Kind::HwClock
+                | wp_presentation_feedback::Kind::HwCompletion
+            ),
+            None => (trayle.clock.now(),
+                wp_presentation_feedback::Kind::Vsync
+            ),
+        };
+
+        let submit_result = surface
+            .drm_output
+            .frame_submitted()
+            .map_err(Into::<SwapBuffersError>::into);
+        surface.frame_pending = false;
+
+        let Some(frame_duration) = surface.frame_duration() else {
+            return
+        };
+
+        // with adaptive sync, the mode refresh rate is only the upper bound
+        let refresh = match surface.vrr {
+            true => Refresh::Variable(frame_duration),
+            false => Refresh::Fixed(frame_duration),
+        };
+
+        let schedule_render = match submit_result {
+            Ok(user_data) => {
+                if let Some(mut feedback) = user_data.flatten() {
+                    feedback.presented(clock, refresh, seq as u64, flags);
+                }
+                true
+            },
+            Err(err) => {
+                tracing::warn!("rendering error: {err:?}");
+                match err {
+                    SwapBuffersError::AlreadySwapped => true,
+                    // If the device has been deactivated do not reschedule, this will be done
+                    // by session resume
+                    SwapBuffersError::TemporaryFailure(err)
+                        if matches!(err.downcast_ref::<DrmError>(),Some(&DrmError::DeviceInactive)) =>
+                    {
+                        false
+                    }
+                    SwapBuffersError::TemporaryFailure(err) => matches!{
+                        err.downcast_ref::<DrmError>(),
+                        Some(DrmError::Access(DrmAccessError { source, .. }))
+                            if source.kind() == std::io::ErrorKind::PermissionDenied,
+                    },
+                    SwapBuffersError::ContextLost(err) => panic!("rendering loop lost: {err}")
+                }
+            },
+        };
+
+        if schedule_render {
+            let next_frame_target = clock + frame_duration;
+
+            // What are we trying to solve by introducing a delay here:
+            //
+            // Basically it is all about latency of client provided buffers.
+            // A client driven by frame callbacks will wait for a frame callback
+            // to repaint and submit a new buffer. As we send frame callbacks
+            // as part of the repaint in the compositor the latency would always
+            // be approx. 2 frames. By introducing a delay before we repaint in
+            // the compositor we can reduce the latency to approx. 1 frame + the
+            // remaining duration from the repaint to the next VBlank.
+            //
+            // With the delay it is also possible to further reduce latency if
+            // the client is driven by presentation feedback. As the presentation
+            // feedback is directly sent after a VBlank the client can submit a
+            // new buffer during the repaint delay that can hit the very next
+            // VBlank, thus reducing the potential latency to below one frame.
+            //
+            // Choosing a good delay is a topic on its own so we just implement
+            // a simple strategy here. We just split the duration between two
+            // VBlanks into two steps, one for the client repaint and one for the
+            // compositor repaint. Theoretically the repaint in the compositor should
+            // be faster so we give the client a bit more time to repaint. On a typical
+            // modern system the repaint in the compositor should not take more than 2ms
+            // so this should be safe for refresh rates up to at least 120 Hz. For 120 Hz
+            // this results in approx. 3.33ms time for repainting in the compositor.
+            // A too big delay could result in missing the next VBlank in the compositor.
+            //
+            // So the delay is predicted from a sliding window of past repaints, leaving the
+            // slowest recent repaint plus a safety margin before the next VBlank.
+            let repaint_delay = utils::repaint_delay(frame_duration, &surface.repaint_durations);
+
+            let timer = if surface.vrr {
+                // the display waits for the next frame, no need to align repaint to a fixed vblank
+                tracing::trace!("scheduling repaint timer immediately on {crtc:?} with adaptive sync");
+                Timer::immediate()
+            } else if tty.primary_gpu != surface.render_node {
+                // However, if we need to do a copy, that might not be enough.
+                // (And without actual comparision to previous frames we cannot really know.)
+                // So lets ignore that in those cases to avoid thrashing performance.
+                tracing::trace!("scheduling repaint timer immediately on {crtc:?}");
+                Timer::immediate()
+            } else {
+                tracing::trace!("scheduling repaint timer with delay {repaint_delay:?} on {crtc:?}");
+                Timer::from_duration(repaint_delay)
+            };
 
-            let timer = Timer::from_duration(reschedule_timeout);
             trayle.handle.insert_source(timer, move|_,_,trayle|{
-                self::node(node, Some(crtc), next_frame_target, trayle);
+                render::node(node, Some(crtc), next_frame_target, trayle);
                 TimeoutAction::Drop
-            })
-            .expect("failed to reschedule frame timer");
-        } else {
-            let elapsed = start.elapsed();
-            tracing::trace!(?elapsed, "rendered surface");
+            }).expect("failed to schedule frame timer");
+        }
+
+        #[cfg(feature = "screencast")]
+        self::screencast(node, &output, trayle);
+    }
+
+    /// feed the presented output to its screencasts
+    #[cfg(feature = "screencast")]
+    fn screencast(node: DrmNode, output: &Output, trayle: &mut Trayle) {
+        if !trayle.backend.casts.iter().any(|cast|&cast.output == output) {
+            return;
+        }
+
+        let Some(tty) = trayle.backend.state.tty_mut() else {
+            return;
+        };
+
+        let Some(render_node) = tty.devices.get(&node).map(|device|device.render_node) else {
+            return;
+        };
+
+        let mut renderer = match tty.gpus.single_renderer(&render_node) {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                tracing::warn!("failed to get screencast renderer: {err:?}");
+                return;
+            }
+        };
+
+        let wallpaper = trayle.backend.wallpapers.get(&trayle.config, output);
+        let elements = self::elements::outputs(output, &trayle.frontend.space, wallpaper.as_ref(), [], &mut renderer);
+
+        for cast in trayle.backend.casts.iter_mut().filter(|cast|&cast.output == output) {
+            if let Err(err) = cast.render(&mut renderer, &elements, trayle.config.clear_color(output)) {
+                tracing::warn!("failed to render screencast of {}: {err:#}", output.name());
+            }
+        }
+    }
+
+    /// [`BackendState::render`] of [`Winit`], repeated on every redraw of the window
+    ///
+    /// the host compositor draws the cursor, so only the space and wallpaper are rendered
+    ///
+    /// [`BackendState::render`]: crate::backend::BackendState::render
+    pub fn winit(winit: &mut Winit, output: &Output, ctx: RenderContext<'_>) -> Option<Submitted> {
+        let clear_color = ctx.config.clear_color(output);
+        let age = winit.backend.buffer_age().unwrap_or(0);
+
+        let rendered = {
+            let (renderer, mut framebuffer) = match winit.backend.bind() {
+                Ok(ok) => ok,
+                Err(err) => {
+                    tracing::warn!("failed to bind winit framebuffer: {err:?}");
+                    return None;
+                }
+            };
+            let elements = self::elements::outputs(output, ctx.space, ctx.wallpaper.as_ref(), [], renderer);
+            winit.damage_tracker
+                .render_output(renderer, &mut framebuffer, age, &elements, clear_color)
+                .map(|result|(result.damage.cloned(), result.states))
+        };
+        // keep redrawing, frame callbacks are paced by the host compositor
+        winit.backend.window().request_redraw();
+
+        let (damage, states) = match rendered {
+            Ok(ok) => ok,
+            Err(err) => {
+                tracing::warn!("failed to render: {err:?}");
+                return None;
+            }
+        };
+
+        if let Some(damage) = damage {
+            if let Err(err) = winit.backend.submit(Some(&damage)) {
+                tracing::warn!("failed to submit winit frame: {err:?}");
+            }
+        }
+
+        // the only output is the primary scanout output of every surface shown
+        crate::frontend::utils::update_primary_scanout_output(
+            ctx.space,
+            output,
+            ctx.dnd_icon,
+            ctx.cursor_status,
+            &states,
+        );
+
+        Some((states, None))
+    }
+
+    pub mod elements {
+        use smithay::{
+            backend::renderer::{
+                element::{
+                    memory::MemoryRenderBufferRenderElement, solid::SolidColorRenderElement, Element,
+                    RenderElement, UnderlyingStorage,
+                },
+                Renderer,
+            },
+            wayland::single_pixel_buffer,
+        };
+
+        use super::*;
+
+        pub use crate::{drawing::PointerRenderElement, shell::elements::WindowRenderElement};
+
+        smithay::render_elements! {
+            pub CustomRenderElements<R> where R: ImportAll + ImportMem;
+            Pointer=PointerRenderElement<R>,
+            Surface=WaylandSurfaceRenderElement<R>,
+            Wallpaper=MemoryRenderBufferRenderElement<R>,
+            SolidColor=SolidColorRenderElement,
+        }
+
+        smithay::render_elements! {
+            pub OutputRenderElements<R, E> where R: ImportAll + ImportMem;
+            Space=SpaceRenderElements<R, E>,
+            Window=Wrap<E>,
+            Custom=CustomRenderElements<R>,
+            Preview=CropRenderElement<RelocateRenderElement<RescaleRenderElement<WindowRenderElement<R>>>>,
+        }
+
+        /// output elements
+        ///
+        /// `custom_elements` are drawn on top of the [`Space`] elements,
+        /// `wallpaper` below them, hidden by a fullscreen window
+        pub fn outputs<R>(
+            output: &Output,
+            space: &Space<WindowElement>,
+            wallpaper: Option<&Wallpaper>,
+            custom_elements: impl IntoIterator<Item = CustomRenderElements<R>>,
+            renderer: &mut R,
+        ) -> Vec<OutputRenderElements<R, WindowRenderElement<R>>>
+        where
+            R: Renderer + ImportAll + ImportMem,
+            R::TextureId: Clone + 'static,
+        {
+            let mut output_elements = custom_elements
+                .into_iter()
+                .map(OutputRenderElements::from)
+                .collect::<Vec<_>>();
+
+            // fullscreen window hides everything else, unless its workspace is hidden
+            let fullscreen = output
+                .user_data()
+                .get::<FullscreenSurface>()
+                .and_then(|fullscreen|fullscreen.get())
+                .filter(|window|space.element_location(window).is_some());
+
+            let scale = output.current_scale().fractional_scale().into();
+
+            if let Some(window) = fullscreen {
+                // video or game content is drawn without decorations, leaving its buffer
+                // alone at the output origin for direct scanout
+                let window_elements = match window.is_scanout_content() {
+                    true => AsRenderElements::<R>::render_elements::<WaylandSurfaceRenderElement<R>>(
+                        &window.0,
+                        renderer,
+                        (0,0).into(),
+                        scale,
+                        1.0,
+                    )
+                    .into_iter()
+                    .map(|element|match self::single_pixel(&element, renderer, scale) {
+                        Some(solid) => WindowRenderElement::SolidColor(solid),
+                        None => WindowRenderElement::Window(element),
+                    })
+                    .collect(),
+                    false => AsRenderElements::<R>::render_elements::<WindowRenderElement<R>>(
+                        &window,
+                        renderer,
+                        (0,0).into(),
+                        scale,
+                        1.0,
+                    ),
+                };
+                output_elements.extend(window_elements.into_iter().map(|element|OutputRenderElements::Window(Wrap::from(element))));
+                return output_elements;
+            }
+
+            // elements are scaled by the output current scale, the last argument is alpha
+            let space_elements = smithay::desktop::space::space_render_elements::<_, WindowElement, _>(
+                renderer,
+                [space],
+                output,
+                1.0,
+            )
+            .unwrap();
+
+            // windows are handled by `WindowElement`, layer surfaces are space surfaces
+            output_elements.extend(space_elements.into_iter().map(|element|match element {
+                SpaceRenderElements::Surface(surface) => match self::single_pixel(&surface, renderer, scale) {
+                    Some(solid) => OutputRenderElements::Custom(CustomRenderElements::SolidColor(solid)),
+                    None => OutputRenderElements::Space(SpaceRenderElements::Surface(surface)),
+                },
+                element => OutputRenderElements::Space(element),
+            }));
+
+            if let Some((wallpaper, geo)) = wallpaper.zip(space.output_geometry(output)) {
+                let wallpaper_elements = wallpaper.render_elements(renderer, geo.size, scale);
+                output_elements.extend(
+                    wallpaper_elements
+                        .into_iter()
+                        .map(|element|OutputRenderElements::Custom(CustomRenderElements::Wallpaper(element))),
+                );
+            }
+
+            output_elements
+        }
+
+        /// surface with a single pixel buffer as a solid color, instead of importing the
+        /// buffer as a texture
+        ///
+        /// the color fills the element geometry, which is the viewport destination of the
+        /// surface if it has one
+        pub fn single_pixel<R>(
+            element: &WaylandSurfaceRenderElement<R>,
+            renderer: &mut R,
+            scale: Scale<f64>,
+        ) -> Option<SolidColorRenderElement>
+        where
+            R: Renderer + ImportAll,
+            R::TextureId: Clone + 'static,
+        {
+            let Some(UnderlyingStorage::Wayland(buffer)) = element.underlying_storage(renderer) else {
+                return None;
+            };
+            let pixel = single_pixel_buffer::get_single_pixel_buffer(buffer).ok()?;
+            // channels are premultiplied, the element alpha applies to all of them
+            let channel = |value: u32|value as f32 / u32::MAX as f32 * element.alpha();
+            let color = Color32F::new(channel(pixel.r), channel(pixel.g), channel(pixel.b), channel(pixel.a));
+            Some(SolidColorRenderElement::new(
+                element.id().clone(),
+                element.geometry(scale),
+                element.current_commit(),
+                color,
+                element.kind(),
+            ))
         }
     }
+}
 
-    fn inner_render_surface<'a>(
-        surface: &'a mut SurfaceData,
-        renderer: &mut UdevRenderer<'a>,
-        space: &Space<Window>,
-        output: &Output,
-        config: &Config,
-    ) -> Result<(bool, RenderElementStates)> {
-        // let output_geometry = space.output_geometry(output).unwrap();
-        // let scale = Scale::from(output.current_scale().fractional_scale());
-        //
-        // let mut custom_elements = Vec::<String>::new();
+pub mod input {
+    use smithay::{
+        backend::input::{
+            AbsolutePositionEvent, Axis, AxisSource, ButtonState, Event, GestureBeginEvent,
+            GestureEndEvent, GesturePinchUpdateEvent as _, GestureSwipeUpdateEvent as _,
+            InputBackend, InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent,
+            PointerButtonEvent, PointerMotionEvent, ProximityState, Switch, SwitchState,
+            SwitchToggleEvent, TabletToolButtonEvent, TabletToolEvent, TabletToolProximityEvent,
+            TabletToolTipEvent, TabletToolTipState,
+        },
+        input::{
+            keyboard::{FilterResult, KeysymHandle, ModifiersState},
+            pointer::{
+                AxisFrame, ButtonEvent, Focus, GestureHoldBeginEvent, GestureHoldEndEvent,
+                GesturePinchBeginEvent, GesturePinchEndEvent, GesturePinchUpdateEvent,
+                GestureSwipeBeginEvent, GestureSwipeEndEvent, GestureSwipeUpdateEvent,
+                GrabStartData, MotionEvent, RelativeMotionEvent,
+            },
+        },
+        wayland::{
+            pointer_constraints::{self, PointerConstraint},
+            tablet_manager::{TabletDescriptor, TabletSeatTrait},
+        },
+    };
 
-        /*
+    use super::*;
+    use crate::{
+        config::{Action, KeyCombo, Modifiers},
+        shell::grabs::{MoveGrab, ResizeEdge, ResizeGrab},
+    };
+    use ::xkbcommon::xkb::keysyms;
+
+    /// linux input event codes for mouse buttons
+    const BTN_LEFT: u32 = 0x110;
+    const BTN_RIGHT: u32 = 0x111;
+
+    /// swipes with this many fingers switch workspace instead of reaching clients
+    const WORKSPACE_SWIPE_FINGERS: u32 = 3;
+    /// horizontal distance of a workspace swipe, in touchpad logical pixels
+    const WORKSPACE_SWIPE_DISTANCE: f64 = 100.0;
+
+    fn on_keyboard(
+        trayle: &mut Trayle,
+        state: KeyState,
+        mods: &ModifiersState,
+        handle: KeysymHandle,
+    ) -> FilterResult<Action> {
+        tracing::debug!(
+            ?mods,
+            keysym = ::xkbcommon::xkb::keysym_get_name(handle.modified_sym()),
+            "keysym"
+        );
 
-        if output_geometry.to_f64().contains(pointer_location) {
-            let cursor_hotspot = if let CursorImageStatus::Surface(ref surface) = cursor_status {
-                compositor::with_states(surface, |states|{
-                    states.data_map
-                        .get::<Mutex<CursorImageAttributes>>()
-                        .unwrap()
-                        .lock()
-                        .unwrap()
-                        .hotspot
-                })
-            } else {
-                (0,0).into()
+        // window cycling ends when its modifier is released
+        if trayle.frontend.cycle.is_some() && !mods.alt {
+            trayle.finish_cycle_windows();
+        }
+
+        if state != KeyState::Pressed {
+            return FilterResult::Forward;
+        }
+
+        // `Ctrl+Alt+F<n>` is translated by xkb, never forwarded so clients do not see half a combo
+        let keysym = handle.modified_sym().raw();
+        if (keysyms::KEY_XF86Switch_VT_1..=keysyms::KEY_XF86Switch_VT_12).contains(&keysym) {
+            let vt = (keysym - keysyms::KEY_XF86Switch_VT_1 + 1) as i32;
+            return FilterResult::Intercept(Action::ChangeVt(vt));
+        }
+
+        // an inhibiting surface gets every keybinding, `Super+Escape` gives them back
+        let inhibited = trayle.frontend.shortcuts_inhibitor
+            .as_ref()
+            .is_some_and(|inhibitor|inhibitor.is_active());
+        if inhibited {
+            let release = mods.logo && handle.raw_syms().iter().any(|keysym|keysym.raw() == keysyms::KEY_Escape);
+            return match release {
+                true => FilterResult::Intercept(Action::ReleaseShortcutsInhibitor),
+                false => FilterResult::Forward,
             };
+        }
 
-            let cursor_pos = pointer_location - output_geometry.loc.to_f64();
+        // match against raw keysyms, so `Super+Shift+Q` is not affected by shift level
+        let modifiers = Modifiers::from(mods);
+        let action = handle.raw_syms().iter().find_map(|&keysym|{
+            trayle.config.keybindings.get(&KeyCombo { modifiers, keysym })
+        });
 
-            // set cursor
-            pointer_element.set_buffer(pointer_image.clone());
+        match action {
+            Some(action) => FilterResult::Intercept(action.clone()),
+            None => FilterResult::Forward,
+        }
+    }
 
-            // draw the cursor as relevant
-            {
-                // reset the cursor if the surface is no longer alive
-                let mut reset = false;
-                if let CursorImageStatus::Surface(ref surface) = *cursor_status {
-                    reset = !surface.alive();
+    /// run action triggered by keybinding
+    pub fn dispatch(action: Action, trayle: &mut Trayle) {
+        tracing::debug!(?action, "dispatch action");
+
+        match action {
+            Action::Spawn(command) => {
+                // failure is logged by spawn
+                let _ = trayle.spawn_shell(&command);
+            }
+            Action::Quit => {
+                tracing::info!("shutting down");
+                trayle.signal.stop();
+            }
+            Action::SwitchWorkspace(n) => trayle.switch_workspace(n as usize - 1),
+            Action::MoveToWorkspace(n) => trayle.move_to_workspace(n as usize - 1),
+            Action::ToggleFloating => trayle.toggle_floating(),
+            Action::CycleWindows(forward) => trayle.cycle_windows(forward),
+            Action::ToggleDebugFlag(flag) => trayle.toggle_debug_flag(flag),
+            Action::ToggleDirectScanout => {
+                let enabled = trayle.backend.disable_direct_scanout;
+                trayle.set_direct_scanout(enabled);
+            }
+            Action::CloseWindow => trayle.close_focused_window(),
+            Action::CenterCursor => trayle.center_pointer_on_focused(false),
+            Action::ReleaseShortcutsInhibitor => {
+                if let Some(inhibitor) = trayle.frontend.shortcuts_inhibitor.as_ref() {
+                    tracing::info!("keyboard shortcuts released from the focused surface");
+                    inhibitor.deactivate();
                 }
-                if reset {
-                    *cursor_status = CursorImageStatus::default_named();
+            }
+            // rendering stops on pause and resumes on activate, see `callbacks::session`
+            Action::ChangeVt(vt) => {
+                let Some(session) = trayle.backend.session.as_mut() else {
+                    return;
+                };
+                if let Err(err) = session.change_vt(vt) {
+                    tracing::error!("failed to switch to vt {vt}: {err}");
                 }
+            }
+        }
+    }
 
-                pointer_element.set_status(cursor_status.clone());
+    pub fn handle<B: InputBackend>(event: InputEvent<B>, trayle: &mut Trayle) {
+        match event {
+            InputEvent::Keyboard { event } => self::keyboard(event, trayle),
+            InputEvent::PointerMotion { event } => self::pointer_motion(event, trayle),
+            InputEvent::PointerMotionAbsolute { event } => self::pointer_motion_absolute(event, trayle),
+            InputEvent::PointerButton { event } => self::pointer_button(event, trayle),
+            InputEvent::PointerAxis { event } => self::pointer_axis(event, trayle),
+            InputEvent::GestureSwipeBegin { event } => self::gesture_swipe_begin(event, trayle),
+            InputEvent::GestureSwipeUpdate { event } => self::gesture_swipe_update(event, trayle),
+            InputEvent::GestureSwipeEnd { event } => self::gesture_swipe_end(event, trayle),
+            InputEvent::GesturePinchBegin { event } => self::gesture_pinch_begin(event, trayle),
+            InputEvent::GesturePinchUpdate { event } => self::gesture_pinch_update(event, trayle),
+            InputEvent::GesturePinchEnd { event } => self::gesture_pinch_end(event, trayle),
+            InputEvent::GestureHoldBegin { event } => self::gesture_hold_begin(event, trayle),
+            InputEvent::GestureHoldEnd { event } => self::gesture_hold_end(event, trayle),
+            InputEvent::SwitchToggle { event } => self::switch_toggle(event, trayle),
+            InputEvent::TabletToolAxis { event } => self::tablet_tool_axis(event, trayle),
+            InputEvent::TabletToolProximity { event } => self::tablet_tool_proximity(event, trayle),
+            InputEvent::TabletToolTip { event } => self::tablet_tool_tip(event, trayle),
+            InputEvent::TabletToolButton { event } => self::tablet_tool_button(event, trayle),
+            _ => {}
+        }
+    }
+
+    fn keyboard<B: InputBackend>(event: impl KeyboardKeyEvent<B>, trayle: &mut Trayle) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let time = event.time_msec();
+        let state = event.state();
+        // the seat keyboard may be gone while its events are still buffered
+        let Some(keyboard) = trayle.seat.get_keyboard() else {
+            tracing::debug!("dropping key event without a keyboard");
+            return;
+        };
+        let action = keyboard.input(
+            trayle,
+            event.key_code(),
+            state,
+            serial,
+            time,
+            |trayle,mods,handle|self::on_keyboard(trayle, state, mods, handle),
+        );
+
+        if let Some(action) = action {
+            self::dispatch(action, trayle);
+        }
+    }
+
+    /// relative deltas are always sent, a locked pointer stops there,
+    /// a confined pointer does not leave its surface or confinement region
+    fn pointer_motion<B: InputBackend>(event: impl PointerMotionEvent<B>, trayle: &mut Trayle) {
+        let pointer = trayle.pointer.clone();
+        let location = pointer.current_location();
+        let under = utils::surface_under(&trayle.frontend.space, location);
+
+        let mut locked = false;
+        let mut confined = false;
+        let mut confine_region = None;
+        if let Some((surface,surface_location)) = under.as_ref() {
+            pointer_constraints::with_pointer_constraint(surface, &pointer, |constraint|{
+                let Some(constraint) = constraint.filter(|constraint|constraint.is_active()) else {
+                    return;
+                };
+                // constraint only applies within its region
+                let point = (location - *surface_location).to_i32_round();
+                if !constraint.region().is_none_or(|region|region.contains(point)) {
+                    return;
+                }
+                match &*constraint {
+                    PointerConstraint::Locked(_) => locked = true,
+                    PointerConstraint::Confined(confine) => {
+                        confined = true;
+                        confine_region = confine.region().cloned();
+                    }
+                }
+            });
+        }
+
+        pointer.relative_motion(trayle, under.clone(), &RelativeMotionEvent {
+            delta: event.delta(),
+            delta_unaccel: event.delta_unaccel(),
+            utime: event.time(),
+        });
+
+        if locked {
+            pointer.frame(trayle);
+            return;
+        }
+
+        let location = utils::clamp_to_outputs(&trayle.frontend.space, location + event.delta());
+        let new_under = utils::surface_under(&trayle.frontend.space, location);
+
+        if let Some((surface,surface_location)) = under.as_ref().filter(|_|confined) {
+            let left_surface = new_under.as_ref().map(|(new,_)|new) != Some(surface);
+            let left_region = confine_region
+                .is_some_and(|region|!region.contains((location - *surface_location).to_i32_round()));
+            if left_surface || left_region {
+                pointer.frame(trayle);
+                return;
             }
+        }
 
-            custom_elements.extend(
-                pointer_element.render_elements(
-                    renderer,
-                    (cursor_pos - cursor_hotspot.to_f64())
-                        .to_physical(scale)
-                        .to_i32_round(),
-                    scale,
-                    1.0
-                ),
-            );
+        let serial = SERIAL_COUNTER.next_serial();
+        pointer.motion(trayle, new_under.clone(), &MotionEvent { location, serial, time: event.time_msec() });
+        pointer.frame(trayle);
+
+        // constraint of the surface now under the pointer activates once it is within its region
+        if let Some((surface,surface_location)) = new_under.as_ref() {
+            pointer_constraints::with_pointer_constraint(surface, &pointer, |constraint|{
+                let Some(constraint) = constraint.filter(|constraint|!constraint.is_active()) else {
+                    return;
+                };
+                let point = (location - *surface_location).to_i32_round();
+                if constraint.region().is_none_or(|region|region.contains(point)) {
+                    constraint.activate();
+                }
+            });
+        }
+    }
 
-            // draw the dnd icon if applicable
-            {
-                if let Some(icon) = dnd_icon.as_ref() {
-                    let dnd_icon_pos = (cursor_pos + icon.offset.to_f64())
-                        .to_physical(scale)
-                        .to_i32_round();
-                    if icon.surface.alive() {
-                        custom_elements.extend(AsRenderElements::<UdevRenderer<'a>>::render_elements(
-                            &SurfaceTree::from_surface(&icon.surface),
-                            renderer,
-                            dnd_icon_pos,
-                            scale,
-                            1.0
-                        ));
+    /// absolute position is mapped against the output the pointer is currently on
+    ///
+    /// device coordinate follows the panel, so it is rotated along with the output transform
+    fn pointer_motion_absolute<B: InputBackend>(event: impl AbsolutePositionEvent<B>, trayle: &mut Trayle) {
+        let space = &trayle.frontend.space;
+        let Some((output, output_geometry)) = space
+            .output_under(trayle.pointer.current_location())
+            .next()
+            .or_else(||space.outputs().next())
+            .and_then(|output|Some((output, space.output_geometry(output)?)))
+        else {
+            return;
+        };
+
+        let transform = output.current_transform();
+        let panel_size = transform.invert().transform_size(output_geometry.size);
+        let panel_location = event.position_transformed(panel_size);
+        let location = transform.transform_point_in(panel_location, &panel_size.to_f64())
+            + output_geometry.loc.to_f64();
+        self::pointer_motion_to(location, event.time_msec(), trayle);
+    }
+
+    pub fn pointer_motion_to(location: Point<f64, Logical>, time: u32, trayle: &mut Trayle) {
+        let location = utils::clamp_to_outputs(&trayle.frontend.space, location);
+        let under = utils::surface_under(&trayle.frontend.space, location);
+        let serial = SERIAL_COUNTER.next_serial();
+
+        let pointer = trayle.pointer.clone();
+        pointer.motion(trayle, under, &MotionEvent { location, serial, time });
+        pointer.frame(trayle);
+    }
+
+    fn pointer_button<B: InputBackend>(event: impl PointerButtonEvent<B>, trayle: &mut Trayle) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let button = event.button_code();
+        let state = event.state();
+
+        let pointer = trayle.pointer.clone();
+
+        if state == ButtonState::Pressed && !pointer.is_grabbed() {
+            let location = pointer.current_location();
+            let space = &trayle.frontend.space;
+
+            // keyboard interactive layers above windows take the focus first
+            let layer_focus = space.output_under(location).next().and_then(|output|{
+                let output_location = space.output_geometry(output)?.loc.to_f64();
+                let map = desktop::layer_map_for_output(output);
+                let layer = map
+                    .layer_under(Layer::Overlay, location - output_location)
+                    .or_else(||map.layer_under(Layer::Top, location - output_location))?;
+                layer.can_receive_keyboard_focus().then(||layer.wl_surface().clone())
+            });
+
+            let (focus, window) = match layer_focus {
+                Some(focus) => (Some(focus), None),
+                None => {
+                    let window = space
+                        .element_under(location)
+                        .map(|(window,_)|window.clone());
+
+                    if let Some(window) = window.as_ref() {
+                        trayle.frontend.space.raise_element(window, true);
+                    }
+
+                    let focus = window.as_ref().and_then(|window|Some(window.toplevel()?.wl_surface().clone()));
+                    (focus, window)
+                },
+            };
+
+            let keyboard = trayle.seat.get_keyboard();
+            if let Some(keyboard) = keyboard.as_ref() {
+                keyboard.set_focus(trayle, focus, serial);
+            }
+
+            // Super + left drag or titlebar drag moves, Super + right drag resizes the window
+            if let Some(window) = window {
+                if let Some(initial_window_location) = trayle.frontend.space.element_location(&window) {
+                    let start_data = GrabStartData { focus: None, button, location };
+                    let relative = location - initial_window_location.to_f64();
+                    let on_header = relative.y < window.header_height() as f64;
+                    let logo = keyboard.as_ref().is_some_and(|keyboard|keyboard.modifier_state().logo);
+
+                    match button {
+                        BTN_LEFT if on_header && window.decoration_state().header_bar.is_close_button(relative) => {
+                            if let Some(toplevel) = window.toplevel() {
+                                toplevel.send_close();
+                            }
+                        }
+                        BTN_LEFT if logo || on_header => {
+                            let grab = MoveGrab { start_data, window, initial_window_location };
+                            pointer.set_grab(trayle, grab, serial, Focus::Clear);
+                        }
+                        BTN_RIGHT if logo => {
+                            let edges = ResizeEdge::nearest(relative, window.geometry().size);
+                            if let Some(grab) = ResizeGrab::start(start_data, window, edges, initial_window_location) {
+                                pointer.set_grab(trayle, grab, serial, Focus::Clear);
+                            }
+                        }
+                        _ => {}
                     }
                 }
             }
         }
-        */
 
-        let elements = self::elements::outputs(output, space, renderer);
+        pointer.button(trayle, &ButtonEvent { button, state, serial, time: event.time_msec() });
+        pointer.frame(trayle);
+    }
+
+    fn pointer_axis<B: InputBackend>(event: impl PointerAxisEvent<B>, trayle: &mut Trayle) {
+        let source = event.source();
+        let mut frame = AxisFrame::new(event.time_msec()).source(source);
+
+        for axis in [Axis::Horizontal, Axis::Vertical] {
+            // wheel without continuous value, 120 is one detent of 15 logical pixel
+            let amount = event
+                .amount(axis)
+                .or_else(||event.amount_v120(axis).map(|v120|v120 * 15.0 / 120.0))
+                .unwrap_or(0.0);
+
+            if amount != 0.0 {
+                frame = frame
+                    .relative_direction(axis, event.relative_direction(axis))
+                    .value(axis, amount);
+
+                if let Some(v120) = event.amount_v120(axis) {
+                    frame = frame.v120(axis, v120 as i32);
+                }
+            }
+
+            // finger lifted from touchpad, lets client start kinetic scrolling
+            if source == AxisSource::Finger && event.amount(axis) == Some(0.0) {
+                frame = frame.stop(axis);
+            }
+        }
+
+        let pointer = trayle.pointer.clone();
+        pointer.axis(trayle, frame);
+        pointer.frame(trayle);
+    }
+
+    /// location of a tablet tool, the tablet area is mapped to [`Config::tablet_output`]
+    fn tablet_tool_location<B: InputBackend>(event: &impl TabletToolEvent<B>, trayle: &Trayle) -> Option<Point<f64, Logical>> {
+        let space = &trayle.frontend.space;
+        let output = trayle.config.tablet_output
+            .as_ref()
+            .and_then(|name|space.outputs().find(|output|{
+                output.name() == *name
+                    || output.user_data().get::<EdidName>().is_some_and(|EdidName(edid)|edid == name)
+            }))
+            .or_else(||space.outputs().next())?;
+        let output_geometry = space.output_geometry(output)?;
+
+        let transform = output.current_transform();
+        let panel_size = transform.invert().transform_size(output_geometry.size);
+        let panel_location = event.position_transformed(panel_size);
+        Some(transform.transform_point_in(panel_location, &panel_size.to_f64()) + output_geometry.loc.to_f64())
+    }
+
+    /// tools move the pointer too, clients without tablet support still get pointer events
+    fn tablet_tool_axis<B: InputBackend>(event: B::TabletToolAxisEvent, trayle: &mut Trayle) {
+        let Some(location) = self::tablet_tool_location(&event, trayle) else {
+            return;
+        };
+        self::pointer_motion_to(location, event.time_msec(), trayle);
+
+        let tablet_seat = trayle.seat.tablet_seat();
+        let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&event.device()));
+        let Some((tablet, tool)) = tablet.zip(tablet_seat.get_tool(&event.tool())) else {
+            return;
+        };
+
+        if event.pressure_has_changed() {
+            tool.pressure(event.pressure());
+        }
+        if event.distance_has_changed() {
+            tool.distance(event.distance());
+        }
+        if event.tilt_has_changed() {
+            tool.tilt(event.tilt());
+        }
+        if event.slider_has_changed() {
+            tool.slider_position(event.slider_position());
+        }
+        if event.rotation_has_changed() {
+            tool.rotation(event.rotation());
+        }
+        if event.wheel_has_changed() {
+            tool.wheel(event.wheel_delta(), event.wheel_delta_discrete());
+        }
+
+        let under = utils::surface_under(&trayle.frontend.space, location);
+        tool.motion(location, under, &tablet, SERIAL_COUNTER.next_serial(), event.time_msec());
+    }
+
+    /// a tool is advertised with its capabilities the first time it comes in proximity
+    fn tablet_tool_proximity<B: InputBackend>(event: B::TabletToolProximityEvent, trayle: &mut Trayle) {
+        let Some(location) = self::tablet_tool_location(&event, trayle) else {
+            return;
+        };
+
+        let dh = trayle.dh.clone();
+        let tablet_seat = trayle.seat.tablet_seat();
+        let tool = tablet_seat.add_tool::<Trayle>(trayle, &dh, &event.tool());
+        self::pointer_motion_to(location, event.time_msec(), trayle);
+
+        let Some(tablet) = tablet_seat.get_tablet(&TabletDescriptor::from(&event.device())) else {
+            return;
+        };
+        match event.state() {
+            ProximityState::In => {
+                let Some(under) = utils::surface_under(&trayle.frontend.space, location) else {
+                    return;
+                };
+                tool.proximity_in(location, under, &tablet, SERIAL_COUNTER.next_serial(), event.time_msec());
+            }
+            ProximityState::Out => tool.proximity_out(event.time_msec()),
+        }
+    }
+
+    fn tablet_tool_tip<B: InputBackend>(event: B::TabletToolTipEvent, trayle: &mut Trayle) {
+        let Some(tool) = trayle.seat.tablet_seat().get_tool(&event.tool()) else {
+            return;
+        };
+        match event.tip_state() {
+            TabletToolTipState::Down => tool.tip_down(SERIAL_COUNTER.next_serial(), event.time_msec()),
+            TabletToolTipState::Up => tool.tip_up(event.time_msec()),
+        }
+    }
+
+    fn tablet_tool_button<B: InputBackend>(event: B::TabletToolButtonEvent, trayle: &mut Trayle) {
+        let Some(tool) = trayle.seat.tablet_seat().get_tool(&event.tool()) else {
+            return;
+        };
+        tool.button(event.button(), event.button_state(), SERIAL_COUNTER.next_serial(), event.time_msec());
+    }
+
+    /// closing the lid powers off internal panels, see [`Trayle::set_lid_closed`]
+    fn switch_toggle<B: InputBackend>(event: impl SwitchToggleEvent<B>, trayle: &mut Trayle) {
+        if event.switch() == Some(Switch::Lid) {
+            trayle.set_lid_closed(event.state() == SwitchState::On);
+        }
+    }
+
+    /// a swipe of [`WORKSPACE_SWIPE_FINGERS`] is kept from clients, see [`gesture_swipe_end`]
+    fn gesture_swipe_begin<B: InputBackend>(event: impl GestureBeginEvent<B>, trayle: &mut Trayle) {
+        if event.fingers() == WORKSPACE_SWIPE_FINGERS {
+            trayle.frontend.workspace_swipe = Some(0.0);
+            return;
+        }
+
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_swipe_begin(trayle, &GestureSwipeBeginEvent {
+            serial: SERIAL_COUNTER.next_serial(),
+            time: event.time_msec(),
+            fingers: event.fingers(),
+        });
+    }
+
+    fn gesture_swipe_update<B: InputBackend>(event: B::GestureSwipeUpdateEvent, trayle: &mut Trayle) {
+        if let Some(distance) = trayle.frontend.workspace_swipe.as_mut() {
+            *distance += event.delta_x();
+            return;
+        }
+
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_swipe_update(trayle, &GestureSwipeUpdateEvent {
+            time: event.time_msec(),
+            delta: event.delta(),
+        });
+    }
+
+    /// a workspace swipe to the left shows the next workspace, to the right the previous one
+    fn gesture_swipe_end<B: InputBackend>(event: impl GestureEndEvent<B>, trayle: &mut Trayle) {
+        if let Some(distance) = trayle.frontend.workspace_swipe.take() {
+            if event.cancelled() || distance.abs() < WORKSPACE_SWIPE_DISTANCE {
+                return;
+            }
+            let active = trayle.frontend.active_workspace;
+            let index = match distance < 0.0 {
+                true => active + 1,
+                false => active.saturating_sub(1),
+            };
+            if index < trayle.frontend.workspaces.len() {
+                trayle.switch_workspace(index);
+            }
+            return;
+        }
+
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_swipe_end(trayle, &GestureSwipeEndEvent {
+            serial: SERIAL_COUNTER.next_serial(),
+            time: event.time_msec(),
+            cancelled: event.cancelled(),
+        });
+    }
+
+    fn gesture_pinch_begin<B: InputBackend>(event: impl GestureBeginEvent<B>, trayle: &mut Trayle) {
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_pinch_begin(trayle, &GesturePinchBeginEvent {
+            serial: SERIAL_COUNTER.next_serial(),
+            time: event.time_msec(),
+            fingers: event.fingers(),
+        });
+    }
+
+    fn gesture_pinch_update<B: InputBackend>(event: B::GesturePinchUpdateEvent, trayle: &mut Trayle) {
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_pinch_update(trayle, &GesturePinchUpdateEvent {
+            time: event.time_msec(),
+            delta: event.delta(),
+            scale: event.scale(),
+            rotation: event.rotation(),
+        });
+    }
 
-        let frame_mode = match surface.disable_direct_scanout {
-            true => FrameFlags::empty(),
-            false => FrameFlags::DEFAULT,
-        };
+    fn gesture_pinch_end<B: InputBackend>(event: impl GestureEndEvent<B>, trayle: &mut Trayle) {
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_pinch_end(trayle, &GesturePinchEndEvent {
+            serial: SERIAL_COUNTER.next_serial(),
+            time: event.time_msec(),
+            cancelled: event.cancelled(),
+        });
+    }
 
-        let (rendered, render_elements_states) = surface
-            .drm_output
-            .render_frame(renderer, &elements, config.clear_color, frame_mode)
-            .map(|render_frame_result|{
-                // renderer_sync feature
-                (!render_frame_result.is_empty,render_frame_result.states)
-            })
-            .unwrap();
+    fn gesture_hold_begin<B: InputBackend>(event: impl GestureBeginEvent<B>, trayle: &mut Trayle) {
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_hold_begin(trayle, &GestureHoldBeginEvent {
+            serial: SERIAL_COUNTER.next_serial(),
+            time: event.time_msec(),
+            fingers: event.fingers(),
+        });
+    }
 
-        for window in space.elements() {
-            window.with_surfaces(|surface,states|{
-                smithay::desktop::utils::update_surface_primary_scanout_output(
-                    surface, output, states, &render_elements_states,
-                    smithay::backend::renderer::element::default_primary_scanout_output_compare
-                );
-            });
-        }
+    fn gesture_hold_end<B: InputBackend>(event: impl GestureEndEvent<B>, trayle: &mut Trayle) {
+        let pointer = trayle.pointer.clone();
+        pointer.gesture_hold_end(trayle, &GestureHoldEndEvent {
+            serial: SERIAL_COUNTER.next_serial(),
+            time: event.time_msec(),
+            cancelled: event.cancelled(),
+        });
+    }
+}
 
-        let map = smithay::desktop::layer_map_for_output(output);
-        for layer_surface in map.layers() {
-            layer_surface.with_surfaces(|surface,states|{
-                smithay::desktop::utils::update_surface_primary_scanout_output(
-                    surface, output, states, &render_elements_states,
-                    smithay::backend::renderer::element::default_primary_scanout_output_compare
-                );
-            });
-        }
 
+/// utilities for combining multiple domain logic
+pub mod utils {
+    use smithay::{utils::{Rectangle, Size}, wayland::shell::xdg::PopupSurface};
 
+    use super::*;
 
-        if rendered {
-            let mut output_presentation_feedback = OutputPresentationFeedback::new(output);
-            for window in space.elements() {
-                if space.outputs_for_element(window).contains(output) {
-                    window.take_presentation_feedback(
-                        &mut output_presentation_feedback,
-                        smithay::desktop::utils::surface_primary_scanout_output,
-                        |surface,_| smithay::desktop::utils::surface_presentation_feedback_flags_from_states(
-                            surface, &render_elements_states
-                        ),
-                    );
-                }
+    /// flush pending events to every client, `false` when a client socket is full
+    ///
+    /// clients with a full socket buffer keep their events queued until the next flush,
+    /// see [`Trayle::flush_clients`]
+    pub fn flush_clients(dh: &mut DisplayHandle) -> bool {
+        match dh.flush_clients() {
+            Ok(()) => true,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                tracing::trace!("client socket is full, retrying once it is writable");
+                false
             }
-            let map = smithay::desktop::layer_map_for_output(output);
-            for layer_surface in map.layers() {
-                layer_surface.take_presentation_feedback(
-                    &mut output_presentation_feedback,
-                    smithay::desktop::utils::surface_primary_scanout_output,
-                    |surface,_| smithay::desktop::utils::surface_presentation_feedback_flags_from_states(
-                        surface, &render_elements_states
-                    ),
-                );
+            Err(err) => {
+                tracing::error!("failed to flush clients in display handle: {err}");
+                true
             }
-            surface
-                .drm_output
-                .queue_frame(Some(output_presentation_feedback))
-                .unwrap();
         }
+    }
 
-        Ok((rendered,render_elements_states))
+    /// built-in laptop panel, eg: `eDP-1` or `LVDS-1`, turned off while the lid is closed
+    pub fn is_internal_panel(connector: &connector::Info) -> bool {
+        matches!(
+            connector.interface(),
+            connector::Interface::EmbeddedDisplayPort | connector::Interface::LVDS | connector::Interface::DSI
+        )
     }
 
-    pub fn frame_finish(node: DrmNode, crtc: crtc::Handle, meta: &mut Option<DrmEventMetadata>, trayle: &mut Trayle) {
-        let device_backend = match trayle.backend.devices.get_mut(&node) {
-            Some(some) => some,
-            None => {
-                tracing::error!("attempt to finish frame on non-existent crtc {crtc:?}");
-                return;
-            },
-        };
+    /// output name of a connector, eg: `DP-1`
+    pub fn connector_name(connector: &connector::Info) -> String {
+        format!("{}-{}", connector.interface().as_str(), connector.interface_id())
+    }
 
-        let surface = match device_backend.surfaces.get_mut(&crtc) {
-            Some(some) => some,
-            None => {
-                tracing::error!("attempt to finish frame on non-existent crtc {crtc:?}");
-                return;
-            },
-        };
+    /// clamp `location` into the union of all mapped output geometries
+    ///
+    /// if `location` is outside every output, it is moved to the nearest point of the
+    /// nearest output
+    pub fn clamp_to_outputs(space: &Space<WindowElement>, location: Point<f64, Logical>) -> Point<f64, Logical> {
+        let geometries = space
+            .outputs()
+            .filter_map(|output|space.output_geometry(output))
+            .collect::<Vec<_>>();
 
-        let Some(output) = trayle.frontend.space.outputs().find(|o|{
-            o.user_data().get::<UdevOutputId>() == Some(&UdevOutputId {
-                device_id: surface.device_id, crtc
+        if geometries.iter().any(|geo|geo.to_f64().contains(location)) {
+            return location;
+        }
+
+        geometries
+            .iter()
+            .map(|geo|clamp_to_rect(*geo, location))
+            .min_by(|a, b|{
+                let a = (a.x - location.x).powi(2) + (a.y - location.y).powi(2);
+                let b = (b.x - location.x).powi(2) + (b.y - location.y).powi(2);
+                a.total_cmp(&b)
             })
-        }).cloned()
-        else {
-            return;
-        };
+            .unwrap_or(location)
+    }
 
-        let tp = meta.as_ref().and_then(|meta|match meta.time {
-            smithay::backend::drm::DrmEventTime::Monotonic(tp) => Some(tp),
-            smithay::backend::drm::DrmEventTime::Realtime(_) => None,
-        });
+    fn clamp_to_rect(rect: Rectangle<i32, Logical>, location: Point<f64, Logical>) -> Point<f64, Logical> {
+        let rect = rect.to_f64();
+        // keep the pointer inside, `Rectangle::contains` excludes the far edges
+        let max_x = rect.loc.x + (rect.size.w - 1.0).max(0.0);
+        let max_y = rect.loc.y + (rect.size.h - 1.0).max(0.0);
+        (location.x.clamp(rect.loc.x, max_x), location.y.clamp(rect.loc.y, max_y)).into()
+    }
 
-        let seq = meta.as_ref().map(|meta|meta.sequence).unwrap_or(0);
+    /// output geometry excluding the exclusive zones of its layer surfaces
+    pub fn output_usable_geometry(space: &Space<WindowElement>, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        let geo = space.output_geometry(output)?;
+        let map = desktop::layer_map_for_output(output);
+        let zone = map.non_exclusive_zone();
+        Some(Rectangle::new(geo.loc + zone.loc, zone.size))
+    }
 
-        let (clock,flags) = match tp {
-            Some(tp) => (tp.into(),
-                wp_presentation_feedback::Kind::Vsync
-                | wp_presentation_feedback::Kind::HwClock
-                | wp_presentation_feedback::Kind::HwCompletion
-            ),
-            None => (trayle.clock.now(),
-                wp_presentation_feedback::Kind::Vsync
-            ),
+    /// offset between cascaded windows
+    const CASCADE_OFFSET: i32 = 32;
+
+    /// location of a newly mapped window inside `area`
+    ///
+    /// the first window in `area` is centered, following windows are cascaded from the
+    /// topmost window, wrapping back to the area edge when it runs off the output
+    pub fn place_window(
+        space: &Space<WindowElement>,
+        area: Rectangle<i32, Logical>,
+        size: Option<Size<i32, Logical>>,
+    ) -> Point<i32, Logical> {
+        // client usually does not know its size before the initial configure
+        let size = size
+            .filter(|size|size.w > 0 && size.h > 0)
+            .unwrap_or_else(||(area.size.w / 2, area.size.h / 2).into());
+
+        let last = space
+            .elements()
+            .rev()
+            .filter_map(|window|space.element_location(window))
+            .find(|location|area.contains(*location));
+
+        let Some(last) = last else {
+            let x = area.loc.x + ((area.size.w - size.w) / 2).max(0);
+            let y = area.loc.y + ((area.size.h - size.h) / 2).max(0);
+            return (x,y).into();
         };
 
-        let submit_result = surface
-            .drm_output
-            .frame_submitted()
-            .map_err(Into::<SwapBuffersError>::into);
+        let mut location = last + Point::from((CASCADE_OFFSET, CASCADE_OFFSET));
+        if location.x + size.w > area.loc.x + area.size.w {
+            location.x = area.loc.x;
+        }
+        if location.y + size.h > area.loc.y + area.size.h {
+            location.y = area.loc.y;
+        }
+        location
+    }
 
-        let Some(frame_duration) = output
-            .current_mode()
-            .map(|mode|Duration::from_secs_f64(1_000f64/mode.refresh as f64))
+    /// unconstrain popup against the output its parent window is mostly on
+    pub fn unconstrain_popup(space: &Space<WindowElement>, popup: &PopupSurface) {
+        let Ok(root) = desktop::find_popup_root_surface(&PopupKind::Xdg(popup.clone())) else {
+            return;
+        };
+
+        let Some(window) = space
+            .elements()
+            .find(|window|window.toplevel().map(|t|t.wl_surface() == &root).unwrap_or(false))
         else {
-            return
+            return;
         };
 
-        let schedule_render = match submit_result {
-            Ok(user_data) => {
-                if let Some(mut feedback) = user_data.flatten() {
-                    feedback.presented(clock, Refresh::Fixed(frame_duration), seq as u64, flags);
-                }
-                true
-            },
-            Err(err) => {
-                tracing::warn!("rendering error: {err:?}");
-                match err {
-                    SwapBuffersError::AlreadySwapped => true,
-                    // If the device has been deactivated do not reschedule, this will be done
-                    // by session resume
-                    SwapBuffersError::TemporaryFailure(err)
-                        if matches!(err.downcast_ref::<DrmError>(),Some(&DrmError::DeviceInactive)) =>
-                    {
-                        false
-                    }
-                    SwapBuffersError::TemporaryFailure(err) => matches!{
-                        err.downcast_ref::<DrmError>(),
-                        Some(DrmError::Access(DrmAccessError { source, .. }))
-                            if source.kind() == std::io::ErrorKind::PermissionDenied,
-                    },
-                    SwapBuffersError::ContextLost(err) => panic!("rendering loop lost: {err}")
-                }
-            },
+        let Some(window_geometry) = space.element_geometry(window) else {
+            return;
         };
 
-        if schedule_render {
-            let next_frame_target = clock + frame_duration;
+        let output_geometry = space
+            .outputs()
+            .filter_map(|output|space.output_geometry(output))
+            .filter_map(|geo|Some((geo, geo.intersection(window_geometry)?)))
+            .max_by_key(|(_, overlap)|overlap.size.w * overlap.size.h)
+            .map(|(geo,_)|geo)
+            .or_else(||space.outputs().next().and_then(|output|space.output_geometry(output)));
 
-            // What are we trying to solve by introducing a delay here:
-            //
-            // Basically it is all about latency of client provided buffers.
-            // A client driven by frame callbacks will wait for a frame callback
-            // to repaint and submit a new buffer. As we send frame callbacks
-            // as part of the repaint in the compositor the latency would always
-            // be approx. 2 frames. By introducing a delay before we repaint in
-            // the compositor we can reduce the latency to approx. 1 frame + the
-            // remaining duration from the repaint to the next VBlank.
-            //
-            // With the delay it is also possible to further reduce latency if
-            // the client is driven by presentation feedback. As the presentation
-            // feedback is directly sent after a VBlank the client can submit a
-            // new buffer during the repaint delay that can hit the very next
-            // VBlank, thus reducing the potential latency to below one frame.
-            //
-            // Choosing a good delay is a topic on its own so we just implement
-            // a simple strategy here. We just split the duration between two
-            // VBlanks into two steps, one for the client repaint and one for the
-            // compositor repaint. Theoretically the repaint in the compositor should
-            // be faster so we give the client a bit more time to repaint. On a typical
-            // modern system the repaint in the compositor should not take more than 2ms
-            // so this should be safe for refresh rates up to at least 120 Hz. For 120 Hz
-            // this results in approx. 3.33ms time for repainting in the compositor.
-            // A too big delay could result in missing the next VBlank in the compositor.
-            //
-            // A more complete solution could work on a sliding window analyzing past repaints
-            // and do some prediction for the next repaint.
-            let repaint_delay = Duration::from_secs_f64(frame_duration.as_secs_f64() * 0.6f64);
+        let Some(mut target) = output_geometry else {
+            return;
+        };
 
-            let timer = if trayle.backend.primary_gpu != surface.render_node {
-                // However, if we need to do a copy, that might not be enough.
-                // (And without actual comparision to previous frames we cannot really know.)
-                // So lets ignore that in those cases to avoid thrashing performance.
-                tracing::trace!("scheduling repaint timer immediately on {crtc:?}");
-                Timer::immediate()
-            } else {
-                tracing::trace!("scheduling repaint timer with delay {repaint_delay:?} on {crtc:?}");
-                Timer::from_duration(repaint_delay)
-            };
+        target.loc -= desktop::get_popup_toplevel_coords(&PopupKind::Xdg(popup.clone()));
+        target.loc -= window_geometry.loc;
+        target.loc.y -= window.header_height();
 
-            trayle.handle.insert_source(timer, move|_,_,trayle|{
-                render::node(node, Some(crtc), next_frame_target, trayle);
-                TimeoutAction::Drop
-            }).expect("failed to schedule frame timer");
+        popup.with_pending_state(|state|{
+            state.geometry = state.positioner.get_unconstrained_geometry(target);
+        });
+    }
+
+    /// index of the mode closest to `wanted`
+    ///
+    /// size must match exactly, refresh rate must be within 1 Hz, if refresh rate is unset,
+    /// the highest is chosen
+    pub fn find_mode(modes: &[DrmMode], wanted: ModeConfig) -> Option<usize> {
+        const TOLERANCE_MHZ: i32 = 1000;
+
+        let candidates = modes
+            .iter()
+            .enumerate()
+            .filter(|(_,mode)|mode.size() == (wanted.width, wanted.height))
+            .map(|(i,mode)|(i,WlMode::from(*mode).refresh));
+
+        match wanted.refresh {
+            Some(refresh) => {
+                let wanted_mhz = (refresh * 1000.0).round() as i32;
+                candidates
+                    .map(|(i,mhz)|(i,(mhz - wanted_mhz).abs()))
+                    .filter(|(_,diff)|*diff <= TOLERANCE_MHZ)
+                    .min_by_key(|(_,diff)|*diff)
+                    .map(|(i,_)|i)
+            }
+            None => candidates.max_by_key(|(_,mhz)|*mhz).map(|(i,_)|i),
         }
     }
 
-    pub mod elements {
-        use smithay::backend::renderer::Renderer;
+    /// enable or disable adaptive sync on the surface
+    pub fn set_vrr(surface: &mut SurfaceData, enabled: bool) -> Result<()> {
+        let connector = surface.connector;
+        surface.drm_output.with_compositor(|compositor|{
+            if enabled {
+                match compositor.vrr_supported(connector) {
+                    Ok(VrrSupport::NotSupported) => anyhow::bail!("adaptive sync is not supported"),
+                    Ok(_) => {}
+                    Err(err) => anyhow::bail!("failed to query adaptive sync support: {err}"),
+                }
+            }
+            compositor
+                .use_vrr(enabled)
+                .map_err(|err|anyhow::anyhow!("failed to set adaptive sync: {err}"))
+        })?;
+        surface.vrr = enabled;
+        Ok(())
+    }
 
-        use super::*;
+    /// set crtc gamma ramp, `None` to reset it to linear
+    ///
+    /// `ramp` is red, green and blue ramp laid out consecutively, each of crtc `gamma_size`
+    pub fn set_gamma(drm: &DrmDevice, crtc: crtc::Handle, ramp: Option<&[u16]>) -> Result<()> {
+        let gamma_size = drm.get_crtc(crtc).context("failed to query crtc")?.gamma_length() as usize;
+        anyhow::ensure!(gamma_size > 0, "crtc does not support gamma");
+
+        let linear;
+        let ramp = match ramp {
+            Some(ramp) => {
+                anyhow::ensure!(ramp.len() == gamma_size * 3, "gamma ramp size mismatch");
+                ramp
+            }
+            None => {
+                let max = (gamma_size - 1).max(1);
+                linear = (0..gamma_size)
+                    .map(|i|(i * u16::MAX as usize / max) as u16)
+                    .collect::<Vec<_>>()
+                    .repeat(3);
+                &linear
+            }
+        };
 
-        smithay::render_elements! {
-            pub WindowRenderElement<R> where R: ImportAll + ImportMem;
-            Window=WaylandSurfaceRenderElement<R>,
-            Decoration=SolidColorRenderElement,
-        }
+        let (red, rest) = ramp.split_at(gamma_size);
+        let (green, blue) = rest.split_at(gamma_size);
+        drm.set_gamma(crtc, red, green, blue).context("failed to set gamma")?;
+        Ok(())
+    }
 
-        smithay::render_elements! {
-            pub PointerRenderElement<R> where R: ImportAll + ImportMem;
-            Surface=WaylandSurfaceRenderElement<R>,
-            Memory=MemoryRenderBufferRenderElement<R>,
-        }
+    /// test commit `mode` on the surface, the pending mode is left unchanged
+    pub fn test_mode(surface: &mut SurfaceData, mode: DrmMode) -> Result<()> {
+        surface.drm_output.with_compositor(|compositor|{
+            let drm_surface = compositor.surface();
+            let current = drm_surface.pending_mode();
+            if current == mode {
+                return Ok(());
+            }
 
-        smithay::render_elements! {
-            pub CustomRenderElements<R> where R: ImportAll + ImportMem;
-            Pointer=PointerRenderElement<R>,
-            Surface=WaylandSurfaceRenderElement<R>,
-        }
+            // atomic `use_mode` only does a test commit, the mode itself
+            // would be committed along with the next frame
+            drm_surface.use_mode(mode).context("mode rejected by drm")?;
+            drm_surface.use_mode(current).context("failed to restore pending mode")?;
+            Ok(())
+        })
+    }
 
-        smithay::render_elements! {
-            pub OutputRenderElements<R, E> where R: ImportAll + ImportMem;
-            Space=SpaceRenderElements<R, E>,
-            Window=Wrap<E>,
-            Custom=CustomRenderElements<R>,
-            Preview=CropRenderElement<RelocateRenderElement<RescaleRenderElement<WindowRenderElement<R>>>>,
+    /// duration of one frame of `mode`, from its pixel clock and totals
+    ///
+    /// the refresh rate of a mode is rounded, eg: 59.94 Hz is reported as 60 Hz,
+    /// scheduling with it drifts against the actual vblanks
+    pub fn frame_duration(mode: &DrmMode) -> Option<Duration> {
+        use smithay::reexports::drm::control::ModeFlags;
+
+        let clock = mode.clock() as u64;
+        if clock == 0 {
+            return None;
         }
 
-        /// output elements
-        pub fn outputs<R>(
-            output: &Output,
-            space: &Space<Window>,
-            renderer: &mut R,
-        ) -> Vec<SpaceRenderElements<R, WaylandSurfaceRenderElement<R>>>
-        where
-            R: Renderer + ImportAll + ImportMem,
-            R::TextureId: Clone + 'static,
-        {
-            let space_elements = smithay::desktop::space::space_render_elements::<_, Window, _>(
-                renderer,
-                [space],
-                output,
-                1.0,
-            )
-            .unwrap();
+        let (_, _, htotal) = mode.hsync();
+        let (_, _, vtotal) = mode.vsync();
+        let mut lines = vtotal as u64;
+        if mode.flags().contains(ModeFlags::DBLSCAN) {
+            lines *= 2;
+        }
+        if mode.vscan() > 1 {
+            lines *= mode.vscan() as u64;
+        }
 
-            space_elements
+        // clock is in khz
+        let nanos = htotal as u64 * lines * 1_000_000 / clock;
+        match mode.flags().contains(ModeFlags::INTERLACE) {
+            true => Some(Duration::from_nanos(nanos / 2)),
+            false => Some(Duration::from_nanos(nanos)),
         }
     }
-}
 
-pub mod input {
-    use smithay::{backend::input::{Event, KeyboardKeyEvent}, input::keyboard::{FilterResult, KeysymHandle, ModifiersState}, utils::SERIAL_COUNTER};
-    use xkbcommon::xkb::Keysym;
+    /// number of past repaints considered by [`repaint_delay`]
+    pub const REPAINT_HISTORY: usize = 16;
+    /// fewer repaints than this use the fixed fraction of the frame duration
+    const REPAINT_MIN_HISTORY: usize = 4;
+    const REPAINT_FALLBACK_FACTOR: f64 = 0.6;
+    const REPAINT_MAX_FACTOR: f64 = 0.8;
+    /// margin before vblank on top of the slowest recent repaint
+    const REPAINT_SAFETY_MARGIN: Duration = Duration::from_millis(1);
+
+    /// delay after vblank before repainting, leaving enough time for the compositor repaint
+    /// to hit the next vblank, while giving clients as much time as possible
+    pub fn repaint_delay(frame_duration: Duration, durations: &VecDeque<Duration>) -> Duration {
+        let fallback = frame_duration.mul_f64(REPAINT_FALLBACK_FACTOR);
+        if durations.len() < REPAINT_MIN_HISTORY {
+            return fallback;
+        }
 
-    use super::*;
+        // slowest repaint with half of it again, as repaint time fluctuates
+        let slowest = durations.iter().max().copied().unwrap_or_default();
+        let budget = slowest + slowest / 2 + REPAINT_SAFETY_MARGIN;
+
+        // some time is always left for the repaint, even if recent repaints were instant
+        frame_duration
+            .saturating_sub(budget)
+            .min(frame_duration.mul_f64(REPAINT_MAX_FACTOR))
+    }
 
-    fn on_keyboard(trayle: &mut Trayle, mods: &ModifiersState, handle: KeysymHandle) -> FilterResult<()> {
-        let keysym = handle.modified_sym();
-        tracing::debug!(?mods, keysym = ::xkbcommon::xkb::keysym_get_name(keysym), "keysym");
+    /// output position is also updated so that xdg-output reports the same logical geometry
+    pub fn map_output(space: &mut Space<WindowElement>, output: &Output, position: Point<i32, Logical>) {
+        output.change_current_state(None, None, None, Some(position));
+        space.map_output(output, position);
+    }
 
-        match keysym {
-            Keysym::Return if mods.logo => {
-                std::process::Command::new("alacritty")
-                    .env("WAYLAND_DISPLAY", &trayle.frontend.wlsocket)
-                    .spawn().inspect_err(|err|tracing::error!("{err}")).ok();
-                FilterResult::Intercept(())
-            }
-            Keysym::Q if mods.logo => {
-                tracing::info!("shutting down");
-                trayle.signal.stop();
-                // self.running.store(false, std::sync::atomic::Ordering::SeqCst);
-                FilterResult::Intercept(())
-            }
-            _ => FilterResult::Forward
-        }
-    }
-    pub fn handle(event: InputEvent, trayle: &mut Trayle) {
-        if let InputEvent::Keyboard { event } = event {
-            let serial = SERIAL_COUNTER.next_serial();
-            let time = event.time_msec();
-            trayle.seat.get_keyboard().unwrap().input::<(), _>(
-                trayle,
-                event.key_code(),
-                event.state(),
-                serial,
-                time,
-                self::on_keyboard,
-            );
-            return;
+    /// whether the root of `surface` is a window or a layer surface shown on any output
+    pub fn is_surface_visible(space: &Space<WindowElement>, surface: &WlSurface) -> bool {
+        let mut root = surface.clone();
+        while let Some(parent) = compositor::get_parent(&root) {
+            root = parent;
         }
 
-        // match event {
-        //     InputEvent::DeviceAdded { device } => todo!(),
-        //     InputEvent::DeviceRemoved { device } => todo!(),
-        //     InputEvent::Keyboard { event } => todo!(),
-        //     InputEvent::PointerMotion { event } => todo!(),
-        //     InputEvent::PointerMotionAbsolute { event } => todo!(),
-        //     InputEvent::PointerButton { event } => todo!(),
-        //     InputEvent::PointerAxis { event } => todo!(),
-        //     InputEvent::GestureSwipeBegin { event } => todo!(),
-        //     InputEvent::GestureSwipeUpdate { event } => todo!(),
-        //     InputEvent::GestureSwipeEnd { event } => todo!(),
-        //     InputEvent::GesturePinchBegin { event } => todo!(),
-        //     InputEvent::GesturePinchUpdate { event } => todo!(),
-        //     InputEvent::GesturePinchEnd { event } => todo!(),
-        //     InputEvent::GestureHoldBegin { event } => todo!(),
-        //     InputEvent::GestureHoldEnd { event } => todo!(),
-        //     InputEvent::TouchDown { event } => todo!(),
-        //     InputEvent::TouchMotion { event } => todo!(),
-        //     InputEvent::TouchUp { event } => todo!(),
-        //     InputEvent::TouchCancel { event } => todo!(),
-        //     InputEvent::TouchFrame { event } => todo!(),
-        //     InputEvent::TabletToolAxis { event } => todo!(),
-        //     InputEvent::TabletToolProximity { event } => todo!(),
-        //     InputEvent::TabletToolTip { event } => todo!(),
-        //     InputEvent::TabletToolButton { event } => todo!(),
-        //     InputEvent::SwitchToggle { event } => todo!(),
-        //     InputEvent::Special(_) => todo!(),
-        // }
+        let is_window = space.elements().any(|window|{
+            window.toplevel().is_some_and(|toplevel|toplevel.wl_surface() == &root)
+                && !space.outputs_for_element(window).is_empty()
+        });
+
+        is_window || space.outputs().any(|output|{
+            desktop::layer_map_for_output(output)
+                .layer_for_surface(&root, WindowSurfaceType::TOPLEVEL)
+                .is_some()
+        })
     }
-}
 
+    /// the surface under `location` and its location in global coordinate
+    ///
+    /// follows the render order, overlay and top layers are above windows, a fullscreen
+    /// window only has overlay layers above it, and bottom and background layers are below
+    pub fn surface_under(
+        space: &Space<WindowElement>,
+        location: Point<f64, Logical>,
+    ) -> Option<(WlSurface, Point<f64, Logical>)> {
+        let output = space.output_under(location).next();
+
+        let layer_under = |layer: Layer|{
+            let output = output?;
+            let output_location = space.output_geometry(output)?.loc;
+            let map = desktop::layer_map_for_output(output);
+            let relative = location - output_location.to_f64();
+            let layer_surface = map.layer_under(layer, relative)?;
+            let layer_location = map.layer_geometry(layer_surface)?.loc;
+            layer_surface
+                .surface_under(relative - layer_location.to_f64(), WindowSurfaceType::ALL)
+                .map(|(surface, surface_location)|{
+                    (surface, (surface_location + layer_location + output_location).to_f64())
+                })
+        };
 
+        let window_under = |window: &WindowElement, window_location: Point<i32, Logical>|{
+            window
+                .surface_under(location - window_location.to_f64(), WindowSurfaceType::ALL)
+                .map(|(surface, surface_location)|(surface, (surface_location + window_location).to_f64()))
+        };
 
-/// utilities for combining multiple domain logic
-pub mod utils {
-    use super::*;
+        let fullscreen = output
+            .and_then(|output|output.user_data().get::<FullscreenSurface>()?.get())
+            .and_then(|window|{
+                let window_location = space.element_location(&window)?;
+                Some((window, window_location))
+            });
+
+        if let Some((window, window_location)) = fullscreen {
+            return layer_under(Layer::Overlay).or_else(||window_under(&window, window_location));
+        }
+
+        layer_under(Layer::Overlay)
+            .or_else(||layer_under(Layer::Top))
+            .or_else(||{
+                let (window, window_location) = space.element_under(location)?;
+                window_under(window, window_location)
+            })
+            .or_else(||layer_under(Layer::Bottom))
+            .or_else(||layer_under(Layer::Background))
+    }
 
     pub fn get_surface_dmabuf_feedback(
         primary_gpu: DrmNode,
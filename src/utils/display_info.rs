@@ -5,6 +5,52 @@ use smithay::reexports::drm::control::{
 
 use libdisplay_info::info::Info as DisplayInfo;
 
+/// monitor identity from its edid, eg: `Dell Inc. DELL U2720Q 1A2B3C`
+///
+/// stored in the output user data, stays the same when the monitor is plugged to another connector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdidName(pub String);
+
+/// parsed edid of a connected monitor, cached per connector until it disconnects
+#[derive(Debug, Clone, Default)]
+pub struct MonitorInfo {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+}
+
+impl MonitorInfo {
+    /// parse the edid of `connector`, every field is `None` if it has no valid edid
+    pub fn parse(device: &impl ControlDevice, connector: connector::Handle) -> MonitorInfo {
+        let Some(info) = for_connectors(device, connector) else {
+            return MonitorInfo::default();
+        };
+        MonitorInfo {
+            make: info.make(),
+            model: info.model(),
+            serial: info.serial(),
+        }
+    }
+
+    pub fn make(&self) -> String {
+        self.make.clone().unwrap_or_else(||"Unknown".into())
+    }
+
+    pub fn model(&self) -> String {
+        self.model.clone().unwrap_or_else(||"Unknown".into())
+    }
+
+    /// make, model and serial, `None` if none of them is known
+    pub fn edid_name(&self) -> Option<EdidName> {
+        let parts = [&self.make, &self.model, &self.serial]
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        (!parts.is_empty()).then(||EdidName(parts.join(" ")))
+    }
+}
+
 pub fn for_connectors(device: &impl ControlDevice, connector: connector::Handle) -> Option<DisplayInfo> {
     let props = device.get_properties(connector).ok()?;
 
@@ -1,22 +1,120 @@
+//! connector scanner, with a pluggable crtc mapping policy
+//!
+//! [`DrmScanner::new`] uses the [`SimpleCrtcMapper`], a custom policy, eg: prefer the crtc
+//! with the most overlay planes or pin a connector to a crtc, implements [`CrtcMapper`]
+//! and is given to [`DrmScanner::new_with_mapper`]:
+//!
+//! ```ignore
+//! struct PinnedMapper {
+//!     pins: HashMap<connector::Handle, crtc::Handle>,
+//!     mapped: HashMap<connector::Handle, crtc::Handle>,
+//! }
+//!
+//! impl CrtcMapper for PinnedMapper {
+//!     fn map_crtc<'a, D: ScanDevice>(&mut self, drm: &D, connectors: impl Iterator<Item = &'a D::Connector> + Clone)
+//!     where
+//!         D::Connector: 'a,
+//!     {
+//!         // release crtcs of disconnected connectors, map connected ones to their pinned crtc
+//!     }
+//!
+//!     fn crtc_for_connector(&self, connector: &connector::Handle) -> Option<crtc::Handle> {
+//!         self.mapped.get(connector).copied()
+//!     }
+//! }
+//!
+//! let scanner = DrmScanner::new_with_mapper(PinnedMapper::new());
+//! ```
+//!
+//! the scanner reads devices through [`ScanDevice`], implemented for every [`ControlDevice`]
 pub use connector_scanner::{ConnectorScanEvent, ConnectorScanResult, ConnectorScanner};
 use smithay::reexports::drm::control::{connector, crtc, Device as ControlDevice};
 
 mod connector_scanner;
-use std::{collections::HashMap, iter::{Chain, Map}};
+use std::{collections::{HashMap, HashSet}, iter::{Chain, Map}};
 
 mod crtc_mapper;
 pub use crtc_mapper::{CrtcMapper, SimpleCrtcMapper};
 
-#[derive(Default)]
-pub struct DrmScanner<Mapper = SimpleCrtcMapper>
+/// connector as read by a [`ScanDevice`]
+pub trait ScanConnector: Clone {
+    fn handle(&self) -> connector::Handle;
+    fn state(&self) -> connector::State;
+}
+
+impl ScanConnector for connector::Info {
+    fn handle(&self) -> connector::Handle {
+        connector::Info::handle(self)
+    }
+
+    fn state(&self) -> connector::State {
+        connector::Info::state(self)
+    }
+}
+
+/// what the scanner and crtc mappers read from a drm device
+pub trait ScanDevice {
+    type Connector: ScanConnector;
+
+    /// every connector of the device, with its current state
+    fn connectors(&self) -> std::io::Result<Vec<Self::Connector>>;
+
+    /// crtc currently driving `connector`, eg: left by the previous drm master
+    fn current_crtc(&self, connector: &Self::Connector) -> Option<crtc::Handle>;
+
+    /// crtcs able to drive `connector`, by order of its encoders
+    fn possible_crtcs(&self, connector: &Self::Connector) -> Vec<crtc::Handle>;
+}
+
+impl<T: ControlDevice> ScanDevice for T {
+    type Connector = connector::Info;
+
+    fn connectors(&self) -> std::io::Result<Vec<connector::Info>> {
+        let res_handles = self.resource_handles()?;
+        Ok(res_handles
+            .connectors()
+            .iter()
+            .filter_map(|conn|self.get_connector(*conn, true).ok())
+            .collect())
+    }
+
+    fn current_crtc(&self, connector: &connector::Info) -> Option<crtc::Handle> {
+        self.get_encoder(connector.current_encoder()?).ok()?.crtc()
+    }
+
+    fn possible_crtcs(&self, connector: &connector::Info) -> Vec<crtc::Handle> {
+        let Ok(res_handles) = self.resource_handles() else {
+            return vec![];
+        };
+        connector
+            .encoders()
+            .iter()
+            .flat_map(|encoder|self.get_encoder(*encoder))
+            .flat_map(|encoder|res_handles.filter_crtcs(encoder.possible_crtcs()))
+            .collect()
+    }
+}
+
+pub struct DrmScanner<Mapper = SimpleCrtcMapper, C = connector::Info>
 where
     Mapper: CrtcMapper,
 {
-    connectors: ConnectorScanner,
+    connectors: ConnectorScanner<C>,
     crtc_mapper: Mapper,
+    /// connectors not given to the crtc mapper, see [`DrmScanner::set_enabled`]
+    disabled: HashSet<connector::Handle>,
+}
+
+impl<Mapper, C> Default for DrmScanner<Mapper, C>
+where
+    Mapper: CrtcMapper + Default,
+{
+    fn default() -> Self {
+        Self::new_with_mapper(Mapper::default())
+    }
 }
 
-impl<Mapper> DrmScanner<Mapper>
+impl<Mapper, C> DrmScanner<Mapper, C>
 where
     Mapper: CrtcMapper + Default,
 {
@@ -25,18 +123,13 @@ where
     }
 }
 
-impl<Mapper> DrmScanner<Mapper>
+impl<Mapper, C> DrmScanner<Mapper, C>
 where
     Mapper: CrtcMapper,
 {
+    /// scanner with a custom crtc mapping policy
     pub fn new_with_mapper(crtc_mapper: Mapper) -> Self {
-        Self { crtc_mapper, connectors: Default::default(), }
-    }
-
-    pub fn crtcs(&self) -> impl Iterator<Item = (&connector::Info, crtc::Handle)> {
-        self.connectors()
-            .iter()
-            .filter_map(|(handle,info)|Some((info,self.crtc_for_connector(handle)?)))
+        Self { crtc_mapper, connectors: Default::default(), disabled: Default::default() }
     }
 
     pub fn crtc_mapper(&self) -> &Mapper {
@@ -47,7 +140,7 @@ where
         &mut self.crtc_mapper
     }
 
-    pub fn connectors(&self) -> &HashMap<connector::Handle, connector::Info> {
+    pub fn connectors(&self) -> &HashMap<connector::Handle, C> {
         self.connectors.connectors()
     }
 
@@ -55,8 +148,74 @@ where
         self.crtc_mapper.crtc_for_connector(connector)
     }
 
-    pub fn scan_connectors(&mut self, drm: &impl ControlDevice) -> std::io::Result<DrmScanResult> {
+    pub fn is_enabled(&self, connector: &connector::Handle) -> bool {
+        !self.disabled.contains(connector)
+    }
+}
+
+impl<Mapper, C> DrmScanner<Mapper, C>
+where
+    Mapper: CrtcMapper,
+    C: ScanConnector,
+{
+    pub fn crtcs(&self) -> impl Iterator<Item = (&C, crtc::Handle)> {
+        self.connectors()
+            .iter()
+            .filter_map(|(handle,info)|Some((info,self.crtc_for_connector(handle)?)))
+    }
+
+    /// enable or disable driving `connector`, a disabled connector releases its crtc
+    ///
+    /// a connected connector is reported as disconnected when disabled and as connected
+    /// when enabled again, connectors that got the released crtc are reported as connected
+    pub fn set_enabled(&mut self, drm: &impl ScanDevice<Connector = C>, connector: connector::Handle, enabled: bool) -> DrmScanResult<C> {
+        let changed = match enabled {
+            true => self.disabled.remove(&connector),
+            false => self.disabled.insert(connector),
+        };
+        let Some(info) = self.connectors()
+            .get(&connector)
+            .filter(|info|info.state() == connector::State::Connected)
+            .cloned()
+        else {
+            return DrmScanResult::default();
+        };
+        if !changed {
+            return DrmScanResult::default();
+        }
+
+        let crtc = self.crtc_for_connector(&connector);
+        let mut crtcless = self.crtcless();
+        crtcless.retain(|conn|conn.handle() != connector);
+
+        self.map_crtcs(drm);
+        let reassigned = self.reassigned(crtcless);
+
+        match enabled {
+            true => {
+                let crtc = self.crtc_for_connector(&connector);
+                DrmScanResult {
+                    connected: std::iter::once((info,crtc)).chain(reassigned).collect(),
+                    disconnected: vec![],
+                }
+            },
+            false => DrmScanResult {
+                connected: reassigned.collect(),
+                disconnected: vec![(info,crtc)],
+            },
+        }
+    }
+
+    /// scan connectors state changes and map crtcs for connected connectors
+    ///
+    /// connectors that stayed connected without a crtc are reported as connected
+    /// once a crtc freed by a disconnected connector is mapped to them
+    pub fn scan_connectors(&mut self, drm: &impl ScanDevice<Connector = C>) -> std::io::Result<DrmScanResult<C>> {
         let scan = self.connectors.scan(drm)?;
+
+        let mut crtcless = self.crtcless();
+        crtcless.retain(|conn|scan.connected.iter().all(|added|added.handle() != conn.handle()));
+
         let removed = scan.disconnected
             .into_iter()
             .map(|conn|{
@@ -65,74 +224,247 @@ where
             })
             .collect();
 
-        self.crtc_mapper
-            .map(drm, self.connectors.connectors().iter().map(|(_,conn)|conn));
+        self.map_crtcs(drm);
+        let reassigned = self.reassigned(crtcless);
 
         let added = scan.connected
             .into_iter()
+            .filter(|conn|self.is_enabled(&conn.handle()))
             .map(|conn|{
                 let crtc = self.crtc_mapper.crtc_for_connector(&conn.handle());
                 (conn,crtc)
             })
+            .chain(reassigned)
             .collect();
 
         Ok(DrmScanResult { connected: added, disconnected: removed })
     }
+
+    /// connected and enabled connectors without a crtc
+    fn crtcless(&self) -> Vec<C> {
+        self.connectors()
+            .values()
+            .filter(|conn|conn.state() == connector::State::Connected)
+            .filter(|conn|self.is_enabled(&conn.handle()))
+            .filter(|conn|self.crtc_for_connector(&conn.handle()).is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// map crtcs of enabled connectors, disabled ones release their crtc
+    fn map_crtcs(&mut self, drm: &impl ScanDevice<Connector = C>) {
+        let disabled = &self.disabled;
+        self.crtc_mapper.map_crtc(
+            drm,
+            self.connectors.connectors().values().filter(|conn|!disabled.contains(&conn.handle())),
+        );
+    }
+
+    /// `crtcless` connectors that got a crtc from the last mapping
+    fn reassigned(&self, crtcless: Vec<C>) -> impl Iterator<Item = DrmScanItem<C>> + '_ {
+        crtcless
+            .into_iter()
+            .filter_map(|conn|{
+                let crtc = self.crtc_for_connector(&conn.handle())?;
+                Some((conn,Some(crtc)))
+            })
+    }
 }
 
-type DrmScanItem = (connector::Info,Option<crtc::Handle>);
+type DrmScanItem<C> = (C,Option<crtc::Handle>);
 
-#[derive(Default,Clone)]
-pub struct DrmScanResult {
-    pub connected: Vec<DrmScanItem>,
-    pub disconnected: Vec<DrmScanItem>,
+#[derive(Clone)]
+pub struct DrmScanResult<C = connector::Info> {
+    pub connected: Vec<DrmScanItem<C>>,
+    pub disconnected: Vec<DrmScanItem<C>>,
+}
+
+impl<C> Default for DrmScanResult<C> {
+    fn default() -> Self {
+        Self { connected: vec![], disconnected: vec![] }
+    }
 }
 
-impl DrmScanResult {
-    pub fn iter(&self) -> impl Iterator<Item = DrmScanEvent> {
+impl<C: Clone> DrmScanResult<C> {
+    pub fn iter(&self) -> impl Iterator<Item = DrmScanEvent<C>> {
         self.clone().into_iter()
     }
 }
 
 #[derive(Clone)]
-pub enum DrmScanEvent {
+pub enum DrmScanEvent<C = connector::Info> {
     Connected {
-        connector: connector::Info,
-        crtc: Option<crtc::Handle>
+        connector: C,
+        crtc: crtc::Handle
+    },
+    /// connector is connected but every crtc it can use is taken
+    ///
+    /// it is reported as [`DrmScanEvent::Connected`] by a later scan once a crtc is released
+    ConnectedNoCrtc {
+        connector: C,
     },
     Disconnected {
-        connector: connector::Info,
+        connector: C,
         crtc: Option<crtc::Handle>
     }
 }
 
-impl DrmScanEvent {
-    fn connected((connector, crtc): DrmScanItem) -> Self {
-        Self::Connected { connector, crtc }
+impl<C> DrmScanEvent<C> {
+    fn connected((connector, crtc): DrmScanItem<C>) -> Self {
+        match crtc {
+            Some(crtc) => Self::Connected { connector, crtc },
+            None => Self::ConnectedNoCrtc { connector },
+        }
     }
-    fn disconnected((connector, crtc): DrmScanItem) -> Self {
+    fn disconnected((connector, crtc): DrmScanItem<C>) -> Self {
         Self::Disconnected { connector, crtc }
     }
 }
 
-type DrmScanItemToEvent = fn(DrmScanItem) -> DrmScanEvent;
+type DrmScanItemToEvent<C> = fn(DrmScanItem<C>) -> DrmScanEvent<C>;
 
-impl IntoIterator for DrmScanResult {
-    type Item = DrmScanEvent;
+impl<C> IntoIterator for DrmScanResult<C> {
+    type Item = DrmScanEvent<C>;
     type IntoIter = Chain<
-        Map<std::vec::IntoIter<DrmScanItem>, DrmScanItemToEvent>,
-        Map<std::vec::IntoIter<DrmScanItem>, DrmScanItemToEvent>,
+        Map<std::vec::IntoIter<DrmScanItem<C>>, DrmScanItemToEvent<C>>,
+        Map<std::vec::IntoIter<DrmScanItem<C>>, DrmScanItemToEvent<C>>,
     >;
 
     fn into_iter(self) -> Self::IntoIter {
         self.disconnected
             .into_iter()
-            .map(DrmScanEvent::disconnected as DrmScanItemToEvent)
+            .map(DrmScanEvent::disconnected as DrmScanItemToEvent<C>)
             .chain(
                 self.connected
                     .into_iter()
-                    .map(DrmScanEvent::connected as DrmScanItemToEvent)
+                    .map(DrmScanEvent::connected as DrmScanItemToEvent<C>)
             )
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use smithay::reexports::drm::control::{connector::State, from_u32};
+
+    use super::*;
+
+    #[derive(Clone)]
+    struct FakeConnector {
+        handle: connector::Handle,
+        state: State,
+    }
+
+    impl ScanConnector for FakeConnector {
+        fn handle(&self) -> connector::Handle {
+            self.handle
+        }
+
+        fn state(&self) -> State {
+            self.state
+        }
+    }
+
+    /// device where every crtc can drive every connector
+    struct FakeDevice {
+        connectors: Vec<FakeConnector>,
+        crtcs: Vec<crtc::Handle>,
+    }
+
+    impl FakeDevice {
+        fn set_state(&mut self, connector: connector::Handle, state: State) {
+            match self.connectors.iter_mut().find(|conn|conn.handle == connector) {
+                Some(conn) => conn.state = state,
+                None => self.connectors.push(FakeConnector { handle: connector, state }),
+            }
+        }
+    }
+
+    impl ScanDevice for FakeDevice {
+        type Connector = FakeConnector;
+
+        fn connectors(&self) -> std::io::Result<Vec<FakeConnector>> {
+            Ok(self.connectors.clone())
+        }
+
+        fn current_crtc(&self, _: &FakeConnector) -> Option<crtc::Handle> {
+            None
+        }
+
+        fn possible_crtcs(&self, _: &FakeConnector) -> Vec<crtc::Handle> {
+            self.crtcs.clone()
+        }
+    }
+
+    fn scan(scanner: &mut DrmScanner<SimpleCrtcMapper, FakeConnector>, device: &FakeDevice) -> Vec<DrmScanEvent<FakeConnector>> {
+        scanner.scan_connectors(device).unwrap().into_iter().collect()
+    }
+
+    #[test]
+    fn crtcless_connector_takes_released_crtc() {
+        let crtc = from_u32::<crtc::Handle>(10).unwrap();
+        let first = from_u32::<connector::Handle>(1).unwrap();
+        let second = from_u32::<connector::Handle>(2).unwrap();
+        let mut device = FakeDevice { connectors: vec![], crtcs: vec![crtc] };
+        let mut scanner = DrmScanner::new();
+
+        device.set_state(first, State::Connected);
+        let events = scan(&mut scanner, &device);
+        assert!(matches!(
+            events.as_slice(),
+            [DrmScanEvent::Connected { connector, crtc: c }] if connector.handle == first && *c == crtc
+        ));
+
+        device.set_state(second, State::Connected);
+        let events = scan(&mut scanner, &device);
+        assert!(matches!(
+            events.as_slice(),
+            [DrmScanEvent::ConnectedNoCrtc { connector }] if connector.handle == second
+        ));
+        assert_eq!(scanner.crtc_for_connector(&second), None);
+
+        device.set_state(first, State::Disconnected);
+        let events = scan(&mut scanner, &device);
+        assert!(matches!(
+            events.as_slice(),
+            [
+                DrmScanEvent::Disconnected { connector: removed, crtc: Some(c1) },
+                DrmScanEvent::Connected { connector: added, crtc: c2 },
+            ] if removed.handle == first && *c1 == crtc && added.handle == second && *c2 == crtc
+        ));
+        assert_eq!(scanner.crtc_for_connector(&first), None);
+        assert_eq!(scanner.crtc_for_connector(&second), Some(crtc));
+
+        // nothing changed, the reassignment is not reported twice
+        assert!(scan(&mut scanner, &device).is_empty());
+    }
+
+    #[test]
+    fn disabled_connector_releases_crtc() {
+        let crtc = from_u32::<crtc::Handle>(10).unwrap();
+        let first = from_u32::<connector::Handle>(1).unwrap();
+        let second = from_u32::<connector::Handle>(2).unwrap();
+        let mut device = FakeDevice { connectors: vec![], crtcs: vec![crtc] };
+        let mut scanner = DrmScanner::new();
+
+        device.set_state(first, State::Connected);
+        scan(&mut scanner, &device);
+        device.set_state(second, State::Connected);
+        scan(&mut scanner, &device);
+
+        let events = scanner.set_enabled(&device, first, false).into_iter().collect::<Vec<_>>();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                DrmScanEvent::Disconnected { connector: removed, crtc: Some(c1) },
+                DrmScanEvent::Connected { connector: added, crtc: c2 },
+            ] if removed.handle == first && *c1 == crtc && added.handle == second && *c2 == crtc
+        ));
+
+        // enabled again while the crtc is taken
+        let events = scanner.set_enabled(&device, first, true).into_iter().collect::<Vec<_>>();
+        assert!(matches!(
+            events.as_slice(),
+            [DrmScanEvent::ConnectedNoCrtc { connector }] if connector.handle == first
+        ));
+    }
+}
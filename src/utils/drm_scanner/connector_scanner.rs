@@ -2,30 +2,31 @@ use std::{
     collections::HashMap,
     iter::{Chain, Map},
 };
-use smithay::reexports::drm::control::{connector, Device as ControlDevice};
+use smithay::reexports::drm::control::connector;
 
+use super::{ScanConnector, ScanDevice};
 
-#[derive(Debug,Default)]
-pub struct ConnectorScanner {
-    connectors: HashMap<connector::Handle, connector::Info>,
+#[derive(Debug)]
+pub struct ConnectorScanner<C = connector::Info> {
+    connectors: HashMap<connector::Handle, C>,
 }
 
-impl ConnectorScanner {
+impl<C> Default for ConnectorScanner<C> {
+    fn default() -> Self {
+        Self { connectors: HashMap::new() }
+    }
+}
+
+impl<C: ScanConnector> ConnectorScanner<C> {
     pub fn new() -> Self {
         Self::default()
     }
 
-    pub fn scan(&mut self, drm: &impl ControlDevice) -> std::io::Result<ConnectorScanResult> {
-        let res_handles = drm.resource_handles()?;
-        let connector_handles = res_handles.connectors();
-
+    pub fn scan(&mut self, drm: &impl ScanDevice<Connector = C>) -> std::io::Result<ConnectorScanResult<C>> {
         let mut added = vec![];
         let mut removed = vec![];
 
-        for conn in connector_handles
-            .iter()
-            .filter_map(|conn|drm.get_connector(*conn, true).ok())
-        {
+        for conn in drm.connectors()? {
             let curr_state = conn.state();
             use connector::State;
             if let Some(old) = self.connectors.insert(conn.handle(), conn.clone()) {
@@ -50,47 +51,52 @@ impl ConnectorScanner {
         })
     }
 
-    pub fn connectors(&self) -> &HashMap<connector::Handle, connector::Info> {
+    pub fn connectors(&self) -> &HashMap<connector::Handle, C> {
         &self.connectors
     }
 }
 
-#[derive(Debug,Default,Clone)]
-pub struct ConnectorScanResult {
-    pub connected: Vec<connector::Info>,
-    pub disconnected: Vec<connector::Info>,
+#[derive(Debug,Clone)]
+pub struct ConnectorScanResult<C = connector::Info> {
+    pub connected: Vec<C>,
+    pub disconnected: Vec<C>,
+}
+
+impl<C> Default for ConnectorScanResult<C> {
+    fn default() -> Self {
+        Self { connected: vec![], disconnected: vec![] }
+    }
 }
 
 #[derive(Debug,Clone)]
-pub enum ConnectorScanEvent {
-    Connected(connector::Info),
-    Disconnected(connector::Info),
+pub enum ConnectorScanEvent<C = connector::Info> {
+    Connected(C),
+    Disconnected(C),
 }
 
-impl ConnectorScanResult {
-    pub fn iter(&self) -> impl Iterator<Item = ConnectorScanEvent> {
+impl<C: Clone> ConnectorScanResult<C> {
+    pub fn iter(&self) -> impl Iterator<Item = ConnectorScanEvent<C>> {
         self.clone().into_iter()
     }
 }
 
-type ConnectorScanItemToEvent = fn(connector::Info) -> ConnectorScanEvent;
+type ConnectorScanItemToEvent<C> = fn(C) -> ConnectorScanEvent<C>;
 
-impl IntoIterator for ConnectorScanResult {
-    type Item = ConnectorScanEvent;
+impl<C> IntoIterator for ConnectorScanResult<C> {
+    type Item = ConnectorScanEvent<C>;
     type IntoIter = Chain<
-        Map<std::vec::IntoIter<connector::Info>, ConnectorScanItemToEvent>,
-        Map<std::vec::IntoIter<connector::Info>, ConnectorScanItemToEvent>,
+        Map<std::vec::IntoIter<C>, ConnectorScanItemToEvent<C>>,
+        Map<std::vec::IntoIter<C>, ConnectorScanItemToEvent<C>>,
     >;
 
     fn into_iter(self) -> Self::IntoIter {
         self.disconnected
             .into_iter()
-            .map(ConnectorScanEvent::Disconnected as ConnectorScanItemToEvent)
+            .map(ConnectorScanEvent::Disconnected as ConnectorScanItemToEvent<C>)
             .chain(
                 self.connected
                     .into_iter()
-                    .map(ConnectorScanEvent::Connected as ConnectorScanItemToEvent)
+                    .map(ConnectorScanEvent::Connected as ConnectorScanItemToEvent<C>)
             )
     }
 }
-
@@ -1,11 +1,32 @@
 use std::collections::HashMap;
-use smithay::reexports::drm::control::{connector, crtc, Device as ControlDevice};
+use smithay::reexports::drm::control::{connector, crtc};
 
+use super::{ScanConnector, ScanDevice};
+
+/// policy of which crtc drives which connector
+///
+/// [`DrmScanner`] calls [`CrtcMapper::map_crtc`] on every scan, then query the result
+/// with [`CrtcMapper::crtc_for_connector`]
+///
+/// [`DrmScanner`]: super::DrmScanner
 pub trait CrtcMapper {
-    fn map<'a>(&mut self, drm: &impl ControlDevice, connectors: impl Iterator<Item = &'a connector::Info> + Clone);
+    /// update the mapping for the current state of all known `connectors`
+    ///
+    /// connectors that are no longer connected or not given should release their crtc,
+    /// connected connectors without a crtc should be given one if possible,
+    /// a crtc must not be mapped to more than one connector
+    fn map_crtc<'a, D: ScanDevice>(&mut self, drm: &D, connectors: impl Iterator<Item = &'a D::Connector> + Clone)
+    where
+        D::Connector: 'a;
+
+    /// crtc currently mapped to `connector`
     fn crtc_for_connector(&self, connector: &connector::Handle) -> Option<crtc::Handle>;
 }
 
+/// default [`CrtcMapper`]
+///
+/// connectors keep the crtc they are mapped to, newly connected connectors restore
+/// the crtc currently driving them, or else take the first available one
 #[derive(Default)]
 pub struct SimpleCrtcMapper {
     crtcs: HashMap<connector::Handle, crtc::Handle>,
@@ -24,47 +45,40 @@ impl SimpleCrtcMapper {
         !self.is_taken(crtc)
     }
 
-    fn restored_for_connector(
+    fn restored_for_connector<D: ScanDevice>(
         &self,
-        drm: &impl ControlDevice,
-        connector: &connector::Info,
+        drm: &D,
+        connector: &D::Connector,
     ) -> Option<crtc::Handle> {
-        let encoder = drm.get_encoder(connector.current_encoder()?).ok()?;
-        let crtc = encoder.crtc()?;
+        let crtc = drm.current_crtc(connector)?;
         self.is_available(&crtc).then_some(crtc)
     }
 
-    fn next_available_for_connector(
+    fn next_available_for_connector<D: ScanDevice>(
         &self,
-        drm: &impl ControlDevice,
-        connector: &connector::Info,
+        drm: &D,
+        connector: &D::Connector,
     ) -> Option<crtc::Handle> {
-        let res_handle = drm.resource_handles().ok()?;
-        connector
-            .encoders()
-            .iter()
-            .flat_map(|encoder| drm.get_encoder(*encoder))
-            .find_map(|encoder| {
-                res_handle
-                    .filter_crtcs(encoder.possible_crtcs())
-                    .into_iter()
-                    .find(|crtc| self.is_available(crtc))
-            })
+        drm.possible_crtcs(connector)
+            .into_iter()
+            .find(|crtc| self.is_available(crtc))
     }
 }
 
 impl CrtcMapper for SimpleCrtcMapper {
-    fn map<'a>(
+    fn map_crtc<'a, D: ScanDevice>(
         &mut self,
-        drm: &impl ControlDevice,
-        connectors: impl Iterator<Item = &'a connector::Info> + Clone,
-    ) {
-        for connector in connectors
-            .clone()
-            .filter(|conn| conn.state() != connector::State::Connected)
-        {
-            self.crtcs.remove(&connector.handle());
-        }
+        drm: &D,
+        connectors: impl Iterator<Item = &'a D::Connector> + Clone,
+    )
+    where
+        D::Connector: 'a,
+    {
+        self.crtcs.retain(|handle,_|{
+            connectors
+                .clone()
+                .any(|conn|conn.handle() == *handle && conn.state() == connector::State::Connected)
+        });
 
         let mut needs_crtc = connectors
             .filter(|conn|conn.state()==connector::State::Connected)
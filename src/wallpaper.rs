@@ -0,0 +1,180 @@
+//! built-in wallpaper, drawn below every other element of an output
+use std::{
+    collections::HashMap,
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use smithay::{
+    backend::{
+        allocator::Fourcc,
+        renderer::{
+            element::{
+                memory::{MemoryRenderBuffer, MemoryRenderBufferRenderElement},
+                Kind,
+            },
+            ImportMem, Renderer,
+        },
+    },
+    output::Output,
+    utils::{Buffer, Logical, Point, Rectangle, Scale, Size, Transform},
+};
+
+use crate::config::{Config, WallpaperMode};
+
+/// loaded wallpaper image and how it fills an output
+#[derive(Clone)]
+pub struct Wallpaper {
+    buffer: MemoryRenderBuffer,
+    size: Size<i32, Buffer>,
+    mode: WallpaperMode,
+}
+
+impl Wallpaper {
+    /// elements filling an output of logical `output_size`, relative to the output
+    pub fn render_elements<R>(
+        &self,
+        renderer: &mut R,
+        output_size: Size<i32, Logical>,
+        scale: Scale<f64>,
+    ) -> Vec<MemoryRenderBufferRenderElement<R>>
+    where
+        R: Renderer + ImportMem,
+        R::TextureId: Clone + 'static,
+    {
+        // centered and tiled images are drawn at their native pixel size
+        let native_size = Size::<i32, Logical>::from((
+            (self.size.w as f64 / scale.x).round().max(1.0) as i32,
+            (self.size.h as f64 / scale.y).round().max(1.0) as i32,
+        ));
+
+        let rects = match self.mode {
+            WallpaperMode::Scale => {
+                // cover the output, cropping the image edges that do not fit
+                let factor = f64::max(
+                    output_size.w as f64 / self.size.w as f64,
+                    output_size.h as f64 / self.size.h as f64,
+                );
+                let src_size = Size::from((output_size.w as f64 / factor, output_size.h as f64 / factor));
+                let src_loc = Point::from((
+                    (self.size.w as f64 - src_size.w) / 2.0,
+                    (self.size.h as f64 - src_size.h) / 2.0,
+                ));
+                vec![(Rectangle::from_size(output_size), Some(Rectangle::new(src_loc, src_size)))]
+            },
+            WallpaperMode::Center => {
+                let loc = Point::from((
+                    (output_size.w - native_size.w) / 2,
+                    (output_size.h - native_size.h) / 2,
+                ));
+                vec![(Rectangle::new(loc, native_size), None)]
+            },
+            WallpaperMode::Tile => {
+                (0..output_size.h)
+                    .step_by(native_size.h as usize)
+                    .flat_map(|y|{
+                        (0..output_size.w)
+                            .step_by(native_size.w as usize)
+                            .map(move |x|(Rectangle::new((x,y).into(), native_size), None))
+                    })
+                    .collect()
+            },
+        };
+
+        rects
+            .into_iter()
+            .filter_map(|(geo,src)|{
+                MemoryRenderBufferRenderElement::from_buffer(
+                    renderer,
+                    geo.loc.to_f64().to_physical(scale),
+                    &self.buffer,
+                    None,
+                    src,
+                    Some(geo.size),
+                    Kind::Unspecified,
+                )
+                .inspect_err(|err|tracing::warn!("failed to import wallpaper: {err:?}"))
+                .ok()
+            })
+            .collect()
+    }
+}
+
+/// wallpaper images by path, images that failed to load are not retried
+#[derive(Default)]
+pub struct Wallpapers {
+    images: HashMap<PathBuf, Option<(MemoryRenderBuffer, Size<i32, Buffer>)>>,
+}
+
+impl Wallpapers {
+    /// wallpaper of `output`, fallback to the default wallpaper
+    ///
+    /// `None` if no wallpaper is configured or it failed to load
+    pub fn get(&mut self, config: &Config, output: &Output) -> Option<Wallpaper> {
+        let wallpaper = config
+            .output(output)
+            .and_then(|output|output.wallpaper.as_ref())
+            .or(config.wallpaper.as_ref())?;
+
+        let (buffer, size) = self
+            .images
+            .entry(wallpaper.path.clone())
+            .or_insert_with(||{
+                load(&wallpaper.path)
+                    .inspect(|_|tracing::info!("wallpaper loaded from {:?}", wallpaper.path))
+                    .inspect_err(|err|tracing::error!("failed to load wallpaper {:?}: {err:#}", wallpaper.path))
+                    .ok()
+            })
+            .clone()?;
+
+        Some(Wallpaper { buffer, size, mode: wallpaper.mode })
+    }
+}
+
+/// decode a png into a premultiplied rgba buffer
+fn load(path: &Path) -> Result<(MemoryRenderBuffer, Size<i32, Buffer>)> {
+    let file = fs::File::open(path)?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+    let mut reader = decoder.read_info().context("failed to read png header")?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).context("failed to decode png")?;
+    pixels.truncate(info.buffer_size());
+
+    let mut pixels = match info.color_type {
+        png::ColorType::Rgba => pixels,
+        png::ColorType::Rgb => pixels.chunks_exact(3).flat_map(|p|[p[0],p[1],p[2],255]).collect(),
+        png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|p|[p[0],p[0],p[0],p[1]]).collect(),
+        png::ColorType::Grayscale => pixels.iter().flat_map(|&p|[p,p,p,255]).collect(),
+        png::ColorType::Indexed => bail!("indexed png was not expanded"),
+    };
+
+    // renderer expects premultiplied alpha
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = pixel[3] as u16;
+        for channel in &mut pixel[..3] {
+            *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+        }
+    }
+
+    let size = Size::<i32, Buffer>::from((info.width as i32, info.height as i32));
+    let opaque_regions = pixels
+        .chunks_exact(4)
+        .all(|pixel|pixel[3] == 255)
+        .then(||vec![Rectangle::from_size(size)]);
+
+    // little endian abgr is rgba in memory
+    let buffer = MemoryRenderBuffer::from_slice(
+        &pixels,
+        Fourcc::Abgr8888,
+        size,
+        1,
+        Transform::Normal,
+        opaque_regions,
+    );
+
+    Ok((buffer, size))
+}
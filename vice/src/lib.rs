@@ -1,4 +1,4 @@
-use std::{collections::HashMap, os::unix::net::UnixStream, path::Path, sync::Arc};
+use std::{collections::HashMap, os::unix::net::UnixStream, path::Path, sync::Arc, time::Duration};
 use anyhow::{Context, Result};
 use smithay::{
     backend::{
@@ -7,12 +7,13 @@ use smithay::{
             Fourcc,
         },
         drm::{
-            compositor::FrameFlags,
+            compositor::{FrameError, FrameFlags},
             output::{DrmOutput, DrmOutputManager, DrmOutputRenderElements},
-            DrmDevice, DrmDeviceFd, DrmNode, NodeType,
+            DrmDevice, DrmDeviceFd, DrmError, DrmEvent, DrmEventMetadata, DrmEventTime, DrmNode,
+            NodeType,
         },
         egl::{context::ContextPriority, EGLDevice, EGLDisplay},
-        input::InputEvent,
+        input::{Event, InputEvent, KeyState, KeyboardKeyEvent},
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{
             element as element_utils,
@@ -27,12 +28,13 @@ use smithay::{
         self, space::SpaceRenderElements, utils::OutputPresentationFeedback, PopupKind,
         PopupManager, Space, Window,
     },
-    input::{SeatHandler, SeatState},
+    input::{keyboard::{FilterResult, Keysym}, Seat, SeatHandler, SeatState},
     output::{Output, PhysicalProperties},
     reexports::{
         calloop::{
             self,
             generic::{Generic, NoIoDrop},
+            timer::{TimeoutAction, Timer},
             EventLoop, Interest, LoopHandle, LoopSignal, Readiness, RegistrationToken,
         },
         drm::control::{connector, crtc, Device as ControlDevice, ModeTypeFlags},
@@ -43,12 +45,14 @@ use smithay::{
             protocol::{wl_buffer::WlBuffer, wl_seat::WlSeat, wl_surface::WlSurface},
             Client, Display, DisplayHandle,
         },
+        wayland_protocols::wp::presentation_time::server::wp_presentation_feedback,
     },
-    utils::{Clock, Monotonic, Serial, Time},
+    utils::{Clock, Logical, Monotonic, Point, Serial, Time, SERIAL_COUNTER},
     wayland::{
         buffer::BufferHandler,
         compositor::{self, CompositorClientState, CompositorHandler, CompositorState},
         output::{OutputHandler, OutputManagerState},
+        presentation::Refresh,
         selection::{
             data_device::{
                 ClientDndGrabHandler, DataDeviceHandler, DataDeviceState, ServerDndGrabHandler,
@@ -85,6 +89,8 @@ pub struct Vice {
     clock: Clock<Monotonic>,
     socket_name: String,
     primary_gpu: DrmNode,
+    session: LibSeatSession,
+    libinput: Libinput,
 
     gpus: Gpus,
     devices: HashMap<DrmNode, Device>,
@@ -92,6 +98,7 @@ pub struct Vice {
     compositor_state: CompositorState,
     xdg_shell_state: XdgShellState,
     seat_state: SeatState<Vice>,
+    seat: Seat<Vice>,
     shm_state: ShmState,
     output_manager_state: OutputManagerState,
     data_device_state: DataDeviceState,
@@ -163,7 +170,7 @@ impl Vice {
         let data_device_state = DataDeviceState::new::<Vice>(&dh);
 
         let mut seat = seat_state.new_wl_seat(&dh, &seat_name);
-        seat.add_keyboard(Default::default(), 150, 50).unwrap();
+        seat.add_keyboard(Default::default(), 150, 50).context("failed to setup keyboard")?;
 
         let socket_source = ListeningSocketSource::new_auto()?;
         let socket_name = socket_source.socket_name().to_string_lossy().into_owned();
@@ -187,6 +194,8 @@ impl Vice {
             clock,
             socket_name,
             primary_gpu,
+            session,
+            libinput,
 
             gpus,
             devices,
@@ -194,6 +203,7 @@ impl Vice {
             compositor_state,
             xdg_shell_state,
             seat_state,
+            seat,
             shm_state,
             output_manager_state,
             data_device_state,
@@ -324,6 +334,25 @@ mod util {
             state.geometry = state.positioner.get_unconstrained_geometry(target);
         });
     }
+
+    /// cascade new window from the topmost window, wrapping at the output edge
+    pub fn place_window(vice: &Vice) -> Point<i32, Logical> {
+        const OFFSET: i32 = 32;
+
+        let Some(output_geo) = vice.space.outputs().next().and_then(|o|vice.space.output_geometry(o)) else {
+            return (0,0).into();
+        };
+
+        let Some(last) = vice.space.elements().last().and_then(|w|vice.space.element_location(w)) else {
+            return output_geo.loc;
+        };
+
+        let mut location = last + Point::from((OFFSET,OFFSET));
+        if !output_geo.contains(location) {
+            location = output_geo.loc;
+        }
+        location
+    }
 }
 
 mod handlers {
@@ -338,20 +367,68 @@ mod handlers {
         Ok(calloop::PostAction::Continue)
     }
 
-    pub fn session(event: session::Event, _: &mut (), _: &mut Vice) {
-        tracing::warn!("session event {event:?} is not yet implemented");
+    pub fn session(event: session::Event, _: &mut (), vice: &mut Vice) {
+        match event {
+            session::Event::PauseSession => {
+                tracing::info!("session pause");
+                vice.libinput.suspend();
+                for device in vice.devices.values_mut() {
+                    device.drm_output_manager.pause();
+                }
+            }
+            session::Event::ActivateSession => {
+                tracing::info!("session resume");
+                if let Err(err) = vice.libinput.resume() {
+                    tracing::error!("failed to resume libinput context: {err:?}");
+                }
+                for (&node, device) in vice.devices.iter_mut() {
+                    // keep connectors as is, the state is reset if the next frame fails
+                    if let Err(err) = device.drm_output_manager.activate(false) {
+                        tracing::error!("failed to activate {node}: {err}");
+                        continue;
+                    }
+                    for &crtc in device.surfaces.keys() {
+                        render::schedule(&vice.lh, node, crtc);
+                    }
+                }
+            }
+        }
     }
 
     pub fn input(event: InputEvent<LibinputInputBackend>, _: &mut (), vice: &mut Vice) {
         match event {
-            InputEvent::Keyboard { event } => {
-                tracing::debug!(?event);
-                vice.signal.stop();
-            }
+            InputEvent::Keyboard { event } => keyboard(event, vice),
             _ => {}
         }
     }
 
+    /// forward key to the focused client, unless it is a compositor keybinding
+    fn keyboard(event: impl KeyboardKeyEvent<LibinputInputBackend>, vice: &mut Vice) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let time = event.time_msec();
+        let state = event.state();
+        let Some(keyboard) = vice.seat.get_keyboard() else {
+            return;
+        };
+
+        let quit = keyboard.input(vice, event.key_code(), state, serial, time, |_,mods,handle|{
+            // `Ctrl+Alt+BackSpace` quits
+            let is_quit = state == KeyState::Pressed
+                && mods.ctrl
+                && mods.alt
+                && handle.raw_syms().contains(&Keysym::BackSpace);
+            match is_quit {
+                true => FilterResult::Intercept(()),
+                false => FilterResult::Forward,
+            }
+        });
+
+        if quit.is_some() {
+            tracing::info!("quit keybinding pressed");
+            vice.signal.stop();
+        }
+    }
+
     #[allow(unused)]
     pub fn udev(event: udev::UdevEvent, _: &mut (), _: &mut Vice) {
         macro_rules! node {
@@ -384,7 +461,7 @@ mod handlers {
 }
 
 mod device {
-    //! single device could have multiple pair of crtc and surface
+    //! single device could have multiple pair of crtc and surface, one for each connected connector
 
     use smithay::backend::renderer::element::surface::WaylandSurfaceRenderElement;
 
@@ -419,8 +496,9 @@ mod device {
         };
 
         // NOTE: #2 setup VBlank event listener
-        let device_token = lh.insert_source(drm_source, |_,_,_|{
-            tracing::trace!("VBlank event");
+        let device_token = lh.insert_source(drm_source, move|event,meta,vice|match event {
+            DrmEvent::VBlank(crtc) => render::frame_finish(node, crtc, meta, vice),
+            DrmEvent::Error(err) => tracing::error!("drm device {node} error: {err}"),
         }).unwrap();
 
         let mut renderer = gpus.single_renderer(&render_node)?;
@@ -435,44 +513,105 @@ mod device {
             )
         };
 
-        let device = drm_output_manager.device();
-
         // following setup usually also called on udev device change event
 
-        // NOTE: #4 setup crtc connector stuff
-        // FIXME: ideally, single device could have multiple pair of crtc and surface
-        let (connector,crtc) = {
+        // NOTE: #4 pair every connected connector with its own crtc
+        let pairs = {
+            let device = drm_output_manager.device();
             let resource = device.resource_handles()?;
-            resource
-                .connectors()
-                .iter()
-                .find_map(|conn|{
-                    // TODO: maybe add error handling, or logging instead of silently skipping
-                    let connector = device.get_connector(*conn, true).ok()?;
-                    match connector.state() {
-                        connector::State::Connected => {}
-                        connector::State::Disconnected |
-                            connector::State::Unknown => {
-                                tracing::warn!("skipping connector {connector}");
-                                return None;
-                            },
+            let mut pairs = Vec::<(connector::Info, crtc::Handle)>::new();
+
+            for conn in resource.connectors() {
+                let connector = match device.get_connector(*conn, true) {
+                    Ok(ok) => ok,
+                    Err(err) => {
+                        tracing::warn!("skipping connector {conn:?}: {err}");
+                        continue;
                     }
+                };
+                match connector.state() {
+                    connector::State::Connected => {}
+                    connector::State::Disconnected |
+                        connector::State::Unknown => {
+                            tracing::debug!("skipping disconnected connector {connector}");
+                            continue;
+                        },
+                }
 
-                    let crtc = connector.encoders()
-                        .iter()
-                        .flat_map(|enc|device.get_encoder(*enc))
-                        .find_map(|encoder|{
-                            resource
-                                .filter_crtcs(encoder.possible_crtcs())
-                                .first()
-                                .copied()
-                        })?;
-
-                    Some((connector,crtc))
-                })
-                .context("no available pair of connector and crtc")?
+                // a crtc can only drive one connector
+                let crtc = connector.encoders()
+                    .iter()
+                    .flat_map(|enc|device.get_encoder(*enc))
+                    .find_map(|encoder|{
+                        resource
+                            .filter_crtcs(encoder.possible_crtcs())
+                            .into_iter()
+                            .find(|crtc|pairs.iter().all(|(_,used)|used != crtc))
+                    });
+                let Some(crtc) = crtc else {
+                    tracing::warn!("skipping connector {connector}, no crtc left");
+                    continue;
+                };
+
+                pairs.push((connector, crtc));
+            }
+
+            anyhow::ensure!(!pairs.is_empty(), "no available pair of connector and crtc");
+            pairs
         };
 
+        let mut surfaces = HashMap::with_capacity(pairs.len());
+        for (connector, crtc) in pairs {
+            let result = connector_surface(
+                node, render_node, &connector, crtc, dh, &mut drm_output_manager, &mut renderer, space,
+            );
+            match result {
+                Ok(surface) => { surfaces.insert(crtc, surface); },
+                Err(err) => tracing::error!("skipping connector {connector}: {err}"),
+            }
+        }
+
+        {
+            let resource = drm_output_manager.device().resource_handles()?;
+            let connectors = resource.connectors();
+            let crtcs = resource.crtcs();
+            tracing::info!(?connectors,?crtcs,"setup ok {path:?}");
+        }
+
+        for crtc in surfaces.keys().copied() {
+            render::schedule(lh, node, crtc);
+        }
+
+        let device = Device {
+            device_token,
+            render_node,
+            drm_output_manager,
+            surfaces,
+        };
+
+        Ok(device)
+    }
+
+    /// setup [`Output`] and [`Surface`] for a connector driven by `crtc`
+    ///
+    /// outputs are placed left to right
+    #[allow(clippy::too_many_arguments)]
+    fn connector_surface(
+        node: DrmNode,
+        render_node: DrmNode,
+        connector: &connector::Info,
+        crtc: crtc::Handle,
+        dh: &DisplayHandle,
+        drm_output_manager: &mut DrmOutputManager<
+            GbmAllocator<DrmDeviceFd>,
+            GbmDevice<DrmDeviceFd>,
+            Option<OutputPresentationFeedback>,
+            DrmDeviceFd,
+        >,
+        renderer: &mut UdevRenderer<'_>,
+        space: &mut Space<Window>,
+    ) -> Result<Surface> {
+        let device = drm_output_manager.device();
 
         // NOTE: #5 setup physical properties for Output
         let physical = {
@@ -508,73 +647,49 @@ mod device {
             let wl_mode = mode.into();
 
             let output = Output::new(output_name, physical);
-            let pos = (0,0).into();
+            let x = space.outputs()
+                .filter_map(|o|space.output_geometry(o))
+                .fold(0, |acc,geo|acc.max(geo.loc.x + geo.size.w));
+            let pos = (x,0).into();
 
             output.change_current_state(Some(wl_mode), None, None, Some(pos));
             output.set_preferred(wl_mode);
             output.user_data().get_or_insert(||UdevOutputId{node,crtc});
             space.map_output(&output, pos);
 
-            // TODO: output user data
-            // let damage_tracker = OutputDamageTracker::from_output(&output);
-
             output
         };
 
         let global_output = output.create_global::<Vice>(dh);
 
-        {
-            let resource = device.resource_handles()?;
-            let connectors = resource.connectors();
-            let crtcs = resource.connectors();
-            tracing::info!(?connectors,?crtcs,"setup ok {path:?}");
-        }
-
         // NOTE: #7 setup DrmOutput
         let drm_output = {
             let planes = device.planes(&crtc)?;
-            drm_output_manager
+            let result = drm_output_manager
                 .initialize_output::<_, OutputRenderElements<UdevRenderer<'_>, WaylandSurfaceRenderElement<UdevRenderer<'_>>>>(
                     crtc,
                     mode,
                     &[connector.handle()],
                     &output,
                     Some(planes),
-                    &mut renderer,
+                    renderer,
                     &DrmOutputRenderElements::default(),
-                )?
-        };
-
-        let device = {
-            let mut surfaces = HashMap::with_capacity(1);
-            surfaces.insert(crtc, Surface {
-                global_output,
-                render_node,
-                drm_output,
-            });
-            Device {
-                device_token,
-                render_node,
-                drm_output_manager,
-                surfaces,
+                );
+            match result {
+                Ok(ok) => ok,
+                Err(err) => {
+                    space.unmap_output(&output);
+                    dh.remove_global::<Vice>(global_output);
+                    return Err(err.into());
+                }
             }
         };
 
-        lh.insert_idle(move|vice|{
-            if let Err(err) = render::surface(
-                node,
-                crtc,
-                vice.clock.now(),
-                vice.primary_gpu,
-                &mut vice.space,
-                &mut vice.devices,
-                &mut vice.gpus,
-            ) {
-                tracing::error!("render surface error: {err}");
-            };
-        });
-
-        Ok(device)
+        Ok(Surface {
+            global_output,
+            render_node,
+            drm_output,
+        })
     }
 }
 
@@ -599,16 +714,129 @@ mod render {
     //     pub WindowRenderElements<R> where R: ImportAll + ImportMem;
     // }
 
-    /// render for single crtc and surface
+    /// render surface of `crtc` once the event loop is idle
+    pub fn schedule(lh: &LoopHandle<'static, Vice>, node: DrmNode, crtc: crtc::Handle) {
+        lh.insert_idle(move|vice|{
+            let frame_target = vice.clock.now();
+            self::render(node, crtc, frame_target, vice);
+        });
+    }
+
+    /// render surface of `crtc`, retry after a frame if nothing was submitted
+    ///
+    /// a submitted frame is continued by its VBlank in [`frame_finish`]
+    fn render(node: DrmNode, crtc: crtc::Handle, frame_target: Time<Monotonic>, vice: &mut Vice) {
+        let result = self::surface(
+            node,
+            crtc,
+            frame_target,
+            vice.primary_gpu,
+            &mut vice.space,
+            &mut vice.devices,
+            &mut vice.gpus,
+        );
+
+        let frame_duration = match result {
+            Ok(false) => frame_duration(&vice.space, node, crtc),
+            Ok(true) => return,
+            Err(err) => {
+                tracing::error!("render surface error: {err}");
+                return;
+            },
+        };
+        let Some(frame_duration) = frame_duration else {
+            return;
+        };
+
+        // no VBlank will come for an empty frame
+        let timer = Timer::from_duration(frame_duration);
+        let next_frame_target = frame_target + frame_duration;
+        vice.lh.insert_source(timer, move|_,_,vice|{
+            self::render(node, crtc, next_frame_target, vice);
+            TimeoutAction::Drop
+        }).unwrap();
+    }
+
+    /// refresh interval of the output of `crtc`
+    fn frame_duration(space: &Space<Window>, node: DrmNode, crtc: crtc::Handle) -> Option<Duration> {
+        space.outputs()
+            .find(|o|o.user_data().get::<UdevOutputId>() == Some(&UdevOutputId{node,crtc}))?
+            .current_mode()
+            .map(|mode|Duration::from_secs_f64(1_000f64/mode.refresh as f64))
+    }
+
+    /// VBlank of `crtc`, send presentation feedback and schedule the next frame
+    pub fn frame_finish(node: DrmNode, crtc: crtc::Handle, meta: &mut Option<DrmEventMetadata>, vice: &mut Vice) {
+        let Some(surface) = vice.devices
+            .get_mut(&node)
+            .and_then(|device|device.surfaces.get_mut(&crtc))
+        else {
+            tracing::error!("attempt to finish frame on non-existent crtc {crtc:?}");
+            return;
+        };
+
+        let tp = meta.as_ref().and_then(|meta|match meta.time {
+            DrmEventTime::Monotonic(tp) => Some(tp),
+            DrmEventTime::Realtime(_) => None,
+        });
+        let seq = meta.as_ref().map(|meta|meta.sequence).unwrap_or(0);
+
+        let (clock,flags) = match tp {
+            Some(tp) => (tp.into(),
+                wp_presentation_feedback::Kind::Vsync
+                | wp_presentation_feedback::Kind::HwClock
+                | wp_presentation_feedback::Kind::HwCompletion
+            ),
+            None => (vice.clock.now(),
+                wp_presentation_feedback::Kind::Vsync
+            ),
+        };
+
+        let submit_result = surface.drm_output.frame_submitted();
+
+        let Some(frame_duration) = frame_duration(&vice.space, node, crtc) else {
+            return;
+        };
+
+        let schedule_render = match submit_result {
+            Ok(user_data) => {
+                if let Some(mut feedback) = user_data.flatten() {
+                    feedback.presented(clock, Refresh::Fixed(frame_duration), seq as u64, flags);
+                }
+                true
+            },
+            Err(err) => {
+                tracing::warn!("frame submit error: {err:?}");
+                // an inactive device is rescheduled by session resume
+                !matches!(err, FrameError::DrmError(DrmError::DeviceInactive))
+            },
+        };
+
+        if !schedule_render {
+            return;
+        }
+
+        // leave clients most of the frame to repaint, while the compositor repaint
+        // still hit the next VBlank
+        let repaint_delay = frame_duration.mul_f32(0.6);
+        let next_frame_target = clock + frame_duration;
+
+        vice.lh.insert_source(Timer::from_duration(repaint_delay), move|_,_,vice|{
+            self::render(node, crtc, next_frame_target, vice);
+            TimeoutAction::Drop
+        }).unwrap();
+    }
+
+    /// render for single crtc and surface, returns whether a frame is submitted
     pub fn surface(
         node: DrmNode,
         crtc: crtc::Handle,
-        _frame_target: Time<Monotonic>,
+        frame_target: Time<Monotonic>,
         primary_gpu: DrmNode,
         space: &mut Space<Window>,
         devices: &mut HashMap<DrmNode, Device>,
         gpus: &mut Gpus,
-    ) -> Result<()> {
+    ) -> Result<bool> {
         let output = space.outputs()
             .find(|o|{
                 o.user_data().get::<UdevOutputId>()==Some(&UdevOutputId{node,crtc})
@@ -627,11 +855,19 @@ mod render {
             gpus.renderer(&primary_gpu, &render_node, format)
         }?;
 
-        let _res = render_surface(surface, &mut renderer, space, &output)?;
+        let (rendered,_) = render_surface(surface, &mut renderer, space, &output)?;
 
-        // do something post render
+        // let clients draw their next frame
+        space.elements().for_each(|window|{
+            window.send_frame(
+                &output,
+                frame_target,
+                Some(Duration::ZERO),
+                desktop::utils::surface_primary_scanout_output,
+            );
+        });
 
-        Ok(())
+        Ok(rendered)
     }
 
     fn render_surface<'a>(
@@ -756,8 +992,15 @@ impl XdgShellHandler for Vice {
     }
 
     fn new_toplevel(&mut self, surface: ToplevelSurface) {
+        let location = util::place_window(self);
+        let wl_surface = surface.wl_surface().clone();
         let window = Window::new_wayland_window(surface);
-        self.space.map_element(window, (0,0), false);
+        self.space.map_element(window, location, true);
+
+        // newly mapped window takes the keyboard focus
+        if let Some(keyboard) = self.seat.get_keyboard() {
+            keyboard.set_focus(self, Some(wl_surface), SERIAL_COUNTER.next_serial());
+        }
     }
 
     fn new_popup(&mut self, surface: PopupSurface, _positioner: PositionerState) {